@@ -117,7 +117,20 @@ pub struct AnimeImageDiagonal {
 pub struct AnimeGif {
     #[options(help = "print help message")]
     pub help: bool,
-    #[options(meta = "", help = "full path to the png to display")]
+    #[options(
+        free,
+        help = "GIF or APNG of any size to fit to the display and stream to it"
+    )]
+    pub file: Vec<String>,
+    #[options(no_short, meta = "", help = "brightness 0.0-1.0 of a streamed file")]
+    pub brightness: Option<f32>,
+    #[options(
+        no_short,
+        long = "loop",
+        help = "replay a streamed file until interrupted"
+    )]
+    pub repeat: bool,
+    #[options(meta = "", help = "full path to the gif to display")]
     pub path: String,
     #[options(meta = "", default = "1.0", help = "scale 1.0 == normal")]
     pub scale: f32,
@@ -0,0 +1,46 @@
+use asusd::charge_schedule::ChargePower;
+use gumdrop::Options;
+
+#[derive(Options)]
+pub struct ChargeCommand {
+    #[options(help = "print help message")]
+    pub help: bool,
+    #[options(no_short, help = "charge to 100% once, then return to the usual limit")]
+    pub full_once: bool,
+    #[options(no_short, help = "cancel a pending --full-once")]
+    pub cancel_full_once: bool,
+    #[options(command)]
+    pub command: Option<ChargeSubCommand>,
+}
+
+#[derive(Options)]
+pub enum ChargeSubCommand {
+    #[options(help = "Set charge limits by time of day or power source")]
+    Schedule(ChargeScheduleCommand),
+}
+
+#[derive(Options)]
+pub struct ChargeScheduleCommand {
+    #[options(help = "print help message")]
+    pub help: bool,
+    #[options(help = "list the rules in the order they are checked")]
+    pub list: bool,
+    #[options(meta = "", help = "add a rule with this charge limit <20-100>")]
+    pub add: Option<u8>,
+    #[options(
+        meta = "",
+        help = "start time of the added rule <HH:MM>, default 00:00"
+    )]
+    pub start: Option<String>,
+    #[options(
+        meta = "",
+        help = "end time of the added rule <HH:MM>, default all day"
+    )]
+    pub end: Option<String>,
+    #[options(meta = "", help = "power source of the added rule <any, ac, dock>")]
+    pub power: Option<ChargePower>,
+    #[options(meta = "", help = "remove the rule at this position in --list")]
+    pub remove: Option<usize>,
+    #[options(help = "remove all rules")]
+    pub clear: bool,
+}
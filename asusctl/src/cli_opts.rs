@@ -3,8 +3,18 @@ use rog_platform::platform::ThrottlePolicy;
 
 use crate::anime_cli::AnimeCommand;
 use crate::aura_cli::{LedBrightness, LedPowerCommand1, LedPowerCommand2, SetAuraBuiltin};
+use crate::charge_cli::ChargeCommand;
+use crate::completions_cli::CompletionsCommand;
 use crate::fan_curve_cli::FanCurveCommand;
+use crate::gpu_cli::GpuCommand;
+use crate::led_auto_cli::LedAutoCommand;
+use crate::led_db_cli::LedDbCommand;
+use crate::led_pow_cli::LedPowCommand;
+use crate::led_trail_cli::LedTrailCommand;
+use crate::preset_cli::PresetCommand;
+use crate::settings_cli::SettingsCommand;
 use crate::slash_cli::SlashCommand;
+use crate::watch_cli::WatchCommand;
 
 #[derive(Default, Options)]
 pub struct CliStart {
@@ -14,6 +24,21 @@ pub struct CliStart {
     pub version: bool,
     #[options(help = "show supported functions of this laptop")]
     pub show_supported: bool,
+    #[options(
+        no_short,
+        help = "print query results as JSON, for scripts and status bars"
+    )]
+    pub json: bool,
+    #[options(
+        no_short,
+        help = "print software effect frame timings in the Prometheus format"
+    )]
+    pub effect_metrics: bool,
+    #[options(
+        no_short,
+        help = "estimate the LED power draw and suggest ways to lower it"
+    )]
+    pub led_power_estimate: bool,
     #[options(meta = "", help = "<off, low, med, high>")]
     pub kbd_bright: Option<LedBrightness>,
     #[options(help = "Toggle to next keyboard brightness")]
@@ -34,18 +59,38 @@ pub enum CliCommand {
     LedPow1(LedPowerCommand1),
     #[options(help = "Set the LED power states")]
     LedPow2(LedPowerCommand2),
+    #[options(help = "Set the LED power state of one zone and phase at a time")]
+    LedPow(LedPowCommand),
+    #[options(help = "Inspect LED support data files")]
+    LedDb(LedDbCommand),
+    #[options(help = "Change the LED brightness by time of day or ambient light")]
+    LedAuto(LedAutoCommand),
+    #[options(help = "Light pressed keys and fade them out through a gradient")]
+    LedTrail(LedTrailCommand),
+    #[options(help = "Save the current lighting by name and apply it again later")]
+    Preset(PresetCommand),
     #[options(help = "Set or select platform_profile")]
     Profile(ProfileCommand),
     #[options(help = "Set, select, or modify fan curves if supported")]
     FanCurve(FanCurveCommand),
     #[options(help = "Set the graphics mode (obsoleted by supergfxctl)")]
     Graphics(GraphicsCommand),
+    #[options(help = "Control the dGPU power and MUX switch")]
+    Gpu(GpuCommand),
     #[options(name = "anime", help = "Manage AniMe Matrix")]
     Anime(AnimeCommand),
     #[options(name = "slash", help = "Manage Slash Ledbar")]
     Slash(SlashCommand),
     #[options(help = "Change bios settings")]
     Bios(BiosCommand),
+    #[options(help = "Charge limit overrides and scheduling")]
+    Charge(ChargeCommand),
+    #[options(help = "Print a shell completion script")]
+    Completions(CompletionsCommand),
+    #[options(help = "Set any setting asusd describes by name, see `set --list`")]
+    Set(SettingsCommand),
+    #[options(help = "Print each event from asusd as a line of JSON")]
+    Watch(WatchCommand),
 }
 
 #[derive(Debug, Clone, Options)]
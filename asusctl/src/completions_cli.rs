@@ -0,0 +1,497 @@
+//! Shell completion scripts built from the gumdrop usage text of each
+//! command, so they follow the options without being kept in step by hand.
+//! Values which depend on the laptop, the aura modes, zones and profiles,
+//! are never written into the script. It runs `asusctl completions --values`
+//! when completing them, which reads them from the settings schema of asusd,
+//! or offers every value asusctl knows of if asusd can't say.
+
+use std::fmt::Write;
+use std::str::FromStr;
+
+use gumdrop::Options;
+use rog_aura::AuraZone;
+use rog_platform::platform::ThrottlePolicy;
+
+use crate::anime_cli::AnimeCommand;
+use crate::aura_cli::LedPowerCommand2;
+use crate::charge_cli::ChargeCommand;
+use crate::cli_opts::{CliStart, LedModeCommand};
+use crate::fan_curve_cli::FanCurveCommand;
+use crate::led_db_cli::LedDbCommand;
+use crate::led_pow_cli::LedPowCommand;
+
+#[derive(Options)]
+pub struct CompletionsCommand {
+    #[options(help = "print help message")]
+    pub help: bool,
+    #[options(
+        meta = "",
+        help = "print the values this laptop supports <modes, zones, profiles, settings>"
+    )]
+    pub values: Option<CompletionValues>,
+    #[options(
+        free,
+        help = "the shell to print a completion script for <bash, zsh, fish>"
+    )]
+    pub shell: Option<Shell>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl FromStr for Shell {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "bash" => Ok(Self::Bash),
+            "zsh" => Ok(Self::Zsh),
+            "fish" => Ok(Self::Fish),
+            _ => Err(format!("{s} is not one of bash, zsh, fish")),
+        }
+    }
+}
+
+/// Completions which asusd is asked for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompletionValues {
+    /// The `led-mode` commands of the supported aura modes
+    Modes,
+    Zones,
+    Profiles,
+    /// The settings `asusctl set` takes
+    Settings,
+}
+
+impl CompletionValues {
+    const fn name(self) -> &'static str {
+        match self {
+            Self::Modes => "modes",
+            Self::Zones => "zones",
+            Self::Profiles => "profiles",
+            Self::Settings => "settings",
+        }
+    }
+}
+
+impl FromStr for CompletionValues {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "modes" => Ok(Self::Modes),
+            "zones" => Ok(Self::Zones),
+            "profiles" => Ok(Self::Profiles),
+            "settings" => Ok(Self::Settings),
+            _ => Err(format!(
+                "{s} is not one of modes, zones, profiles, settings"
+            )),
+        }
+    }
+}
+
+/// Options taking a value only asusd knows, by long name
+const DAEMON_OPTIONS: &[(&str, CompletionValues)] = &[
+    ("--profile-set", CompletionValues::Profiles),
+    ("--mod-profile", CompletionValues::Profiles),
+    ("--zone", CompletionValues::Zones),
+];
+
+/// The `led-mode` commands which set one of `modes`, named as in the schema
+pub fn mode_commands(modes: &[String]) -> Vec<String> {
+    command_names(LedModeCommand::command_list())
+        .into_iter()
+        .filter(|command| modes.iter().any(|mode| command.eq_ignore_ascii_case(mode)))
+        .collect()
+}
+
+/// Every value asusctl itself knows of, for when asusd has no schema to give
+pub fn static_values(values: CompletionValues) -> Vec<String> {
+    match values {
+        CompletionValues::Modes => command_names(LedModeCommand::command_list()),
+        CompletionValues::Zones => [
+            AuraZone::None,
+            AuraZone::Key1,
+            AuraZone::Key2,
+            AuraZone::Key3,
+            AuraZone::Key4,
+            AuraZone::Logo,
+            AuraZone::BarLeft,
+            AuraZone::BarRight,
+        ]
+        .iter()
+        .map(|zone| zone.name().to_owned())
+        .collect(),
+        CompletionValues::Profiles => ThrottlePolicy::list()
+            .iter()
+            .map(|p| p.to_string().to_lowercase())
+            .collect(),
+        CompletionValues::Settings => Vec::new(),
+    }
+}
+
+enum Values {
+    Fixed(Vec<String>),
+    Daemon(CompletionValues),
+}
+
+struct ValueOption {
+    names: Vec<String>,
+    values: Values,
+}
+
+/// One command or subcommand, e.g. `asusctl led-mode`
+struct Node {
+    path: String,
+    /// Options, plus any subcommands and positional values
+    words: Vec<String>,
+    /// Subcommands offered depend on the laptop
+    daemon_words: Option<CompletionValues>,
+    value_options: Vec<ValueOption>,
+    /// Every subcommand, used to follow the command line down the tree
+    subcommands: Vec<String>,
+}
+
+fn command_names(list: Option<&str>) -> Vec<String> {
+    list.unwrap_or_default()
+        .lines()
+        .filter_map(|line| line.split_whitespace().next())
+        .map(|s| s.to_owned())
+        .collect()
+}
+
+/// The choices in a help text such as `<off, low, med, high>` or
+/// `<true/false>`, but not ranges like `<20-100>`
+fn help_choices(help: &str) -> Option<Vec<String>> {
+    let start = help.find('<')?;
+    let end = start + help[start..].find('>')?;
+    let choices: Vec<String> = help[start + 1..end]
+        .split([',', '/'])
+        .map(|c| c.trim().to_owned())
+        .collect();
+    let valid = choices.len() > 1
+        && choices
+            .iter()
+            .all(|c| !c.is_empty() && c.chars().all(|ch| ch.is_ascii_lowercase() || ch == '-'));
+    valid.then_some(choices)
+}
+
+impl Node {
+    fn new(path: String, usage: &str, commands: Option<&str>) -> Self {
+        let subcommands = command_names(commands);
+        let mut node = Self {
+            path,
+            words: subcommands.clone(),
+            daemon_words: None,
+            value_options: Vec::new(),
+            subcommands,
+        };
+        for line in usage.lines() {
+            let names: Vec<String> = line
+                .split_whitespace()
+                .map(|word| word.trim_end_matches(','))
+                .take_while(|word| word.starts_with('-'))
+                .map(|word| word.to_owned())
+                .collect();
+            if names.is_empty() {
+                // A positional argument
+                if let Some(choices) = help_choices(line) {
+                    node.words.extend(choices);
+                }
+                continue;
+            }
+            let daemon = DAEMON_OPTIONS
+                .iter()
+                .find(|(long, _)| names.iter().any(|name| name == long));
+            let values = match daemon {
+                Some((_, values)) => Some(Values::Daemon(*values)),
+                None => help_choices(line).map(Values::Fixed),
+            };
+            node.words.extend(names.iter().cloned());
+            if let Some(values) = values {
+                node.value_options.push(ValueOption { names, values });
+            }
+        }
+        node
+    }
+}
+
+/// Add the node at `path` and those of its subcommands
+fn push_tree<T: Options>(nodes: &mut Vec<Node>, path: String, usage: &str) {
+    let list = T::command_list();
+    for sub in command_names(list) {
+        let usage = T::command_usage(&sub).unwrap_or_default();
+        nodes.push(Node::new(format!("{path} {sub}"), usage, None));
+    }
+    nodes.push(Node::new(path, usage, list));
+}
+
+fn nodes() -> Vec<Node> {
+    let mut nodes = vec![Node::new(
+        "asusctl".to_owned(),
+        CliStart::usage(),
+        CliStart::command_list(),
+    )];
+    for command in command_names(CliStart::command_list()) {
+        let path = format!("asusctl {command}");
+        let usage = CliStart::command_usage(&command).unwrap_or_default();
+        match command.as_str() {
+            "led-mode" => {
+                push_tree::<LedModeCommand>(&mut nodes, path, usage);
+                // Only offer the modes this laptop has
+                if let Some(node) = nodes.last_mut() {
+                    let modes = node.subcommands.clone();
+                    node.words.retain(|w| !modes.contains(w));
+                    node.daemon_words = Some(CompletionValues::Modes);
+                }
+            }
+            "led-pow-2" => push_tree::<LedPowerCommand2>(&mut nodes, path, usage),
+            "led-pow" => push_tree::<LedPowCommand>(&mut nodes, path, usage),
+            "led-db" => push_tree::<LedDbCommand>(&mut nodes, path, usage),
+            "fan-curve" => push_tree::<FanCurveCommand>(&mut nodes, path, usage),
+            "anime" => push_tree::<AnimeCommand>(&mut nodes, path, usage),
+            "charge" => push_tree::<ChargeCommand>(&mut nodes, path, usage),
+            "set" => {
+                let mut node = Node::new(path, usage, None);
+                node.daemon_words = Some(CompletionValues::Settings);
+                nodes.push(node);
+            }
+            _ => nodes.push(Node::new(path, usage, None)),
+        }
+    }
+    nodes
+}
+
+/// A shell expression giving `words` and the asusd values
+fn candidates(shell: Shell, words: &[String], daemon: Option<CompletionValues>) -> String {
+    let mut parts = Vec::new();
+    if shell == Shell::Fish {
+        if !words.is_empty() {
+            parts.push(format!("printf '%s\\n' {}", words.join(" ")));
+        }
+        if let Some(values) = daemon {
+            parts.push(format!(
+                "asusctl completions --values {} 2>/dev/null",
+                values.name()
+            ));
+        }
+        return parts.join("; ");
+    }
+    parts.extend(words.iter().cloned());
+    if let Some(values) = daemon {
+        parts.push(format!(
+            "$(asusctl completions --values {} 2>/dev/null)",
+            values.name()
+        ));
+    }
+    parts.join(" ")
+}
+
+fn value_candidates(shell: Shell, values: &Values) -> String {
+    match values {
+        Values::Fixed(words) => candidates(shell, words, None),
+        Values::Daemon(values) => candidates(shell, &[], Some(*values)),
+    }
+}
+
+/// The case patterns for every subcommand path
+fn subcommand_paths(nodes: &[Node]) -> Vec<String> {
+    nodes
+        .iter()
+        .flat_map(|node| {
+            node.subcommands
+                .iter()
+                .map(move |sub| format!("{} {sub}", node.path))
+        })
+        .collect()
+}
+
+fn bash(nodes: &[Node]) -> String {
+    let mut out = String::new();
+    out.push_str("# bash completion for asusctl, from `asusctl completions bash`\n");
+    out.push_str("_asusctl() {\n");
+    out.push_str("    local cur prev cmdpath word i\n");
+    out.push_str("    cur=\"${COMP_WORDS[COMP_CWORD]}\"\n");
+    out.push_str("    prev=\"${COMP_WORDS[COMP_CWORD-1]}\"\n");
+    out.push_str("    cmdpath=asusctl\n");
+    out.push_str("    for ((i = 1; i < COMP_CWORD; i++)); do\n");
+    out.push_str("        word=\"${COMP_WORDS[i]}\"\n");
+    out.push_str("        case \"$cmdpath $word\" in\n");
+    let paths: Vec<String> = subcommand_paths(nodes)
+        .iter()
+        .map(|p| format!("\"{p}\""))
+        .collect();
+    writeln!(
+        out,
+        "            {}) cmdpath=\"$cmdpath $word\" ;;",
+        paths.join("|")
+    )
+    .ok();
+    out.push_str("        esac\n    done\n");
+    out.push_str("    case \"$cmdpath:$prev\" in\n");
+    for node in nodes {
+        for opt in &node.value_options {
+            let patterns: Vec<String> = opt
+                .names
+                .iter()
+                .map(|name| format!("\"{}:{name}\"", node.path))
+                .collect();
+            writeln!(
+                out,
+                "        {})\n            COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))\n            \
+                 return ;;",
+                patterns.join("|"),
+                value_candidates(Shell::Bash, &opt.values)
+            )
+            .ok();
+        }
+    }
+    out.push_str("    esac\n");
+    out.push_str("    case \"$cmdpath\" in\n");
+    for node in nodes {
+        writeln!(
+            out,
+            "        \"{}\")\n            COMPREPLY=($(compgen -W \"{}\" -- \"$cur\")) ;;",
+            node.path,
+            candidates(Shell::Bash, &node.words, node.daemon_words)
+        )
+        .ok();
+    }
+    out.push_str("    esac\n}\n");
+    out.push_str("complete -F _asusctl asusctl\n");
+    out
+}
+
+fn zsh(nodes: &[Node]) -> String {
+    let mut out = String::new();
+    out.push_str("#compdef asusctl\n");
+    out.push_str("# zsh completion for asusctl, from `asusctl completions zsh`\n");
+    out.push_str("_asusctl() {\n");
+    out.push_str("    local cmdpath=asusctl word prev=${words[CURRENT-1]}\n");
+    out.push_str("    local -i i\n");
+    out.push_str("    for ((i = 2; i < CURRENT; i++)); do\n");
+    out.push_str("        word=${words[i]}\n");
+    out.push_str("        case \"$cmdpath $word\" in\n");
+    let paths: Vec<String> = subcommand_paths(nodes)
+        .iter()
+        .map(|p| format!("\"{p}\""))
+        .collect();
+    writeln!(
+        out,
+        "            ({}) cmdpath=\"$cmdpath $word\" ;;",
+        paths.join("|")
+    )
+    .ok();
+    out.push_str("        esac\n    done\n");
+    out.push_str("    case \"$cmdpath:$prev\" in\n");
+    for node in nodes {
+        for opt in &node.value_options {
+            let patterns: Vec<String> = opt
+                .names
+                .iter()
+                .map(|name| format!("\"{}:{name}\"", node.path))
+                .collect();
+            writeln!(
+                out,
+                "        ({}) compadd -- {}; return ;;",
+                patterns.join("|"),
+                value_candidates(Shell::Zsh, &opt.values)
+            )
+            .ok();
+        }
+    }
+    out.push_str("    esac\n");
+    out.push_str("    case \"$cmdpath\" in\n");
+    for node in nodes {
+        writeln!(
+            out,
+            "        (\"{}\") compadd -- {} ;;",
+            node.path,
+            candidates(Shell::Zsh, &node.words, node.daemon_words)
+        )
+        .ok();
+    }
+    out.push_str("    esac\n}\n");
+    out.push_str(
+        "if [[ $funcstack[1] == _asusctl ]]; then\n    _asusctl \"$@\"\nelse\n    compdef _asusctl \
+         asusctl\nfi\n",
+    );
+    out
+}
+
+fn fish(nodes: &[Node]) -> String {
+    let mut out = String::new();
+    out.push_str("# fish completion for asusctl, from `asusctl completions fish`\n");
+    out.push_str("function __asusctl_complete\n");
+    out.push_str("    set -l words (commandline -opc)\n");
+    out.push_str("    set -e words[1]\n");
+    out.push_str("    set -l cmdpath asusctl\n");
+    out.push_str("    for word in $words\n");
+    out.push_str("        switch \"$cmdpath $word\"\n");
+    let paths: Vec<String> = subcommand_paths(nodes)
+        .iter()
+        .map(|p| format!("\"{p}\""))
+        .collect();
+    writeln!(out, "            case {}", paths.join(" ")).ok();
+    out.push_str("                set cmdpath \"$cmdpath $word\"\n");
+    out.push_str("        end\n    end\n");
+    out.push_str("    set -l prev $words[-1]\n");
+    out.push_str("    switch \"$cmdpath:$prev\"\n");
+    for node in nodes {
+        for opt in &node.value_options {
+            let patterns: Vec<String> = opt
+                .names
+                .iter()
+                .map(|name| format!("\"{}:{name}\"", node.path))
+                .collect();
+            writeln!(
+                out,
+                "        case {}\n            {}\n            return",
+                patterns.join(" "),
+                value_candidates(Shell::Fish, &opt.values)
+            )
+            .ok();
+        }
+    }
+    out.push_str("    end\n");
+    out.push_str("    switch \"$cmdpath\"\n");
+    for node in nodes {
+        writeln!(
+            out,
+            "        case \"{}\"\n            {}",
+            node.path,
+            candidates(Shell::Fish, &node.words, node.daemon_words)
+        )
+        .ok();
+    }
+    out.push_str("    end\nend\n");
+    out.push_str("complete -c asusctl -f -a '(__asusctl_complete)'\n");
+    out
+}
+
+/// The completion script for `shell`
+pub fn script(shell: Shell) -> String {
+    let nodes = nodes();
+    match shell {
+        Shell::Bash => bash(&nodes),
+        Shell::Zsh => zsh(&nodes),
+        Shell::Fish => fish(&nodes),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::mode_commands;
+
+    #[test]
+    fn mode_commands_match_whole_name() {
+        let modes = ["Rain".to_owned(), "static".to_owned()];
+        assert_eq!(mode_commands(&modes), ["static", "rain"]);
+        assert_eq!(mode_commands(&["Rainbow".to_owned()]), ["rainbow"]);
+        assert!(mode_commands(&["rai".to_owned()]).is_empty());
+    }
+}
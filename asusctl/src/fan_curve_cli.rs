@@ -1,7 +1,8 @@
 use gumdrop::Options;
 use rog_platform::platform::ThrottlePolicy;
-use rog_profiles::fan_curve_set::CurveData;
+use rog_profiles::fan_curve_set::{CurveData, CURVE_TEMP_MAX};
 use rog_profiles::FanCurvePU;
+use serde_derive::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Options)]
 pub struct FanCurveCommand {
@@ -46,4 +47,91 @@ pub struct FanCurveCommand {
                 `--mod-profile` required. If '%' is omitted the fan range is 0-255"
     )]
     pub data: Option<CurveData>,
+
+    #[options(command)]
+    pub command: Option<FanCurveSubCommand>,
+}
+
+#[derive(Debug, Clone, Options)]
+pub enum FanCurveSubCommand {
+    #[options(help = "edit the curves of a profile, validating them before they are applied")]
+    Edit(FanCurveEdit),
+}
+
+#[derive(Debug, Clone, Options)]
+pub struct FanCurveEdit {
+    #[options(help = "print help message")]
+    pub help: bool,
+
+    #[options(meta = "", help = "profile to edit, defaults to the active profile")]
+    pub profile: Option<ThrottlePolicy>,
+
+    #[options(
+        meta = "",
+        help = "TOML file of curves to apply. Run `edit` with no curves to print the current ones \
+                in this format"
+    )]
+    pub file: Option<String>,
+
+    #[options(meta = "", help = "CPU fan curve, same format as `--data`")]
+    pub cpu: Option<CurveData>,
+
+    #[options(meta = "", help = "GPU fan curve, same format as `--data`")]
+    pub gpu: Option<CurveData>,
+
+    #[options(meta = "", help = "Mid fan curve, same format as `--data`")]
+    pub mid: Option<CurveData>,
+
+    #[options(no_short, help = "plot the edited curves and ask before applying them")]
+    pub graph: bool,
+}
+
+/// The layout of a curve file for `fan-curve edit --file`
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct CurveFile {
+    #[serde(default)]
+    pub curves: Vec<CurveData>,
+}
+
+const GRAPH_WIDTH: usize = 60;
+const GRAPH_HEIGHT: usize = 10;
+
+/// Plot the curves as fan percentage against temperature. Each fan is drawn
+/// with the first letter of its name, and where two fans overlap a `*` is
+/// drawn.
+pub fn plot_curves(curves: &[CurveData]) -> String {
+    let mut grid = [[' '; GRAPH_WIDTH]; GRAPH_HEIGHT + 1];
+    for curve in curves {
+        let mark = match curve.fan {
+            FanCurvePU::CPU => 'C',
+            FanCurvePU::GPU => 'G',
+            FanCurvePU::MID => 'M',
+        };
+        for x in 0..GRAPH_WIDTH {
+            let temp = (x * CURVE_TEMP_MAX as usize / (GRAPH_WIDTH - 1)) as u8;
+            let pwm = curve.pwm_at(temp) as usize;
+            let y = GRAPH_HEIGHT - (pwm * GRAPH_HEIGHT + 127) / 255;
+            let cell = &mut grid[y][x];
+            *cell = if *cell == ' ' || *cell == mark {
+                mark
+            } else {
+                '*'
+            };
+        }
+    }
+
+    let mut out = String::new();
+    for (row, line) in grid.iter().enumerate() {
+        let percent = (GRAPH_HEIGHT - row) * 100 / GRAPH_HEIGHT;
+        out.push_str(&format!("{percent:>4}% |"));
+        out.extend(line.iter());
+        out.push('\n');
+    }
+    out.push_str(&format!("      +{}\n", "-".repeat(GRAPH_WIDTH)));
+    out.push_str(&format!(
+        "       0c{:>width$}\n",
+        format!("{CURVE_TEMP_MAX}c"),
+        width = GRAPH_WIDTH - 2
+    ));
+    out
 }
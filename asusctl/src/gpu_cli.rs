@@ -0,0 +1,19 @@
+use gumdrop::Options;
+use rog_platform::platform::GpuMuxMode;
+
+#[derive(Options)]
+pub struct GpuCommand {
+    #[options(help = "print help message")]
+    pub help: bool,
+    #[options(help = "show the MUX mode and dGPU state")]
+    pub status: bool,
+    #[options(
+        meta = "",
+        help = "Set the MUX mode <integrated, dedicated>, applies after reboot"
+    )]
+    pub mux: Option<GpuMuxMode>,
+    #[options(help = "Power on the dGPU")]
+    pub enable_dgpu: bool,
+    #[options(help = "Power off the dGPU")]
+    pub disable_dgpu: bool,
+}
@@ -0,0 +1,60 @@
+use gumdrop::Options;
+use rog_aura::aura_detection::PowerZones;
+
+use crate::aura_cli::LedBrightness;
+
+#[derive(Options)]
+pub struct LedAutoCommand {
+    #[options(help = "print help message")]
+    pub help: bool,
+    #[options(help = "list the rules in the order they are checked")]
+    pub list: bool,
+    #[options(
+        meta = "",
+        help = "add a rule setting this brightness <off, low, med, high>"
+    )]
+    pub add: Option<LedBrightness>,
+    #[options(
+        meta = "",
+        help = "start time of the added rule <HH:MM>, default 00:00"
+    )]
+    pub start: Option<String>,
+    #[options(
+        meta = "",
+        help = "end time of the added rule <HH:MM>, default all day"
+    )]
+    pub end: Option<String>,
+    #[options(
+        no_short,
+        meta = "",
+        help = "only apply the added rule while the ambient light is below this many lux"
+    )]
+    pub below_lux: Option<u32>,
+    #[options(
+        no_short,
+        meta = "",
+        help = "power zones the added rule turns off <keyboard,logo,lightbar,lid,rear-glow>"
+    )]
+    pub zones_off: Option<String>,
+    #[options(meta = "", help = "remove the rule at this position in --list")]
+    pub remove: Option<usize>,
+    #[options(help = "remove all rules")]
+    pub clear: bool,
+}
+
+/// Parse a comma separated list of power zones
+pub fn parse_zones(zones: &str) -> Result<Vec<PowerZones>, String> {
+    zones
+        .split(',')
+        .map(|zone| match zone.trim().to_ascii_lowercase().as_str() {
+            "keyboard" => Ok(PowerZones::Keyboard),
+            "logo" => Ok(PowerZones::Logo),
+            "lightbar" => Ok(PowerZones::Lightbar),
+            "lid" => Ok(PowerZones::Lid),
+            "rear-glow" | "rearglow" => Ok(PowerZones::RearGlow),
+            _ => Err(format!(
+                "{zone} is not one of keyboard, logo, lightbar, lid, rear-glow"
+            )),
+        })
+        .collect()
+}
@@ -0,0 +1,36 @@
+use gumdrop::Options;
+
+#[derive(Options)]
+pub struct LedDbCommand {
+    #[options(help = "print help message")]
+    pub help: bool,
+    #[options(command)]
+    pub command: Option<LedDbSubCommand>,
+}
+
+#[derive(Options)]
+pub enum LedDbSubCommand {
+    #[options(help = "Check a LED support data file for errors without loading it")]
+    Check(LedDbCheck),
+    #[options(help = "Print the LED support data used for this laptop, including drop-ins")]
+    Dump(LedDbDump),
+    #[options(help = "Make asusd reload the LED support data and drop-ins")]
+    Reload(LedDbDump),
+}
+
+#[derive(Options)]
+pub struct LedDbCheck {
+    #[options(help = "print help message")]
+    pub help: bool,
+    #[options(
+        free,
+        help = "path to the file, e.g /etc/asusd/asusd_user_ledmodes.ron"
+    )]
+    pub file: Option<String>,
+}
+
+#[derive(Options)]
+pub struct LedDbDump {
+    #[options(help = "print help message")]
+    pub help: bool,
+}
@@ -0,0 +1,47 @@
+use gumdrop::Options;
+
+#[derive(Options)]
+pub struct LedPowCommand {
+    #[options(help = "print help message")]
+    pub help: bool,
+    #[options(command)]
+    pub command: Option<LedPowSubCommand>,
+}
+
+#[derive(Options)]
+pub enum LedPowSubCommand {
+    #[options(help = "Turn a zone on or off for some phases, leaving the rest as they are")]
+    Set(LedPowSet),
+    #[options(help = "Show which zones are lit in each phase")]
+    Show(LedPowShow),
+}
+
+#[derive(Options)]
+pub struct LedPowSet {
+    #[options(help = "print help message")]
+    pub help: bool,
+    #[options(meta = "", help = "<keyboard, logo, lightbar, lid, rear-glow>")]
+    pub zone: Option<String>,
+    #[options(meta = "", help = "lit during the boot animation <on/off>")]
+    pub boot: Option<String>,
+    #[options(meta = "", help = "lit while awake <on/off>")]
+    pub awake: Option<String>,
+    #[options(meta = "", help = "lit while suspended <on/off>")]
+    pub sleep: Option<String>,
+    #[options(no_short, meta = "", help = "lit while shutting down <on/off>")]
+    pub shutdown: Option<String>,
+}
+
+#[derive(Options)]
+pub struct LedPowShow {
+    #[options(help = "print help message")]
+    pub help: bool,
+}
+
+pub fn parse_on_off(state: &str) -> Result<bool, String> {
+    match state.trim().to_ascii_lowercase().as_str() {
+        "on" | "true" => Ok(true),
+        "off" | "false" => Ok(false),
+        _ => Err(format!("{state} is not one of on, off")),
+    }
+}
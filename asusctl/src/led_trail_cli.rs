@@ -0,0 +1,32 @@
+use gumdrop::Options;
+use rog_aura::Colour;
+
+#[derive(Options)]
+pub struct LedTrailCommand {
+    #[options(help = "print help message")]
+    pub help: bool,
+    #[options(help = "show the gradient and decay time")]
+    pub show: bool,
+    #[options(
+        meta = "",
+        help = "colours a pressed key fades through <hex,hex,...>, e.g. ffffff,00a0ff,000000"
+    )]
+    pub gradient: Option<String>,
+    #[options(meta = "", help = "time a key takes to fade out in milliseconds")]
+    pub decay_ms: Option<u32>,
+    #[options(help = "start the effect, it runs until a mode is set")]
+    pub run: bool,
+}
+
+/// Parse a comma separated list of hex colours
+pub fn parse_gradient(gradient: &str) -> Result<Vec<Colour>, String> {
+    gradient
+        .split(',')
+        .map(|colour| {
+            let colour = colour.trim().trim_start_matches('#');
+            colour
+                .parse()
+                .map_err(|_| format!("{colour} is not a hex colour such as ff00a0"))
+        })
+        .collect()
+}
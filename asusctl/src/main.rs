@@ -1,39 +1,83 @@
 use std::convert::TryFrom;
 use std::env::args;
+use std::io::Write;
 use std::path::Path;
 use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::thread::sleep;
 
 use anime_cli::{AnimeActions, AnimeCommand};
+use asusd::charge_schedule::{self, ChargePower, ChargeRule};
 use asusd::ctrl_fancurves::FAN_CURVE_ZBUS_NAME;
+use asusd::led_automation::LedRule;
+use asusd::settings_schema::{SETTING_AURA_ZONE, SETTING_LED_MODE, SETTING_PROFILE};
 use aura_cli::{LedPowerCommand1, LedPowerCommand2};
 use dmi_id::DMIID;
-use fan_curve_cli::FanCurveCommand;
+use fan_curve_cli::{plot_curves, CurveFile, FanCurveCommand, FanCurveEdit, FanCurveSubCommand};
 use gumdrop::{Opt, Options};
+use led_db_cli::{LedDbCommand, LedDbSubCommand};
 use rog_anime::usb::get_anime_type;
 use rog_anime::{AnimTime, AnimeDataBuffer, AnimeDiagonal, AnimeGif, AnimeImage, AnimeType, Vec2};
-use rog_aura::aura_detection::PowerZones;
-use rog_aura::keyboard::{AuraPowerState, LaptopAuraPower};
+use rog_aura::aura_detection::{LaptopLedData, LedSupportFile, PowerZones};
+use rog_aura::keyboard::{AuraPowerState, LaptopAuraPower, PowerPhase};
 use rog_aura::{self, AuraDeviceType, AuraEffect};
 use rog_dbus::zbus_anime::AnimeProxyBlocking;
 use rog_dbus::zbus_aura::AuraProxyBlocking;
+use rog_dbus::zbus_clients::ClientsProxyBlocking;
 use rog_dbus::zbus_fan_curves::FanCurvesProxyBlocking;
+use rog_dbus::zbus_gpu::GpuProxyBlocking;
 use rog_dbus::zbus_platform::PlatformProxyBlocking;
 use rog_dbus::zbus_slash::SlashProxyBlocking;
+use rog_dbus::RogDbusClientBlocking;
 use rog_platform::platform::{GpuMode, Properties, ThrottlePolicy};
 use rog_profiles::error::ProfileError;
+use rog_profiles::FanCurvePU;
 use rog_slash::SlashMode;
+use serde_json::{json, Map, Value};
 use zbus::blocking::Connection;
 
 use crate::aura_cli::{AuraPowerStates, LedBrightness};
+use crate::charge_cli::{ChargeCommand, ChargeScheduleCommand, ChargeSubCommand};
 use crate::cli_opts::*;
+use crate::completions_cli::{CompletionValues, CompletionsCommand};
+use crate::gpu_cli::GpuCommand;
+use crate::led_auto_cli::LedAutoCommand;
+use crate::led_pow_cli::{LedPowCommand, LedPowSubCommand};
+use crate::led_trail_cli::LedTrailCommand;
+use crate::preset_cli::{PresetCommand, PresetName, PresetSubCommand};
+use crate::settings_cli::SettingsCommand;
 use crate::slash_cli::SlashCommand;
+use crate::watch_cli::WatchCommand;
 
 mod anime_cli;
 mod aura_cli;
+mod charge_cli;
 mod cli_opts;
+mod completions_cli;
 mod fan_curve_cli;
+mod gpu_cli;
+mod led_auto_cli;
+mod led_db_cli;
+mod led_pow_cli;
+mod led_trail_cli;
+mod preset_cli;
+mod settings_cli;
 mod slash_cli;
+mod watch_cli;
+
+/// Set by `--json`, which keeps stdout for the JSON alone
+static JSON_OUTPUT: AtomicBool = AtomicBool::new(false);
+
+/// Print help and usage text, to stderr with `--json`
+macro_rules! usage {
+    ($($arg:tt)*) => {
+        if JSON_OUTPUT.load(Ordering::Relaxed) {
+            eprintln!($($arg)*);
+        } else {
+            println!($($arg)*);
+        }
+    };
+}
 
 fn main() {
     let args: Vec<String> = args().skip(1).collect();
@@ -51,6 +95,21 @@ fn main() {
         }
     };
 
+    JSON_OUTPUT.store(parsed.json, Ordering::Relaxed);
+
+    // Inspecting data files doesn't need the daemon
+    if let Some(CliCommand::LedDb(cmd)) = &parsed.command {
+        if let Err(err) = handle_led_db(cmd) {
+            println!("Error: {}", err);
+        }
+        return;
+    }
+    // Nor do completions, and they must not print the daemon errors below
+    if let Some(CliCommand::Completions(cmd)) = &parsed.command {
+        handle_completions(cmd);
+        return;
+    }
+
     let conn = Connection::system().unwrap();
     if let Ok(platform_proxy) = PlatformProxyBlocking::new(&conn).map_err(|e| {
         check_service("asusd");
@@ -66,6 +125,7 @@ fn main() {
 
         let supported_properties = platform_proxy.supported_properties().unwrap();
         let supported_interfaces = platform_proxy.supported_interfaces().unwrap();
+        register_client(&conn);
 
         if parsed.version {
             println!("asusctl v{}", env!("CARGO_PKG_VERSION"));
@@ -74,7 +134,11 @@ fn main() {
         }
 
         if let Err(err) = do_parsed(&parsed, &supported_interfaces, &supported_properties, conn) {
-            print_error_help(&*err, &supported_interfaces, &supported_properties);
+            if parsed.json {
+                println!("{}", json!({ "error": err.to_string() }));
+            } else {
+                print_error_help(&*err, &supported_interfaces, &supported_properties);
+            }
         }
     }
 }
@@ -118,40 +182,26 @@ fn check_service(name: &str) -> bool {
     false
 }
 
+/// Name the connection so asusd can attribute changes to asusctl
+fn register_client(conn: &Connection) {
+    ClientsProxyBlocking::new(conn)
+        .and_then(|proxy| proxy.register_client("asusctl"))
+        .ok();
+}
+
 fn find_aura_iface() -> Result<Vec<AuraProxyBlocking<'static>>, Box<dyn std::error::Error>> {
-    let conn = zbus::blocking::Connection::system().unwrap();
-    let f = zbus::blocking::fdo::ObjectManagerProxy::new(&conn, "org.asuslinux.Daemon", "/org")
-        .unwrap();
-    let interfaces = f.get_managed_objects().unwrap();
-    let mut aura_paths = Vec::new();
-    for v in interfaces.iter() {
-        // let o: Vec<zbus::names::OwnedInterfaceName> = v.1.keys().map(|e|
-        // e.to_owned()).collect(); println!("{}, {:?}", v.0, o);
-        for k in v.1.keys() {
-            if k.as_str() == "org.asuslinux.Aura" {
-                println!("Found aura device at {}, {}", v.0, k);
-                aura_paths.push(v.0.clone());
-            }
-        }
-    }
-    if aura_paths.len() > 1 {
-        println!("Multiple aura devices found: {aura_paths:?}");
-        println!("TODO: enable selection");
-    }
-    if !aura_paths.is_empty() {
-        let mut ctrl = Vec::new();
-        for path in aura_paths {
-            ctrl.push(
-                AuraProxyBlocking::builder(&conn)
-                    .path(path.clone())?
-                    .destination("org.asuslinux.Daemon")?
-                    .build()?,
-            );
-        }
-        return Ok(ctrl);
+    let ctrl = RogDbusClientBlocking::new("asusctl")?.aura_devices()?;
+    for proxy in &ctrl {
+        eprintln!("Found aura device at {}", proxy.inner().path());
     }
-
-    Err("No Aura interface".into())
+    if ctrl.len() > 1 {
+        eprintln!("Multiple aura devices found");
+        eprintln!("TODO: enable selection");
+    }
+    if ctrl.is_empty() {
+        return Err("No Aura interface".into());
+    }
+    Ok(ctrl)
 }
 
 fn do_parsed(
@@ -161,31 +211,43 @@ fn do_parsed(
     conn: Connection,
 ) -> Result<(), Box<dyn std::error::Error>> {
     match &parsed.command {
-        Some(CliCommand::LedMode(mode)) => handle_led_mode(&find_aura_iface()?, mode)?,
+        Some(CliCommand::LedMode(mode)) => handle_led_mode(&find_aura_iface()?, mode, parsed.json)?,
         Some(CliCommand::LedPow1(pow)) => handle_led_power1(&find_aura_iface()?, pow)?,
         Some(CliCommand::LedPow2(pow)) => handle_led_power2(&find_aura_iface()?, pow)?,
+        Some(CliCommand::LedPow(cmd)) => handle_led_pow(cmd, parsed.json)?,
+        Some(CliCommand::LedDb(cmd)) => handle_led_db(cmd)?,
+        Some(CliCommand::LedAuto(cmd)) => handle_led_auto(&find_aura_iface()?, cmd, parsed.json)?,
+        Some(CliCommand::LedTrail(cmd)) => handle_led_trail(&find_aura_iface()?, cmd, parsed.json)?,
+        Some(CliCommand::Preset(cmd)) => handle_preset(cmd, parsed.json)?,
+        Some(CliCommand::Completions(cmd)) => handle_completions(cmd),
         Some(CliCommand::Profile(cmd)) => {
-            handle_throttle_profile(&conn, supported_properties, cmd)?
+            handle_throttle_profile(&conn, supported_properties, cmd, parsed.json)?
         }
         Some(CliCommand::FanCurve(cmd)) => {
-            handle_fan_curve(&conn, supported_interfaces, cmd)?;
+            handle_fan_curve(&conn, supported_interfaces, cmd, parsed.json)?;
         }
         Some(CliCommand::Graphics(_)) => do_gfx(),
         Some(CliCommand::Anime(cmd)) => handle_anime(&conn, cmd)?,
         Some(CliCommand::Slash(cmd)) => handle_slash(&conn, cmd)?,
+        Some(CliCommand::Set(cmd)) => handle_set(&conn, cmd, parsed.json)?,
+        Some(CliCommand::Gpu(cmd)) => handle_gpu(&conn, cmd, parsed.json)?,
+        Some(CliCommand::Charge(cmd)) => handle_charge(&conn, cmd, parsed.json)?,
+        Some(CliCommand::Watch(cmd)) => handle_watch(&conn, cmd)?,
         Some(CliCommand::Bios(cmd)) => {
-            handle_platform_properties(&conn, supported_properties, cmd)?
+            handle_platform_properties(&conn, supported_properties, cmd, parsed.json)?
         }
         None => {
             if (!parsed.show_supported
+                && !parsed.effect_metrics
+                && !parsed.led_power_estimate
                 && parsed.kbd_bright.is_none()
                 && parsed.chg_limit.is_none()
                 && !parsed.next_kbd_bright
                 && !parsed.prev_kbd_bright)
                 || parsed.help
             {
-                println!("{}", CliStart::usage());
-                println!();
+                usage!("{}", CliStart::usage());
+                usage!();
                 if let Some(cmdlist) = CliStart::command_list() {
                     let dev_type = if let Ok(proxy) = find_aura_iface() {
                         // TODO: commands on all?
@@ -210,13 +272,13 @@ fn do_parsed(
                         }
                         true
                     }) {
-                        println!("{}", command);
+                        usage!("{}", command);
                     }
                 }
 
-                println!("\nExtra help can be requested on any command or subcommand:");
-                println!(" asusctl led-mode --help");
-                println!(" asusctl led-mode static --help");
+                usage!("\nExtra help can be requested on any command or subcommand:");
+                usage!(" asusctl led-mode --help");
+                usage!(" asusctl led-mode static --help");
             }
         }
     }
@@ -227,7 +289,11 @@ fn do_parsed(
                 match brightness.level() {
                     None => {
                         let level = aura.brightness()?;
-                        println!("Current keyboard led brightness: {level:?}");
+                        if parsed.json {
+                            println!("{}", json!({ "brightness": level }));
+                        } else {
+                            println!("Current keyboard led brightness: {level:?}");
+                        }
                     }
                     Some(level) => aura.set_brightness(rog_aura::LedBrightness::from(level))?,
                 }
@@ -259,7 +325,49 @@ fn do_parsed(
         }
     }
 
-    if parsed.show_supported {
+    if parsed.led_power_estimate {
+        if let Ok(aura) = find_aura_iface() {
+            for aura in aura.iter() {
+                if parsed.json {
+                    let estimate = json!({
+                        "total_mw": aura.estimated_power_mw()?,
+                        "zones_mw": aura.estimated_zone_power_mw()?,
+                        "suggestions": aura.power_saving_suggestions()?,
+                    });
+                    println!("{estimate}");
+                    continue;
+                }
+                println!("Estimated LED power: ~{} mW", aura.estimated_power_mw()?);
+                for (zone, mw) in aura.estimated_zone_power_mw()? {
+                    println!("  {zone:?}: ~{mw} mW");
+                }
+                for suggestion in aura.power_saving_suggestions()? {
+                    println!("- {suggestion}");
+                }
+            }
+        } else {
+            println!("No aura interface found");
+        }
+    }
+
+    if parsed.show_supported && parsed.json {
+        let aura = find_aura_iface().unwrap_or_default();
+        let aura = match aura.first() {
+            Some(aura) => json!({
+                "brightness": aura.supported_brightness()?,
+                "modes": aura.supported_basic_modes()?,
+                "zones": aura.supported_basic_zones()?,
+                "power_zones": aura.power_zone_descriptions()?,
+            }),
+            None => Value::Null,
+        };
+        let supported = json!({
+            "interfaces": supported_interfaces,
+            "properties": supported_properties,
+            "aura": aura,
+        });
+        println!("{supported}");
+    } else if parsed.show_supported {
         println!("Supported Core Functions:\n{:#?}", supported_interfaces);
         println!(
             "Supported Platform Properties:\n{:#?}",
@@ -270,7 +378,7 @@ fn do_parsed(
             let bright = aura.first().unwrap().supported_brightness()?;
             let modes = aura.first().unwrap().supported_basic_modes()?;
             let zones = aura.first().unwrap().supported_basic_zones()?;
-            let power = aura.first().unwrap().supported_power_zones()?;
+            let power = aura.first().unwrap().power_zone_descriptions()?;
             println!("Supported Keyboard Brightness:\n{:#?}", bright);
             println!("Supported Aura Modes:\n{:#?}", modes);
             println!("Supported Aura Zones:\n{:#?}", zones);
@@ -280,6 +388,11 @@ fn do_parsed(
         }
     }
 
+    if parsed.effect_metrics {
+        let proxy = PlatformProxyBlocking::new(&conn)?;
+        print!("{}", proxy.effect_metrics()?);
+    }
+
     if let Some(chg_limit) = parsed.chg_limit {
         let proxy = PlatformProxyBlocking::new(&conn)?;
         proxy.set_charge_control_end_threshold(chg_limit)?;
@@ -308,9 +421,9 @@ fn handle_anime(conn: &Connection, cmd: &AnimeCommand) -> Result<(), Box<dyn std
         && !cmd.clear)
         || cmd.help
     {
-        println!("Missing arg or command\n\n{}", cmd.self_usage());
+        usage!("Missing arg or command\n\n{}", cmd.self_usage());
         if let Some(lst) = cmd.self_command_list() {
-            println!("\n{}", lst);
+            usage!("\n{}", lst);
         }
     }
     let proxy = AnimeProxyBlocking::new(conn)?;
@@ -353,9 +466,9 @@ fn handle_anime(conn: &Connection, cmd: &AnimeCommand) -> Result<(), Box<dyn std
         match action {
             AnimeActions::Image(image) => {
                 if image.help_requested() || image.path.is_empty() {
-                    println!("Missing arg or command\n\n{}", image.self_usage());
+                    usage!("Missing arg or command\n\n{}", image.self_usage());
                     if let Some(lst) = image.self_command_list() {
-                        println!("\n{}", lst);
+                        usage!("\n{}", lst);
                     }
                     return Ok(());
                 }
@@ -374,9 +487,9 @@ fn handle_anime(conn: &Connection, cmd: &AnimeCommand) -> Result<(), Box<dyn std
             }
             AnimeActions::PixelImage(image) => {
                 if image.help_requested() || image.path.is_empty() {
-                    println!("Missing arg or command\n\n{}", image.self_usage());
+                    usage!("Missing arg or command\n\n{}", image.self_usage());
                     if let Some(lst) = image.self_command_list() {
-                        println!("\n{}", lst);
+                        usage!("\n{}", lst);
                     }
                     return Ok(());
                 }
@@ -392,10 +505,13 @@ fn handle_anime(conn: &Connection, cmd: &AnimeCommand) -> Result<(), Box<dyn std
                 proxy.write(matrix.into_data_buffer(anime_type)?)?;
             }
             AnimeActions::Gif(gif) => {
+                if let Some(file) = gif.file.first() {
+                    return stream_anime_file(&proxy, gif, Path::new(file), anime_type);
+                }
                 if gif.help_requested() || gif.path.is_empty() {
-                    println!("Missing arg or command\n\n{}", gif.self_usage());
+                    usage!("Missing arg or command\n\n{}", gif.self_usage());
                     if let Some(lst) = gif.self_command_list() {
-                        println!("\n{}", lst);
+                        usage!("\n{}", lst);
                     }
                     return Ok(());
                 }
@@ -427,9 +543,9 @@ fn handle_anime(conn: &Connection, cmd: &AnimeCommand) -> Result<(), Box<dyn std
             }
             AnimeActions::PixelGif(gif) => {
                 if gif.help_requested() || gif.path.is_empty() {
-                    println!("Missing arg or command\n\n{}", gif.self_usage());
+                    usage!("Missing arg or command\n\n{}", gif.self_usage());
                     if let Some(lst) = gif.self_command_list() {
-                        println!("\n{}", lst);
+                        usage!("\n{}", lst);
                     }
                     return Ok(());
                 }
@@ -458,10 +574,10 @@ fn handle_anime(conn: &Connection, cmd: &AnimeCommand) -> Result<(), Box<dyn std
             }
             AnimeActions::SetBuiltins(builtins) => {
                 if builtins.help_requested() || builtins.set.is_none() {
-                    println!("\nAny unspecified args will be set to default (first shown var)\n");
-                    println!("\n{}", builtins.self_usage());
+                    usage!("\nAny unspecified args will be set to default (first shown var)\n");
+                    usage!("\n{}", builtins.self_usage());
                     if let Some(lst) = builtins.self_command_list() {
-                        println!("\n{}", lst);
+                        usage!("\n{}", lst);
                     }
                     return Ok(());
                 }
@@ -478,6 +594,44 @@ fn handle_anime(conn: &Connection, cmd: &AnimeCommand) -> Result<(), Box<dyn std
     Ok(())
 }
 
+/// Levels the streamed frames are dithered to. The LEDs show little
+/// difference between nearby values so fewer levels with dithering look
+/// smoother than the full range.
+const STREAM_DITHER_LEVELS: u8 = 16;
+
+fn stream_anime_file(
+    proxy: &AnimeProxyBlocking,
+    gif: &anime_cli::AnimeGif,
+    file: &Path,
+    anime_type: AnimeType,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let brightness = gif.brightness.unwrap_or(1.0);
+    verify_brightness(brightness);
+    let frames = rog_anime::load_animation(file, anime_type, brightness, STREAM_DITHER_LEVELS)?;
+
+    let shortest = frames
+        .iter()
+        .map(|f| f.delay().as_millis())
+        .min()
+        .unwrap_or(1000)
+        .max(1);
+    let fps = (1000 / shortest).clamp(1, u8::MAX as u128) as u8;
+    let fd = proxy.stream_frames(fps)?;
+    let mut socket = std::os::unix::net::UnixStream::from(std::os::fd::OwnedFd::from(fd));
+
+    println!("Streaming {} frames from {}", frames.len(), file.display());
+    loop {
+        for frame in &frames {
+            socket.write_all(frame.frame().data())?;
+            sleep(frame.delay());
+        }
+        if !gif.repeat {
+            break;
+        }
+    }
+    Ok(())
+}
+
 fn verify_brightness(brightness: f32) {
     if !(0.0..=1.0).contains(&brightness) {
         println!(
@@ -491,14 +645,16 @@ fn handle_slash(conn: &Connection, cmd: &SlashCommand) -> Result<(), Box<dyn std
     if (cmd.brightness.is_none()
         && cmd.interval.is_none()
         && cmd.slash_mode.is_none()
+        && cmd.off_when_suspended.is_none()
+        && cmd.off_at_boot.is_none()
         && !cmd.list
         && !cmd.enable
         && !cmd.disable)
         || cmd.help
     {
-        println!("Missing arg or command\n\n{}", cmd.self_usage());
+        usage!("Missing arg or command\n\n{}", cmd.self_usage());
         if let Some(lst) = cmd.self_command_list() {
-            println!("\n{}", lst);
+            usage!("\n{}", lst);
         }
     }
     let proxy = SlashProxyBlocking::new(conn)?;
@@ -517,6 +673,12 @@ fn handle_slash(conn: &Connection, cmd: &SlashCommand) -> Result<(), Box<dyn std
     if let Some(slash_mode) = cmd.slash_mode {
         proxy.set_slash_mode(slash_mode)?;
     }
+    if let Some(enable) = cmd.off_when_suspended {
+        proxy.set_off_when_suspended(enable)?;
+    }
+    if let Some(enable) = cmd.off_at_boot {
+        proxy.set_off_at_boot(enable)?;
+    }
     if cmd.list {
         let res = SlashMode::list();
         for p in &res {
@@ -527,16 +689,577 @@ fn handle_slash(conn: &Connection, cmd: &SlashCommand) -> Result<(), Box<dyn std
     Ok(())
 }
 
+fn handle_watch(conn: &Connection, cmd: &WatchCommand) -> Result<(), Box<dyn std::error::Error>> {
+    if cmd.help {
+        usage!("{}", cmd.self_usage());
+        return Ok(());
+    }
+    watch_cli::watch(conn, cmd)
+}
+
+fn handle_charge(
+    conn: &Connection,
+    cmd: &ChargeCommand,
+    json: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let proxy = PlatformProxyBlocking::new(conn)?;
+    match &cmd.command {
+        Some(ChargeSubCommand::Schedule(schedule)) => {
+            handle_charge_schedule(&proxy, schedule, json)?
+        }
+        None => {
+            if (!cmd.full_once && !cmd.cancel_full_once) || cmd.help {
+                usage!("Missing arg or command\n\n{}", cmd.self_usage());
+                if let Some(lst) = cmd.self_command_list() {
+                    usage!("\n{}", lst);
+                }
+            }
+        }
+    }
+    if cmd.full_once {
+        proxy.set_charge_full_once(true)?;
+        println!("Charging to 100% until the battery is full");
+    }
+    if cmd.cancel_full_once {
+        proxy.set_charge_full_once(false)?;
+    }
+    Ok(())
+}
+
+fn handle_charge_schedule(
+    proxy: &PlatformProxyBlocking,
+    cmd: &ChargeScheduleCommand,
+    json: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if (!cmd.list && cmd.add.is_none() && cmd.remove.is_none() && !cmd.clear) || cmd.help {
+        usage!("Missing arg or command\n\n{}", cmd.self_usage());
+        return Ok(());
+    }
+    let mut rules = proxy.charge_schedule()?;
+    let old = rules.clone();
+    if cmd.clear {
+        rules.clear();
+    }
+    if let Some(position) = cmd.remove {
+        if position == 0 || position > rules.len() {
+            return Err(format!("There is no rule {position}").into());
+        }
+        rules.remove(position - 1);
+    }
+    if let Some(limit) = cmd.add {
+        let time = |time: &Option<String>| match time {
+            Some(time) => charge_schedule::parse_time(time)
+                .ok_or_else(|| format!("{time} is not a time in the form HH:MM")),
+            None => Ok(0),
+        };
+        let start = time(&cmd.start)?;
+        let end = if cmd.end.is_some() {
+            time(&cmd.end)?
+        } else {
+            start
+        };
+        rules.push(ChargeRule {
+            limit,
+            start,
+            end,
+            power: cmd.power.unwrap_or_default(),
+        });
+    }
+    if rules != old {
+        proxy.set_charge_schedule(&rules)?;
+    }
+
+    if cmd.list && json {
+        println!("{}", json!({ "rules": rules }));
+    } else if cmd.list {
+        if rules.is_empty() {
+            println!("No charge schedule rules");
+        }
+        for (i, rule) in rules.iter().enumerate() {
+            let power = match rule.power {
+                ChargePower::Any => "",
+                ChargePower::Ac => " on AC",
+                ChargePower::Dock => " when docked",
+            };
+            let when = if rule.start == rule.end {
+                "all day".to_owned()
+            } else {
+                format!(
+                    "{}-{}",
+                    charge_schedule::format_time(rule.start),
+                    charge_schedule::format_time(rule.end)
+                )
+            };
+            println!("{}. {}% {when}{power}", i + 1, rule.limit);
+        }
+    }
+    Ok(())
+}
+
+fn handle_led_auto(
+    aura: &[AuraProxyBlocking],
+    cmd: &LedAutoCommand,
+    json: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if (!cmd.list && cmd.add.is_none() && cmd.remove.is_none() && !cmd.clear) || cmd.help {
+        usage!("Missing arg or command\n\n{}", cmd.self_usage());
+        return Ok(());
+    }
+    let time = |time: &Option<String>| match time {
+        Some(time) => charge_schedule::parse_time(time)
+            .ok_or_else(|| format!("{time} is not a time in the form HH:MM")),
+        None => Ok(0),
+    };
+    let zones_off = match &cmd.zones_off {
+        Some(zones) => led_auto_cli::parse_zones(zones)?,
+        None => Vec::new(),
+    };
+
+    for aura in aura {
+        let mut rules = aura.led_rules()?;
+        let old = rules.clone();
+        if cmd.clear {
+            rules.clear();
+        }
+        if let Some(position) = cmd.remove {
+            if position == 0 || position > rules.len() {
+                return Err(format!("There is no rule {position}").into());
+            }
+            rules.remove(position - 1);
+        }
+        if let Some(level) = cmd.add.as_ref().and_then(|b| b.level()) {
+            let start = time(&cmd.start)?;
+            let end = if cmd.end.is_some() {
+                time(&cmd.end)?
+            } else {
+                start
+            };
+            rules.push(LedRule {
+                brightness: rog_aura::LedBrightness::from(level),
+                start,
+                end,
+                below_lux: cmd.below_lux.unwrap_or_default(),
+                zones_off: zones_off.clone(),
+            });
+        }
+        if rules != old {
+            aura.set_led_rules(&rules)?;
+        }
+
+        if cmd.list && json {
+            println!(
+                "{}",
+                json!({ "rules": rules, "active": aura.active_led_rule()? })
+            );
+        } else if cmd.list {
+            if rules.is_empty() {
+                println!("No LED rules");
+            }
+            let active = aura.active_led_rule()? as usize;
+            for (i, rule) in rules.iter().enumerate() {
+                let when = if rule.start == rule.end {
+                    "all day".to_owned()
+                } else {
+                    format!(
+                        "{}-{}",
+                        charge_schedule::format_time(rule.start),
+                        charge_schedule::format_time(rule.end)
+                    )
+                };
+                let light = if rule.below_lux > 0 {
+                    format!(" below {} lux", rule.below_lux)
+                } else {
+                    String::new()
+                };
+                let zones = if rule.zones_off.is_empty() {
+                    String::new()
+                } else {
+                    format!(", {:?} off", rule.zones_off)
+                };
+                let shown = if active == i + 1 { " (active)" } else { "" };
+                println!(
+                    "{}. {:?} {when}{light}{zones}{shown}",
+                    i + 1,
+                    rule.brightness
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+fn handle_led_trail(
+    aura: &[AuraProxyBlocking],
+    cmd: &LedTrailCommand,
+    json: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if (!cmd.show && cmd.gradient.is_none() && cmd.decay_ms.is_none() && !cmd.run) || cmd.help {
+        usage!("Missing arg or command\n\n{}", cmd.self_usage());
+        return Ok(());
+    }
+    let gradient = match &cmd.gradient {
+        Some(gradient) => Some(led_trail_cli::parse_gradient(gradient)?),
+        None => None,
+    };
+
+    for aura in aura {
+        let mut trail = aura.typing_trail()?;
+        let old = trail.clone();
+        if let Some(gradient) = &gradient {
+            trail.gradient = gradient.clone();
+        }
+        if let Some(decay_ms) = cmd.decay_ms {
+            trail.decay_ms = decay_ms;
+        }
+        if trail != old {
+            aura.set_typing_trail(trail.clone())?;
+        }
+
+        if cmd.show && json {
+            println!("{}", json!(trail));
+        } else if cmd.show {
+            let colours: Vec<String> = trail
+                .gradient
+                .iter()
+                .map(|c| format!("{:02x}{:02x}{:02x}", c.r, c.g, c.b))
+                .collect();
+            println!("Gradient: {}", colours.join(","));
+            println!("Decay: {}ms", trail.decay_ms);
+        }
+        if cmd.run {
+            aura.run_typing_trail()?;
+        }
+    }
+    Ok(())
+}
+
+fn handle_preset(cmd: &PresetCommand, json: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let named = |sub: &PresetName| match &sub.name {
+        Some(name) if !sub.help => Some(name.clone()),
+        _ => {
+            usage!("{}", sub.self_usage());
+            None
+        }
+    };
+    match &cmd.command {
+        Some(PresetSubCommand::Save(save)) => {
+            let Some(name) = named(save) else {
+                return Ok(());
+            };
+            for aura in find_aura_iface()? {
+                aura.save_current_as_preset(&name)?;
+            }
+            println!("Saved the lighting as {name}");
+        }
+        Some(PresetSubCommand::Apply(apply)) => {
+            let Some(name) = named(apply) else {
+                return Ok(());
+            };
+            for aura in find_aura_iface()? {
+                aura.apply_preset(&name)?;
+            }
+        }
+        Some(PresetSubCommand::Remove(remove)) => {
+            let Some(name) = named(remove) else {
+                return Ok(());
+            };
+            for aura in find_aura_iface()? {
+                aura.remove_preset(&name)?;
+            }
+        }
+        Some(PresetSubCommand::List(list)) => {
+            if list.help {
+                usage!("{}", list.self_usage());
+                return Ok(());
+            }
+            for aura in find_aura_iface()? {
+                let presets = aura.presets()?;
+                if json {
+                    println!("{}", json!({ "presets": presets }));
+                } else if presets.is_empty() {
+                    println!("No saved presets");
+                } else {
+                    println!("{}", presets.join("\n"));
+                }
+            }
+        }
+        None => {
+            if !cmd.help {
+                usage!("Missing arg or command\n");
+            }
+            usage!("{}", cmd.self_usage());
+            if let Some(lst) = cmd.self_command_list() {
+                usage!("\n{}", lst);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn handle_led_pow(cmd: &LedPowCommand, json: bool) -> Result<(), Box<dyn std::error::Error>> {
+    match &cmd.command {
+        Some(LedPowSubCommand::Set(set)) => {
+            let phases = [
+                (PowerPhase::Boot, &set.boot),
+                (PowerPhase::Awake, &set.awake),
+                (PowerPhase::Sleep, &set.sleep),
+                (PowerPhase::Shutdown, &set.shutdown),
+            ];
+            let zone = match &set.zone {
+                Some(zone) if !set.help && phases.iter().any(|(_, state)| state.is_some()) => zone,
+                _ => {
+                    usage!("{}", set.self_usage());
+                    return Ok(());
+                }
+            };
+            let zone = match led_auto_cli::parse_zones(zone)?.as_slice() {
+                [zone] => *zone,
+                _ => return Err("Set one zone at a time".into()),
+            };
+            let mut cells = Vec::new();
+            for (phase, state) in phases {
+                if let Some(state) = state {
+                    cells.push((phase, led_pow_cli::parse_on_off(state)?));
+                }
+            }
+            for aura in find_aura_iface()? {
+                for (phase, on) in &cells {
+                    aura.set_led_power_state(zone, *phase, *on)?;
+                }
+            }
+        }
+        Some(LedPowSubCommand::Show(show)) => {
+            if show.help {
+                usage!("{}", show.self_usage());
+                return Ok(());
+            }
+            for aura in find_aura_iface()? {
+                let power = aura.led_power()?;
+                if json {
+                    println!("{}", json!(power));
+                    continue;
+                }
+                let on = |on: bool| if on { "on" } else { "off" };
+                println!("{:<20} boot awake sleep shutdown", "zone");
+                for state in &power.states {
+                    println!(
+                        "{:<20} {:<4} {:<5} {:<5} {}",
+                        format!("{:?}", state.zone),
+                        on(state.boot),
+                        on(state.awake),
+                        on(state.sleep),
+                        on(state.shutdown)
+                    );
+                }
+            }
+        }
+        None => {
+            if !cmd.help {
+                usage!("Missing arg or command\n");
+            }
+            usage!("{}", cmd.self_usage());
+            if let Some(lst) = cmd.self_command_list() {
+                usage!("\n{}", lst);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn handle_gpu(
+    conn: &Connection,
+    cmd: &GpuCommand,
+    json: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if (!cmd.status && cmd.mux.is_none() && !cmd.enable_dgpu && !cmd.disable_dgpu) || cmd.help {
+        usage!("Missing arg or command\n\n{}", cmd.self_usage());
+        return Ok(());
+    }
+    if cmd.enable_dgpu && cmd.disable_dgpu {
+        println!("Only one of --enable-dgpu or --disable-dgpu can be used");
+        return Ok(());
+    }
+    let proxy = GpuProxyBlocking::new(conn)?;
+    // Power the dGPU on before switching the MUX to it, and off after
+    // switching away
+    if cmd.enable_dgpu {
+        proxy.set_dgpu_disable(false)?;
+    }
+    if let Some(mode) = cmd.mux {
+        proxy.set_mux_mode(mode)?;
+        println!("MUX set to {mode}, reboot to apply");
+    }
+    if cmd.disable_dgpu {
+        proxy.set_dgpu_disable(true)?;
+    }
+    if cmd.status && json {
+        let status = json!({
+            "mux_mode": proxy.mux_mode().ok(),
+            "dgpu_disabled": proxy.dgpu_disable().ok(),
+        });
+        println!("{status}");
+    } else if cmd.status {
+        match proxy.mux_mode() {
+            Ok(mode) => println!("MUX mode: {mode}"),
+            Err(_) => println!("MUX mode: not supported"),
+        }
+        match proxy.dgpu_disable() {
+            Ok(disabled) => println!("dGPU disabled: {disabled}"),
+            Err(_) => println!("dGPU disable: not supported"),
+        }
+    }
+    Ok(())
+}
+
+fn handle_led_db(cmd: &LedDbCommand) -> Result<(), Box<dyn std::error::Error>> {
+    match &cmd.command {
+        Some(LedDbSubCommand::Check(check)) => {
+            let file = match &check.file {
+                Some(file) if !check.help => file,
+                _ => {
+                    usage!("{}", check.self_usage());
+                    return Ok(());
+                }
+            };
+            let text = std::fs::read_to_string(file)?;
+            let (data, issues) = LedSupportFile::validate(&text)?;
+            for issue in &issues {
+                println!("{file}: {issue}");
+            }
+            println!(
+                "{file}: {} entries, {} problems found",
+                data.get().len(),
+                issues.len()
+            );
+        }
+        Some(LedDbSubCommand::Dump(dump)) => {
+            if dump.help {
+                usage!("{}", dump.self_usage());
+                return Ok(());
+            }
+            let data = LaptopLedData::get_data();
+            if data.board_name.is_empty() {
+                println!("No LED support data matches this laptop, generic brightness only");
+            }
+            let config = ron::ser::PrettyConfig::new().depth_limit(2);
+            println!("{}", ron::ser::to_string_pretty(&data, config)?);
+        }
+        Some(LedDbSubCommand::Reload(reload)) => {
+            if reload.help {
+                usage!("{}", reload.self_usage());
+                return Ok(());
+            }
+            for aura in find_aura_iface()? {
+                aura.reload_support_data()?;
+            }
+            println!("LED support data reloaded");
+        }
+        None => {
+            if !cmd.help {
+                usage!("Missing arg or command\n");
+            }
+            usage!("{}", cmd.self_usage());
+            if let Some(lst) = cmd.self_command_list() {
+                usage!("\n{}", lst);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn handle_completions(cmd: &CompletionsCommand) {
+    if let Some(values) = cmd.values {
+        // Run by the completion scripts, which only want what can be found
+        for value in completion_values(values).unwrap_or_default() {
+            println!("{value}");
+        }
+        return;
+    }
+    match cmd.shell {
+        Some(shell) if !cmd.help => print!("{}", completions_cli::script(shell)),
+        _ => {
+            if !cmd.help {
+                usage!("Missing arg or command\n");
+            }
+            usage!("{}", cmd.self_usage());
+        }
+    }
+}
+
+fn completion_values(values: CompletionValues) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let conn = Connection::system()?;
+    let schema = match settings_cli::schema(&conn) {
+        Ok(schema) => schema,
+        // An older asusd, or none running. Offer everything asusctl knows.
+        Err(_) => return Ok(completions_cli::static_values(values)),
+    };
+    let names = |setting: &str| {
+        settings_cli::find(&schema, setting)
+            .map(|s| s.value_names())
+            .unwrap_or_default()
+    };
+    Ok(match values {
+        CompletionValues::Modes => completions_cli::mode_commands(&names(SETTING_LED_MODE)),
+        CompletionValues::Zones => names(SETTING_AURA_ZONE),
+        CompletionValues::Profiles => names(SETTING_PROFILE),
+        CompletionValues::Settings => schema
+            .iter()
+            .filter(|s| !s.property.is_empty())
+            .map(|s| s.name.clone())
+            .collect(),
+    })
+}
+
+fn handle_set(
+    conn: &Connection,
+    cmd: &SettingsCommand,
+    json: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if (cmd.args.is_empty() && !cmd.list) || cmd.help {
+        usage!("Missing arg or command\n\n{}", cmd.self_usage());
+        return Ok(());
+    }
+    let schema = settings_cli::schema(conn)?;
+    if cmd.list {
+        if json {
+            println!("{}", serde_json::to_string(&schema)?);
+            return Ok(());
+        }
+        for setting in &schema {
+            println!("{}: {}", setting.name, setting.description);
+            println!("    {}", setting.value_names().join(", "));
+        }
+        return Ok(());
+    }
+    let [name, value] = cmd.args.as_slice() else {
+        return Err("Expected a setting and a value, e.g. `asusctl set profile quiet`".into());
+    };
+    settings_cli::set(conn, settings_cli::find(&schema, name)?, value)
+}
+
 fn handle_led_mode(
     aura: &[AuraProxyBlocking],
     mode: &LedModeCommand,
+    json: bool,
 ) -> Result<(), Box<dyn std::error::Error>> {
+    if json && mode.command.is_none() && !mode.prev_mode && !mode.next_mode {
+        let state = match aura.first() {
+            Some(aura) => json!({
+                "mode": aura.led_mode()?,
+                "effect": aura.led_mode_data()?,
+                "supported_modes": aura.supported_basic_modes()?,
+            }),
+            None => Value::Null,
+        };
+        println!("{state}");
+        return Ok(());
+    }
     if mode.command.is_none() && !mode.prev_mode && !mode.next_mode {
         if !mode.help {
-            println!("Missing arg or command\n");
+            usage!("Missing arg or command\n");
         }
-        println!("{}\n", mode.self_usage());
-        println!("Commands available");
+        usage!("{}\n", mode.self_usage());
+        usage!("Commands available");
 
         if let Some(cmdlist) = LedModeCommand::command_list() {
             let commands: Vec<String> = cmdlist.lines().map(|s| s.to_owned()).collect();
@@ -557,11 +1280,11 @@ fn handle_led_mode(
                 // }
                 false
             }) {
-                println!("{}", command);
+                usage!("{}", command);
             }
         }
 
-        println!("\nHelp can also be requested on modes, e.g: static --help");
+        usage!("\nHelp can also be requested on modes, e.g: static --help");
         return Ok(());
     }
 
@@ -593,7 +1316,7 @@ fn handle_led_mode(
         }
     } else if let Some(mode) = mode.command.as_ref() {
         if mode.help_requested() {
-            println!("{}", mode.self_usage());
+            usage!("{}", mode.self_usage());
             return Ok(());
         }
         for aura in aura {
@@ -621,9 +1344,9 @@ fn handle_led_power1(
             && !power.lightbar
         {
             if !power.help {
-                println!("Missing arg or command\n");
+                usage!("Missing arg or command\n");
             }
-            println!("{}\n", power.self_usage());
+            usage!("{}\n", power.self_usage());
             return Ok(());
         }
 
@@ -676,25 +1399,25 @@ fn handle_led_power2(
 
         if power.command().is_none() {
             if !power.help {
-                println!("Missing arg or command\n");
+                usage!("Missing arg or command\n");
             }
-            println!("{}\n", power.self_usage());
-            println!("Commands available");
+            usage!("{}\n", power.self_usage());
+            usage!("Commands available");
 
             if let Some(cmdlist) = LedPowerCommand2::command_list() {
                 let commands: Vec<String> = cmdlist.lines().map(|s| s.to_owned()).collect();
                 for command in &commands {
-                    println!("{}", command);
+                    usage!("{}", command);
                 }
             }
 
-            println!("\nHelp can also be requested on commands, e.g: boot --help");
+            usage!("\nHelp can also be requested on commands, e.g: boot --help");
             return Ok(());
         }
 
         if let Some(pow) = power.command.as_ref() {
             if pow.help_requested() {
-                println!("{}", pow.self_usage());
+                usage!("{}", pow.self_usage());
                 return Ok(());
             }
 
@@ -732,6 +1455,7 @@ fn handle_throttle_profile(
     conn: &Connection,
     supported: &[Properties],
     cmd: &ProfileCommand,
+    json: bool,
 ) -> Result<(), Box<dyn std::error::Error>> {
     if !supported.contains(&Properties::ThrottlePolicy) {
         println!("Profiles not supported by either this kernel or by the laptop.");
@@ -740,12 +1464,12 @@ fn handle_throttle_profile(
 
     if !cmd.next && !cmd.list && cmd.profile_set.is_none() && !cmd.profile_get {
         if !cmd.help {
-            println!("Missing arg or command\n");
+            usage!("Missing arg or command\n");
         }
-        println!("{}", ProfileCommand::usage());
+        usage!("{}", ProfileCommand::usage());
 
         if let Some(lst) = cmd.self_command_list() {
-            println!("\n{}", lst);
+            usage!("\n{}", lst);
         }
         return Ok(());
     }
@@ -759,6 +1483,20 @@ fn handle_throttle_profile(
         proxy.set_throttle_thermal_policy(profile)?;
     }
 
+    if json {
+        let mut out = Map::new();
+        if cmd.list {
+            out.insert("profiles".into(), json!(ThrottlePolicy::list()));
+        }
+        if cmd.profile_get {
+            out.insert("profile".into(), json!(current));
+        }
+        if !out.is_empty() {
+            println!("{}", Value::Object(out));
+        }
+        return Ok(());
+    }
+
     if cmd.list {
         let res = ThrottlePolicy::list();
         for p in &res {
@@ -777,20 +1515,21 @@ fn handle_fan_curve(
     conn: &Connection,
     supported: &[String],
     cmd: &FanCurveCommand,
+    json: bool,
 ) -> Result<(), Box<dyn std::error::Error>> {
     if !supported.contains(&FAN_CURVE_ZBUS_NAME.to_string()) {
         println!("Fan-curves not supported by either this kernel or by the laptop.");
         return Err(ProfileError::NotSupported.into());
     }
 
-    if !cmd.get_enabled && !cmd.default && cmd.mod_profile.is_none() {
+    if !cmd.get_enabled && !cmd.default && cmd.mod_profile.is_none() && cmd.command.is_none() {
         if !cmd.help {
-            println!("Missing arg or command\n");
+            usage!("Missing arg or command\n");
         }
-        println!("{}", FanCurveCommand::usage());
+        usage!("{}", FanCurveCommand::usage());
 
         if let Some(lst) = cmd.self_command_list() {
-            println!("\n{}", lst);
+            usage!("\n{}", lst);
         }
         return Ok(());
     }
@@ -807,11 +1546,19 @@ fn handle_fan_curve(
 
     let plat_proxy = PlatformProxyBlocking::new(conn)?;
     let fan_proxy = FanCurvesProxyBlocking::new(conn)?;
+    if let Some(FanCurveSubCommand::Edit(edit)) = &cmd.command {
+        return handle_fan_curve_edit(&plat_proxy, &fan_proxy, edit);
+    }
+
     if cmd.get_enabled {
         let profile = plat_proxy.throttle_thermal_policy()?;
         let curves = fan_proxy.fan_curve_data(profile)?;
-        for curve in curves.iter() {
-            println!("{}", String::from(curve));
+        if json {
+            println!("{}", json!({ "profile": profile, "curves": curves }));
+        } else {
+            for curve in curves.iter() {
+                println!("{}", String::from(curve));
+            }
         }
     }
 
@@ -823,8 +1570,12 @@ fn handle_fan_curve(
     if let Some(profile) = cmd.mod_profile {
         if cmd.enable_fan_curves.is_none() && cmd.data.is_none() {
             let data = fan_proxy.fan_curve_data(profile)?;
-            let data = toml::to_string(&data)?;
-            println!("\nFan curves for {:?}\n\n{}", profile, data);
+            if json {
+                println!("{}", json!({ "profile": profile, "curves": data }));
+            } else {
+                let data = toml::to_string(&data)?;
+                println!("\nFan curves for {:?}\n\n{}", profile, data);
+            }
         }
 
         if let Some(enabled) = cmd.enable_fan_curves {
@@ -852,10 +1603,107 @@ fn handle_fan_curve(
     Ok(())
 }
 
+fn handle_fan_curve_edit(
+    plat_proxy: &PlatformProxyBlocking,
+    fan_proxy: &FanCurvesProxyBlocking,
+    cmd: &FanCurveEdit,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if cmd.help {
+        usage!("{}", FanCurveEdit::usage());
+        return Ok(());
+    }
+
+    let profile = match cmd.profile {
+        Some(profile) => profile,
+        None => plat_proxy.throttle_thermal_policy()?,
+    };
+    let current = fan_proxy.fan_curve_data(profile)?;
+
+    let mut edits = Vec::new();
+    if let Some(path) = &cmd.file {
+        let text = std::fs::read_to_string(path)?;
+        let file: CurveFile = toml::from_str(&text)?;
+        edits.extend(file.curves);
+    }
+    for (fan, curve) in [
+        (FanCurvePU::CPU, &cmd.cpu),
+        (FanCurvePU::GPU, &cmd.gpu),
+        (FanCurvePU::MID, &cmd.mid),
+    ] {
+        if let Some(curve) = curve {
+            let mut curve = curve.clone();
+            curve.set_fan(fan);
+            curve.enabled = true;
+            edits.push(curve);
+        }
+    }
+
+    if edits.is_empty() {
+        let file = CurveFile { curves: current };
+        println!("Fan curves for {profile:?}\n\n{}", toml::to_string(&file)?);
+        if cmd.graph {
+            println!("{}", plot_curves(&file.curves));
+        }
+        return Ok(());
+    }
+
+    // Later edits of the same fan replace earlier ones, so CLI args win over
+    // the file
+    let mut edited = current.clone();
+    for curve in edits {
+        match edited.iter_mut().find(|c| c.fan == curve.fan) {
+            Some(existing) => *existing = curve,
+            None => {
+                println!("{profile:?} has no {:?} fan curve", curve.fan);
+                return Err(ProfileError::NotSupported.into());
+            }
+        }
+    }
+    for curve in &edited {
+        curve.validate().map_err(|e| {
+            println!("{:?} fan curve: {e}", curve.fan);
+            e
+        })?;
+    }
+
+    if cmd.graph {
+        for curve in &edited {
+            println!("{}", String::from(curve));
+        }
+        println!("\n{}", plot_curves(&edited));
+        print!("Apply these curves to {profile:?}? [y/N] ");
+        std::io::stdout().flush()?;
+        let mut answer = String::new();
+        std::io::stdin().read_line(&mut answer)?;
+        if !answer.trim().eq_ignore_ascii_case("y") {
+            println!("Not applied");
+            return Ok(());
+        }
+    }
+
+    // The daemon takes one curve at a time, so if any fails put back the ones
+    // already written to leave the profile as it was
+    for (i, curve) in edited.iter().enumerate() {
+        if let Err(e) = fan_proxy.set_fan_curve(profile, curve.clone()) {
+            println!(
+                "Setting {:?} fan curve failed, restoring previous curves",
+                curve.fan
+            );
+            for old in current.iter().take(i) {
+                fan_proxy.set_fan_curve(profile, old.clone()).ok();
+            }
+            return Err(e.into());
+        }
+    }
+    println!("Fan curves for {profile:?} applied");
+    Ok(())
+}
+
 fn handle_platform_properties(
     conn: &Connection,
     supported: &[Properties],
     cmd: &BiosCommand,
+    json: bool,
 ) -> Result<(), Box<dyn std::error::Error>> {
     {
         if (cmd.gpu_mux_mode_set.is_none()
@@ -866,7 +1714,7 @@ fn handle_platform_properties(
             && !cmd.panel_overdrive_get)
             || cmd.help
         {
-            println!("Missing arg or command\n");
+            usage!("Missing arg or command\n");
 
             let usage: Vec<String> = BiosCommand::usage().lines().map(|s| s.to_owned()).collect();
 
@@ -875,18 +1723,23 @@ fn handle_platform_properties(
                     || line.contains("GPU") && supported.contains(&Properties::GpuMuxMode)
                     || line.contains("panel") && supported.contains(&Properties::PanelOd)
             }) {
-                println!("{}", line);
+                usage!("{}", line);
             }
         }
 
         let proxy = PlatformProxyBlocking::new(conn)?;
+        let mut out = Map::new();
 
         if let Some(opt) = cmd.post_sound_set {
             proxy.set_boot_sound(opt)?;
         }
         if cmd.post_sound_get {
             let res = proxy.boot_sound()?;
-            println!("Bios POST sound on: {}", res);
+            if json {
+                out.insert("post_sound".into(), json!(res));
+            } else {
+                println!("Bios POST sound on: {}", res);
+            }
         }
 
         if let Some(opt) = cmd.gpu_mux_mode_set {
@@ -899,7 +1752,11 @@ fn handle_platform_properties(
         }
         if cmd.gpu_mux_mode_get {
             let res = proxy.gpu_mux_mode()?;
-            println!("Bios GPU MUX: {:?}", res);
+            if json {
+                out.insert("gpu_mux_mode".into(), json!(res));
+            } else {
+                println!("Bios GPU MUX: {:?}", res);
+            }
         }
 
         if let Some(opt) = cmd.panel_overdrive_set {
@@ -907,7 +1764,14 @@ fn handle_platform_properties(
         }
         if cmd.panel_overdrive_get {
             let res = proxy.panel_od()?;
-            println!("Panel overdrive on: {}", res);
+            if json {
+                out.insert("panel_overdrive".into(), json!(res));
+            } else {
+                println!("Panel overdrive on: {}", res);
+            }
+        }
+        if !out.is_empty() {
+            println!("{}", Value::Object(out));
         }
     }
     Ok(())
@@ -0,0 +1,35 @@
+use gumdrop::Options;
+
+#[derive(Options)]
+pub struct PresetCommand {
+    #[options(help = "print help message")]
+    pub help: bool,
+    #[options(command)]
+    pub command: Option<PresetSubCommand>,
+}
+
+#[derive(Options)]
+pub enum PresetSubCommand {
+    #[options(help = "Save the lighting as it is now, replacing a preset of the same name")]
+    Save(PresetName),
+    #[options(help = "Switch the lighting to a saved preset")]
+    Apply(PresetName),
+    #[options(help = "Delete a saved preset")]
+    Remove(PresetName),
+    #[options(help = "List the saved presets")]
+    List(PresetList),
+}
+
+#[derive(Options)]
+pub struct PresetName {
+    #[options(help = "print help message")]
+    pub help: bool,
+    #[options(free, help = "name of the preset")]
+    pub name: Option<String>,
+}
+
+#[derive(Options)]
+pub struct PresetList {
+    #[options(help = "print help message")]
+    pub help: bool,
+}
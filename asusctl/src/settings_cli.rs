@@ -0,0 +1,65 @@
+//! Settings set by name from the schema asusd describes, so a setting or value
+//! added to the daemon can be used before asusctl has a command for it.
+
+use asusd::settings_schema::{SettingSchema, SettingValue};
+use gumdrop::Options;
+use rog_dbus::zbus_platform::PlatformProxyBlocking;
+use zbus::blocking::fdo::PropertiesProxy;
+use zbus::blocking::Connection;
+use zbus::names::InterfaceName;
+use zbus::zvariant::Value;
+
+#[derive(Options)]
+pub struct SettingsCommand {
+    #[options(help = "print help message")]
+    pub help: bool,
+    #[options(help = "list the settings of this laptop and the values each takes")]
+    pub list: bool,
+    #[options(free, help = "the setting and its value, e.g. `profile quiet`")]
+    pub args: Vec<String>,
+}
+
+/// What asusd says can be set, an error if it is too old to say
+pub fn schema(conn: &Connection) -> Result<Vec<SettingSchema>, Box<dyn std::error::Error>> {
+    Ok(PlatformProxyBlocking::new(conn)?.settings_schema()?)
+}
+
+pub fn find<'a>(
+    schema: &'a [SettingSchema],
+    name: &str,
+) -> Result<&'a SettingSchema, Box<dyn std::error::Error>> {
+    schema
+        .iter()
+        .find(|s| s.name.eq_ignore_ascii_case(name))
+        .ok_or_else(|| {
+            format!("{name} is not a setting of this laptop, see `asusctl set --list`").into()
+        })
+}
+
+/// Write `value` to the property `setting` describes
+pub fn set(
+    conn: &Connection,
+    setting: &SettingSchema,
+    value: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if setting.property.is_empty() {
+        return Err(format!(
+            "{} can only be given as part of another setting",
+            setting.name
+        )
+        .into());
+    }
+    let Some(SettingValue { value, .. }) = setting.value(value) else {
+        return Err(format!("{value} is not one of {}", setting.value_names().join(", ")).into());
+    };
+    let proxy = PropertiesProxy::builder(conn)
+        .destination("org.asuslinux.Daemon")?
+        .path(setting.path.as_str())?
+        .build()?;
+    proxy.set(
+        InterfaceName::try_from(setting.interface.as_str())?,
+        &setting.property,
+        &Value::from(*value),
+    )?;
+    Ok(())
+}
@@ -15,6 +15,18 @@ pub struct SlashCommand {
     pub interval: Option<u8>,
     #[options(help = "Set SlashMode (so 'list' for all options)")]
     pub slash_mode: Option<SlashMode>,
+    #[options(
+        no_short,
+        meta = "",
+        help = "turn the Slash off when the laptop suspends"
+    )]
+    pub off_when_suspended: Option<bool>,
+    #[options(
+        no_short,
+        meta = "",
+        help = "keep the Slash off through boot until asusd starts"
+    )]
+    pub off_at_boot: Option<bool>,
     #[options(help = "list available animations")]
     pub list: bool,
 }
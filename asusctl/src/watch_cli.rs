@@ -0,0 +1,187 @@
+//! Every signal asusd sends, printed as one JSON object per line so shell
+//! scripts can act on changes without a D-Bus client of their own.
+
+use std::collections::HashMap;
+
+use gumdrop::Options;
+use serde_json::{json, Map, Value as Json};
+use zbus::blocking::fdo::DBusProxy;
+use zbus::blocking::{Connection, MessageIterator};
+use zbus::message::Type;
+use zbus::names::BusName;
+use zbus::zvariant::{OwnedValue, Structure, Value};
+use zbus::MatchRule;
+
+const DAEMON: &str = "org.asuslinux.Daemon";
+const PROPERTIES: &str = "org.freedesktop.DBus.Properties";
+const DBUS: &str = "org.freedesktop.DBus";
+const NAME_OWNER_CHANGED: &str = "NameOwnerChanged";
+
+#[derive(Options)]
+pub struct WatchCommand {
+    #[options(help = "print help message")]
+    pub help: bool,
+    #[options(
+        meta = "",
+        help = "only print events of interfaces with this in their name, e.g. `aura`"
+    )]
+    pub interface: Option<String>,
+}
+
+/// Print the events from asusd, over restarts of it. A property change is
+/// printed as `{"interface", "path", "property", "value"}` for each property,
+/// any other signal as `{"interface", "path", "signal", "args"}`.
+pub fn watch(conn: &Connection, cmd: &WatchCommand) -> Result<(), Box<dyn std::error::Error>> {
+    let dbus = DBusProxy::new(conn)?;
+    // The bus follows the well known name, but the signals carry the unique
+    // name of asusd, which changes when it is restarted
+    dbus.add_match_rule(
+        MatchRule::builder()
+            .msg_type(Type::Signal)
+            .sender(DAEMON)?
+            .build(),
+    )?;
+    dbus.add_match_rule(
+        MatchRule::builder()
+            .msg_type(Type::Signal)
+            .sender(DBUS)?
+            .member(NAME_OWNER_CHANGED)?
+            .arg(0, DAEMON)?
+            .build(),
+    )?;
+    let mut owner = dbus
+        .get_name_owner(BusName::try_from(DAEMON)?)
+        .ok()
+        .map(|owner| owner.to_string());
+    let filter = cmd.interface.as_ref().map(|i| i.to_lowercase());
+
+    for msg in MessageIterator::from(conn) {
+        let msg = msg?;
+        let header = msg.header();
+        let (Some(path), Some(interface), Some(member)) =
+            (header.path(), header.interface(), header.member())
+        else {
+            continue;
+        };
+        if interface.as_str() == DBUS && member.as_str() == NAME_OWNER_CHANGED {
+            if let Ok((name, _, new_owner)) = msg.body().deserialize::<(String, String, String)>() {
+                if name == DAEMON {
+                    owner = (!new_owner.is_empty()).then_some(new_owner);
+                }
+            }
+            continue;
+        }
+        if header.sender().map(|s| s.as_str()) != owner.as_deref() {
+            continue;
+        }
+        let events = if interface.as_str() == PROPERTIES {
+            let Ok((interface, changed, _)) =
+                msg.body()
+                    .deserialize::<(String, HashMap<String, OwnedValue>, Vec<String>)>()
+            else {
+                continue;
+            };
+            changed
+                .iter()
+                .map(|(property, value)| {
+                    json!({
+                        "interface": interface,
+                        "path": path.as_str(),
+                        "property": property,
+                        "value": to_json(value),
+                    })
+                })
+                .collect()
+        } else {
+            let args: Vec<Json> = msg
+                .body()
+                .deserialize::<Structure>()
+                .map(|s| s.fields().iter().map(to_json).collect())
+                .unwrap_or_default();
+            vec![json!({
+                "interface": interface.as_str(),
+                "path": path.as_str(),
+                "signal": member.as_str(),
+                "args": args,
+            })]
+        };
+        for event in events {
+            let wanted = filter.as_ref().map_or(true, |f| {
+                event["interface"]
+                    .as_str()
+                    .is_some_and(|i| i.to_lowercase().contains(f))
+            });
+            if wanted {
+                println!("{event}");
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The JSON for a D-Bus value. Structures are arrays of their fields and
+/// dictionary keys are made strings.
+fn to_json(value: &Value<'_>) -> Json {
+    match value {
+        Value::U8(v) => json!(v),
+        Value::Bool(v) => json!(v),
+        Value::I16(v) => json!(v),
+        Value::U16(v) => json!(v),
+        Value::I32(v) => json!(v),
+        Value::U32(v) => json!(v),
+        Value::I64(v) => json!(v),
+        Value::U64(v) => json!(v),
+        Value::F64(v) => json!(v),
+        Value::Str(v) => json!(v.as_str()),
+        Value::Signature(v) => json!(v.as_str()),
+        Value::ObjectPath(v) => json!(v.as_str()),
+        Value::Value(v) => to_json(v),
+        Value::Array(v) => Json::Array(v.iter().map(to_json).collect()),
+        Value::Structure(v) => Json::Array(v.fields().iter().map(to_json).collect()),
+        Value::Dict(v) => {
+            let map: Map<String, Json> = v
+                .iter()
+                .map(|(key, value)| {
+                    let key = match to_json(key) {
+                        Json::String(key) => key,
+                        key => key.to_string(),
+                    };
+                    (key, to_json(value))
+                })
+                .collect();
+            Json::Object(map)
+        }
+        // File descriptors mean nothing outside of asusd
+        _ => Json::Null,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde_json::json;
+    use zbus::zvariant::{ObjectPath, Value};
+
+    use super::to_json;
+
+    #[test]
+    fn values_to_json() {
+        assert_eq!(to_json(&Value::from(7u32)), json!(7));
+        assert_eq!(to_json(&Value::from("Quiet")), json!("Quiet"));
+        let path = ObjectPath::from_static_str_unchecked("/org/asuslinux");
+        assert_eq!(to_json(&Value::from(path)), json!("/org/asuslinux"));
+        let variant = Value::Value(Box::new(Value::from(true)));
+        assert_eq!(to_json(&variant), json!(true));
+        assert_eq!(to_json(&Value::from(vec![1u8, 2])), json!([1, 2]));
+        assert_eq!(to_json(&Value::from((1u8, "a"))), json!([1, "a"]));
+    }
+
+    #[test]
+    fn dict_keys_made_strings() {
+        let dict: HashMap<u32, &str> = [(3, "b")].into_iter().collect();
+        assert_eq!(to_json(&Value::from(dict)), json!({"3": "b"}));
+        let dict: HashMap<&str, f64> = [("a", 0.5)].into_iter().collect();
+        assert_eq!(to_json(&Value::from(dict)), json!({"a": 0.5}));
+    }
+}
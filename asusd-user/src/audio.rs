@@ -0,0 +1,154 @@
+//! Tells asusd which audio output is in use for the Aura audio indicator. The
+//! sound server belongs to the user session, so it is asked with `pactl`,
+//! which PulseAudio and PipeWire both answer.
+
+use std::collections::HashMap;
+use std::process::Command;
+use std::time::Duration;
+
+use log::{info, warn};
+use rog_aura::audio_indicator::AudioOutput;
+use rog_dbus::zbus_aura::{AuraDevicesProxyBlocking, AuraProxyBlocking};
+use zbus::blocking::{Connection, MessageIterator};
+use zbus::message::Type;
+use zbus::zvariant::OwnedValue;
+use zbus::{CacheProperties, MatchRule};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+const AURA_INTERFACE: &str = "org.asuslinux.Aura";
+const PROPERTIES: &str = "org.freedesktop.DBus.Properties";
+
+fn pactl(args: &[&str]) -> Option<String> {
+    let output = Command::new("pactl").args(args).output().ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// The active port of `sink` from the output of `pactl list sinks`
+fn active_port<'a>(sinks: &'a str, sink: &str) -> Option<&'a str> {
+    let block = sinks
+        .split("\n\n")
+        .find(|block| block.lines().any(|l| l.trim() == format!("Name: {sink}")))?;
+    block
+        .lines()
+        .find_map(|l| l.trim().strip_prefix("Active Port:"))
+        .map(str::trim)
+}
+
+/// Guess the kind of output from the sink and port names, such as
+/// `alsa_output.pci-0000_00_1f.3.analog-stereo` and `analog-output-headphones`
+fn classify(sink: &str, port: &str) -> AudioOutput {
+    let sink = sink.to_lowercase();
+    let port = port.to_lowercase();
+    let either = |s: &str| sink.contains(s) || port.contains(s);
+    if either("hdmi") || either("displayport") {
+        AudioOutput::Hdmi
+    } else if either("headphone") || either("headset") || sink.starts_with("bluez_") {
+        AudioOutput::Headphones
+    } else if either("speaker") || either("analog-output") || either("analog-stereo") {
+        AudioOutput::Speakers
+    } else {
+        AudioOutput::Unknown
+    }
+}
+
+fn default_output() -> AudioOutput {
+    let Some(sink) = pactl(&["get-default-sink"]) else {
+        return AudioOutput::Unknown;
+    };
+    let sink = sink.trim();
+    let sinks = pactl(&["list", "sinks"]).unwrap_or_default();
+    classify(sink, active_port(&sinks, sink).unwrap_or_default())
+}
+
+/// The connected Aura devices with the indicator turned on
+fn indicator_devices(conn: &Connection) -> zbus::Result<Vec<AuraProxyBlocking<'static>>> {
+    let mut devices = Vec::new();
+    for device in AuraDevicesProxyBlocking::new(conn)?.known_devices()? {
+        if !device.connected {
+            continue;
+        }
+        let aura = AuraProxyBlocking::builder(conn)
+            .path(device.dbus_path)?
+            .cache_properties(CacheProperties::No)
+            .build()?;
+        if aura.audio_indicator().is_ok_and(|i| i.enabled) {
+            devices.push(aura);
+        }
+    }
+    Ok(devices)
+}
+
+/// Changes of the properties of every Aura device
+fn aura_property_changes(conn: &Connection) -> zbus::Result<MessageIterator> {
+    let rule = MatchRule::builder()
+        .msg_type(Type::Signal)
+        .interface(PROPERTIES)?
+        .member("PropertiesChanged")?
+        .arg(0, AURA_INTERFACE)?
+        .build();
+    MessageIterator::for_match_rule(rule, conn, None)
+}
+
+/// Block until the `AudioIndicator` of an Aura device changes
+fn wait_for_indicator(changes: &mut MessageIterator) {
+    for msg in changes {
+        let Ok(msg) = msg else {
+            continue;
+        };
+        let changed = msg
+            .body()
+            .deserialize::<(String, HashMap<String, OwnedValue>, Vec<String>)>();
+        if changed.is_ok_and(|(_, changed, _)| changed.contains_key("AudioIndicator")) {
+            return;
+        }
+    }
+}
+
+/// Poll the default audio sink on a new thread, sending its kind to each Aura
+/// device showing the indicator whenever it changes. While no device shows it
+/// the thread waits for one to be turned on instead.
+pub fn forward_audio_output(conn: Connection) {
+    std::thread::Builder::new()
+        .name("Audio output".into())
+        .spawn(move || {
+            info!("Forwarding the audio output to the Aura audio indicator");
+            let mut changes = aura_property_changes(&conn)
+                .map_err(|e| warn!("Could not watch the Aura audio indicator: {e}"))
+                .ok();
+            let mut last = AudioOutput::Unknown;
+            let mut sent_to = 0;
+            loop {
+                let devices = match indicator_devices(&conn) {
+                    Ok(devices) => devices,
+                    Err(e) => {
+                        warn!("Could not find the Aura devices: {e}");
+                        std::thread::sleep(POLL_INTERVAL);
+                        continue;
+                    }
+                };
+                if devices.is_empty() {
+                    sent_to = 0;
+                    match &mut changes {
+                        Some(changes) => wait_for_indicator(changes),
+                        None => std::thread::sleep(POLL_INTERVAL),
+                    }
+                    continue;
+                }
+                let output = default_output();
+                // Also sent when an indicator has been turned on
+                if output != last || devices.len() != sent_to {
+                    for aura in &devices {
+                        aura.set_audio_output(output).ok();
+                    }
+                    last = output;
+                    sent_to = devices.len();
+                }
+                std::thread::sleep(POLL_INTERVAL);
+            }
+        })
+        .map_err(|e| warn!("Could not start audio output thread: {e}"))
+        .ok();
+}
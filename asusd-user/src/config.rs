@@ -5,6 +5,7 @@ use config_traits::{StdConfig, StdConfigLoad};
 use rog_anime::{ActionLoader, AnimTime, AnimeType, Fade, Sequences as AnimeSequences, Vec2};
 use rog_aura::effects::{AdvancedEffects as AuraSequences, Breathe, DoomFlicker, Effect, Static};
 use rog_aura::keyboard::LedCode;
+use rog_aura::user_override::AuraOverride;
 use rog_aura::{Colour, Speed};
 use serde_derive::{Deserialize, Serialize};
 
@@ -210,6 +211,9 @@ pub struct ConfigBase {
     pub active_anime: Option<String>,
     /// Name of active aura config file in the user config directory
     pub active_aura: Option<String>,
+    /// Mode and brightness shown on the Aura devices in place of the system
+    /// lighting while this user's session is the active one
+    pub aura_override: Option<AuraOverride>,
 }
 
 impl StdConfig for ConfigBase {
@@ -217,6 +221,7 @@ impl StdConfig for ConfigBase {
         Self {
             active_anime: Some("anime-default".to_owned()),
             active_aura: Some("aura-default".to_owned()),
+            aura_override: None,
         }
     }
 
@@ -14,6 +14,7 @@ use zbus::zvariant::{ObjectPath, Type};
 
 use crate::config::ConfigAnime;
 use crate::error::Error;
+use crate::session::SessionActive;
 
 #[derive(Debug, Clone, Deserialize, Serialize, Type)]
 pub struct Timer {
@@ -63,6 +64,7 @@ pub struct CtrlAnimeInner<'a> {
     sequences: Sequences,
     client: AnimeProxyBlocking<'a>,
     do_early_return: Arc<AtomicBool>,
+    session: SessionActive,
 }
 
 impl<'a> CtrlAnimeInner<'static> {
@@ -70,18 +72,27 @@ impl<'a> CtrlAnimeInner<'static> {
         sequences: Sequences,
         client: AnimeProxyBlocking<'static>,
         do_early_return: Arc<AtomicBool>,
+        session: SessionActive,
     ) -> Result<Self, Error> {
         Ok(Self {
             sequences,
             client,
             do_early_return,
+            session,
         })
     }
 
+    /// Stop the sequence, for a change to it or as the session went to the
+    /// background
+    fn stop(&self) -> bool {
+        self.do_early_return.load(Ordering::Acquire) || !self.session.is_active()
+    }
+
     /// To be called on each main loop iteration to pump out commands to the
-    /// anime
+    /// anime. Returns at once while stopped, the caller should wait for the
+    /// session with `SessionActive::wait_active`.
     pub fn run(&'a self) -> Result<(), Error> {
-        if self.do_early_return.load(Ordering::SeqCst) {
+        if self.stop() {
             return Ok(());
         }
 
@@ -89,7 +100,7 @@ impl<'a> CtrlAnimeInner<'static> {
             match action {
                 ActionData::Animation(frames) => {
                     rog_anime::run_animation(frames, &|output| {
-                        if self.do_early_return.load(Ordering::Acquire) {
+                        if self.stop() {
                             return Ok(true); // Do safe exit
                         }
                         self.client
@@ -104,7 +115,7 @@ impl<'a> CtrlAnimeInner<'static> {
                 ActionData::Pause(duration) => {
                     let start = Instant::now();
                     'pause: loop {
-                        if self.do_early_return.load(Ordering::SeqCst) {
+                        if self.stop() {
                             return Ok(());
                         }
                         if Instant::now().duration_since(start) > *duration {
@@ -130,6 +141,7 @@ pub struct CtrlAnime<'a> {
     inner: Arc<Mutex<CtrlAnimeInner<'a>>>,
     /// Must be the same Atomic as in CtrlAnimeInner
     inner_early_return: Arc<AtomicBool>,
+    session: SessionActive,
 }
 
 impl CtrlAnime<'static> {
@@ -138,12 +150,14 @@ impl CtrlAnime<'static> {
         inner: Arc<Mutex<CtrlAnimeInner<'static>>>,
         client: AnimeProxyBlocking<'static>,
         inner_early_return: Arc<AtomicBool>,
+        session: SessionActive,
     ) -> Result<Self, Error> {
         Ok(CtrlAnime {
             config,
             client,
             inner,
             inner_early_return,
+            session,
         })
     }
 
@@ -169,6 +183,11 @@ impl CtrlAnime<'static> {
 // - Do actions
 // - Write config if required
 // - Unset inner_early_return
+//
+// Changes to the sequence are kept while the session is in the background and
+// shown once it is active again, as the runner waits for the session rather
+// than `inner_early_return`. Only `set_state`, which writes to asusd, is
+// refused.
 #[interface(name = "org.asuslinux.Daemon")]
 impl CtrlAnime<'static> {
     pub fn insert_asus_gif(
@@ -348,6 +367,11 @@ impl CtrlAnime<'static> {
     }
 
     pub fn set_state(&mut self, on: bool) -> zbus::fdo::Result<()> {
+        if !self.session.is_active() {
+            return Err(zbus::fdo::Error::AccessDenied(
+                "The session is not the active one".into(),
+            ));
+        }
         // Operations here need to be in specific order
         if on {
             self.client.set_enable_display(on).ok();
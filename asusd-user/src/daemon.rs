@@ -1,11 +1,14 @@
 use std::io::Write;
 use std::path::PathBuf;
-use std::sync::atomic::AtomicBool;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 
 use asusd_user::config::*;
 use asusd_user::ctrl_anime::{CtrlAnime, CtrlAnimeInner};
+use asusd_user::metrics::{self, EffectMetrics, METRICS_PATH};
+use asusd_user::session::SessionActive;
 use config_traits::{StdConfig, StdConfigLoad};
+use log::warn;
 use rog_anime::usb::get_anime_type;
 use rog_aura::aura_detection::LaptopLedData;
 use rog_aura::keyboard::KeyLayout;
@@ -14,13 +17,14 @@ use rog_dbus::zbus_aura::AuraProxyBlocking;
 use rog_dbus::zbus_platform::PlatformProxyBlocking;
 use rog_dbus::DBUS_NAME;
 use smol::Executor;
-use zbus::Connection;
 
 #[cfg(not(feature = "local_data"))]
 const DATA_DIR: &str = "/usr/share/rog-gui/";
 #[cfg(feature = "local_data")]
 const DATA_DIR: &str = env!("CARGO_MANIFEST_DIR");
 const BOARD_NAME: &str = "/sys/class/dmi/id/board_name";
+/// How often a stopped AniMe sequence is checked for being started again
+const STOPPED_POLL: std::time::Duration = std::time::Duration::from_millis(100);
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let mut logger = env_logger::Builder::new();
@@ -44,10 +48,35 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         .contains(&"Anime".to_string());
     let config = ConfigBase::new().load();
     let executor = Executor::new();
+    // Serves the effect metrics, and the AniMe sequence if there is one
+    let session = zbus::blocking::connection::Builder::session()?
+        .name(DBUS_NAME)?
+        .serve_at(METRICS_PATH, EffectMetrics)?
+        .build()?;
 
     let early_return = Arc::new(AtomicBool::new(false));
+    let session_active = SessionActive::new(true);
+    {
+        let anime = (supported && config.active_anime.is_some())
+            .then(|| AnimeProxyBlocking::new(&conn).ok())
+            .flatten();
+        let session_active = session_active.clone();
+        asusd_user::session::follow_session(
+            conn.clone(),
+            config.aura_override.clone(),
+            move |active| {
+                session_active.set(active);
+                // The system animations run while the user's are stopped
+                if let (false, Some(anime)) = (active, &anime) {
+                    anime.run_main_loop(true).ok();
+                }
+            },
+        );
+    }
+
     // Set up the anime data and run loop/thread
     if supported {
+        asusd_user::media::forward_media_title(AnimeProxyBlocking::new(&conn).unwrap());
         if let Some(cfg) = config.active_anime {
             let anime_type = get_anime_type()?;
             let anime_config = ConfigAnime::new().set_name(cfg).load();
@@ -55,18 +84,17 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             let anime_config = Arc::new(Mutex::new(anime_config));
 
             let anime_proxy_blocking = AnimeProxyBlocking::new(&conn).unwrap();
+            let session_active = session_active.clone();
+            let mut connection = session.inner().clone();
             executor
                 .spawn(async move {
-                    // Create server
-                    let mut connection = Connection::session().await.unwrap();
-                    connection.request_name(DBUS_NAME).await.unwrap();
-
                     // Inner behind mutex required for thread safety
                     let inner = Arc::new(Mutex::new(
                         CtrlAnimeInner::new(
                             anime,
                             anime_proxy_blocking.clone(),
                             early_return.clone(),
+                            session_active.clone(),
                         )
                         .unwrap(),
                     ));
@@ -75,11 +103,18 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                         anime_config,
                         inner.clone(),
                         anime_proxy_blocking,
-                        early_return,
+                        early_return.clone(),
+                        session_active.clone(),
                     )
                     .unwrap();
                     anime_control.add_to_server(&mut connection).await;
                     loop {
+                        session_active.wait_active();
+                        // Stopped by `set_state` or while the sequence changes
+                        if early_return.load(Ordering::SeqCst) {
+                            std::thread::sleep(STOPPED_POLL);
+                            continue;
+                        }
                         if let Ok(inner) = inner.clone().try_lock() {
                             inner.run().ok();
                         }
@@ -89,6 +124,8 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     }
 
+    asusd_user::audio::forward_audio_output(conn.clone());
+
     // if supported.keyboard_led.per_key_led_mode {
     if let Some(cfg) = config.active_aura {
         let mut aura_config = ConfigAura::new().set_name(cfg).load();
@@ -105,11 +142,22 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         let aura_proxy_blocking = AuraProxyBlocking::new(&conn).unwrap();
         executor
             .spawn(async move {
+                // Filled again for each frame
+                let mut packets = aura_config.aura.new_packets();
+                // Warn once for each run of failed writes
+                let mut failing = false;
                 loop {
-                    aura_config.aura.next_state(&layout);
-                    let packets = aura_config.aura.create_packets();
+                    session_active.wait_active();
+                    metrics::render_frame(&mut aura_config.aura, &layout, &mut packets);
 
-                    aura_proxy_blocking.direct_addressing_raw(packets).unwrap();
+                    match aura_proxy_blocking.direct_addressing_frame(&packets) {
+                        Ok(()) => failing = false,
+                        Err(e) if !failing => {
+                            warn!("Could not write the Aura frame: {e}");
+                            failing = true;
+                        }
+                        Err(_) => {}
+                    }
                     std::thread::sleep(std::time::Duration::from_millis(33));
                 }
             })
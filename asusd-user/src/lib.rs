@@ -4,6 +4,14 @@ pub mod error;
 
 pub mod ctrl_anime;
 
+pub mod media;
+
+pub mod metrics;
+
+pub mod audio;
+
+pub mod session;
+
 pub mod zbus_anime;
 
 pub static VERSION: &str = env!("CARGO_PKG_VERSION");
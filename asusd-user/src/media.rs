@@ -0,0 +1,78 @@
+//! Forwards the title of the playing media to asusd for the AniMe `Media`
+//! widget. MPRIS players are only on the session bus, which the system daemon
+//! can't see.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use log::{info, warn};
+use rog_dbus::zbus_anime::AnimeProxyBlocking;
+use zbus::blocking::fdo::DBusProxy;
+use zbus::blocking::{Connection, Proxy};
+use zbus::zvariant::OwnedValue;
+
+const MPRIS_PREFIX: &str = "org.mpris.MediaPlayer2.";
+const MPRIS_PATH: &str = "/org/mpris/MediaPlayer2";
+const MPRIS_PLAYER: &str = "org.mpris.MediaPlayer2.Player";
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// The title of the first player that is currently playing
+fn playing_title(conn: &Connection) -> zbus::Result<Option<String>> {
+    for name in DBusProxy::new(conn)?.list_names()? {
+        if !name.as_str().starts_with(MPRIS_PREFIX) {
+            continue;
+        }
+        let player = Proxy::new(conn, name.as_str(), MPRIS_PATH, MPRIS_PLAYER)?;
+        if player
+            .get_property::<String>("PlaybackStatus")
+            .ok()
+            .as_deref()
+            != Some("Playing")
+        {
+            continue;
+        }
+        let metadata: HashMap<String, OwnedValue> = player.get_property("Metadata")?;
+        if let Some(title) = metadata
+            .get("xesam:title")
+            .and_then(|v| String::try_from(v.try_clone().ok()?).ok())
+        {
+            return Ok(Some(title));
+        }
+    }
+    Ok(None)
+}
+
+/// Poll the session's media players on a new thread, sending the title to
+/// asusd whenever it changes. Does nothing while the widgets are disabled.
+pub fn forward_media_title(anime: AnimeProxyBlocking<'static>) {
+    std::thread::Builder::new()
+        .name("MPRIS title".into())
+        .spawn(move || {
+            let conn = match Connection::session() {
+                Ok(conn) => conn,
+                Err(e) => {
+                    warn!("No session bus, media titles will not be shown: {e}");
+                    return;
+                }
+            };
+            info!("Forwarding media titles to the AniMe widgets");
+            let mut last = String::new();
+            loop {
+                std::thread::sleep(POLL_INTERVAL);
+                if !anime.widgets_enabled().unwrap_or_default() {
+                    continue;
+                }
+                let title = playing_title(&conn)
+                    .map_err(|e| warn!("Could not read media players: {e}"))
+                    .ok()
+                    .flatten()
+                    .unwrap_or_default();
+                if title != last {
+                    anime.set_media_title(&title).ok();
+                    last = title;
+                }
+            }
+        })
+        .map_err(|e| warn!("Could not start media title thread: {e}"))
+        .ok();
+}
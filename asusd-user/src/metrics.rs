@@ -0,0 +1,56 @@
+//! Render times of the effects asusd-user runs, kept in the same histograms
+//! as those of asusd. asusd only sees the finished frames, so these are served
+//! on the session bus by `EffectMetrics` at `METRICS_PATH`.
+
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+use effect_metrics::Stage;
+use rog_aura::effects::{AdvancedEffects, Effect};
+use rog_aura::keyboard::{KeyLayout, LedUsbPackets};
+use zbus::interface;
+
+pub const METRICS_PATH: &str = "/org/asuslinux/Metrics";
+
+/// The label of an effect in the histograms
+fn effect_name(effect: &Effect) -> &'static str {
+    match effect {
+        Effect::Static(_) => "user_static",
+        Effect::Breathe(_) => "user_breathe",
+        Effect::DoomFlicker(_) => "user_doom_flicker",
+        Effect::DoomLightFlash(_) => "user_doom_light_flash",
+        Effect::Marquee(_) => "user_marquee",
+    }
+}
+
+/// Step every effect and write the frame in to `packets`. The time taken by
+/// each kind of effect is recorded once for the frame.
+pub fn render_frame(
+    effects: &mut AdvancedEffects,
+    layout: &KeyLayout,
+    packets: &mut LedUsbPackets,
+) {
+    let mut times: BTreeMap<&'static str, Duration> = BTreeMap::new();
+    for effect in effects.effects_mut() {
+        let start = Instant::now();
+        effect.next_state(layout);
+        *times.entry(effect_name(effect)).or_default() += start.elapsed();
+    }
+    for (effect, time) in times {
+        effect_metrics::record(effect, Stage::Render, time);
+    }
+    effect_metrics::timed("user_packets", Stage::Render, || {
+        effects.write_packets(packets)
+    });
+}
+
+pub struct EffectMetrics;
+
+#[interface(name = "org.asuslinux.Metrics")]
+impl EffectMetrics {
+    /// Frame render time histograms of the effects, in the Prometheus text
+    /// format
+    fn effect_metrics(&self) -> String {
+        effect_metrics::metrics_text()
+    }
+}
@@ -0,0 +1,161 @@
+//! Follows the graphical session of the user. asusd only shows the lighting
+//! of a user while their session is the active one on the seat, so it is sent
+//! again each time the session comes to the foreground. asusd goes back to
+//! the system lighting by itself once another user or the greeter is active.
+
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+
+use log::{debug, info, warn};
+use rog_aura::user_override::AuraOverride;
+use rog_dbus::zbus_aura::{AuraDevicesProxyBlocking, AuraProxyBlocking};
+use zbus::blocking::Connection;
+use zbus::zvariant::OwnedObjectPath;
+use zbus::{proxy, CacheProperties};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+#[proxy(
+    interface = "org.freedesktop.login1.Manager",
+    default_service = "org.freedesktop.login1",
+    default_path = "/org/freedesktop/login1"
+)]
+trait Login1Manager {
+    fn get_user_by_pid(&self, pid: u32) -> zbus::Result<OwnedObjectPath>;
+}
+
+#[proxy(
+    interface = "org.freedesktop.login1.User",
+    default_service = "org.freedesktop.login1"
+)]
+trait Login1User {
+    /// The id and path of the main session of the user, the path is `/` if
+    /// the user has none
+    #[zbus(property)]
+    fn display(&self) -> zbus::Result<(String, OwnedObjectPath)>;
+}
+
+#[proxy(
+    interface = "org.freedesktop.login1.Session",
+    default_service = "org.freedesktop.login1"
+)]
+trait Login1Session {
+    #[zbus(property)]
+    fn active(&self) -> zbus::Result<bool>;
+}
+
+/// Whether the user's session is active, shared with the loops that only run
+/// while it is. They block in `wait_active` rather than poll.
+#[derive(Debug, Clone)]
+pub struct SessionActive(Arc<(Mutex<bool>, Condvar)>);
+
+impl SessionActive {
+    pub fn new(active: bool) -> Self {
+        Self(Arc::new((Mutex::new(active), Condvar::new())))
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.0 .0.lock().map(|a| *a).unwrap_or(true)
+    }
+
+    pub fn set(&self, active: bool) {
+        if let Ok(mut current) = self.0 .0.lock() {
+            *current = active;
+            self.0 .1.notify_all();
+        }
+    }
+
+    /// Block until the session is active
+    pub fn wait_active(&self) {
+        let (lock, changed) = &*self.0;
+        if let Ok(active) = lock.lock() {
+            changed.wait_while(active, |active| !*active).ok();
+        }
+    }
+}
+
+/// True if the main session of the user running asusd-user is in the
+/// foreground of its seat
+fn session_active(conn: &Connection) -> zbus::Result<bool> {
+    let user = Login1ManagerProxyBlocking::new(conn)?.get_user_by_pid(std::process::id())?;
+    let user = Login1UserProxyBlocking::builder(conn)
+        .path(user)?
+        .cache_properties(CacheProperties::No)
+        .build()?;
+    let (_, session) = user.display()?;
+    if session.as_str() == "/" {
+        return Ok(false);
+    }
+    Login1SessionProxyBlocking::builder(conn)
+        .path(session)?
+        .cache_properties(CacheProperties::No)
+        .build()?
+        .active()
+}
+
+/// The Aura devices which are connected
+fn connected_devices(conn: &Connection) -> zbus::Result<Vec<AuraProxyBlocking<'static>>> {
+    let mut devices = Vec::new();
+    for device in AuraDevicesProxyBlocking::new(conn)?.known_devices()? {
+        if !device.connected {
+            continue;
+        }
+        devices.push(
+            AuraProxyBlocking::builder(conn)
+                .path(device.dbus_path)?
+                .cache_properties(CacheProperties::No)
+                .build()?,
+        );
+    }
+    Ok(devices)
+}
+
+/// Poll the session of the user on a new thread. `on_change` is called with
+/// `true` as it becomes active and `false` once it isn't, and while it is
+/// active `lighting` is shown on each connected Aura device. Without logind
+/// the session is taken to always be active, though asusd then refuses the
+/// lighting.
+pub fn follow_session<F>(conn: Connection, lighting: Option<AuraOverride>, on_change: F)
+where
+    F: Fn(bool) + Send + 'static,
+{
+    std::thread::Builder::new()
+        .name("User session".into())
+        .spawn(move || {
+            let mut last = None;
+            let mut sent_to = 0;
+            loop {
+                let active = session_active(&conn).unwrap_or_else(|e| {
+                    debug!("Could not ask logind for the session, taking it as active: {e}");
+                    true
+                });
+                if last != Some(active) {
+                    info!(
+                        "User session is {}",
+                        if active { "active" } else { "inactive" }
+                    );
+                    on_change(active);
+                    last = Some(active);
+                    sent_to = 0;
+                }
+
+                if let (true, Some(lighting)) = (active, &lighting) {
+                    let devices = connected_devices(&conn)
+                        .map_err(|e| warn!("Could not find the Aura devices: {e}"))
+                        .unwrap_or_default();
+                    // Also sent when a device is plugged in
+                    if devices.len() != sent_to {
+                        for aura in &devices {
+                            aura.set_user_override(lighting.clone())
+                                .map_err(|e| warn!("Could not set the user lighting: {e}"))
+                                .ok();
+                        }
+                        sent_to = devices.len();
+                    }
+                }
+                std::thread::sleep(POLL_INTERVAL);
+            }
+        })
+        .map_err(|e| warn!("Could not start user session thread: {e}"))
+        .ok();
+}
@@ -0,0 +1,191 @@
+//! Charge limit rules that override the configured limit by time of day or
+//! power source, e.g. 60% while docked at a desk but 100% on a travel day.
+//! The first rule that matches is used, and the configured limit applies
+//! when none do.
+
+use std::fs;
+
+use serde_derive::{Deserialize, Serialize};
+use zbus::zvariant::Type;
+
+const THUNDERBOLT_DEVICES: &str = "/sys/bus/thunderbolt/devices";
+pub(crate) const MINUTES_PER_DAY: u16 = 24 * 60;
+
+/// The power source a rule needs
+#[derive(Deserialize, Serialize, Type, Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd)]
+#[zvariant(signature = "s")]
+pub enum ChargePower {
+    #[default]
+    Any,
+    Ac,
+    /// A Thunderbolt or USB4 dock is connected
+    Dock,
+}
+
+impl std::str::FromStr for ChargePower {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().trim() {
+            "any" => Ok(Self::Any),
+            "ac" => Ok(Self::Ac),
+            "dock" => Ok(Self::Dock),
+            _ => Err(format!("{s} is not one of any, ac, dock")),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Type, Debug, Clone, PartialEq, PartialOrd)]
+pub struct ChargeRule {
+    pub limit: u8,
+    /// Local time the rule starts, in minutes after midnight
+    pub start: u16,
+    /// Local time the rule ends. A window ending before it starts carries on
+    /// past midnight, and one ending when it starts covers the whole day.
+    pub end: u16,
+    pub power: ChargePower,
+}
+
+impl ChargeRule {
+    pub fn in_window(&self, minute: u16) -> bool {
+        in_window(self.start, self.end, minute)
+    }
+
+    pub fn matches(&self, minute: u16, on_ac: bool, docked: bool) -> bool {
+        let power = match self.power {
+            ChargePower::Any => true,
+            ChargePower::Ac => on_ac,
+            ChargePower::Dock => docked,
+        };
+        power && self.in_window(minute)
+    }
+
+    /// Check the limit and times are in range
+    pub fn validate(&self) -> Result<(), String> {
+        if !(20..=100).contains(&self.limit) {
+            return Err(format!("Charge limit {} must be 20-100", self.limit));
+        }
+        if self.start >= MINUTES_PER_DAY || self.end >= MINUTES_PER_DAY {
+            return Err(format!(
+                "Times must be less than {MINUTES_PER_DAY} minutes after midnight"
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// The limit that should be set right now. `full_once` charges to 100%
+/// regardless of the rules.
+pub fn effective_limit(
+    base: u8,
+    full_once: bool,
+    rules: &[ChargeRule],
+    minute: u16,
+    on_ac: bool,
+    docked: bool,
+) -> u8 {
+    if full_once {
+        return 100;
+    }
+    rules
+        .iter()
+        .find(|rule| rule.matches(minute, on_ac, docked))
+        .map_or(base, |rule| rule.limit)
+}
+
+/// True if `minute` is in the window from `start` to `end`. A window ending
+/// before it starts carries on past midnight, and one ending when it starts
+/// covers the whole day.
+pub fn in_window(start: u16, end: u16, minute: u16) -> bool {
+    match start.cmp(&end) {
+        std::cmp::Ordering::Equal => true,
+        std::cmp::Ordering::Less => (start..end).contains(&minute),
+        std::cmp::Ordering::Greater => minute >= start || minute < end,
+    }
+}
+
+/// Minutes since local midnight
+pub fn local_minute() -> u16 {
+    use chrono::Timelike;
+    let now = chrono::Local::now();
+    (now.hour() * 60 + now.minute()) as u16
+}
+
+/// True if a Thunderbolt/USB4 device other than the host controller is
+/// attached. Host routers are named `<domain>-0`.
+pub fn dock_connected() -> bool {
+    let Ok(dir) = fs::read_dir(THUNDERBOLT_DEVICES) else {
+        return false;
+    };
+    dir.flatten().any(|entry| {
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        name.split_once('-')
+            .is_some_and(|(_, route)| route != "0" && !route.contains(':'))
+    })
+}
+
+/// Parse `HH:MM` in to minutes after midnight
+pub fn parse_time(time: &str) -> Option<u16> {
+    let (h, m) = time.trim().split_once(':')?;
+    let (h, m): (u16, u16) = (h.parse().ok()?, m.parse().ok()?);
+    (h < 24 && m < 60).then_some(h * 60 + m)
+}
+
+pub fn format_time(minute: u16) -> String {
+    format!("{:02}:{:02}", minute / 60, minute % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{effective_limit, format_time, parse_time, ChargePower, ChargeRule};
+
+    fn rule(limit: u8, start: &str, end: &str, power: ChargePower) -> ChargeRule {
+        ChargeRule {
+            limit,
+            start: parse_time(start).unwrap(),
+            end: parse_time(end).unwrap(),
+            power,
+        }
+    }
+
+    #[test]
+    fn windows() {
+        let day = rule(60, "09:00", "17:00", ChargePower::Any);
+        assert!(!day.in_window(parse_time("08:59").unwrap()));
+        assert!(day.in_window(parse_time("09:00").unwrap()));
+        assert!(!day.in_window(parse_time("17:00").unwrap()));
+
+        let night = rule(80, "22:00", "06:00", ChargePower::Any);
+        assert!(night.in_window(parse_time("23:30").unwrap()));
+        assert!(night.in_window(parse_time("05:59").unwrap()));
+        assert!(!night.in_window(parse_time("12:00").unwrap()));
+
+        assert!(rule(80, "00:00", "00:00", ChargePower::Any).in_window(700));
+    }
+
+    #[test]
+    fn first_match_wins() {
+        let rules = [
+            rule(60, "00:00", "00:00", ChargePower::Dock),
+            rule(80, "09:00", "17:00", ChargePower::Ac),
+        ];
+        let noon = parse_time("12:00").unwrap();
+        assert_eq!(effective_limit(100, false, &rules, noon, true, true), 60);
+        assert_eq!(effective_limit(100, false, &rules, noon, true, false), 80);
+        assert_eq!(effective_limit(100, false, &rules, noon, false, false), 100);
+        assert_eq!(effective_limit(70, false, &rules, 0, true, false), 70);
+        assert_eq!(effective_limit(70, true, &rules, noon, true, true), 100);
+    }
+
+    #[test]
+    fn times() {
+        assert_eq!(parse_time("07:30"), Some(450));
+        assert_eq!(parse_time("24:00"), None);
+        assert_eq!(parse_time("7"), None);
+        assert_eq!(format_time(450), "07:30");
+        assert!(rule(10, "01:00", "02:00", ChargePower::Any)
+            .validate()
+            .is_err());
+    }
+}
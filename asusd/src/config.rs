@@ -1,14 +1,33 @@
+use std::collections::BTreeMap;
+
 use config_traits::{StdConfig, StdConfigLoad3};
+use rog_aura::AuraModeNum;
 use rog_platform::cpu::CPUEPP;
 use rog_platform::platform::ThrottlePolicy;
+use rog_profiles::fan_curve_set::CurveData;
 use serde_derive::{Deserialize, Serialize};
 
-const CONFIG_FILE: &str = "asusd.ron";
+use crate::charge_schedule::ChargeRule;
+use crate::ctrl_events::{Hotkey, HotkeyHandling};
+use crate::ctrl_numpad::NumpadConfig;
+use crate::shutdown::ShutdownPolicy;
+
+pub const CONFIG_FILE: &str = "asusd.ron";
+const CONFIG_VERSION: u32 = 1;
 
-#[derive(Deserialize, Serialize, Debug, PartialEq, PartialOrd)]
+#[derive(Deserialize, Serialize, Debug, PartialEq)]
 pub struct Config {
+    /// Version of the file format, upgraded on load
+    #[serde(default)]
+    pub version: u32,
     /// Save charge limit for restoring on boot/resume
     pub charge_control_end_threshold: u8,
+    /// Rules which override the charge limit, the first match is used
+    #[serde(default)]
+    pub charge_schedule: Vec<ChargeRule>,
+    /// Charge to 100% once, cleared when the battery reports full
+    #[serde(default)]
+    pub charge_full_once: bool,
     pub panel_od: bool,
     pub boot_sound: bool,
     pub mini_led_mode: bool,
@@ -44,15 +63,57 @@ pub struct Config {
     pub nv_dynamic_boost: Option<u8>,
     /// Defaults to `None` if not supported
     pub nv_temp_target: Option<u8>,
+    /// An Aura mode and fan curves to apply when a throttle policy becomes
+    /// active, by any means
+    #[serde(default)]
+    pub profile_associations: BTreeMap<ThrottlePolicy, ProfileAssociation>,
+    /// Hotkeys asusd acts on itself, any not listed are left to the desktop.
+    /// The brightness up and down keys are skipped while the Aura
+    /// `brightness_keys` handling is on, as that already steps them.
+    #[serde(default)]
+    pub hotkeys: BTreeMap<Hotkey, HotkeyHandling>,
+    /// Set in a newly created config until a client completes its first run
+    /// setup. Configs from before this was added don't have it, so default to
+    /// done.
+    #[serde(default)]
+    pub first_run_pending: bool,
+    /// What the LEDs are left showing when asusd stops or the machine powers
+    /// down
+    #[serde(default)]
+    pub shutdown_policy: ShutdownPolicy,
+    /// The light of the touchpad numpad, on laptops which have one
+    #[serde(default)]
+    pub numpad: NumpadConfig,
+    /// Turn off the caps, num and scroll lock LEDs in tablet mode on
+    /// convertibles
+    #[serde(default)]
+    pub tablet_mode_input_leds_off: bool,
     /// Temporary state for AC/Batt
     #[serde(skip)]
     pub last_power_plugged: u8,
 }
 
+/// What is applied along with a throttle policy. Each part is optional and
+/// left alone if `None`.
+#[derive(Deserialize, Serialize, Default, Debug, Clone, PartialEq, PartialOrd)]
+pub struct ProfileAssociation {
+    /// The Aura mode to switch all keyboards to. The mode uses the colours and
+    /// speed already stored for it.
+    #[serde(default)]
+    pub aura_mode: Option<AuraModeNum>,
+    /// Fan curves for the policy. These replace the curves stored by the fan
+    /// curve controller whenever the policy is activated.
+    #[serde(default)]
+    pub fan_curves: Option<Vec<CurveData>>,
+}
+
 impl Default for Config {
     fn default() -> Self {
         Self {
+            version: CONFIG_VERSION,
             charge_control_end_threshold: 100,
+            charge_schedule: Default::default(),
+            charge_full_once: false,
             panel_od: false,
             boot_sound: false,
             mini_led_mode: false,
@@ -72,12 +133,20 @@ impl Default for Config {
             ppt_platform_sppt: Default::default(),
             nv_dynamic_boost: Default::default(),
             nv_temp_target: Default::default(),
+            profile_associations: Default::default(),
+            hotkeys: Default::default(),
+            first_run_pending: true,
+            shutdown_policy: Default::default(),
+            numpad: Default::default(),
+            tablet_mode_input_leds_off: false,
             last_power_plugged: Default::default(),
         }
     }
 }
 
 impl StdConfig for Config {
+    const VERSION: Option<u32> = Some(CONFIG_VERSION);
+
     fn new() -> Self {
         Config {
             charge_control_end_threshold: 100,
@@ -97,6 +166,10 @@ impl StdConfig for Config {
     fn config_dir() -> std::path::PathBuf {
         std::path::PathBuf::from(crate::CONFIG_PATH_BASE)
     }
+
+    fn set_version(&mut self, version: u32) {
+        self.version = version;
+    }
 }
 
 impl StdConfigLoad3<Config472, Config506, Config507> for Config {}
@@ -127,6 +200,8 @@ impl From<Config507> for Config {
     fn from(c: Config507) -> Self {
         Self {
             charge_control_end_threshold: c.charge_control_end_threshold,
+            charge_schedule: Default::default(),
+            charge_full_once: false,
             panel_od: c.panel_od,
             boot_sound: false,
             disable_nvidia_powerd_on_battery: c.disable_nvidia_powerd_on_battery,
@@ -146,6 +221,13 @@ impl From<Config507> for Config {
             ppt_platform_sppt: c.ppt_platform_sppt,
             nv_dynamic_boost: c.nv_dynamic_boost,
             nv_temp_target: c.nv_temp_target,
+            profile_associations: Default::default(),
+            hotkeys: Default::default(),
+            first_run_pending: false,
+            shutdown_policy: Default::default(),
+            numpad: Default::default(),
+            tablet_mode_input_leds_off: false,
+            version: CONFIG_VERSION,
             last_power_plugged: 0,
         }
     }
@@ -179,6 +261,8 @@ impl From<Config506> for Config {
     fn from(c: Config506) -> Self {
         Self {
             charge_control_end_threshold: c.charge_control_end_threshold,
+            charge_schedule: Default::default(),
+            charge_full_once: false,
             panel_od: c.panel_od,
             boot_sound: false,
             disable_nvidia_powerd_on_battery: c.disable_nvidia_powerd_on_battery,
@@ -198,6 +282,13 @@ impl From<Config506> for Config {
             ppt_platform_sppt: c.ppt_platform_sppt,
             nv_dynamic_boost: c.nv_dynamic_boost,
             nv_temp_target: c.nv_temp_target,
+            profile_associations: Default::default(),
+            hotkeys: Default::default(),
+            first_run_pending: false,
+            shutdown_policy: Default::default(),
+            numpad: Default::default(),
+            tablet_mode_input_leds_off: false,
+            version: CONFIG_VERSION,
             last_power_plugged: 0,
         }
     }
@@ -222,6 +313,10 @@ impl From<Config472> for Config {
             disable_nvidia_powerd_on_battery: true,
             ac_command: c.ac_command,
             bat_command: c.bat_command,
+            first_run_pending: false,
+            shutdown_policy: Default::default(),
+            numpad: Default::default(),
+            tablet_mode_input_leds_off: false,
             ..Default::default()
         }
     }
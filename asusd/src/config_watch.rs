@@ -0,0 +1,87 @@
+//! Live reload of the configs in `/etc/asusd`. The directory is watched, not
+//! the files, so editors that replace a file on save are seen too. Each change
+//! which asusd didn't write itself is passed on by file name through the
+//! `ConfigChangeHook`, and the controller owning the file reloads it.
+
+use std::path::PathBuf;
+
+use config_traits::is_own_write;
+use futures_lite::stream::StreamExt;
+use inotify::{Inotify, WatchMask};
+use log::{debug, error, info};
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::{ConfigChangeHook, ConfigChangeListener};
+
+/// Files asusd keeps beside the configs, such as locks and backups
+fn is_config_file(name: &str) -> bool {
+    !name.starts_with('.') && !name.ends_with(".bak") && !name.ends_with("-old")
+}
+
+/// Start watching the config dir for external edits
+pub fn watch_config_dir(hook: ConfigChangeHook) {
+    let dir = PathBuf::from(crate::CONFIG_PATH_BASE);
+    tokio::spawn(async move {
+        let inotify = Inotify::init()?;
+        inotify
+            .watches()
+            .add(&dir, WatchMask::CLOSE_WRITE | WatchMask::MOVED_TO)
+            .map_err(|e| error!("Could not watch {dir:?} for config edits: {e}"))
+            .ok();
+        info!("Watching {dir:?} for config edits");
+
+        let mut buffer = [0; 1024];
+        let mut events = inotify.into_event_stream(&mut buffer)?;
+        while let Some(event) = events.next().await {
+            let Some(name) = event.ok().and_then(|e| e.name) else {
+                continue;
+            };
+            let name = name.to_string_lossy().to_string();
+            if !is_config_file(&name) {
+                continue;
+            }
+            let Ok(data) = std::fs::read(dir.join(&name)) else {
+                continue;
+            };
+            if is_own_write(&dir.join(&name), &data) {
+                continue;
+            }
+            debug!("{name} was edited outside of asusd");
+            hook.send(name).ok();
+        }
+        Ok::<(), std::io::Error>(())
+    });
+}
+
+/// Pass on every config on `SIGHUP`, as if each was edited, for inits which
+/// reload a service that way
+pub fn reload_on_sighup(hook: ConfigChangeHook) {
+    tokio::spawn(async move {
+        let mut hangup = signal(SignalKind::hangup())?;
+        while hangup.recv().await.is_some() {
+            info!("Reloading the configs on SIGHUP");
+            for entry in std::fs::read_dir(crate::CONFIG_PATH_BASE)?.flatten() {
+                let name = entry.file_name().to_string_lossy().to_string();
+                if is_config_file(&name) && entry.path().is_file() {
+                    hook.send(name).ok();
+                }
+            }
+        }
+        Ok::<(), std::io::Error>(())
+    });
+}
+
+/// Wait for the next external edit of `file_name`. Returns `false` once the
+/// watcher has stopped.
+pub async fn changed(listener: &mut ConfigChangeListener, file_name: &str) -> bool {
+    loop {
+        match listener.recv().await {
+            Ok(name) if name == file_name => return true,
+            Ok(_) => {}
+            // The edit may have been one of those missed
+            Err(RecvError::Lagged(_)) => return true,
+            Err(RecvError::Closed) => return false,
+        }
+    }
+}
@@ -8,7 +8,10 @@ use rog_anime::{
 };
 use serde_derive::{Deserialize, Serialize};
 
+use super::widgets::AnimeWidgetConfig;
+
 const CONFIG_FILE: &str = "anime.ron";
+const CONFIG_VERSION: u32 = 1;
 
 #[derive(Deserialize, Serialize)]
 pub struct AnimeConfigV460 {
@@ -108,6 +111,9 @@ impl AnimeConfigCached {
 /// Config for base system actions for the anime display
 #[derive(Deserialize, Serialize, Debug)]
 pub struct AnimeConfig {
+    /// Version of the file format, upgraded on load
+    #[serde(default)]
+    pub version: u32,
     pub model_override: Option<AnimeType>,
     pub system: Vec<ActionLoader>,
     pub boot: Vec<ActionLoader>,
@@ -122,11 +128,14 @@ pub struct AnimeConfig {
     pub off_when_lid_closed: bool,
     pub brightness_on_battery: Brightness,
     pub builtin_anims: Animations,
+    #[serde(default)]
+    pub widgets: AnimeWidgetConfig,
 }
 
 impl Default for AnimeConfig {
     fn default() -> Self {
         AnimeConfig {
+            version: CONFIG_VERSION,
             model_override: None,
             system: Vec::new(),
             boot: Vec::new(),
@@ -141,11 +150,14 @@ impl Default for AnimeConfig {
             off_when_lid_closed: true,
             brightness_on_battery: Brightness::Low,
             builtin_anims: Animations::default(),
+            widgets: AnimeWidgetConfig::default(),
         }
     }
 }
 
 impl StdConfig for AnimeConfig {
+    const VERSION: Option<u32> = Some(CONFIG_VERSION);
+
     fn new() -> Self {
         Self::create_default()
     }
@@ -157,6 +169,10 @@ impl StdConfig for AnimeConfig {
     fn config_dir() -> std::path::PathBuf {
         std::path::PathBuf::from(crate::CONFIG_PATH_BASE)
     }
+
+    fn set_version(&mut self, version: u32) {
+        self.version = version;
+    }
 }
 
 impl StdConfigLoad2<AnimeConfigV460, AnimeConfigV472> for AnimeConfig {}
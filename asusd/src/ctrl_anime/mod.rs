@@ -1,6 +1,10 @@
 pub mod config;
+/// Client frame streams and the widget display session
+pub mod stream;
 /// Implements `CtrlTask`, Reloadable, `ZbusRun`
 pub mod trait_impls;
+/// Clock, temperature and media widgets drawn by the daemon
+pub mod widgets;
 
 use std::convert::TryFrom;
 use std::sync::atomic::{AtomicBool, Ordering};
@@ -8,17 +12,20 @@ use std::sync::Arc;
 use std::thread::sleep;
 
 use ::zbus::export::futures_util::lock::Mutex;
+use futures_lite::future::block_on;
 use log::{error, info, warn};
 use rog_anime::error::AnimeError;
 use rog_anime::usb::{
     get_anime_type, pkt_flush, pkt_set_brightness, pkt_set_enable_display,
     pkt_set_enable_powersave_anim, pkts_for_init, Brightness,
 };
-use rog_anime::{ActionData, AnimeDataBuffer, AnimePacketType, AnimeType};
+use rog_anime::{ActionData, AnimeDataBuffer, AnimeGrid, AnimePacketType, AnimeType};
 use rog_platform::hid_raw::HidRaw;
 use rog_platform::usb_raw::USBRaw;
 
 use self::config::{AnimeConfig, AnimeConfigCached};
+use self::stream::DisplaySession;
+use self::widgets::WidgetState;
 use crate::error::RogError;
 
 enum Node {
@@ -54,6 +61,13 @@ pub struct CtrlAnime {
     anime_type: AnimeType,
     cache: AnimeConfigCached,
     config: AnimeConfig,
+    /// Canvas for the drawing primitives, shown on `draw_present`
+    canvas: AnimeGrid,
+    /// Brightness levels the canvas is dithered to, 0 to disable
+    dither: u8,
+    /// Frame stream or widget session currently driving the display
+    display: Option<DisplaySession>,
+    widget_state: WidgetState,
     // set to force thread to exit
     thread_exit: Arc<AtomicBool>,
     // Set to false when the thread exits
@@ -104,6 +118,10 @@ impl CtrlAnime {
             anime_type,
             cache,
             config,
+            canvas: AnimeGrid::new(anime_type),
+            dither: 0,
+            display: None,
+            widget_state: WidgetState::default(),
             thread_exit: Arc::new(AtomicBool::new(false)),
             thread_running: Arc::new(AtomicBool::new(false)),
         };
@@ -118,21 +136,27 @@ impl CtrlAnime {
     /// be only one running - so the thread uses atomics to signal run/exit.
     ///
     /// Because this also writes to the usb device, other write tries (display
-    /// only) *must* get the mutex lock and set the `thread_exit` atomic.
-    async fn run_thread(inner: Arc<Mutex<CtrlAnime>>, actions: Vec<ActionData>, mut once: bool) {
+    /// only) *must* get the mutex lock and set the `thread_exit` atomic. The
+    /// thread waits for the lock rather than dropping a write, and checks
+    /// `thread_exit` again once it has it. `ctrl` is the caller's lock of
+    /// `inner`.
+    async fn run_thread(
+        inner: Arc<Mutex<CtrlAnime>>,
+        ctrl: &CtrlAnime,
+        actions: Vec<ActionData>,
+        mut once: bool,
+    ) {
         if actions.is_empty() {
             warn!("AniMe system actions was empty");
             return;
         }
 
-        if let Some(lock) = inner.try_lock() {
-            lock.node
-                .write_bytes(&pkt_set_enable_powersave_anim(false))
-                .map_err(|err| {
-                    warn!("rog_anime::run_animation:callback {}", err);
-                })
-                .ok();
-        }
+        ctrl.node
+            .write_bytes(&pkt_set_enable_powersave_anim(false))
+            .map_err(|err| {
+                warn!("rog_anime::run_animation:callback {}", err);
+            })
+            .ok();
 
         // Loop rules:
         // - Lock the mutex **only when required**. That is, the lock must be held for
@@ -150,20 +174,14 @@ impl CtrlAnime {
                 info!("AniMe new system thread started");
                 // Getting copies of these Atomics is done *in* the thread to ensure
                 // we don't block other threads/main
-                let thread_exit;
-                let thread_running;
-                let anime_type;
-                loop {
-                    if let Some(lock) = inner.try_lock() {
-                        thread_exit = lock.thread_exit.clone();
-                        thread_running = lock.thread_running.clone();
-                        anime_type = lock.anime_type;
-                        break;
-                    }
-                }
-                // First two loops are to ensure we *do* aquire a lock on the mutex
-                // The reason the loop is required is because the USB writes can block
-                // for up to 10ms. We can't fail to get the atomics.
+                let (thread_exit, thread_running, anime_type) = {
+                    let lock = block_on(inner.lock());
+                    (
+                        lock.thread_exit.clone(),
+                        lock.thread_running.clone(),
+                        lock.anime_type,
+                    )
+                };
                 while thread_running.load(Ordering::SeqCst) {
                     // Make any running loop exit first
                     thread_exit.store(true, Ordering::SeqCst);
@@ -184,26 +202,17 @@ impl CtrlAnime {
                                         info!("rog-anime: animation sub-loop was asked to exit");
                                         return Ok(true); // Do safe exit
                                     }
-                                    inner
-                                        .try_lock()
-                                        .map(|lock| {
-                                            lock.write_data_buffer(frame)
-                                                .map_err(|err| {
-                                                    warn!(
-                                                        "rog_anime::run_animation:callback {}",
-                                                        err
-                                                    );
-                                                })
-                                                .ok();
-                                            false // Don't exit yet
+                                    let lock = block_on(inner.lock());
+                                    // Asked to exit while waiting for the lock
+                                    if thread_exit.load(Ordering::Acquire) {
+                                        return Ok(true);
+                                    }
+                                    lock.write_data_buffer(frame)
+                                        .map_err(|err| {
+                                            warn!("rog_anime::run_animation:callback {}", err);
                                         })
-                                        .map_or_else(
-                                            || {
-                                                warn!("rog_anime::run_animation:callback failed");
-                                                Err(AnimeError::NoFrames)
-                                            },
-                                            Ok,
-                                        )
+                                        .ok();
+                                    Ok(false) // Don't exit yet
                                 });
                                 if thread_exit.load(Ordering::Acquire) {
                                     info!("rog-anime: sub-loop exited and main loop exiting now");
@@ -212,7 +221,8 @@ impl CtrlAnime {
                             }
                             ActionData::Image(image) => {
                                 once = false;
-                                if let Some(lock) = inner.try_lock() {
+                                let lock = block_on(inner.lock());
+                                if !thread_exit.load(Ordering::SeqCst) {
                                     lock.write_data_buffer(image.as_ref().clone())
                                         .map_err(|e| error!("{}", e))
                                         .ok();
@@ -232,8 +242,10 @@ impl CtrlAnime {
                         break 'main;
                     }
                 }
-                // Clear the display on exit
-                if let Some(lock) = inner.try_lock() {
+                // Clear the display if the sequence ended by itself. A writer that
+                // set `thread_exit` has drawn its own frame, so leave the panel be.
+                let lock = block_on(inner.lock());
+                if !thread_exit.load(Ordering::SeqCst) {
                     if let Ok(data) =
                         AnimeDataBuffer::from_vec(anime_type, vec![0u8; anime_type.data_length()])
                             .map_err(|e| error!("{}", e))
@@ -253,6 +265,7 @@ impl CtrlAnime {
                         })
                         .ok();
                 }
+                drop(lock);
                 // Loop ended, set the atmonics
                 thread_running.store(false, Ordering::SeqCst);
                 info!("AniMe system thread exited");
@@ -279,6 +292,14 @@ impl CtrlAnime {
         Ok(())
     }
 
+    /// End any frame stream or widget session. The session thread exits on
+    /// its own once it sees it was removed.
+    fn stop_display(&mut self) {
+        if let Some(session) = self.display.take() {
+            session.stop();
+        }
+    }
+
     fn do_initialization(&self) -> Result<(), RogError> {
         let pkts = pkts_for_init();
         self.node.write_bytes(&pkts[0])?;
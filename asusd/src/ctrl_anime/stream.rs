@@ -0,0 +1,155 @@
+//! A display session that owns the AniMe until it ends. A session either
+//! plays frames a client writes to a socket, or only draws the widgets. While
+//! widgets are enabled they are drawn over streamed frames, and a session
+//! stays running to show them after a stream ends.
+//!
+//! Streamed frames are raw `AnimeDataBuffer` data, `AnimeType::data_length()`
+//! bytes each, with no header.
+
+use std::io::Read;
+use std::net::Shutdown;
+use std::os::unix::net::UnixStream;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use effect_metrics::Stage;
+use futures_lite::future::block_on;
+use log::{info, warn};
+use rog_anime::usb::pkt_set_enable_powersave_anim;
+use rog_anime::AnimeDataBuffer;
+use zbus::export::futures_util::lock::Mutex;
+use zbus::names::OwnedUniqueName;
+
+use super::widgets::{composite, render};
+use super::CtrlAnime;
+use crate::error::RogError;
+
+/// Highest rate frames are written to the device
+pub const MAX_FPS: u8 = 60;
+
+static SESSION_ID: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Debug)]
+pub struct DisplaySession {
+    id: u64,
+    /// The D-Bus client streaming frames, `None` for widget only sessions
+    owner: Option<OwnedUniqueName>,
+    socket: Option<UnixStream>,
+}
+
+impl DisplaySession {
+    /// Start a session on a new thread. If `owner` is set a socket pair is
+    /// made and the client end returned, otherwise only widgets are drawn.
+    ///
+    /// The caller must store the session in `CtrlAnime::display`, the thread
+    /// exits as soon as it sees a different session there.
+    pub fn start(
+        inner: Arc<Mutex<CtrlAnime>>,
+        owner: Option<OwnedUniqueName>,
+        fps: u8,
+    ) -> Result<(Self, Option<UnixStream>), RogError> {
+        let (mut source, client, socket) = if owner.is_some() {
+            let (daemon_end, client_end) = UnixStream::pair()?;
+            let socket = daemon_end.try_clone()?;
+            (Some(daemon_end), Some(client_end), Some(socket))
+        } else {
+            (None, None, None)
+        };
+        let id = SESSION_ID.fetch_add(1, Ordering::SeqCst);
+        let interval = Duration::from_secs(1) / fps.clamp(1, MAX_FPS) as u32;
+
+        std::thread::Builder::new()
+            .name("AniMe display session".into())
+            .spawn(move || {
+                info!("AniMe display session {id} started");
+                let anime_type = block_on(inner.lock()).anime_type;
+                let blank = vec![0u8; anime_type.data_length()];
+                let mut frame = blank.clone();
+                loop {
+                    let start = Instant::now();
+                    if let Some(socket) = source.as_mut() {
+                        if let Err(e) = socket.read_exact(&mut frame) {
+                            info!("AniMe frame stream {id} ended: {e}");
+                            source = None;
+                            frame.copy_from_slice(&blank);
+                        }
+                    }
+
+                    let mut lock = block_on(inner.lock());
+                    if lock.display.as_ref().map(|s| s.id) != Some(id) {
+                        break;
+                    }
+                    if source.is_none() {
+                        // Anyone may take over once the stream has ended
+                        if let Some(session) = lock.display.as_mut() {
+                            session.owner = None;
+                        }
+                    }
+                    let widgets_enabled = lock.config.widgets.enabled;
+                    if source.is_none() && !widgets_enabled {
+                        lock.display = None;
+                        lock.node
+                            .write_bytes(&pkt_set_enable_powersave_anim(
+                                lock.config.builtin_anims_enabled,
+                            ))
+                            .ok();
+                        drop(lock);
+                        break;
+                    }
+
+                    let Ok(mut buffer) = AnimeDataBuffer::from_vec(anime_type, frame.clone())
+                    else {
+                        break;
+                    };
+                    if widgets_enabled {
+                        let config = lock.config.widgets.clone();
+                        let layer = effect_metrics::timed("anime_widgets", Stage::Render, || {
+                            render(&config, &mut lock.widget_state, anime_type)
+                        });
+                        if let Some(layer) = layer {
+                            composite(&mut buffer, &layer);
+                        }
+                    }
+                    let effect = if source.is_some() {
+                        "anime_stream"
+                    } else {
+                        "anime_widgets"
+                    };
+                    let res = effect_metrics::timed(effect, Stage::Write, || {
+                        lock.write_data_buffer(buffer)
+                    });
+                    if let Err(e) = res {
+                        warn!("AniMe display session {id}: {e}");
+                    }
+                    // Widget only sessions refresh at the widget rate
+                    let interval = if source.is_some() {
+                        interval
+                    } else {
+                        Duration::from_secs(1) / lock.config.widgets.fps.clamp(1, MAX_FPS) as u32
+                    };
+                    drop(lock);
+
+                    let elapsed = start.elapsed();
+                    if elapsed < interval {
+                        std::thread::sleep(interval - elapsed);
+                    }
+                }
+                info!("AniMe display session {id} exited");
+            })?;
+
+        Ok((Self { id, owner, socket }, client))
+    }
+
+    pub fn owner(&self) -> Option<&OwnedUniqueName> {
+        self.owner.as_ref()
+    }
+
+    /// Close the daemon end of the stream, if any. The thread still needs to
+    /// see the session was replaced before it exits.
+    pub fn stop(&self) {
+        if let Some(socket) = &self.socket {
+            socket.shutdown(Shutdown::Both).ok();
+        }
+    }
+}
@@ -1,35 +1,35 @@
+use std::os::unix::net::UnixStream;
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
 
 use config_traits::StdConfig;
-use log::warn;
-use logind_zbus::manager::ManagerProxy;
+use effect_metrics::Stage;
+use log::{info, warn};
 use rog_anime::usb::{
     pkt_set_brightness, pkt_set_builtin_animations, pkt_set_enable_display,
     pkt_set_enable_powersave_anim, Brightness,
 };
-use rog_anime::{Animations, AnimeDataBuffer, DeviceState};
+use rog_anime::{Animations, AnimeDataBuffer, AnimeWidget, DeviceState};
 use zbus::export::futures_util::lock::Mutex;
-use zbus::{interface, CacheProperties, Connection, SignalContext};
-
+use zbus::fdo::Error as ZbErr;
+use zbus::message::Header;
+use zbus::names::OwnedUniqueName;
+use zbus::zvariant::OwnedFd;
+use zbus::{interface, Connection, SignalContext};
+
+use super::config::AnimeConfigCached;
+use super::stream::DisplaySession;
 use super::CtrlAnime;
+use crate::config_watch;
 use crate::error::RogError;
+use crate::polkit::{self, Action};
+use crate::power_events;
+use crate::shutdown::{self, ShutdownPolicy};
+use crate::{user_session, ConfigChangeListener, Reloadable, ShutdownListener};
 
 pub const ANIME_ZBUS_NAME: &str = "Anime";
 pub const ANIME_ZBUS_PATH: &str = "/org/asuslinux";
 
-async fn get_logind_manager<'a>() -> ManagerProxy<'a> {
-    let connection = Connection::system()
-        .await
-        .expect("Controller could not create dbus connection");
-
-    ManagerProxy::builder(&connection)
-        .cache_properties(CacheProperties::No)
-        .build()
-        .await
-        .expect("Controller could not create ManagerProxy")
-}
-
 #[derive(Clone)]
 pub struct CtrlAnimeZbus(pub Arc<Mutex<CtrlAnime>>);
 
@@ -40,23 +40,338 @@ impl crate::ZbusRun for CtrlAnimeZbus {
     }
 }
 
-// None of these calls can be guarnateed to succeed unless we loop until okay
-// If the try_lock *does* succeed then any other thread trying to lock will not
-// grab it until we finish.
+impl CtrlAnimeZbus {
+    /// Replace whatever is driving the display with a new session, a frame
+    /// stream if `owner` is set or else the widgets alone
+    fn start_display(
+        &self,
+        lock: &mut CtrlAnime,
+        owner: Option<OwnedUniqueName>,
+        fps: u8,
+    ) -> Result<Option<UnixStream>, RogError> {
+        lock.stop_display();
+        lock.thread_exit.store(true, Ordering::SeqCst);
+        lock.node
+            .write_bytes(&pkt_set_enable_powersave_anim(false))?;
+        let (session, client) = DisplaySession::start(self.0.clone(), owner, fps)?;
+        lock.display = Some(session);
+        Ok(client)
+    }
+
+    /// Play the cached shutdown animation for `PowerStates` when the builtin
+    /// animations are off, as the device plays its own otherwise. `AllOff`
+    /// turns the display off without changing `anime.ron`.
+    pub fn listen_shutdown(&self, mut listener: ShutdownListener) {
+        let ctrl = self.clone();
+        tokio::spawn(async move {
+            while let Some(shutdown) = shutdown::requested(&mut listener).await {
+                let mut lock = ctrl.0.lock().await;
+                match shutdown.policy {
+                    ShutdownPolicy::LeaveAsIs => {}
+                    // The builtin shutdown animation is played by the device
+                    ShutdownPolicy::PowerStates => {
+                        if lock.config.display_enabled && !lock.config.builtin_anims_enabled {
+                            lock.stop_display();
+                            CtrlAnime::run_thread(
+                                ctrl.0.clone(),
+                                &lock,
+                                lock.cache.shutdown.clone(),
+                                true,
+                            )
+                            .await;
+                        }
+                    }
+                    ShutdownPolicy::AllOff => {
+                        lock.stop_display();
+                        lock.thread_exit.store(true, Ordering::SeqCst);
+                        lock.node
+                            .write_bytes(&pkt_set_enable_powersave_anim(false))
+                            .and_then(|_| lock.node.write_bytes(&pkt_set_enable_display(false)))
+                            .map_err(|err| warn!("listen_shutdown::all_off {}", err))
+                            .ok();
+                    }
+                }
+            }
+        });
+    }
+
+    /// Apply `anime.ron` each time it is edited outside of asusd
+    pub fn listen_config_changes(
+        &self,
+        mut listener: ConfigChangeListener,
+        signal_ctxt: SignalContext<'static>,
+    ) {
+        let mut ctrl = self.clone();
+        tokio::spawn(async move {
+            let file_name = ctrl.0.lock().await.config.file_name();
+            while config_watch::changed(&mut listener, &file_name).await {
+                let mut lock = ctrl.0.lock().await;
+                let Some(edited) = lock.config.read_new() else {
+                    continue;
+                };
+                let mut cache = AnimeConfigCached::default();
+                if let Err(e) = cache.init_from_config(&edited, lock.anime_type) {
+                    warn!("Not applying the edited {file_name}: {e}");
+                    continue;
+                }
+                info!("{file_name} was edited, applying it");
+                lock.cache = cache;
+                lock.config = edited;
+                drop(lock);
+                ctrl.reload()
+                    .await
+                    .map_err(|e| warn!("Could not apply the edited {file_name}: {e}"))
+                    .ok();
+                Self::notify_config_reloaded(&signal_ctxt).await.ok();
+            }
+        });
+    }
+}
+
 #[interface(name = "org.asuslinux.Anime")]
 impl CtrlAnimeZbus {
     /// Writes a data stream of length. Will force system thread to exit until
     /// it is restarted
-    async fn write(&self, input: AnimeDataBuffer) -> zbus::fdo::Result<()> {
-        let lock = self.0.lock().await;
+    async fn write(
+        &self,
+        input: AnimeDataBuffer,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> zbus::fdo::Result<()> {
+        polkit::check_streaming(connection, &header, Action::FlashAnime).await?;
+        let mut lock = self.0.lock().await;
         lock.thread_exit.store(true, Ordering::SeqCst);
-        lock.write_data_buffer(input).map_err(|err| {
+        lock.stop_display();
+        effect_metrics::timed("anime_direct", Stage::Write, || {
+            lock.write_data_buffer(input)
+        })
+        .map_err(|err| {
             warn!("ctrl_anime::run_animation:callback {}", err);
             err
         })?;
         Ok(())
     }
 
+    /// Clear the drawing canvas to a brightness. The `draw_*` methods only
+    /// change the canvas, call `draw_present` to show it on the display.
+    async fn draw_clear(
+        &self,
+        brightness: u8,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> zbus::fdo::Result<()> {
+        polkit::check_streaming(connection, &header, Action::FlashAnime).await?;
+        let mut lock = self.0.lock().await;
+        lock.canvas.fill_with(brightness);
+        Ok(())
+    }
+
+    /// Set a single pixel on the canvas
+    async fn draw_pixel(
+        &self,
+        x: i32,
+        y: i32,
+        brightness: u8,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> zbus::fdo::Result<()> {
+        polkit::check_streaming(connection, &header, Action::FlashAnime).await?;
+        let mut lock = self.0.lock().await;
+        lock.canvas.pixel(x, y, brightness);
+        Ok(())
+    }
+
+    /// Draw a line on the canvas from `x0`,`y0` to `x1`,`y1`
+    #[allow(clippy::too_many_arguments)]
+    async fn draw_line(
+        &self,
+        x0: i32,
+        y0: i32,
+        x1: i32,
+        y1: i32,
+        brightness: u8,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> zbus::fdo::Result<()> {
+        polkit::check_streaming(connection, &header, Action::FlashAnime).await?;
+        let mut lock = self.0.lock().await;
+        lock.canvas.line(x0, y0, x1, y1, brightness);
+        Ok(())
+    }
+
+    /// Draw a rectangle on the canvas, solid if `fill` is true
+    #[allow(clippy::too_many_arguments)]
+    async fn draw_rect(
+        &self,
+        x: i32,
+        y: i32,
+        w: i32,
+        h: i32,
+        brightness: u8,
+        fill: bool,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> zbus::fdo::Result<()> {
+        polkit::check_streaming(connection, &header, Action::FlashAnime).await?;
+        let mut lock = self.0.lock().await;
+        lock.canvas.rect(x, y, w, h, brightness, fill);
+        Ok(())
+    }
+
+    /// Copy a sprite of `width` columns on to the canvas. Zero values are
+    /// transparent.
+    async fn draw_sprite(
+        &self,
+        x: i32,
+        y: i32,
+        width: u32,
+        sprite: Vec<u8>,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> zbus::fdo::Result<()> {
+        polkit::check_streaming(connection, &header, Action::FlashAnime).await?;
+        let mut lock = self.0.lock().await;
+        lock.canvas.blit(x, y, width as usize, &sprite);
+        Ok(())
+    }
+
+    /// Set the number of brightness levels the canvas is dithered to when
+    /// presented. 0 or 1 disables dithering.
+    async fn draw_dither(
+        &self,
+        levels: u8,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> zbus::fdo::Result<()> {
+        polkit::check_streaming(connection, &header, Action::FlashAnime).await?;
+        let mut lock = self.0.lock().await;
+        lock.dither = levels;
+        Ok(())
+    }
+
+    /// Composite the canvas and write it to the display. Will force system
+    /// thread to exit until it is restarted.
+    async fn draw_present(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> zbus::fdo::Result<()> {
+        polkit::check_streaming(connection, &header, Action::FlashAnime).await?;
+        let mut lock = self.0.lock().await;
+        lock.thread_exit.store(true, Ordering::SeqCst);
+        lock.stop_display();
+        let mut canvas = lock.canvas.clone();
+        canvas.dither(lock.dither);
+        let buffer = AnimeDataBuffer::try_from(canvas).map_err(RogError::from)?;
+        lock.write_data_buffer(buffer).map_err(|err| {
+            warn!("ctrl_anime::draw_present {}", err);
+            err
+        })?;
+        Ok(())
+    }
+
+    /// Start a frame stream and return a socket to write frames to. Each frame
+    /// is the raw `AnimeDataBuffer` data for this model, shown at up to `fps`
+    /// frames per second with the widgets drawn over it if enabled.
+    ///
+    /// The stream belongs to the caller and ends when the socket is closed or
+    /// `write`/`draw_present` is called. Only the owner may replace a running
+    /// stream.
+    async fn stream_frames(
+        &self,
+        fps: u8,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> zbus::fdo::Result<OwnedFd> {
+        polkit::check(connection, &header, Action::FlashAnime).await?;
+        let owner = header
+            .sender()
+            .map(|s| OwnedUniqueName::from(s.to_owned()))
+            .ok_or_else(|| ZbErr::Failed("Could not determine the caller".into()))?;
+
+        let mut lock = self.0.lock().await;
+        if let Some(current) = lock.display.as_ref().and_then(|s| s.owner()) {
+            if *current != owner {
+                return Err(ZbErr::AccessDenied(format!(
+                    "A frame stream is already owned by {current}"
+                )));
+            }
+        }
+        let client = self
+            .start_display(&mut lock, Some(owner), fps)?
+            .ok_or_else(|| ZbErr::Failed("No socket for the frame stream".into()))?;
+        Ok(OwnedFd::from(std::os::fd::OwnedFd::from(client)))
+    }
+
+    /// Set the title shown by the `Media` widget. asusd can't see the
+    /// session's media players so the user daemon forwards it. Only taken
+    /// from the user with the active session.
+    async fn set_media_title(
+        &self,
+        title: String,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> zbus::fdo::Result<()> {
+        user_session::check_active_user(connection, &header).await?;
+        let mut lock = self.0.lock().await;
+        lock.widget_state.media_title = title;
+        Ok(())
+    }
+
+    /// The widgets drawn, top to bottom
+    #[zbus(property)]
+    async fn widgets(&self) -> Vec<AnimeWidget> {
+        let lock = self.0.lock().await;
+        lock.config.widgets.widgets.clone()
+    }
+
+    #[zbus(property)]
+    async fn set_widgets(
+        &self,
+        widgets: Vec<AnimeWidget>,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> zbus::fdo::Result<()> {
+        polkit::check(connection, &header, Action::FlashAnime).await?;
+        let mut lock = self.0.lock().await;
+        lock.config.widgets.widgets = widgets;
+        lock.config.write();
+        Ok(())
+    }
+
+    #[zbus(property)]
+    async fn widgets_enabled(&self) -> bool {
+        let lock = self.0.lock().await;
+        lock.config.widgets.enabled
+    }
+
+    /// Show the widgets. They take over the display when nothing else is
+    /// drawn, and are drawn over any frame stream.
+    #[zbus(property)]
+    async fn set_widgets_enabled(
+        &self,
+        enabled: bool,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> zbus::fdo::Result<()> {
+        polkit::check(connection, &header, Action::FlashAnime).await?;
+        let mut lock = self.0.lock().await;
+        lock.config.widgets.enabled = enabled;
+        lock.config.write();
+
+        let streaming = lock.display.as_ref().is_some_and(|s| s.owner().is_some());
+        if enabled && !streaming {
+            let fps = lock.config.widgets.fps;
+            self.start_display(&mut lock, None, fps)?;
+        } else if !enabled && !streaming && lock.display.is_some() {
+            lock.stop_display();
+            lock.node.write_bytes(&pkt_set_enable_powersave_anim(
+                lock.config.builtin_anims_enabled,
+            ))?;
+        }
+        Ok(())
+    }
+
     /// Set base brightness level
     #[zbus(property)]
     async fn brightness(&self) -> Brightness {
@@ -66,7 +381,13 @@ impl CtrlAnimeZbus {
 
     /// Set base brightness level
     #[zbus(property)]
-    async fn set_brightness(&self, brightness: Brightness) {
+    async fn set_brightness(
+        &self,
+        brightness: Brightness,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> zbus::fdo::Result<()> {
+        polkit::check(connection, &header, Action::FlashAnime).await?;
         let mut lock = self.0.lock().await;
         lock.node
             .write_bytes(&pkt_set_brightness(brightness))
@@ -84,6 +405,7 @@ impl CtrlAnimeZbus {
         lock.config.display_enabled = brightness != Brightness::Off;
         lock.config.display_brightness = brightness;
         lock.config.write();
+        Ok(())
     }
 
     #[zbus(property)]
@@ -95,7 +417,13 @@ impl CtrlAnimeZbus {
     /// Enable the builtin animations or not. This is quivalent to "Powersave
     /// animations" in Armory crate
     #[zbus(property)]
-    async fn set_builtins_enabled(&self, enabled: bool) {
+    async fn set_builtins_enabled(
+        &self,
+        enabled: bool,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> zbus::fdo::Result<()> {
+        polkit::check(connection, &header, Action::FlashAnime).await?;
         let mut lock = self.0.lock().await;
         lock.node
             .set_builtins_enabled(enabled, lock.config.display_brightness)
@@ -123,6 +451,7 @@ impl CtrlAnimeZbus {
         if enabled {
             lock.thread_exit.store(true, Ordering::Release);
         }
+        Ok(())
     }
 
     #[zbus(property)]
@@ -133,7 +462,13 @@ impl CtrlAnimeZbus {
 
     /// Set which builtin animation is used for each stage
     #[zbus(property)]
-    async fn set_builtin_animations(&self, settings: Animations) {
+    async fn set_builtin_animations(
+        &self,
+        settings: Animations,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> zbus::fdo::Result<()> {
+        polkit::check(connection, &header, Action::FlashAnime).await?;
         let mut lock = self.0.lock().await;
         lock.node
             .write_bytes(&pkt_set_builtin_animations(
@@ -155,6 +490,7 @@ impl CtrlAnimeZbus {
         lock.config.display_enabled = true;
         lock.config.builtin_anims = settings;
         lock.config.write();
+        Ok(())
     }
 
     #[zbus(property)]
@@ -165,7 +501,13 @@ impl CtrlAnimeZbus {
 
     /// Set whether the AniMe is enabled at all
     #[zbus(property)]
-    async fn set_enable_display(&self, enabled: bool) {
+    async fn set_enable_display(
+        &self,
+        enabled: bool,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> zbus::fdo::Result<()> {
+        polkit::check(connection, &header, Action::FlashAnime).await?;
         let mut lock = self.0.lock().await;
         lock.node
             .write_bytes(&pkt_set_enable_display(enabled))
@@ -175,6 +517,7 @@ impl CtrlAnimeZbus {
             .ok();
         lock.config.display_enabled = enabled;
         lock.config.write();
+        Ok(())
     }
 
     #[zbus(property)]
@@ -185,10 +528,18 @@ impl CtrlAnimeZbus {
 
     /// Set if to turn the AniMe Matrix off when external power is unplugged
     #[zbus(property)]
-    async fn set_off_when_unplugged(&self, enabled: bool) {
+    async fn set_off_when_unplugged(
+        &self,
+        enabled: bool,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> zbus::fdo::Result<()> {
+        polkit::check(connection, &header, Action::FlashAnime).await?;
         let mut lock = self.0.lock().await;
-        let manager = get_logind_manager().await;
-        let pow = manager.on_external_power().await.unwrap_or_default();
+        let manager = power_events::logind_manager().await;
+        let pow = power_events::on_external_power(manager.as_ref())
+            .await
+            .unwrap_or_default();
 
         lock.node
             .write_bytes(&pkt_set_enable_display(!pow && !enabled))
@@ -199,6 +550,7 @@ impl CtrlAnimeZbus {
 
         lock.config.off_when_unplugged = enabled;
         lock.config.write();
+        Ok(())
     }
 
     #[zbus(property)]
@@ -209,10 +561,17 @@ impl CtrlAnimeZbus {
 
     /// Set if to turn the AniMe Matrix off when the laptop is suspended
     #[zbus(property)]
-    async fn set_off_when_suspended(&self, enabled: bool) {
+    async fn set_off_when_suspended(
+        &self,
+        enabled: bool,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> zbus::fdo::Result<()> {
+        polkit::check(connection, &header, Action::FlashAnime).await?;
         let mut lock = self.0.lock().await;
         lock.config.off_when_suspended = enabled;
         lock.config.write();
+        Ok(())
     }
 
     #[zbus(property)]
@@ -223,10 +582,18 @@ impl CtrlAnimeZbus {
 
     /// Set if to turn the AniMe Matrix off when the lid is closed
     #[zbus(property)]
-    async fn set_off_when_lid_closed(&self, enabled: bool) {
+    async fn set_off_when_lid_closed(
+        &self,
+        enabled: bool,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> zbus::fdo::Result<()> {
+        polkit::check(connection, &header, Action::FlashAnime).await?;
         let mut lock = self.0.lock().await;
-        let manager = get_logind_manager().await;
-        let lid = manager.lid_closed().await.unwrap_or_default();
+        let manager = power_events::logind_manager().await;
+        let lid = power_events::lid_closed(manager.as_ref())
+            .await
+            .unwrap_or_default();
 
         lock.node
             .write_bytes(&pkt_set_enable_display(lid && !enabled))
@@ -237,16 +604,24 @@ impl CtrlAnimeZbus {
 
         lock.config.off_when_lid_closed = enabled;
         lock.config.write();
+        Ok(())
     }
 
     /// The main loop is the base system set action if the user isn't running
     /// the user daemon
-    async fn run_main_loop(&self, start: bool) {
+    async fn run_main_loop(
+        &self,
+        start: bool,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> zbus::fdo::Result<()> {
+        polkit::check(connection, &header, Action::FlashAnime).await?;
         if start {
             let lock = self.0.lock().await;
             lock.thread_exit.store(true, Ordering::SeqCst);
-            CtrlAnime::run_thread(self.0.clone(), lock.cache.system.clone(), false).await;
+            CtrlAnime::run_thread(self.0.clone(), &lock, lock.cache.system.clone(), false).await;
         }
+        Ok(())
     }
 
     /// Get the device state as stored by asusd
@@ -255,6 +630,10 @@ impl CtrlAnimeZbus {
         let lock = self.0.lock().await;
         DeviceState::from(&lock.config)
     }
+
+    /// Sent once an edit of `anime.ron` made outside of asusd has been applied
+    #[zbus(signal)]
+    async fn notify_config_reloaded(ctxt: &SignalContext<'_>) -> zbus::Result<()>;
 }
 
 impl crate::CtrlTask for CtrlAnimeZbus {
@@ -300,8 +679,13 @@ impl crate::CtrlTask for CtrlAnimeZbus {
                                 .write_bytes(&pkt_set_enable_powersave_anim(false))
                                 .ok(); // ensure builtins are disabled
 
-                            CtrlAnime::run_thread(inner.clone(), lock.cache.wake.clone(), true)
-                                .await;
+                            CtrlAnime::run_thread(
+                                inner.clone(),
+                                &lock,
+                                lock.cache.wake.clone(),
+                                true,
+                            )
+                            .await;
                         }
                     }
                 }
@@ -310,15 +694,15 @@ impl crate::CtrlTask for CtrlAnimeZbus {
                 // on_shutdown
                 let inner = inner2.clone();
                 async move {
+                    // The shutdown animation is played by `listen_shutdown`,
+                    // this starts over if the shutdown is cancelled
                     let lock = inner.lock().await;
-                    if lock.config.display_enabled && !lock.config.builtin_anims_enabled {
-                        if shutting_down {
-                            CtrlAnime::run_thread(inner.clone(), lock.cache.shutdown.clone(), true)
-                                .await;
-                        } else {
-                            CtrlAnime::run_thread(inner.clone(), lock.cache.boot.clone(), true)
-                                .await;
-                        }
+                    if !shutting_down
+                        && lock.config.display_enabled
+                        && !lock.config.builtin_anims_enabled
+                    {
+                        CtrlAnime::run_thread(inner.clone(), &lock, lock.cache.boot.clone(), true)
+                            .await;
                     }
                 }
             },
@@ -384,50 +768,59 @@ impl crate::CtrlTask for CtrlAnimeZbus {
 
 impl crate::Reloadable for CtrlAnimeZbus {
     async fn reload(&mut self) -> Result<(), RogError> {
-        if let Some(lock) = self.0.try_lock() {
-            let anim = &lock.config.builtin_anims;
-            // Set builtins
-            if lock.config.builtin_anims_enabled {
-                lock.node.write_bytes(&pkt_set_builtin_animations(
-                    anim.boot,
-                    anim.awake,
-                    anim.sleep,
-                    anim.shutdown,
-                ))?;
-            }
-            // Builtins enabled or na?
-            lock.node.set_builtins_enabled(
-                lock.config.builtin_anims_enabled,
-                lock.config.display_brightness,
-            )?;
+        let mut lock = self.0.lock().await;
+        let anim = &lock.config.builtin_anims;
+        // Set builtins
+        if lock.config.builtin_anims_enabled {
+            lock.node.write_bytes(&pkt_set_builtin_animations(
+                anim.boot,
+                anim.awake,
+                anim.sleep,
+                anim.shutdown,
+            ))?;
+        }
+        // Builtins enabled or na?
+        lock.node.set_builtins_enabled(
+            lock.config.builtin_anims_enabled,
+            lock.config.display_brightness,
+        )?;
+
+        let manager = power_events::logind_manager().await;
+        let lid_closed = power_events::lid_closed(manager.as_ref())
+            .await
+            .unwrap_or_default();
+        let power_plugged = power_events::on_external_power(manager.as_ref())
+            .await
+            .unwrap_or_default();
+
+        let turn_off = (lid_closed && lock.config.off_when_lid_closed)
+            || (!power_plugged && lock.config.off_when_unplugged);
+        lock.node
+            .write_bytes(&pkt_set_enable_display(!turn_off))
+            .map_err(|err| {
+                warn!("create_sys_event_tasks::reload {}", err);
+            })
+            .ok();
+
+        if turn_off || !lock.config.display_enabled {
+            lock.node.write_bytes(&pkt_set_enable_display(false))?;
+            // early return so we don't run animation thread
+            return Ok(());
+        }
 
-            let manager = get_logind_manager().await;
-            let lid_closed = manager.lid_closed().await.unwrap_or_default();
-            let power_plugged = manager.on_external_power().await.unwrap_or_default();
+        if lock.config.widgets.enabled {
+            let fps = lock.config.widgets.fps;
+            self.start_display(&mut lock, None, fps)?;
+            return Ok(());
+        }
 
-            let turn_off = (lid_closed && lock.config.off_when_lid_closed)
-                || (!power_plugged && lock.config.off_when_unplugged);
+        if !lock.config.builtin_anims_enabled && !lock.cache.boot.is_empty() {
             lock.node
-                .write_bytes(&pkt_set_enable_display(!turn_off))
-                .map_err(|err| {
-                    warn!("create_sys_event_tasks::reload {}", err);
-                })
+                .write_bytes(&pkt_set_enable_powersave_anim(false))
                 .ok();
 
-            if turn_off || !lock.config.display_enabled {
-                lock.node.write_bytes(&pkt_set_enable_display(false))?;
-                // early return so we don't run animation thread
-                return Ok(());
-            }
-
-            if !lock.config.builtin_anims_enabled && !lock.cache.boot.is_empty() {
-                lock.node
-                    .write_bytes(&pkt_set_enable_powersave_anim(false))
-                    .ok();
-
-                let action = lock.cache.boot.clone();
-                CtrlAnime::run_thread(self.0.clone(), action, true).await;
-            }
+            let action = lock.cache.boot.clone();
+            CtrlAnime::run_thread(self.0.clone(), &lock, action, true).await;
         }
         Ok(())
     }
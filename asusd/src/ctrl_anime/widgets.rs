@@ -0,0 +1,152 @@
+//! Widgets rendered by the daemon and composited over whatever else is on the
+//! display. The widget layer is drawn on an `AnimeGrid` and converted to an
+//! `AnimeDataBuffer` so that it can be merged with any frame.
+
+use std::fs;
+use std::path::Path;
+
+use rog_anime::{text_width, AnimeDataBuffer, AnimeGrid, AnimeType, AnimeWidget, GLYPH_HEIGHT};
+use serde_derive::{Deserialize, Serialize};
+
+/// Left edge of widget text. Columns near the edge are cut off on some
+/// models.
+const LEFT: i32 = 4;
+/// Top of the first widget, also clear of the shortest rows
+const TOP: i32 = 10;
+/// Space between widget lines
+const SPACING: i32 = 3;
+/// Pixels the media title may use before it is scrolled
+const MEDIA_WIDTH: i32 = 24;
+
+const CPU_HWMON: &[&str] = &["k10temp", "zenpower", "coretemp"];
+const GPU_HWMON: &[&str] = &["amdgpu", "nouveau"];
+
+/// The `[anime]` widget settings
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct AnimeWidgetConfig {
+    /// Show the widgets when nothing else is drawn, and over frame streams
+    pub enabled: bool,
+    /// Drawn top to bottom in this order
+    pub widgets: Vec<AnimeWidget>,
+    /// Widget frames per second when no stream is running. Also the speed the
+    /// media title scrolls at.
+    pub fps: u8,
+    /// LED brightness of the widget text
+    pub brightness: u8,
+}
+
+impl Default for AnimeWidgetConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            widgets: vec![AnimeWidget::Clock, AnimeWidget::CpuTemp],
+            fps: 8,
+            brightness: 200,
+        }
+    }
+}
+
+/// Data the widgets show that the daemon can't find itself
+#[derive(Debug, Default)]
+pub struct WidgetState {
+    /// Set over D-Bus by the user session, which can see MPRIS players
+    pub media_title: String,
+    scroll: i32,
+}
+
+/// The first matching hwmon temperature in degrees C
+fn hwmon_temp(names: &[&str]) -> Option<i32> {
+    for entry in fs::read_dir("/sys/class/hwmon").ok()?.flatten() {
+        let path = entry.path();
+        let Ok(name) = fs::read_to_string(path.join("name")) else {
+            continue;
+        };
+        if names.contains(&name.trim()) {
+            return read_millidegrees(&path.join("temp1_input"));
+        }
+    }
+    None
+}
+
+fn read_millidegrees(path: &Path) -> Option<i32> {
+    let milli: i32 = fs::read_to_string(path).ok()?.trim().parse().ok()?;
+    Some(milli / 1000)
+}
+
+fn temp_text(label: &str, temp: Option<i32>) -> String {
+    match temp {
+        Some(t) => format!("{label} {t}C"),
+        None => format!("{label} --"),
+    }
+}
+
+/// Draw the widgets in to a buffer the size of this display. Each call
+/// advances the media title scroll by one pixel.
+pub fn render(
+    config: &AnimeWidgetConfig,
+    state: &mut WidgetState,
+    anime_type: AnimeType,
+) -> Option<AnimeDataBuffer> {
+    let mut grid = AnimeGrid::new(anime_type);
+    let b = config.brightness;
+    let mut y = TOP;
+    for widget in &config.widgets {
+        match widget {
+            AnimeWidget::Clock => {
+                let now = chrono::Local::now().format("%H:%M").to_string();
+                grid.text(LEFT, y, &now, b);
+            }
+            AnimeWidget::CpuTemp => grid.text(LEFT, y, &temp_text("CPU", hwmon_temp(CPU_HWMON)), b),
+            AnimeWidget::GpuTemp => grid.text(LEFT, y, &temp_text("GPU", hwmon_temp(GPU_HWMON)), b),
+            AnimeWidget::Media => {
+                if state.media_title.is_empty() {
+                    continue;
+                }
+                let width = text_width(&state.media_title);
+                if width <= MEDIA_WIDTH {
+                    grid.text(LEFT, y, &state.media_title, b);
+                } else {
+                    // Scroll in from the right and out to the left
+                    state.scroll = (state.scroll + 1) % (width + MEDIA_WIDTH);
+                    grid.text(LEFT + MEDIA_WIDTH - state.scroll, y, &state.media_title, b);
+                }
+            }
+        }
+        y += GLYPH_HEIGHT + SPACING;
+    }
+    AnimeDataBuffer::try_from(grid).ok()
+}
+
+/// Merge the widget layer in to `frame`, keeping the brightest of each LED
+pub fn composite(frame: &mut AnimeDataBuffer, widgets: &AnimeDataBuffer) {
+    for (led, widget) in frame.data_mut().iter_mut().zip(widgets.data()) {
+        *led = (*led).max(*widget);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rog_anime::{AnimeDataBuffer, AnimeType};
+
+    use super::{composite, temp_text};
+
+    #[test]
+    fn composite_keeps_brightest() {
+        let len = AnimeType::GA401.data_length();
+        let mut frame = AnimeDataBuffer::from_vec(AnimeType::GA401, vec![100; len]).unwrap();
+        let mut widgets = vec![0; len];
+        widgets[0] = 255;
+        widgets[1] = 50;
+        let widgets = AnimeDataBuffer::from_vec(AnimeType::GA401, widgets).unwrap();
+
+        composite(&mut frame, &widgets);
+        assert_eq!(&frame.data()[..3], &[255, 100, 100]);
+    }
+
+    #[test]
+    fn temperature_text() {
+        assert_eq!(temp_text("CPU", Some(64)), "CPU 64C");
+        assert_eq!(temp_text("GPU", None), "GPU --");
+    }
+}
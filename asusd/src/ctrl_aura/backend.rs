@@ -0,0 +1,191 @@
+//! How each kind of Aura device is written to. `CtrlKbdLed` keeps the state
+//! and config and decides what to show, a backend turns that in to writes for
+//! its hardware. The USB HID keyboards are here, TUF keyboards are in `tuf`
+//! and those only in the multicolor LED class in `multicolor`.
+
+use rog_aura::keyboard::{LaptopAuraPower, LedUsbPackets};
+use rog_aura::usb::{LED_APPLY, LED_SET};
+use rog_aura::{AuraDeviceType, AuraEffect, AuraModeNum, LED_MSG_LEN};
+use rog_platform::aura_hid::AuraHidWrite;
+
+use crate::error::RogError;
+
+/// The report type of per-key and zoned packets
+const PER_KEY_TYPE: u8 = 0xbc;
+/// The start of the message setting the power states, followed by their
+/// bytes
+const POWER_MSG: [u8; 3] = [0x5d, 0xbd, 0x01];
+
+/// Whether `packet` is a per-key or zoned packet rather than a raw mode. The
+/// second byte is the report type, which tells the two apart. `None` if the
+/// packet is too short to have one.
+pub fn is_per_key_packet(packet: &[u8]) -> Option<bool> {
+    packet.get(1).map(|kind| *kind == PER_KEY_TYPE)
+}
+
+/// What a backend can write, on top of what the support data says the model
+/// has
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AuraCapabilities {
+    /// The modes the hardware takes, `None` if it takes any
+    pub modes: Option<Vec<AuraModeNum>>,
+    /// Zones can be given their own effect
+    pub zones: bool,
+    /// The boot, awake and sleep states can be set
+    pub power_states: bool,
+    /// Per-key or zoned frames can be written
+    pub frames: bool,
+}
+
+impl AuraCapabilities {
+    pub fn has_mode(&self, mode: AuraModeNum) -> bool {
+        self.modes.as_ref().map_or(true, |m| m.contains(&mode))
+    }
+}
+
+pub trait AuraBackend: std::fmt::Debug + Send {
+    /// The USB product ID, such as `19b6`, or a name for devices not on USB
+    fn prod_id(&self) -> &str;
+
+    fn capabilities(&self) -> AuraCapabilities;
+
+    /// Write a builtin mode and keep it. The zone is the one the hardware
+    /// uses and the colours are corrected already.
+    fn write_mode(&self, mode: &AuraEffect) -> Result<(), RogError>;
+
+    /// Show a builtin mode without keeping it, for a stream of changes where
+    /// only the last is kept by `apply`. Backends that can't tell the two
+    /// apart keep every mode.
+    fn show_mode(&self, mode: &AuraEffect) -> Result<(), RogError> {
+        self.write_mode(mode)
+    }
+
+    /// Keep the modes shown since the last `write_mode`
+    fn apply(&self) -> Result<(), RogError> {
+        Ok(())
+    }
+
+    /// Write the states of each power zone
+    fn write_power(&self, power: &LaptopAuraPower) -> Result<(), RogError>;
+
+    /// Read back the power states after `write_power` and check the firmware
+    /// kept them, `PowerStateMismatch` if not. Devices that can't be read
+    /// back, such as TUF keyboards where `kbd_rgb_state` is write only, pass.
+    fn verify_power(&self, _power: &LaptopAuraPower) -> Result<(), RogError> {
+        Ok(())
+    }
+
+    /// Write the rows of a per-key or zoned frame. `start` is set for the
+    /// first per-key frame after a builtin mode was shown. The rows may be
+    /// only those changed since the last frame.
+    fn write_frame(
+        &self,
+        rows: &mut dyn Iterator<Item = &[u8]>,
+        start: bool,
+    ) -> Result<(), RogError>;
+
+    /// Write a raw factory mode packet, such as a captured vendor effect.
+    /// Devices which have none ignore it.
+    fn write_raw(&self, _packet: &[u8]) -> Result<(), RogError> {
+        Ok(())
+    }
+}
+
+/// The USB keyboards, lightbars and such, written with HID reports
+#[derive(Debug)]
+pub struct UsbHid {
+    device_type: AuraDeviceType,
+    prod_id: String,
+    hid: Box<dyn AuraHidWrite>,
+}
+
+impl UsbHid {
+    pub fn new(device_type: AuraDeviceType, prod_id: &str, hid: Box<dyn AuraHidWrite>) -> Self {
+        Self {
+            device_type,
+            prod_id: prod_id.to_owned(),
+            hid,
+        }
+    }
+}
+
+impl AuraBackend for UsbHid {
+    fn prod_id(&self) -> &str {
+        &self.prod_id
+    }
+
+    fn capabilities(&self) -> AuraCapabilities {
+        AuraCapabilities {
+            modes: None,
+            zones: true,
+            power_states: true,
+            frames: true,
+        }
+    }
+
+    fn write_mode(&self, mode: &AuraEffect) -> Result<(), RogError> {
+        self.show_mode(mode)?;
+        self.apply()
+    }
+
+    fn show_mode(&self, mode: &AuraEffect) -> Result<(), RogError> {
+        let bytes: [u8; LED_MSG_LEN] = mode.into();
+        self.hid.write_bytes(&bytes)?;
+        self.hid.write_bytes(&LED_SET)?;
+        Ok(())
+    }
+
+    fn apply(&self) -> Result<(), RogError> {
+        // Changes won't persist unless apply is set
+        self.hid.write_bytes(&LED_APPLY)?;
+        Ok(())
+    }
+
+    fn write_power(&self, power: &LaptopAuraPower) -> Result<(), RogError> {
+        let bytes = power.to_bytes(self.device_type);
+        let [id, cmd, set] = POWER_MSG;
+        let message = [id, cmd, set, bytes[0], bytes[1], bytes[2], bytes[3]];
+
+        self.hid.write_bytes(&message)?;
+        self.hid.write_bytes(&LED_SET)?;
+        // Changes won't persist unless apply is set
+        self.hid.write_bytes(&LED_APPLY)?;
+        Ok(())
+    }
+
+    fn verify_power(&self, power: &LaptopAuraPower) -> Result<(), RogError> {
+        let written = power.to_bytes(self.device_type);
+        let mut report = [0; LED_MSG_LEN];
+        report[0] = POWER_MSG[0];
+        // Only firmware that answers with the power message can be checked
+        if self.hid.read_feature(&mut report).is_err() || report[1..3] != POWER_MSG[1..] {
+            return Ok(());
+        }
+        let read = &report[3..3 + written.len()];
+        if read != written.as_slice() {
+            return Err(RogError::PowerStateMismatch(written, read.to_vec()));
+        }
+        Ok(())
+    }
+
+    fn write_frame(
+        &self,
+        rows: &mut dyn Iterator<Item = &[u8]>,
+        start: bool,
+    ) -> Result<(), RogError> {
+        if start {
+            self.hid.write_bytes(&LedUsbPackets::get_init_msg())?;
+        }
+        for row in rows {
+            self.hid.write_bytes(row)?;
+        }
+        Ok(())
+    }
+
+    fn write_raw(&self, packet: &[u8]) -> Result<(), RogError> {
+        self.hid.write_bytes(packet)?;
+        self.hid.write_bytes(&LED_SET)?;
+        // self.hid.write_bytes(&LED_APPLY)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,1000 @@
+//! Changes to an Aura device are queued and applied in order by one task,
+//! instead of each D-Bus call, hotkey and watcher racing for the controller.
+//! Nothing is dropped while the device is busy, and a caller waits for its own
+//! change and is told if it failed. Every write to the device goes through
+//! here, including the frames of a stream and the restores on resume. Once
+//! the queue is full callers wait for room.
+//!
+//! Effects set faster than the write interval of the config, as a colour
+//! slider does, are coalesced. Only the latest of those waiting is written, at
+//! most once an interval, and the stream is only kept by the firmware with
+//! `LED_APPLY` once it settles. Rapid applies can lock up some firmware.
+
+use std::sync::Arc;
+
+use config_traits::StdConfig;
+use effect_metrics::Stage;
+use log::{info, warn};
+use rog_aura::audio_indicator::{AudioIndicator, AudioOutput};
+use rog_aura::aura_detection::{LaptopLedData, PowerZones};
+use rog_aura::effects::TrailConfig;
+use rog_aura::keyboard::{
+    AdvancedAuraType, LaptopAuraPower, LedUsbPackets, PowerPhase, UsbPackets, USB_PACKET_LEN,
+};
+use rog_aura::user_override::AuraOverride;
+use rog_aura::{AuraEffect, AuraModeNum, AuraZone, LedBrightness, LED_MSG_LEN};
+use tokio::sync::{mpsc, oneshot, watch};
+use tokio::time::{self, Instant};
+use zbus::export::futures_util::lock::Mutex;
+use zbus::fdo::Error as ZbErr;
+
+use super::backend::is_per_key_packet;
+use super::config::AuraConfig;
+use super::controller::CtrlKbdLed;
+use super::stream::FrameStream;
+use crate::ctrl_events::Hotkey;
+use crate::keys::BrightnessKey;
+use crate::led_automation::LedRule;
+use crate::shutdown::ShutdownPolicy;
+
+/// Commands waiting to be applied before callers have to wait to queue more
+const QUEUE_LEN: usize = 64;
+
+#[derive(Debug)]
+pub enum AuraCommand {
+    SetBrightness(LedBrightness),
+    SetLedMode(AuraModeNum),
+    SetLedModeData(AuraEffect),
+    /// The effect of one zone, the others keep theirs
+    SetZone(AuraZone, AuraEffect),
+    SetZoneBrightness(AuraZone, LedBrightness),
+    SetLedPower(LaptopAuraPower),
+    /// Turn a power zone on or off for one phase, leaving the others
+    SetLedPowerState(PowerZones, PowerPhase, bool),
+    SetAudioIndicator(AudioIndicator),
+    SetAudioOutput(AudioOutput),
+    SetTypingTrail(TrailConfig),
+    SetLedRules(Vec<LedRule>),
+    /// Store the lighting as it is now under a name, replacing any preset
+    /// of that name
+    SavePreset(String),
+    ApplyPreset(String),
+    RemovePreset(String),
+    SaveRawMode(String, UsbPackets),
+    /// Show a saved raw mode in place of the current mode, also after a
+    /// restart
+    ApplyRawMode(String),
+    RemoveRawMode(String),
+    /// Show an automation rule that became active, or restore the stored
+    /// state if none is
+    ShowLedRule(Option<LedRule>),
+    /// A convertible was folded in to or out of tablet mode
+    TabletMode(bool),
+    /// Lighting from the session daemon of the user with the active session
+    SetUserOverride(u32, AuraOverride),
+    /// The user ended their own lighting
+    ClearUserOverride(u32),
+    /// The user with the active session changed, `None` at the greeter.
+    /// The lighting of any other user ends.
+    ActiveUser(Option<u32>),
+    /// The mode associated with a throttle policy that became active
+    ProfileMode(AuraModeNum),
+    /// An Aura hotkey which asusd handles
+    Hotkey(Hotkey),
+    /// A step from the brightness key reader. The config is saved later by
+    /// the reader so that holding the key doesn't write it on every step.
+    StepBrightness(BrightnessKey),
+    /// The brightness was changed through sysfs, copy it to the config
+    SyncBrightness,
+    /// Seconds without input before the lighting fades out, `0` for never
+    SetIdleTimeout(u32),
+    SetIdleFade(u32),
+    /// One step of the fade out for a lack of input
+    IdleStep,
+    /// There was input after `IdleStep`
+    EndIdle,
+    Shutdown(ShutdownPolicy),
+    /// The config file was edited outside of asusd
+    ConfigEdited(Box<AuraConfig>),
+    /// Write the stored state again, such as when asusd reloads its
+    /// controllers
+    Reload,
+    /// The laptop is about to suspend
+    Sleep,
+    /// The laptop woke, or a shutdown was cancelled
+    Resume,
+    /// `kbd_rgb_mode` was written (TUF only), by asusd or anything else
+    ReconcileMode,
+    TemporaryEffect(TemporaryEffect),
+    /// Restore the stored mode if the temporary effect `id` is still shown
+    EndTemporaryEffect(u64),
+    /// End the preview of the client, refused if another client's is shown
+    CancelPreview(String),
+    /// Read the LED support data again and bring the config in line with it
+    ReloadSupportData,
+    /// Raw packets from `DirectAddressingRaw`
+    WriteRaw(UsbPackets),
+    /// A frame from `DirectAddressingFrame`, only the rows changed since the
+    /// last are written
+    WriteClientFrame(LedUsbPackets),
+    /// Show the frames of a new stream, unless another client owns one
+    StartFrameStream(FrameStream),
+    /// Send changes of the typing trail config to the trail started last
+    FollowTypingTrail(watch::Sender<TrailConfig>),
+    /// A frame of the stream `id`
+    WriteFrame(u64, LedUsbPackets),
+    /// The stream `id` ended, restore the stored mode if it was still shown
+    EndFrameStream(u64),
+}
+
+/// An effect shown without storing it, until `EndTemporaryEffect` with its
+/// `id`
+#[derive(Debug)]
+pub struct TemporaryEffect {
+    pub id: u64,
+    pub effect: AuraEffect,
+    /// The sessions are idle, the brightness is raised for the effect
+    pub idle: bool,
+    /// The client a preview is for, which can end it with `CancelPreview`
+    pub preview_owner: Option<String>,
+}
+
+type Queued = (AuraCommand, oneshot::Sender<Result<(), ZbErr>>);
+type ApplyFn = fn(&mut CtrlKbdLed, AuraCommand) -> Result<(), ZbErr>;
+
+#[derive(Clone)]
+pub struct AuraQueue(mpsc::Sender<Queued>);
+
+impl AuraQueue {
+    /// Start the task applying queued commands to `ctrl`
+    pub fn start(ctrl: Arc<Mutex<CtrlKbdLed>>) -> Self {
+        Self::start_with(ctrl, apply)
+    }
+
+    /// As `start`, with each command applied by `apply`
+    fn start_with(ctrl: Arc<Mutex<CtrlKbdLed>>, apply: ApplyFn) -> Self {
+        let (tx, mut rx) = mpsc::channel::<Queued>(QUEUE_LEN);
+        tokio::spawn(async move {
+            // A command taken off the channel while coalescing, applied next
+            let mut held = None;
+            // When the last effect was written
+            let mut last_effect: Option<Instant> = None;
+            loop {
+                let next = match held.take() {
+                    Some(next) => Some(next),
+                    None => {
+                        let (pending, interval) = {
+                            let ctrl = ctrl.lock().await;
+                            (ctrl.apply_pending, ctrl.config.write_interval())
+                        };
+                        match last_effect.filter(|_| pending) {
+                            // Keep the last of the stream once no more effects come
+                            Some(last) => {
+                                match time::timeout_at(last + interval, rx.recv()).await {
+                                    Ok(next) => next,
+                                    Err(_) => {
+                                        flush_apply(&mut *ctrl.lock().await);
+                                        continue;
+                                    }
+                                }
+                            }
+                            None => rx.recv().await,
+                        }
+                    }
+                };
+                let Some((mut command, reply)) = next else {
+                    break;
+                };
+                let mut replies = vec![reply];
+
+                let interval = ctrl.lock().await.config.write_interval();
+                let effect = is_effect(&command);
+                let streamed = effect
+                    && !interval.is_zero()
+                    && last_effect.is_some_and(|last| last.elapsed() < interval);
+                if effect && !interval.is_zero() {
+                    // Take newer effects for the same zone in place of this one,
+                    // those queued already and, in a stream, those coming in
+                    // before the interval is up
+                    let due = match last_effect {
+                        Some(last) if streamed => last + interval,
+                        _ => Instant::now(),
+                    };
+                    while let Ok(Some(next)) = time::timeout_at(due, rx.recv()).await {
+                        if !supersedes(&command, &next.0) {
+                            held = Some(next);
+                            break;
+                        }
+                        command = next.0;
+                        replies.push(next.1);
+                    }
+                }
+
+                let mut ctrl = ctrl.lock().await;
+                if effect {
+                    ctrl.defer_apply = streamed;
+                    last_effect = Some(Instant::now());
+                } else {
+                    flush_apply(&mut ctrl);
+                }
+                let res = apply(&mut ctrl, command);
+                ctrl.defer_apply = false;
+                // Callers of an effect that was replaced are given the result
+                // of the one written. The caller may have stopped waiting.
+                for reply in replies {
+                    reply.send(res.clone()).ok();
+                }
+            }
+        });
+        Self(tx)
+    }
+
+    /// Queue `command` and wait until it has been applied. The controller
+    /// must not be locked by the caller while waiting.
+    pub async fn run(&self, command: AuraCommand) -> Result<(), ZbErr> {
+        let (tx, rx) = oneshot::channel();
+        self.0
+            .send((command, tx))
+            .await
+            .map_err(|_| ZbErr::Failed("The Aura command task has stopped".into()))?;
+        rx.await
+            .map_err(|_| ZbErr::Failed("The Aura command was not applied".into()))?
+    }
+
+    /// As `run`, for threads outside of the async runtime
+    pub fn run_blocking(&self, command: AuraCommand) -> Result<(), ZbErr> {
+        let (tx, rx) = oneshot::channel();
+        self.0
+            .blocking_send((command, tx))
+            .map_err(|_| ZbErr::Failed("The Aura command task has stopped".into()))?;
+        rx.blocking_recv()
+            .map_err(|_| ZbErr::Failed("The Aura command was not applied".into()))?
+    }
+}
+
+/// Effects which are written to the device on each change, and can be
+/// coalesced when set faster than the write interval
+fn is_effect(command: &AuraCommand) -> bool {
+    matches!(
+        command,
+        AuraCommand::SetLedModeData(_) | AuraCommand::SetZone(..)
+    )
+}
+
+/// `next` replaces `command` if it sets the effect of the same zone
+fn supersedes(command: &AuraCommand, next: &AuraCommand) -> bool {
+    match (command, next) {
+        (AuraCommand::SetLedModeData(a), AuraCommand::SetLedModeData(b)) => a.zone == b.zone,
+        (AuraCommand::SetZone(a, _), AuraCommand::SetZone(b, _)) => a == b,
+        _ => false,
+    }
+}
+
+/// Keep the modes of a stream of effects before anything else is written
+fn flush_apply(ctrl: &mut CtrlKbdLed) {
+    ctrl.flush_apply()
+        .map_err(|e| warn!("Could not apply the Aura mode: {e}"))
+        .ok();
+}
+
+/// Errors if the mode or zone of `effect` isn't supported by the device
+fn check_effect(ctrl: &CtrlKbdLed, effect: &AuraEffect) -> Result<(), ZbErr> {
+    if !ctrl.supported_data.basic_modes.contains(&effect.mode)
+        || effect.zone != AuraZone::None && !ctrl.supported_data.basic_zones.contains(&effect.zone)
+    {
+        return Err(ZbErr::NotSupported(format!(
+            "The Aura effect is not supported: {effect:?}"
+        )));
+    }
+    Ok(())
+}
+
+/// Make sure a newly set mode can be seen
+fn light_up(ctrl: &mut CtrlKbdLed) -> Result<(), ZbErr> {
+    if ctrl.config.brightness == LedBrightness::Off {
+        ctrl.config.brightness = LedBrightness::Med;
+    }
+    Ok(ctrl
+        .led_node
+        .set_brightness(ctrl.config.brightness.into())?)
+}
+
+/// Show the stored brightness and mode again
+fn restore(ctrl: &mut CtrlKbdLed) -> Result<(), ZbErr> {
+    if ctrl.config.off_for_shutdown {
+        ctrl.config.update(|config| config.off_for_shutdown = false);
+    }
+    ctrl.led_node
+        .set_brightness(ctrl.shown_brightness().into())?;
+    Ok(ctrl.write_current_config_mode()?)
+}
+
+/// Clear the temporary effect and write the stored mode if `id` is the one
+/// shown, then drop back to the brightness it raised from once no temporary
+/// effect is left. A stale `id` only does the latter.
+fn end_temporary_effect(ctrl: &mut CtrlKbdLed, id: u64) -> Result<(), ZbErr> {
+    let mut res = Ok(());
+    if ctrl.temporary_effect == Some(id) {
+        ctrl.temporary_effect = None;
+        ctrl.preview_owner = None;
+        res = ctrl.write_current_config_mode();
+    }
+    // A newer temporary effect restores the brightness when it ends
+    if ctrl.temporary_effect.is_none() && ctrl.woken_from.is_some() {
+        res = res.and(ctrl.end_wake());
+    }
+    Ok(res?)
+}
+
+fn apply(ctrl: &mut CtrlKbdLed, command: AuraCommand) -> Result<(), ZbErr> {
+    match command {
+        AuraCommand::SetBrightness(level) => {
+            ctrl.idle_level = None;
+            ctrl.led_node.set_brightness(level.into())?
+        }
+        AuraCommand::SetLedMode(num) => {
+            ctrl.stop_frame_stream();
+            ctrl.temporary_effect = None;
+            ctrl.config.raw_mode = None;
+            ctrl.config.current_mode = num;
+            ctrl.write_current_config_mode()?;
+            light_up(ctrl)?;
+            ctrl.config.write();
+        }
+        AuraCommand::SetLedModeData(effect) => {
+            check_effect(ctrl, &effect)?;
+            ctrl.stop_frame_stream();
+            ctrl.temporary_effect = None;
+            ctrl.config.raw_mode = None;
+            ctrl.write_mode(&effect)?;
+            light_up(ctrl)?;
+            ctrl.config.set_builtin(effect);
+            ctrl.config.write();
+        }
+        AuraCommand::SetZone(zone, mut effect) => {
+            if zone == AuraZone::None {
+                return Err(ZbErr::InvalidArgs(
+                    "A zone is needed, set LedModeData for the whole keyboard".to_owned(),
+                ));
+            }
+            effect.zone = zone;
+            check_effect(ctrl, &effect)?;
+            ctrl.stop_frame_stream();
+            ctrl.temporary_effect = None;
+            ctrl.config.raw_mode = None;
+            let zones = ctrl.supported_data.basic_zones.clone();
+            ctrl.config.set_zone(effect, &zones);
+            ctrl.write_current_config_mode()?;
+            light_up(ctrl)?;
+            ctrl.config.write();
+        }
+        AuraCommand::SetZoneBrightness(zone, level) => {
+            ctrl.set_zone_brightness(zone, level)?;
+            ctrl.config.write();
+        }
+        AuraCommand::SetLedPower(options) => {
+            for opt in options.states {
+                for config in ctrl.config.enabled.states.iter_mut() {
+                    if config.zone == opt.zone {
+                        *config = opt;
+                    }
+                }
+            }
+            ctrl.config.write();
+            ctrl.set_power_states().map_err(|e| {
+                warn!("{}", e);
+                e
+            })?;
+        }
+        AuraCommand::SetLedPowerState(zone, phase, on) => {
+            if !ctrl.config.enabled.set_state(zone, phase, on) {
+                return Err(ZbErr::NotSupported(format!(
+                    "Power zone {zone:?} is not on this device"
+                )));
+            }
+            ctrl.config.write();
+            ctrl.set_power_states().map_err(|e| {
+                warn!("{}", e);
+                e
+            })?;
+        }
+        AuraCommand::SetAudioIndicator(indicator) => {
+            if indicator.enabled && !ctrl.supported_data.basic_zones.contains(&indicator.zone) {
+                return Err(ZbErr::NotSupported(format!(
+                    "Zone {:?} can't be lit on its own",
+                    indicator.zone
+                )));
+            }
+            ctrl.config.audio_indicator = indicator;
+            ctrl.config.write();
+            if ctrl.stored_mode_shown() {
+                ctrl.write_current_config_mode()?;
+            }
+        }
+        AuraCommand::SetAudioOutput(output) => {
+            if ctrl.audio_output == output {
+                return Ok(());
+            }
+            ctrl.audio_output = output;
+            if ctrl.config.audio_indicator.enabled && ctrl.stored_mode_shown() {
+                ctrl.write_current_config_mode()?;
+            }
+        }
+        AuraCommand::SetTypingTrail(trail) => {
+            trail.validate().map_err(ZbErr::InvalidArgs)?;
+            if ctrl
+                .typing_trail
+                .as_ref()
+                .is_some_and(|running| running.send(trail.clone()).is_err())
+            {
+                ctrl.typing_trail = None;
+            }
+            ctrl.config.typing_trail = trail;
+            ctrl.config.write();
+        }
+        AuraCommand::SetLedRules(rules) => {
+            for rule in &rules {
+                rule.validate().map_err(ZbErr::InvalidArgs)?;
+                if let Some(zone) = rule
+                    .zones_off
+                    .iter()
+                    .find(|z| !ctrl.supported_data.power_zones.contains(z))
+                {
+                    return Err(ZbErr::NotSupported(format!(
+                        "Power zone {zone:?} is not on this device"
+                    )));
+                }
+            }
+            ctrl.config.led_rules = rules;
+            ctrl.config.write();
+        }
+        AuraCommand::ShowLedRule(rule) => ctrl.show_led_rule(rule)?,
+        AuraCommand::TabletMode(on) => ctrl.set_tablet_mode(on)?,
+        AuraCommand::SetUserOverride(uid, user) => {
+            check_effect(ctrl, &user.effect)?;
+            ctrl.set_user_override(Some((uid, user)))?;
+        }
+        AuraCommand::ClearUserOverride(uid) => {
+            if ctrl
+                .user_override
+                .as_ref()
+                .is_some_and(|(owner, _)| *owner == uid)
+            {
+                ctrl.set_user_override(None)?;
+            }
+        }
+        AuraCommand::ActiveUser(uid) => {
+            if ctrl
+                .user_override
+                .as_ref()
+                .is_some_and(|(owner, _)| Some(*owner) != uid)
+            {
+                ctrl.set_user_override(None)?;
+            }
+        }
+        AuraCommand::SavePreset(name) => {
+            let name = name.trim();
+            if name.is_empty() {
+                return Err(ZbErr::InvalidArgs("A preset needs a name".to_owned()));
+            }
+            let preset = ctrl.config.snapshot();
+            ctrl.config.presets.insert(name.to_owned(), preset);
+            ctrl.config.write();
+        }
+        AuraCommand::ApplyPreset(name) => {
+            let preset = ctrl
+                .config
+                .presets
+                .get(&name)
+                .cloned()
+                .ok_or_else(|| ZbErr::InvalidArgs(format!("There is no preset {name}")))?;
+            // Presets can be older than the support data
+            for effect in preset.effects() {
+                check_effect(ctrl, effect)?;
+            }
+            ctrl.stop_frame_stream();
+            ctrl.temporary_effect = None;
+            ctrl.config.raw_mode = None;
+            ctrl.config.apply_preset(&preset);
+            ctrl.led_node
+                .set_brightness(ctrl.shown_brightness().into())?;
+            ctrl.write_current_config_mode()?;
+            ctrl.set_power_states()?;
+            ctrl.config.write();
+        }
+        AuraCommand::RemovePreset(name) => {
+            if ctrl.config.presets.remove(&name).is_none() {
+                return Err(ZbErr::InvalidArgs(format!("There is no preset {name}")));
+            }
+            ctrl.config.write();
+        }
+        AuraCommand::SaveRawMode(name, packets) => {
+            let name = name.trim();
+            if name.is_empty() {
+                return Err(ZbErr::InvalidArgs("A raw mode needs a name".to_owned()));
+            }
+            if packets.is_empty() {
+                return Err(ZbErr::InvalidArgs(
+                    "A raw mode needs at least one packet".to_owned(),
+                ));
+            }
+            if let Some(packet) = packets.iter().find(|packet| {
+                ![LED_MSG_LEN, USB_PACKET_LEN].contains(&packet.len())
+                    || is_per_key_packet(packet) != Some(false)
+            }) {
+                return Err(ZbErr::InvalidArgs(format!(
+                    "Not a raw mode packet of {LED_MSG_LEN} or {USB_PACKET_LEN} bytes: {packet:02x?}"
+                )));
+            }
+            ctrl.config.raw_modes.insert(name.to_owned(), packets);
+            ctrl.config.write();
+        }
+        AuraCommand::ApplyRawMode(name) => {
+            let packets = ctrl
+                .config
+                .raw_modes
+                .get(&name)
+                .cloned()
+                .ok_or_else(|| ZbErr::InvalidArgs(format!("There is no raw mode {name}")))?;
+            ctrl.stop_frame_stream();
+            ctrl.temporary_effect = None;
+            ctrl.write_effect_block(&packets)?;
+            ctrl.config.raw_mode = Some(name);
+            ctrl.config.write();
+        }
+        AuraCommand::RemoveRawMode(name) => {
+            if ctrl.config.raw_modes.remove(&name).is_none() {
+                return Err(ZbErr::InvalidArgs(format!("There is no raw mode {name}")));
+            }
+            if ctrl.config.raw_mode.as_ref() == Some(&name) {
+                ctrl.config.raw_mode = None;
+                if ctrl.stored_mode_shown() {
+                    ctrl.write_current_config_mode()?;
+                }
+            }
+            ctrl.config.write();
+        }
+        AuraCommand::ProfileMode(mode) => {
+            if !ctrl.supported_data.basic_modes.contains(&mode) {
+                return Err(ZbErr::NotSupported(format!(
+                    "Profile Aura mode {mode:?} is not supported by this keyboard"
+                )));
+            }
+            ctrl.stop_frame_stream();
+            ctrl.temporary_effect = None;
+            ctrl.config.raw_mode = None;
+            ctrl.config.current_mode = mode;
+            ctrl.write_current_config_mode()?;
+            ctrl.config.write();
+        }
+        AuraCommand::Hotkey(key) => {
+            match key {
+                // Already stepped by the brightness key reader
+                Hotkey::KbdBrightnessUp | Hotkey::KbdBrightnessDown
+                    if ctrl.config.brightness_keys.enabled =>
+                {
+                    return Ok(())
+                }
+                Hotkey::KbdBrightnessUp => ctrl.step_brightness(BrightnessKey::Up)?,
+                Hotkey::KbdBrightnessDown => ctrl.step_brightness(BrightnessKey::Down)?,
+                Hotkey::KbdBrightnessCycle => ctrl.cycle_brightness()?,
+                Hotkey::AuraNext => ctrl.cycle_mode(true)?,
+                Hotkey::AuraPrevious => ctrl.cycle_mode(false)?,
+                _ => return Ok(()),
+            }
+            ctrl.config.write();
+        }
+        AuraCommand::StepBrightness(key) => ctrl.step_brightness(key)?,
+        // Not copied while turned off for shutdown, the stored level is
+        // restored on start. Nor while an automation rule, a woken
+        // notification or tablet mode sets it, changes then last until it
+        // ends.
+        AuraCommand::SyncBrightness => {
+            let level: LedBrightness = ctrl.led_node.get_brightness()?.into();
+            // Any level but the one faded to is set by the user, which ends
+            // the fade
+            if ctrl.idle_level.is_some_and(|idle| idle != level) {
+                ctrl.idle_level = None;
+            }
+            if !ctrl.brightness_overridden() {
+                ctrl.config.brightness = level;
+            }
+        }
+        AuraCommand::SetIdleTimeout(secs) => {
+            ctrl.config.idle_off.timeout_secs = secs;
+            ctrl.idle_changed.notify_one();
+            if secs == 0 {
+                ctrl.end_idle()?;
+            }
+            ctrl.config.write();
+        }
+        AuraCommand::SetIdleFade(ms) => {
+            ctrl.config.idle_off.fade_ms = ms;
+            ctrl.idle_changed.notify_one();
+            ctrl.config.write();
+        }
+        AuraCommand::IdleStep => ctrl.idle_step()?,
+        AuraCommand::EndIdle => ctrl.end_idle()?,
+        AuraCommand::Shutdown(policy) => ctrl.prepare_for_shutdown(policy)?,
+        AuraCommand::ConfigEdited(edited) => {
+            ctrl.config = *edited;
+            ctrl.idle_changed.notify_one();
+            ctrl.stop_frame_stream();
+            ctrl.temporary_effect = None;
+            let res = ctrl
+                .write_current_config_mode()
+                .and_then(|_| ctrl.led_node.set_brightness(ctrl.shown_brightness().into()));
+            ctrl.set_power_states().map_err(|e| warn!("{e}")).ok();
+            res?;
+        }
+        AuraCommand::Reload => {
+            restore(ctrl)?;
+            ctrl.set_power_states().map_err(|e| warn!("{e}")).ok();
+        }
+        AuraCommand::Sleep => {
+            ctrl.prepare_for_sleep()
+                .map_err(|e| warn!("Aura sleep: {e}"))
+                .ok();
+            // The brightness shown is not one to keep
+            if !ctrl.brightness_overridden() {
+                let level = ctrl.led_node.get_brightness()?;
+                ctrl.config
+                    .update(|config| config.brightness = level.into());
+            }
+        }
+        AuraCommand::Resume => restore(ctrl)?,
+        AuraCommand::ReconcileMode => {
+            ctrl.reconcile_kbd_rgb_mode()?;
+        }
+        AuraCommand::TemporaryEffect(temporary) => {
+            check_effect(ctrl, &temporary.effect)?;
+            ctrl.stop_frame_stream();
+            ctrl.write_mode(&temporary.effect)?;
+            ctrl.temporary_effect = Some(temporary.id);
+            ctrl.preview_owner = temporary.preview_owner;
+            ctrl.wake_for_effect(temporary.idle)?;
+        }
+        AuraCommand::EndTemporaryEffect(id) => end_temporary_effect(ctrl, id)?,
+        AuraCommand::CancelPreview(sender) => {
+            let Some(id) = ctrl.temporary_effect else {
+                return Ok(());
+            };
+            // A temporary effect that isn't a preview is left to end itself
+            match ctrl.preview_owner.as_deref().map(|owner| owner == sender) {
+                Some(true) => end_temporary_effect(ctrl, id)?,
+                Some(false) => {
+                    return Err(ZbErr::AccessDenied(
+                        "The preview shown is another client's".to_owned(),
+                    ))
+                }
+                None => {}
+            }
+        }
+        AuraCommand::ReloadSupportData => {
+            let data = ctrl.backend_support(LaptopLedData::get_data());
+            if data == ctrl.supported_data {
+                info!("LED support data is unchanged");
+                return Ok(());
+            }
+            info!("Reloaded LED support data for {}", data.board_name);
+            ctrl.supported_data = data;
+            let device_type = ctrl.led_type;
+            let supported = ctrl.supported_data.clone();
+            if ctrl.config.revalidate(device_type, &supported) {
+                ctrl.config.write();
+            }
+            ctrl.stop_frame_stream();
+            ctrl.temporary_effect = None;
+            ctrl.write_current_config_mode()?;
+            ctrl.set_power_states()?;
+        }
+        AuraCommand::WriteRaw(packets) => {
+            if packets
+                .first()
+                .and_then(|packet| is_per_key_packet(packet))
+                .is_none()
+            {
+                return Err(ZbErr::InvalidArgs(
+                    "Raw writes need at least one packet".to_owned(),
+                ));
+            }
+            effect_metrics::timed("aura_direct", Stage::Write, || {
+                ctrl.write_effect_block(&packets)
+            })?;
+        }
+        AuraCommand::WriteClientFrame(frame) => {
+            if !ctrl.backend.capabilities().frames {
+                return Err(ZbErr::NotSupported(
+                    "This keyboard can't show frames".to_owned(),
+                ));
+            }
+            // Single colour keyboards, such as TUF, take either kind
+            let zoned = match ctrl.supported_data.advanced_type {
+                AdvancedAuraType::PerKey => Some(false),
+                AdvancedAuraType::Zoned(_) => Some(true),
+                AdvancedAuraType::None => None,
+            };
+            if zoned.is_some_and(|zoned| frame.is_zoned() != zoned) {
+                return Err(ZbErr::InvalidArgs(
+                    "The frame is not for this kind of keyboard".to_owned(),
+                ));
+            }
+            effect_metrics::timed("aura_direct", Stage::Write, || ctrl.write_frame(&frame))?;
+        }
+        AuraCommand::FollowTypingTrail(running) => {
+            // It may have changed since the trail read it
+            running.send_replace(ctrl.config.typing_trail.clone());
+            ctrl.typing_trail = Some(running);
+        }
+        AuraCommand::StartFrameStream(stream) => {
+            if let Some(owner) = ctrl.frame_stream.as_ref().and_then(|s| s.owner()) {
+                if Some(owner) != stream.owner() {
+                    stream.stop();
+                    return Err(ZbErr::AccessDenied(format!(
+                        "A frame stream is already owned by {owner}"
+                    )));
+                }
+            }
+            ctrl.stop_frame_stream();
+            ctrl.frame_stream = Some(stream);
+        }
+        AuraCommand::WriteFrame(id, packets) => {
+            if ctrl.frame_stream.as_ref().map(|s| s.id()) != Some(id) {
+                return Err(ZbErr::Failed("Replaced or stopped".to_owned()));
+            }
+            effect_metrics::timed("aura_stream", Stage::Write, || ctrl.write_frame(&packets))?;
+        }
+        AuraCommand::EndFrameStream(id) => {
+            if ctrl.frame_stream.as_ref().map(|s| s.id()) == Some(id) {
+                ctrl.frame_stream = None;
+                ctrl.write_current_config_mode()?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use rog_aura::keyboard::{AdvancedAuraType, LedUsbPackets};
+    use rog_aura::usb::{LED_APPLY, LED_SET};
+    use rog_aura::{AuraEffect, AuraModeNum, Colour, LedBrightness};
+    use rog_platform::aura_hid::{AuraHidWrite, MockHid};
+    use zbus::export::futures_util::lock::Mutex;
+    use zbus::fdo::Error as ZbErr;
+
+    use super::{apply, AuraCommand, AuraQueue, TemporaryEffect};
+    use crate::ctrl_aura::controller::tests::mock_controller;
+    use crate::ctrl_aura::controller::{CtrlKbdLed, LEDNode};
+
+    fn red(r: u8) -> AuraEffect {
+        AuraEffect {
+            colour1: Colour { r, g: 0, b: 0 },
+            ..Default::default()
+        }
+    }
+
+    fn preview(id: u64, r: u8, owner: &str) -> AuraCommand {
+        AuraCommand::TemporaryEffect(TemporaryEffect {
+            id,
+            effect: red(r),
+            idle: true,
+            preview_owner: Some(owner.to_owned()),
+        })
+    }
+
+    /// The red of the mode written since the last call, if one was
+    fn shown(mock: &MockHid) -> Option<u8> {
+        mock.take_writes().first().map(|packet| packet[4])
+    }
+
+    /// Stores red `1`, with the keyboard off and woken to high for effects
+    fn controller(mock: &MockHid) -> CtrlKbdLed {
+        let mut ctrl = mock_controller(mock);
+        ctrl.config.current_mode = AuraModeNum::Static;
+        ctrl.config.builtins.insert(AuraModeNum::Static, red(1));
+        ctrl.config.wake_brightness = Some(LedBrightness::High);
+        mock.set_brightness(0).unwrap();
+        ctrl
+    }
+
+    #[test]
+    fn preview_shown_then_reverted() {
+        let mock = MockHid::new("19b6");
+        let mut ctrl = controller(&mock);
+        apply(&mut ctrl, preview(1, 2, ":1.1")).unwrap();
+        assert_eq!(shown(&mock), Some(2));
+        // Raised for the preview as the sessions are idle
+        assert_eq!(mock.get_brightness().unwrap(), 3);
+        assert_eq!(ctrl.config.builtins[&AuraModeNum::Static].colour1.r, 1);
+
+        // The timeout, or the owner leaving the bus
+        apply(&mut ctrl, AuraCommand::EndTemporaryEffect(1)).unwrap();
+        assert_eq!(shown(&mock), Some(1));
+        assert_eq!(mock.get_brightness().unwrap(), 0);
+        assert!(ctrl.temporary_effect.is_none());
+    }
+
+    #[test]
+    fn preview_replaced() {
+        let mock = MockHid::new("19b6");
+        let mut ctrl = controller(&mock);
+        apply(&mut ctrl, preview(1, 2, ":1.1")).unwrap();
+        apply(&mut ctrl, preview(2, 3, ":1.1")).unwrap();
+        mock.take_writes();
+
+        // The timeout of the first preview leaves the second shown
+        apply(&mut ctrl, AuraCommand::EndTemporaryEffect(1)).unwrap();
+        assert_eq!(shown(&mock), None);
+        assert_eq!(mock.get_brightness().unwrap(), 3);
+
+        apply(&mut ctrl, AuraCommand::EndTemporaryEffect(2)).unwrap();
+        assert_eq!(shown(&mock), Some(1));
+        assert_eq!(mock.get_brightness().unwrap(), 0);
+    }
+
+    #[test]
+    fn preview_cancelled_by_owner() {
+        let mock = MockHid::new("19b6");
+        let mut ctrl = controller(&mock);
+        apply(&mut ctrl, preview(1, 2, ":1.1")).unwrap();
+        mock.take_writes();
+
+        let res = apply(&mut ctrl, AuraCommand::CancelPreview(":1.2".to_owned()));
+        assert!(matches!(res, Err(ZbErr::AccessDenied(_))));
+        assert_eq!(shown(&mock), None);
+
+        apply(&mut ctrl, AuraCommand::CancelPreview(":1.1".to_owned())).unwrap();
+        assert_eq!(shown(&mock), Some(1));
+        assert_eq!(mock.get_brightness().unwrap(), 0);
+        // The timeout of the cancelled preview does nothing
+        apply(&mut ctrl, AuraCommand::EndTemporaryEffect(1)).unwrap();
+        assert_eq!(shown(&mock), None);
+
+        // A temporary effect which isn't a preview is left to its timeout
+        apply(
+            &mut ctrl,
+            AuraCommand::TemporaryEffect(TemporaryEffect {
+                id: 2,
+                effect: red(3),
+                idle: false,
+                preview_owner: None,
+            }),
+        )
+        .unwrap();
+        mock.take_writes();
+        apply(&mut ctrl, AuraCommand::CancelPreview(":1.1".to_owned())).unwrap();
+        assert_eq!(shown(&mock), None);
+        assert_eq!(ctrl.temporary_effect, Some(2));
+    }
+
+    #[test]
+    fn raw_mode_packets_checked() {
+        let mock = MockHid::new("19b6");
+        let mut ctrl = controller(&mock);
+        let mut per_key = vec![0u8; 64];
+        per_key[..2].copy_from_slice(&[0x5d, 0xbc]);
+        let mut short = vec![0u8; 16];
+        short[..2].copy_from_slice(&[0x5d, 0xb3]);
+        for packets in [vec![], vec![per_key], vec![short]] {
+            let res = apply(&mut ctrl, AuraCommand::SaveRawMode("a".to_owned(), packets));
+            assert!(matches!(res, Err(ZbErr::InvalidArgs(_))));
+        }
+        assert!(ctrl.config.raw_modes.is_empty());
+    }
+
+    #[test]
+    fn frame_taken_without_advanced_type() {
+        let mock = MockHid::new("19b6");
+        let mut ctrl = controller(&mock);
+        ctrl.supported_data.advanced_type = AdvancedAuraType::None;
+        let frame = LedUsbPackets::new_per_key();
+        apply(&mut ctrl, AuraCommand::WriteClientFrame(frame)).unwrap();
+        assert!(!mock.take_writes().is_empty());
+    }
+
+    /// Writes effects without storing them, and a `SetBrightness` as a one
+    /// byte packet of the level so that the order can be seen
+    fn write_only(ctrl: &mut CtrlKbdLed, command: AuraCommand) -> Result<(), ZbErr> {
+        match command {
+            AuraCommand::SetLedModeData(effect) => ctrl.write_mode(&effect)?,
+            AuraCommand::SetBrightness(level) => {
+                if let LEDNode::Mock(mock) = &ctrl.led_node {
+                    mock.write_bytes(&[level as u8]).ok();
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// The writes as `b<level>`, `m<red>`, `set` and `apply`
+    fn describe(mock: &MockHid) -> Vec<String> {
+        mock.take_writes()
+            .iter()
+            .map(|packet| match packet.as_slice() {
+                [level] => format!("b{level}"),
+                p if *p == LED_SET => "set".to_owned(),
+                p if *p == LED_APPLY => "apply".to_owned(),
+                p => format!("m{}", p[4]),
+            })
+            .collect()
+    }
+
+    fn queue(mock: &MockHid, write_interval_ms: u32) -> AuraQueue {
+        let mut ctrl = mock_controller(mock);
+        ctrl.config.write_interval_ms = Some(write_interval_ms);
+        AuraQueue::start_with(Arc::new(Mutex::new(ctrl)), write_only)
+    }
+
+    /// Queue all of `commands` before any is applied, then wait for them
+    async fn run_all(queue: &AuraQueue, commands: Vec<AuraCommand>) {
+        let runs: Vec<_> = commands
+            .into_iter()
+            .map(|command| {
+                let queue = queue.clone();
+                tokio::spawn(async move { queue.run(command).await })
+            })
+            .collect();
+        for run in runs {
+            run.await.unwrap().unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn burst_collapses_to_last() {
+        let mock = MockHid::new("19b6");
+        let queue = queue(&mock, 50);
+        let burst = (1..=5)
+            .map(|r| AuraCommand::SetLedModeData(red(r)))
+            .collect();
+        run_all(&queue, burst).await;
+        assert_eq!(describe(&mock), ["m5", "set", "apply"]);
+    }
+
+    #[tokio::test]
+    async fn others_keep_their_order() {
+        let mock = MockHid::new("19b6");
+        let queue = queue(&mock, 50);
+        run_all(
+            &queue,
+            vec![
+                AuraCommand::SetBrightness(LedBrightness::Low),
+                AuraCommand::SetLedModeData(red(1)),
+                AuraCommand::SetLedModeData(red(2)),
+                AuraCommand::SetBrightness(LedBrightness::High),
+                AuraCommand::SetLedModeData(red(3)),
+            ],
+        )
+        .await;
+        // The last effect follows another within the interval, it is kept
+        // once no more come
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(
+            describe(&mock),
+            ["b1", "m2", "set", "apply", "b3", "m3", "set", "apply"]
+        );
+    }
+
+    #[tokio::test]
+    async fn stream_applied_once() {
+        let mock = MockHid::new("19b6");
+        let queue = queue(&mock, 200);
+        for r in 1..=5 {
+            queue
+                .run(AuraCommand::SetLedModeData(red(r)))
+                .await
+                .unwrap();
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        let writes = describe(&mock);
+        // The first is applied at once, the rest when the stream settles
+        assert_eq!(writes.iter().filter(|w| *w == "apply").count(), 2);
+        assert_eq!(writes.last().unwrap(), "apply");
+        assert_eq!(writes.iter().filter(|w| w.starts_with('m')).count(), 5);
+    }
+}
@@ -1,17 +1,52 @@
 use std::collections::BTreeMap;
+use std::time::Duration;
 
-use config_traits::{StdConfig, StdConfigLoad};
+use config_traits::{StdConfig, StdConfigLoad1};
 use log::{debug, info, warn};
+use rog_aura::audio_indicator::AudioIndicator;
 use rog_aura::aura_detection::LaptopLedData;
-use rog_aura::keyboard::LaptopAuraPower;
+use rog_aura::colour_correction::ColourCorrection;
+use rog_aura::effects::TrailConfig;
+use rog_aura::keyboard::{AuraPowerDev, LaptopAuraPower, UsbPackets};
 use rog_aura::{
     AuraDeviceType, AuraEffect, AuraModeNum, AuraZone, Direction, LedBrightness, Speed, GRADIENT,
 };
 use serde_derive::{Deserialize, Serialize};
 
-#[derive(Deserialize, Serialize, Default, Debug, Clone)]
+use super::idle::IdleOff;
+use crate::keys::KeyRepeat;
+use crate::led_automation::LedRule;
+
+const AURA_CONFIG_VERSION: u32 = 1;
+/// The coalescing interval used when the config doesn't set one
+const WRITE_INTERVAL_MS: u32 = 50;
+
+/// A snapshot of the lighting, saved by name to be applied again later
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct AuraPreset {
+    pub brightness: LedBrightness,
+    pub mode: AuraModeNum,
+    /// The effect of `mode` for the whole keyboard
+    pub effect: Option<AuraEffect>,
+    /// The effect of each zone, empty if the preset isn't multizone
+    pub zones: Vec<AuraEffect>,
+    pub zone_brightness: BTreeMap<AuraZone, LedBrightness>,
+    pub power: LaptopAuraPower,
+}
+
+impl AuraPreset {
+    /// Every effect the preset shows, to check they are all supported
+    pub fn effects(&self) -> impl Iterator<Item = &AuraEffect> {
+        self.effect.iter().chain(self.zones.iter())
+    }
+}
+
+#[derive(Deserialize, Serialize, Default, Debug, Clone, PartialEq)]
 // #[serde(default)]
 pub struct AuraConfig {
+    /// Version of the file format, upgraded on load
+    #[serde(default)]
+    pub version: u32,
     pub config_name: String,
     pub brightness: LedBrightness,
     pub current_mode: AuraModeNum,
@@ -19,9 +54,60 @@ pub struct AuraConfig {
     pub multizone: Option<BTreeMap<AuraModeNum, Vec<AuraEffect>>>,
     pub multizone_on: bool,
     pub enabled: LaptopAuraPower,
+    /// Brightness of individually addressable zones, applied by scaling the
+    /// zone colours. Zones not in here are at full brightness.
+    #[serde(default)]
+    pub zone_brightness: BTreeMap<AuraZone, LedBrightness>,
+    /// Handling of the keyboard brightness hotkeys
+    #[serde(default)]
+    pub brightness_keys: KeyRepeat,
+    /// A zone lit to show the active audio output
+    #[serde(default)]
+    pub audio_indicator: AudioIndicator,
+    /// Set while the LEDs are off for the `AllOff` shutdown policy, so that
+    /// the stored brightness is written again when asusd starts
+    #[serde(default)]
+    pub off_for_shutdown: bool,
+    /// Replaces the colour correction from the support data of the model
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub colour_correction: Option<ColourCorrection>,
+    /// Brightness and power zone rules by time and ambient light, checked in
+    /// order
+    #[serde(default)]
+    pub led_rules: Vec<LedRule>,
+    /// The brightness temporary effects are shown at while the sessions are
+    /// idle or a rule has the keyboard dimmed. `None` leaves it as is.
+    #[serde(default)]
+    pub wake_brightness: Option<LedBrightness>,
+    /// Keep the lighting on while a convertible is in tablet mode
+    #[serde(default)]
+    pub on_in_tablet_mode: bool,
+    /// The gradient and decay of the typing trail effect
+    #[serde(default)]
+    pub typing_trail: TrailConfig,
+    /// Saved lighting, by name
+    #[serde(default)]
+    pub presets: BTreeMap<String, AuraPreset>,
+    /// Raw factory mode packets, by name, as written by
+    /// `write_effect_block`
+    #[serde(default)]
+    pub raw_modes: BTreeMap<String, UsbPackets>,
+    /// The raw mode shown in place of the current mode, until a mode is set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raw_mode: Option<String>,
+    /// How long effects set in quick succession, such as from a colour
+    /// slider, are held so that only the latest is written. `0` writes each
+    /// one, `None` is the default of 50ms.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub write_interval_ms: Option<u32>,
+    /// Turning the lighting off while there is no keyboard or touchpad input
+    #[serde(default)]
+    pub idle_off: IdleOff,
 }
 
 impl StdConfig for AuraConfig {
+    const VERSION: Option<u32> = Some(AURA_CONFIG_VERSION);
+
     /// Detect the keyboard type and load from default DB if data available
     fn new() -> Self {
         panic!("This should not be used");
@@ -37,9 +123,42 @@ impl StdConfig for AuraConfig {
     fn config_dir() -> std::path::PathBuf {
         std::path::PathBuf::from(crate::CONFIG_PATH_BASE)
     }
+
+    fn set_version(&mut self, version: u32) {
+        self.version = version;
+    }
+}
+
+impl StdConfigLoad1<AuraConfig508> for AuraConfig {}
+
+/// The config from before the power states were stored per zone, when
+/// `enabled` was a flat list of the states turned on
+#[derive(Deserialize, Serialize)]
+pub struct AuraConfig508 {
+    pub config_name: String,
+    pub brightness: LedBrightness,
+    pub current_mode: AuraModeNum,
+    pub builtins: BTreeMap<AuraModeNum, AuraEffect>,
+    pub multizone: Option<BTreeMap<AuraModeNum, Vec<AuraEffect>>>,
+    pub multizone_on: bool,
+    pub enabled: AuraPowerDev,
 }
 
-impl StdConfigLoad for AuraConfig {}
+impl From<AuraConfig508> for AuraConfig {
+    fn from(c: AuraConfig508) -> Self {
+        Self {
+            version: AURA_CONFIG_VERSION,
+            config_name: c.config_name,
+            brightness: c.brightness,
+            current_mode: c.current_mode,
+            builtins: c.builtins,
+            multizone: c.multizone,
+            multizone_on: c.multizone_on,
+            enabled: c.enabled.into(),
+            ..Default::default()
+        }
+    }
+}
 
 impl AuraConfig {
     /// Detect the keyboard type and load from default DB if data available
@@ -53,6 +172,7 @@ impl AuraConfig {
         let support_data = LaptopLedData::get_data();
         let enabled = LaptopAuraPower::new(device_type, &support_data);
         let mut config = AuraConfig {
+            version: AURA_CONFIG_VERSION,
             config_name: format!("aura_{prod_id}.ron"),
             brightness: LedBrightness::Med,
             current_mode: AuraModeNum::Static,
@@ -60,6 +180,20 @@ impl AuraConfig {
             multizone: None,
             multizone_on: false,
             enabled,
+            zone_brightness: BTreeMap::new(),
+            brightness_keys: KeyRepeat::default(),
+            audio_indicator: AudioIndicator::default(),
+            off_for_shutdown: false,
+            colour_correction: None,
+            led_rules: Vec::new(),
+            wake_brightness: None,
+            on_in_tablet_mode: false,
+            typing_trail: TrailConfig::default(),
+            presets: BTreeMap::new(),
+            raw_modes: BTreeMap::new(),
+            raw_mode: None,
+            write_interval_ms: None,
+            idle_off: IdleOff::default(),
         };
 
         for n in &support_data.basic_modes {
@@ -69,17 +203,7 @@ impl AuraConfig {
                 .insert(*n, AuraEffect::default_with_mode(*n));
 
             if !support_data.basic_zones.is_empty() {
-                let mut default = vec![];
-                for (i, tmp) in support_data.basic_zones.iter().enumerate() {
-                    default.push(AuraEffect {
-                        mode: *n,
-                        zone: *tmp,
-                        colour1: *GRADIENT.get(i).unwrap_or(&GRADIENT[0]),
-                        colour2: *GRADIENT.get(GRADIENT.len() - i).unwrap_or(&GRADIENT[6]),
-                        speed: Speed::Med,
-                        direction: Direction::Left,
-                    });
-                }
+                let default = default_multizone(*n, &support_data.basic_zones);
                 if let Some(m) = config.multizone.as_mut() {
                     m.insert(*n, default);
                 } else {
@@ -123,19 +247,190 @@ impl AuraConfig {
         }
     }
 
+    /// Replace the effect of one zone in the multizone set of its mode and make
+    /// that the current mode. A mode without a set yet gets the defaults for
+    /// `zones` first, so the other zones aren't left unset.
+    pub fn set_zone(&mut self, effect: AuraEffect, zones: &[AuraZone]) {
+        self.current_mode = effect.mode;
+        self.multizone_on = true;
+        let effects = self
+            .multizone
+            .get_or_insert_with(BTreeMap::new)
+            .entry(effect.mode)
+            .or_insert_with(|| default_multizone(effect.mode, zones));
+        match effects.iter_mut().find(|fx| fx.zone == effect.zone) {
+            Some(fx) => *fx = effect,
+            None => effects.push(effect),
+        }
+    }
+
+    /// The stored lighting as a preset
+    pub fn snapshot(&self) -> AuraPreset {
+        let zones = match self.get_multizone(self.current_mode) {
+            Some(zones) if self.multizone_on => zones.to_vec(),
+            _ => Vec::new(),
+        };
+        AuraPreset {
+            brightness: self.brightness,
+            mode: self.current_mode,
+            effect: self.builtins.get(&self.current_mode).cloned(),
+            zones,
+            zone_brightness: self.zone_brightness.clone(),
+            power: self.enabled.clone(),
+        }
+    }
+
+    /// Store the lighting of `preset`. Power states are only taken for the
+    /// zones this device still has.
+    pub fn apply_preset(&mut self, preset: &AuraPreset) {
+        self.brightness = preset.brightness;
+        self.current_mode = preset.mode;
+        if let Some(effect) = &preset.effect {
+            self.builtins.insert(preset.mode, effect.clone());
+        }
+        self.multizone_on = !preset.zones.is_empty();
+        if self.multizone_on {
+            self.multizone
+                .get_or_insert_with(BTreeMap::new)
+                .insert(preset.mode, preset.zones.clone());
+        }
+        self.zone_brightness = preset.zone_brightness.clone();
+        for state in self.enabled.states.iter_mut() {
+            if let Some(saved) = preset.power.states.iter().find(|s| s.zone == state.zone) {
+                *state = *saved;
+            }
+        }
+    }
+
+    /// The interval effects are coalesced over, see `write_interval_ms`
+    pub fn write_interval(&self) -> Duration {
+        Duration::from_millis(self.write_interval_ms.unwrap_or(WRITE_INTERVAL_MS) as u64)
+    }
+
     pub fn get_multizone(&self, aura_type: AuraModeNum) -> Option<&[AuraEffect]> {
         if let Some(multi) = &self.multizone {
             return multi.get(&aura_type).map(|v| v.as_slice());
         }
         None
     }
+
+    /// Bring the config in line with new support data. Modes and zones that
+    /// are no longer supported are dropped, new ones get defaults, and power
+    /// states are kept for the zones that remain. Returns `true` if anything
+    /// changed.
+    pub fn revalidate(&mut self, device_type: AuraDeviceType, support: &LaptopLedData) -> bool {
+        let before = self.clone();
+
+        self.builtins
+            .retain(|mode, _| support.basic_modes.contains(mode));
+        for mode in &support.basic_modes {
+            self.builtins
+                .entry(*mode)
+                .or_insert_with(|| AuraEffect::default_with_mode(*mode));
+        }
+
+        if support.basic_zones.is_empty() {
+            self.multizone = None;
+            self.multizone_on = false;
+        } else {
+            let multi = self.multizone.get_or_insert_with(BTreeMap::new);
+            multi.retain(|mode, _| support.basic_modes.contains(mode));
+            for effects in multi.values_mut() {
+                effects.retain(|fx| support.basic_zones.contains(&fx.zone));
+            }
+            for mode in &support.basic_modes {
+                let effects = multi.entry(*mode).or_default();
+                if effects.is_empty() {
+                    *effects = default_multizone(*mode, &support.basic_zones);
+                }
+            }
+        }
+        self.zone_brightness
+            .retain(|zone, _| support.basic_zones.contains(zone));
+
+        if !self.builtins.contains_key(&self.current_mode) {
+            self.current_mode = self
+                .builtins
+                .keys()
+                .next()
+                .copied()
+                .unwrap_or(AuraModeNum::Static);
+            warn!(
+                "The current Aura mode is no longer supported, using {}",
+                self.current_mode
+            );
+        }
+
+        let mut enabled = LaptopAuraPower::new(device_type, support);
+        for state in enabled.states.iter_mut() {
+            if let Some(old) = self.enabled.states.iter().find(|s| s.zone == state.zone) {
+                *state = *old;
+            }
+        }
+        self.enabled = enabled;
+
+        *self != before
+    }
+}
+
+/// One effect per zone for `mode`, each zone getting the next colour of the
+/// gradient
+fn default_multizone(mode: AuraModeNum, zones: &[AuraZone]) -> Vec<AuraEffect> {
+    zones
+        .iter()
+        .enumerate()
+        .map(|(i, zone)| AuraEffect {
+            mode,
+            zone: *zone,
+            colour1: *GRADIENT.get(i).unwrap_or(&GRADIENT[0]),
+            colour2: *GRADIENT.get(GRADIENT.len() - i).unwrap_or(&GRADIENT[6]),
+            speed: Speed::Med,
+            direction: Direction::Left,
+        })
+        .collect()
 }
 
 #[cfg(test)]
 mod tests {
-    use rog_aura::{AuraEffect, AuraModeNum, AuraZone, Colour};
+    use config_traits::ron;
+    use rog_aura::aura_detection::{LaptopLedData, PowerZones};
+    use rog_aura::{AuraDeviceType, AuraEffect, AuraModeNum, AuraZone, Colour, LedBrightness};
 
-    use super::AuraConfig;
+    use super::{AuraConfig, AuraConfig508};
+
+    #[test]
+    fn revalidate_against_new_support() {
+        let mut support = LaptopLedData {
+            basic_modes: vec![AuraModeNum::Static, AuraModeNum::Breathe],
+            basic_zones: vec![AuraZone::Key1, AuraZone::Key2],
+            power_zones: vec![PowerZones::Keyboard, PowerZones::Lightbar],
+            ..Default::default()
+        };
+        let device = AuraDeviceType::LaptopPost2021;
+        let mut config = AuraConfig::default();
+        assert!(config.revalidate(device, &support));
+        assert_eq!(config.builtins.len(), 2);
+        assert_eq!(config.get_multizone(AuraModeNum::Breathe).unwrap().len(), 2);
+        assert!(!config.revalidate(device, &support));
+
+        config.current_mode = AuraModeNum::Breathe;
+        let sleep = !config.enabled.states[1].sleep;
+        config.enabled.states[1].sleep = sleep;
+        support.basic_modes = vec![AuraModeNum::Static, AuraModeNum::Pulse];
+        support.basic_zones = vec![AuraZone::Key2];
+        support.power_zones = vec![PowerZones::Lightbar];
+        assert!(config.revalidate(device, &support));
+
+        assert!(!config.builtins.contains_key(&AuraModeNum::Breathe));
+        assert!(config.builtins.contains_key(&AuraModeNum::Pulse));
+        assert_eq!(config.current_mode, AuraModeNum::Static);
+        let zones = config.get_multizone(AuraModeNum::Static).unwrap();
+        assert_eq!(zones.len(), 1);
+        assert_eq!(zones[0].zone, AuraZone::Key2);
+        assert_eq!(config.enabled.states.len(), 1);
+        assert_eq!(config.enabled.states[0].zone, PowerZones::Lightbar);
+        assert_eq!(config.enabled.states[0].sleep, sleep);
+    }
 
     #[test]
     fn set_multizone_4key_config() {
@@ -273,4 +568,108 @@ mod tests {
         let sta = res.get(&AuraModeNum::Pulse).unwrap();
         assert_eq!(sta.len(), 1);
     }
+
+    #[test]
+    fn set_one_zone() {
+        let mut config = AuraConfig::new("19b6");
+        config.multizone = None;
+        let zones = [AuraZone::Key1, AuraZone::Key2, AuraZone::Key3];
+        let white = Colour {
+            r: 0xff,
+            g: 0xff,
+            b: 0xff,
+        };
+
+        config.set_zone(
+            AuraEffect {
+                mode: AuraModeNum::Breathe,
+                zone: AuraZone::Key2,
+                colour1: white,
+                ..Default::default()
+            },
+            &zones,
+        );
+        assert_eq!(config.current_mode, AuraModeNum::Breathe);
+        assert!(config.multizone_on);
+        // The other zones are filled in with the defaults
+        let set = config.get_multizone(AuraModeNum::Breathe).unwrap().to_vec();
+        assert_eq!(set.len(), 3);
+        assert_eq!(set[1].colour1, white);
+        assert_ne!(set[0].colour1, white);
+
+        config.set_zone(
+            AuraEffect {
+                mode: AuraModeNum::Breathe,
+                zone: AuraZone::Key3,
+                colour1: white,
+                ..Default::default()
+            },
+            &zones,
+        );
+        let updated = config.get_multizone(AuraModeNum::Breathe).unwrap();
+        assert_eq!(updated.len(), 3);
+        assert_eq!(updated[0], set[0]);
+        assert_eq!(updated[2].colour1, white);
+    }
+
+    #[test]
+    fn preset_round_trip() {
+        let mut config = AuraConfig::new("19b6");
+        let zones = [AuraZone::Key1, AuraZone::Key2];
+        config.brightness = LedBrightness::High;
+        config.set_zone(
+            AuraEffect {
+                mode: AuraModeNum::Pulse,
+                zone: AuraZone::Key1,
+                ..Default::default()
+            },
+            &zones,
+        );
+        let preset = config.snapshot();
+        assert_eq!(preset.mode, AuraModeNum::Pulse);
+        assert_eq!(preset.zones.len(), 2);
+
+        config.brightness = LedBrightness::Low;
+        config.set_builtin(AuraEffect {
+            mode: AuraModeNum::Static,
+            ..Default::default()
+        });
+        assert!(!config.multizone_on);
+
+        config.apply_preset(&preset);
+        assert_eq!(config.brightness, LedBrightness::High);
+        assert_eq!(config.current_mode, AuraModeNum::Pulse);
+        assert!(config.multizone_on);
+        assert_eq!(config.snapshot(), preset);
+    }
+
+    #[test]
+    fn flat_power_list_migrated() {
+        let old = r#"(
+            config_name: "aura_19b6.ron",
+            brightness: Med,
+            current_mode: Static,
+            builtins: {},
+            multizone: None,
+            multizone_on: false,
+            enabled: (tuf: [], x1866: [], x19b6: [BootKeyb, AwakeKeyb, SleepBar]),
+        )"#;
+        assert!(ron::from_str::<AuraConfig>(old).is_err());
+        let config: AuraConfig = ron::from_str::<AuraConfig508>(old).unwrap().into();
+        assert_eq!(config.brightness, LedBrightness::Med);
+        let keyboard = config
+            .enabled
+            .states
+            .iter()
+            .find(|s| s.zone == PowerZones::Keyboard)
+            .unwrap();
+        assert!(keyboard.boot && keyboard.awake && !keyboard.sleep);
+        let lightbar = config
+            .enabled
+            .states
+            .iter()
+            .find(|s| s.zone == PowerZones::Lightbar)
+            .unwrap();
+        assert!(lightbar.sleep && !lightbar.awake);
+    }
 }
@@ -1,28 +1,60 @@
 use std::collections::{BTreeMap, HashSet};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use config_traits::{StdConfig, StdConfigLoad};
+use config_traits::{StdConfig, StdConfigLoad1};
 use inotify::Inotify;
 use log::{debug, info, warn};
-use rog_aura::aura_detection::LaptopLedData;
-use rog_aura::keyboard::{LedUsbPackets, UsbPackets};
-use rog_aura::usb::{LED_APPLY, LED_SET};
+use rog_aura::audio_indicator::AudioOutput;
+use rog_aura::aura_detection::{LaptopLedData, PowerZones};
+use rog_aura::colour_correction::ColourCorrection;
+use rog_aura::effects::TrailConfig;
+use rog_aura::keyboard::{LaptopAuraPower, LedUsbPackets, UsbPackets};
+use rog_aura::power_estimate::{self, PowerEstimate};
+use rog_aura::user_override::AuraOverride;
 use rog_aura::{
-    AuraDeviceType, AuraEffect, Direction, LedBrightness, Speed, GRADIENT, LED_MSG_LEN,
+    AuraDeviceType, AuraEffect, AuraModeNum, AuraZone, Colour, Direction, LedBrightness, Speed,
+    GRADIENT,
 };
+use rog_platform::aura_hid::MockHid;
 use rog_platform::hid_raw::HidRaw;
 use rog_platform::keyboard_led::KeyboardLed;
-use zbus::zvariant::OwnedObjectPath;
+use rog_platform::multicolor_led::MulticolorLed;
+use tokio::sync::{watch, Notify};
+use zbus::zvariant::{ObjectPath, OwnedObjectPath};
 
+use super::backend::{is_per_key_packet, AuraBackend, UsbHid};
 use super::config::AuraConfig;
+use super::multicolor::{Multicolor, MULTICOLOR_PROD_ID};
+use super::stream::FrameStream;
+use super::tuf::{Tuf, TUF_PROD_ID};
 use crate::ctrl_aura::manager::dbus_path_for_dev;
+use crate::ctrl_aura::trait_impls::AURA_ZBUS_PATH;
 use crate::error::RogError;
+use crate::first_run::note_fresh_config;
+use crate::keys::BrightnessKey;
+use crate::led_automation::LedRule;
+use crate::shutdown::ShutdownPolicy;
 
+/// Writes to `kbd_rgb_mode` within this long of our own are assumed to be the
+/// inotify echo of that write
+const OWN_WRITE_WINDOW: Duration = Duration::from_millis(500);
+/// Other writes to `kbd_rgb_mode` within this long of our own, such as the
+/// restore on resume, are taken to be the firmware resetting the mode
+const REVERT_WINDOW: Duration = Duration::from_secs(5);
+/// Times the power states are written before a mismatch is returned
+const POWER_WRITE_ATTEMPTS: usize = 3;
+
+/// Where the brightness is kept
 #[derive(Debug)]
 pub enum LEDNode {
-    /// Brightness and/or TUF RGB controls
+    /// `asus::kbd_backlight`, which on TUF also has the RGB controls
     KbdLed(KeyboardLed),
-    /// Raw HID handle
-    Rog(KeyboardLed, HidRaw),
+    /// A `kbd_backlight` in the multicolor LED class, for keyboards with no
+    /// USB or TUF controls
+    KbdMulticolor(MulticolorLed),
+    /// For tests and `--aura-dry-run`
+    Mock(MockHid),
 }
 
 impl LEDNode {
@@ -30,7 +62,8 @@ impl LEDNode {
     pub fn set_brightness(&self, value: u8) -> Result<(), RogError> {
         match self {
             LEDNode::KbdLed(k) => k.set_brightness(value)?,
-            LEDNode::Rog(k, _) => k.set_brightness(value)?,
+            LEDNode::KbdMulticolor(k) => k.set_brightness(value)?,
+            LEDNode::Mock(m) => m.set_brightness(value)?,
         }
         Ok(())
     }
@@ -38,14 +71,20 @@ impl LEDNode {
     pub fn get_brightness(&self) -> Result<u8, RogError> {
         Ok(match self {
             LEDNode::KbdLed(k) => k.get_brightness()?,
-            LEDNode::Rog(k, _) => k.get_brightness()?,
+            LEDNode::KbdMulticolor(k) => k.get_brightness()?,
+            LEDNode::Mock(m) => m.get_brightness()?,
         })
     }
 
     pub fn monitor_brightness(&self) -> Result<Inotify, RogError> {
         Ok(match self {
             LEDNode::KbdLed(k) => k.monitor_brightness()?,
-            LEDNode::Rog(k, _) => k.monitor_brightness()?,
+            LEDNode::KbdMulticolor(k) => k.monitor_brightness()?,
+            LEDNode::Mock(_) => {
+                return Err(RogError::MissingFunction(
+                    "The mock has no brightness to watch".to_owned(),
+                ))
+            }
         })
     }
 }
@@ -54,10 +93,49 @@ impl LEDNode {
 pub struct CtrlKbdLed {
     pub led_type: AuraDeviceType,
     pub led_node: LEDNode,
+    pub backend: Box<dyn AuraBackend>,
     pub supported_data: LaptopLedData, // TODO: is storing this really required?
     pub per_key_mode_active: bool,
+    /// The last frame written by `write_frame`, the next only writes the rows
+    /// that differ from it
+    pub last_frame: Option<LedUsbPackets>,
     pub config: AuraConfig,
     pub dbus_path: OwnedObjectPath,
+    /// A client owned stream of software effect frames, if any
+    pub frame_stream: Option<FrameStream>,
+    /// Set while a temporary effect is shown, the stored mode is restored when
+    /// the matching expiry task runs
+    pub temporary_effect: Option<u64>,
+    /// The client whose preview is the temporary effect, only it can end it
+    /// with `CancelPreview`
+    pub preview_owner: Option<String>,
+    /// When `kbd_rgb_mode` was last written by asusd (TUF only)
+    pub kbd_rgb_mode_written: Option<Instant>,
+    /// The audio output last reported by the session helper
+    pub audio_output: AudioOutput,
+    /// The automation rule shown in place of the stored brightness and power
+    /// states
+    pub led_rule: Option<LedRule>,
+    /// The brightness to go back to once the temporary effect that raised it
+    /// ends
+    pub woken_from: Option<LedBrightness>,
+    /// Set while a convertible is folded in to tablet mode
+    pub tablet_mode: bool,
+    /// Set by the command queue while a stream of effects is written, so the
+    /// modes are only shown and kept once by `flush_apply`
+    pub defer_apply: bool,
+    /// A mode was shown with `defer_apply` and isn't kept yet
+    pub apply_pending: bool,
+    /// The level shown while the lighting fades out for a lack of input,
+    /// `None` while there is input
+    pub idle_level: Option<LedBrightness>,
+    /// The uid and lighting of the user with the active session, shown in
+    /// place of the stored mode and brightness
+    pub user_override: Option<(u32, AuraOverride)>,
+    /// Where changes of the typing trail config are sent while it runs
+    pub typing_trail: Option<watch::Sender<TrailConfig>>,
+    /// Wakes the idle off task when its config changes
+    pub idle_changed: Arc<Notify>,
 }
 
 impl CtrlKbdLed {
@@ -119,6 +197,12 @@ impl CtrlKbdLed {
                 devices.push(dev);
             }
         }
+        if devices.is_empty() {
+            devices.extend(Self::find_tuf(data));
+        }
+        if devices.is_empty() {
+            devices.extend(Self::find_multicolor(data));
+        }
         info!("Found {} Aura devices", devices.len());
 
         Ok(devices)
@@ -133,6 +217,7 @@ impl CtrlKbdLed {
         data: &LaptopLedData,
     ) -> Result<Self, RogError> {
         let rgb_led = KeyboardLed::new()?;
+        let id_product = device.prod_id().to_owned();
         let prod_id = AuraDeviceType::from(device.prod_id());
         if prod_id == AuraDeviceType::Unknown {
             log::error!("{} is AuraDevice::Unknown", device.prod_id());
@@ -144,20 +229,152 @@ impl CtrlKbdLed {
 
         let ctrl = CtrlKbdLed {
             led_type: prod_id,
-            led_node: LEDNode::Rog(rgb_led, device),
+            led_node: LEDNode::KbdLed(rgb_led),
+            backend: Box::new(UsbHid::new(prod_id, &id_product, Box::new(device))),
             supported_data: data.clone(),
             per_key_mode_active: false,
+            last_frame: None,
             config: AuraConfig::default(),
             dbus_path,
+            frame_stream: None,
+            temporary_effect: None,
+            preview_owner: None,
+            kbd_rgb_mode_written: None,
+            audio_output: AudioOutput::Unknown,
+            led_rule: None,
+            woken_from: None,
+            tablet_mode: false,
+            defer_apply: false,
+            apply_pending: false,
+            idle_level: None,
+            user_override: None,
+            typing_trail: None,
+            idle_changed: Arc::default(),
         };
         Ok(ctrl)
     }
 
+    /// TUF laptops have no USB device for the keyboard, only the RGB controls
+    /// of `asus::kbd_backlight`
+    fn find_tuf(data: &LaptopLedData) -> Option<Self> {
+        let led = KeyboardLed::new()
+            .ok()
+            .filter(|led| led.has_kbd_rgb_mode())?;
+        info!("AuraControl found a TUF keyboard");
+        let dbus_path = format!("{AURA_ZBUS_PATH}/{TUF_PROD_ID}");
+        let mut ctrl = CtrlKbdLed {
+            led_type: AuraDeviceType::LaptopTuf,
+            backend: Box::new(Tuf::new(&led)),
+            led_node: LEDNode::KbdLed(led),
+            supported_data: data.clone(),
+            per_key_mode_active: false,
+            last_frame: None,
+            config: Self::init_config(TUF_PROD_ID, data),
+            dbus_path: ObjectPath::from_str_unchecked(&dbus_path).into(),
+            frame_stream: None,
+            temporary_effect: None,
+            preview_owner: None,
+            kbd_rgb_mode_written: None,
+            audio_output: AudioOutput::Unknown,
+            led_rule: None,
+            woken_from: None,
+            tablet_mode: false,
+            defer_apply: false,
+            apply_pending: false,
+            idle_level: None,
+            user_override: None,
+            typing_trail: None,
+            idle_changed: Arc::default(),
+        };
+        // The support data of the model may list modes the driver doesn't take
+        ctrl.supported_data = ctrl.backend_support(data.clone());
+        let supported = ctrl.supported_data.clone();
+        if ctrl.config.revalidate(ctrl.led_type, &supported) {
+            ctrl.config.write();
+        }
+        Some(ctrl)
+    }
+
+    /// Machines with neither of the above may still have a `kbd_backlight`
+    /// in the multicolor LED class, which gives at least the colour and
+    /// brightness
+    fn find_multicolor(data: &LaptopLedData) -> Option<Self> {
+        let led = MulticolorLed::new().ok()?;
+        info!("AuraControl found a multicolor LED keyboard");
+        let dbus_path = format!("{AURA_ZBUS_PATH}/{MULTICOLOR_PROD_ID}");
+        let mut ctrl = CtrlKbdLed {
+            led_type: AuraDeviceType::LaptopMulticolor,
+            backend: Box::new(Multicolor::new(&led)),
+            led_node: LEDNode::KbdMulticolor(led),
+            supported_data: data.clone(),
+            per_key_mode_active: false,
+            last_frame: None,
+            config: Self::init_config(MULTICOLOR_PROD_ID, data),
+            dbus_path: ObjectPath::from_str_unchecked(&dbus_path).into(),
+            frame_stream: None,
+            temporary_effect: None,
+            preview_owner: None,
+            kbd_rgb_mode_written: None,
+            audio_output: AudioOutput::Unknown,
+            led_rule: None,
+            woken_from: None,
+            tablet_mode: false,
+            defer_apply: false,
+            apply_pending: false,
+            idle_level: None,
+            user_override: None,
+            typing_trail: None,
+            idle_changed: Arc::default(),
+        };
+        ctrl.supported_data = ctrl.backend_support(data.clone());
+        let supported = ctrl.supported_data.clone();
+        if ctrl.config.revalidate(ctrl.led_type, &supported) {
+            ctrl.config.write();
+        }
+        Some(ctrl)
+    }
+
+    /// `data` less the modes and zones the backend can't write, so that what
+    /// is listed as supported is what the device takes
+    pub(super) fn backend_support(&self, mut data: LaptopLedData) -> LaptopLedData {
+        let caps = self.backend.capabilities();
+        data.basic_modes.retain(|mode| caps.has_mode(*mode));
+        if !caps.zones {
+            data.basic_zones.clear();
+        }
+        data
+    }
+
+    /// The USB product ID of the device, such as `19b6`
+    pub fn prod_id(&self) -> &str {
+        self.backend.prod_id()
+    }
+
+    /// Log the packets instead of writing them, for `asusd --aura-dry-run`. The
+    /// brightness is kept by the mock.
+    pub fn use_dry_run(&mut self) {
+        let mock = MockHid::new(self.prod_id());
+        info!("Dry run, packets for {:?} are logged only", self.dbus_path);
+        self.backend = if self.led_type.is_tuf_laptop() {
+            Box::new(Tuf::with_writer(Box::new(mock.clone()), true))
+        } else if self.led_type.is_multicolor_laptop() {
+            let modes = self.backend.capabilities().modes.unwrap_or_default();
+            Box::new(Multicolor::with_writer(Box::new(mock.clone()), modes))
+        } else {
+            Box::new(UsbHid::new(
+                self.led_type,
+                self.prod_id(),
+                Box::new(mock.clone()),
+            ))
+        };
+        self.led_node = LEDNode::Mock(mock);
+    }
+
     pub fn init_config(prod_id: &str, supported_basic_modes: &LaptopLedData) -> AuraConfig {
         // New loads data from the DB also
         let mut config_init = AuraConfig::new(prod_id);
         // config_init.set_filename(prod_id);
-        let mut config_loaded = config_init.clone().load();
+        let mut config_loaded = note_fresh_config(config_init.clone()).load();
         // update the initialised data with what we loaded from disk
         for mode in &mut config_init.builtins {
             // update init values from loaded values if they exist
@@ -190,96 +407,496 @@ impl CtrlKbdLed {
         config_loaded
     }
 
-    /// Set combination state for boot animation/sleep animation/all leds/keys
-    /// leds/side leds LED active
+    /// Write the stored power states, less the zones an automation rule turns
+    /// off
     pub(super) fn set_power_states(&mut self) -> Result<(), RogError> {
-        if let LEDNode::KbdLed(platform) = &mut self.led_node {
-            // TODO: tuf bool array
-            // if let Some(pwr) =
-            // AuraPowerConfig::to_tuf_bool_array(&self.config.enabled) {
-            //     let buf = [1, pwr[1] as u8, pwr[2] as u8, pwr[3] as u8,
-            // pwr[4] as u8];     platform.set_kbd_rgb_state(&buf)?;
-            // }
-        } else if let LEDNode::Rog(_, hid_raw) = &self.led_node {
-            let bytes = self.config.enabled.to_bytes(self.led_type.into());
-            let message = [0x5d, 0xbd, 0x01, bytes[0], bytes[1], bytes[2], bytes[3]];
-
-            hid_raw.write_bytes(&message)?;
-            hid_raw.write_bytes(&LED_SET)?;
-            // Changes won't persist unless apply is set
-            hid_raw.write_bytes(&LED_APPLY)?;
+        let mut power = self.config.enabled.clone();
+        if let Some(rule) = &self.led_rule {
+            for state in power
+                .states
+                .iter_mut()
+                .filter(|s| rule.zones_off.contains(&s.zone))
+            {
+                state.awake = false;
+            }
+        }
+        self.write_power_states(&power)
+    }
+
+    /// The stored brightness, or that of the active user or the automation
+    /// rule shown. Off in tablet mode unless the config keeps it on.
+    pub(super) fn shown_brightness(&self) -> LedBrightness {
+        if self.off_for_tablet_mode() {
+            return LedBrightness::Off;
+        }
+        if let Some(level) = self.idle_level {
+            return level;
+        }
+        let brightness = self
+            .user_override
+            .as_ref()
+            .map_or(self.config.brightness, |(_, user)| user.brightness);
+        self.led_rule
+            .as_ref()
+            .map_or(brightness, |rule| rule.brightness)
+    }
+
+    fn off_for_tablet_mode(&self) -> bool {
+        self.tablet_mode && !self.config.on_in_tablet_mode
+    }
+
+    /// True while what is shown is not the stored brightness, so changes to
+    /// it are not kept
+    pub(super) fn brightness_overridden(&self) -> bool {
+        self.config.off_for_shutdown
+            || self.led_rule.is_some()
+            || self.woken_from.is_some()
+            || self.off_for_tablet_mode()
+            || self.idle_level.is_some()
+            || self.user_override.is_some()
+    }
+
+    /// Show the lighting of the user with the active session, or the stored
+    /// lighting again if `None`. A software effect running is left to end.
+    pub(super) fn set_user_override(
+        &mut self,
+        user: Option<(u32, AuraOverride)>,
+    ) -> Result<(), RogError> {
+        self.user_override = user;
+        self.woken_from = None;
+        if self.stored_mode_shown() {
+            self.write_current_config_mode()?;
+        }
+        self.led_node
+            .set_brightness(self.shown_brightness().into())?;
+        Ok(())
+    }
+
+    /// Turn the lighting off or back on as the laptop is folded in to or out
+    /// of tablet mode
+    pub(super) fn set_tablet_mode(&mut self, tablet_mode: bool) -> Result<(), RogError> {
+        if self.tablet_mode == tablet_mode {
+            return Ok(());
+        }
+        self.tablet_mode = tablet_mode;
+        self.woken_from = None;
+        self.led_node
+            .set_brightness(self.shown_brightness().into())?;
+        Ok(())
+    }
+
+    /// Raise the brightness to `wake_brightness` for a temporary effect shown
+    /// while the sessions are `idle` or a rule has the keyboard dimmed
+    pub(super) fn wake_for_effect(&mut self, idle: bool) -> Result<(), RogError> {
+        let Some(wake) = self.config.wake_brightness else {
+            return Ok(());
+        };
+        let now = LedBrightness::from(self.led_node.get_brightness()?);
+        // The keyboard is face down in tablet mode
+        let dimmed = idle || self.led_rule.is_some() || self.idle_level.is_some();
+        if !dimmed || now >= wake || self.off_for_tablet_mode() {
+            return Ok(());
+        }
+        self.led_node.set_brightness(wake.into())?;
+        // A notification arriving while woken keeps the level of the first
+        self.woken_from.get_or_insert(now);
+        Ok(())
+    }
+
+    /// Go back to the brightness from before `wake_for_effect`
+    pub(super) fn end_wake(&mut self) -> Result<(), RogError> {
+        if let Some(level) = self.woken_from.take() {
+            self.led_node.set_brightness(level.into())?;
+        }
+        Ok(())
+    }
+
+    /// Take the brightness a step down for a lack of input. The lighting is
+    /// left alone while it is off already or lit for a notification.
+    pub(super) fn idle_step(&mut self) -> Result<(), RogError> {
+        if self.woken_from.is_some() || self.config.off_for_shutdown {
+            return Ok(());
+        }
+        let level = match self.shown_brightness() {
+            LedBrightness::Off => return Ok(()),
+            level => level.prev(),
+        };
+        self.led_node.set_brightness(level.into())?;
+        self.idle_level = Some(level);
+        Ok(())
+    }
+
+    /// Go back to the brightness from before `idle_step` on input
+    pub(super) fn end_idle(&mut self) -> Result<(), RogError> {
+        if self.idle_level.take().is_some() {
+            self.woken_from = None;
+            self.led_node
+                .set_brightness(self.shown_brightness().into())?;
+        }
+        Ok(())
+    }
+
+    /// Show `rule` in place of the stored brightness and power states, or
+    /// restore them if it is `None`
+    pub(super) fn show_led_rule(&mut self, rule: Option<LedRule>) -> Result<(), RogError> {
+        let zones_changed =
+            self.led_rule.as_ref().map(|r| &r.zones_off) != rule.as_ref().map(|r| &r.zones_off);
+        self.led_rule = rule;
+        // The rule's brightness is the one to go back to after a notification
+        self.woken_from = None;
+        self.led_node
+            .set_brightness(self.shown_brightness().into())?;
+        if zones_changed {
+            self.set_power_states()?;
         }
         Ok(())
     }
 
+    /// Write the power states and check the firmware kept them, writing them
+    /// again if not as some firmware revisions drop the write
+    fn write_power_states(&mut self, power: &LaptopAuraPower) -> Result<(), RogError> {
+        if !self.backend.capabilities().power_states {
+            return Ok(());
+        }
+        let mut attempt = 1;
+        loop {
+            self.backend.write_power(power)?;
+            match self.backend.verify_power(power) {
+                Err(RogError::PowerStateMismatch(written, read))
+                    if attempt < POWER_WRITE_ATTEMPTS =>
+                {
+                    warn!(
+                        "{}: power states not kept on attempt {attempt}, wrote {written:02x?} \
+                         read {read:02x?}",
+                        self.backend.prod_id()
+                    );
+                    attempt += 1;
+                }
+                res => return res,
+            }
+        }
+    }
+
+    /// Ready the LEDs for suspend. Any software effect (per-key or zoned frames
+    /// from a client) is stopped by restoring the stored builtin mode, then
+    /// the "sleep" power states are written. Without this some models keep
+    /// the last frame frozen on the keyboard through suspend.
+    pub(super) fn prepare_for_sleep(&mut self) -> Result<(), RogError> {
+        self.stop_frame_stream();
+        if self.per_key_mode_active {
+            self.write_current_config_mode()?;
+        }
+        self.set_power_states()
+    }
+
+    /// Leave the LEDs as `policy` says before asusd stops. `AllOff` only changes the hardware, the config keeps the
+    /// brightness and power states to restore on the next start.
+    pub(super) fn prepare_for_shutdown(&mut self, policy: ShutdownPolicy) -> Result<(), RogError> {
+        if policy == ShutdownPolicy::LeaveAsIs {
+            return Ok(());
+        }
+        let restore = !self.stored_mode_shown();
+        self.stop_frame_stream();
+        self.temporary_effect = None;
+        if restore {
+            self.write_current_config_mode()?;
+        }
+        if policy == ShutdownPolicy::PowerStates {
+            return self.set_power_states();
+        }
+
+        self.config.off_for_shutdown = true;
+        self.config.write();
+        self.led_node.set_brightness(0)?;
+        // The boot state is kept so the machine still lights up when turned on
+        let mut off = self.config.enabled.clone();
+        for state in off.states.iter_mut() {
+            state.awake = false;
+            state.sleep = false;
+            state.shutdown = false;
+        }
+        self.write_power_states(&off)
+    }
+
+    /// End any client frame stream. The stream thread exits on its own once the
+    /// socket is shut down.
+    pub(super) fn stop_frame_stream(&mut self) {
+        if let Some(stream) = self.frame_stream.take() {
+            stream.stop();
+        }
+    }
+
+    /// The colour correction of the config, or else of the model
+    fn colour_correction(&self) -> Option<ColourCorrection> {
+        self.config
+            .colour_correction
+            .or(self.supported_data.colour_correction)
+            .filter(|c| !c.is_identity())
+    }
+
     /// Write an effect block. This is for per-key, but can be repurposed to
     /// write the raw factory mode packets - when doing this it is expected that
     /// only the first `Vec` (`effect[0]`) is valid.
     pub fn write_effect_block(&mut self, effect: &UsbPackets) -> Result<(), RogError> {
-        if self.config.brightness == LedBrightness::Off {
-            self.config.brightness = LedBrightness::Med;
-            self.config.write();
+        self.wake_for_frames()?;
+
+        let per_key = is_per_key_packet(&effect[0]) == Some(true);
+        if !per_key {
+            self.backend.write_raw(&effect[0])?;
+        } else if let Some(correction) = self.colour_correction() {
+            let mut packets = effect.clone();
+            correction.apply_packets(&mut packets);
+            self.backend.write_frame(
+                &mut packets.iter().map(Vec::as_slice),
+                !self.per_key_mode_active,
+            )?;
+        } else {
+            self.backend.write_frame(
+                &mut effect.iter().map(Vec::as_slice),
+                !self.per_key_mode_active,
+            )?;
         }
+        // Not known to match what is on the keyboard now
+        self.last_frame = None;
+        self.frame_written(per_key);
+        Ok(())
+    }
 
-        let pkt_type = effect[0][1];
-        const PER_KEY_TYPE: u8 = 0xbc;
+    /// Write a frame of a software effect. Only the rows that changed since
+    /// the last frame are written, unless a builtin mode or other packets
+    /// were written in between.
+    pub fn write_frame(&mut self, frame: &LedUsbPackets) -> Result<(), RogError> {
+        self.wake_for_frames()?;
 
-        if pkt_type != PER_KEY_TYPE {
-            self.per_key_mode_active = false;
-            if let LEDNode::Rog(_, hid_raw) = &self.led_node {
-                hid_raw.write_bytes(&effect[0])?;
-                hid_raw.write_bytes(&LED_SET)?;
-                // hid_raw.write_bytes(&LED_APPLY)?;
+        let mut corrected;
+        let frame = match self.colour_correction() {
+            Some(correction) => {
+                corrected = frame.clone();
+                correction.apply_packets(corrected.rows_mut());
+                &corrected
             }
-        } else {
-            if !self.per_key_mode_active {
-                if let LEDNode::Rog(_, hid_raw) = &self.led_node {
-                    let init = LedUsbPackets::get_init_msg();
-                    hid_raw.write_bytes(&init)?;
-                }
-                self.per_key_mode_active = true;
+            None => frame,
+        };
+
+        let start = !self.per_key_mode_active;
+        match self.last_frame.as_mut() {
+            Some(last) if !start => {
+                self.backend
+                    .write_frame(&mut frame.changed_rows(last).map(|r| r.as_slice()), false)?;
+                last.clone_from(frame);
             }
-            if let LEDNode::Rog(_, hid_raw) = &self.led_node {
-                for row in effect.iter() {
-                    hid_raw.write_bytes(row)?;
-                }
-            } else if let LEDNode::KbdLed(tuf) = &self.led_node {
-                for row in effect.iter() {
-                    let r = row[9];
-                    let g = row[10];
-                    let b = row[11];
-                    tuf.set_kbd_rgb_mode(&[0, 0, r, g, b, 0])?;
-                }
+            _ => {
+                self.backend
+                    .write_frame(&mut frame.rows().iter().map(|r| r.as_slice()), start)?;
+                self.last_frame = Some(frame.clone());
             }
         }
+        self.frame_written(true);
+        Ok(())
+    }
+
+    /// Turn the LEDs on to show frames if they are off
+    fn wake_for_frames(&mut self) -> Result<(), RogError> {
+        if self.config.brightness == LedBrightness::Off {
+            self.config.brightness = LedBrightness::Med;
+            self.config.write();
+        }
+        if !self.backend.capabilities().frames {
+            return Err(RogError::NotSupported);
+        }
         Ok(())
     }
 
+    fn frame_written(&mut self, per_key: bool) {
+        self.per_key_mode_active = per_key;
+        if per_key && self.led_type.is_tuf_laptop() {
+            self.kbd_rgb_mode_written = Some(Instant::now());
+        }
+    }
+
     pub fn write_mode(&mut self, mode: &AuraEffect) -> Result<(), RogError> {
-        if let LEDNode::KbdLed(platform) = &self.led_node {
-            let buf = [
-                1,
-                mode.mode as u8,
-                mode.colour1.r,
-                mode.colour1.g,
-                mode.colour1.b,
-                mode.speed as u8,
-            ];
-            platform.set_kbd_rgb_mode(&buf)?;
-        } else if let LEDNode::Rog(_, hid_raw) = &self.led_node {
-            let bytes: [u8; LED_MSG_LEN] = mode.into();
-            hid_raw.write_bytes(&bytes)?;
-            hid_raw.write_bytes(&LED_SET)?;
-            // Changes won't persist unless apply is set
-            hid_raw.write_bytes(&LED_APPLY)?;
+        if !self.backend.capabilities().has_mode(mode.mode) {
+            return Err(RogError::AuraEffectNotSupported);
+        }
+        let mut hardware = match self.config.zone_brightness.get(&mode.zone) {
+            Some(level) if mode.zone != AuraZone::None => dim_effect(mode, *level),
+            _ => mode.clone(),
+        };
+        if let Some(correction) = self.colour_correction() {
+            hardware = correction.apply_effect(&hardware);
+        }
+        // Remapped for models with zones out of order
+        hardware.zone = self.supported_data.hardware_zone(mode.zone);
+        if self.defer_apply {
+            self.backend.show_mode(&hardware)?;
+            self.apply_pending = true;
         } else {
-            return Err(RogError::NoAuraKeyboard);
+            self.backend.write_mode(&hardware)?;
+            self.apply_pending = false;
+        }
+        if self.led_type.is_tuf_laptop() {
+            self.kbd_rgb_mode_written = Some(Instant::now());
         }
         self.per_key_mode_active = false;
         Ok(())
     }
 
+    /// Keep the modes shown while `defer_apply` was set
+    pub fn flush_apply(&mut self) -> Result<(), RogError> {
+        if self.apply_pending {
+            self.backend.apply()?;
+            self.apply_pending = false;
+        }
+        Ok(())
+    }
+
+    /// Step the brightness for a hotkey. Unlike `LedBrightness::next()` this
+    /// stops at `High` and `Off` instead of wrapping around.
+    pub(super) fn step_brightness(&mut self, key: BrightnessKey) -> Result<(), RogError> {
+        let level = match (key, self.config.brightness) {
+            (BrightnessKey::Up, LedBrightness::High) => LedBrightness::High,
+            (BrightnessKey::Down, LedBrightness::Off) => LedBrightness::Off,
+            (BrightnessKey::Up, level) => level.next(),
+            (BrightnessKey::Down, level) => level.prev(),
+        };
+        // The step is from the stored level, which the user then wants shown
+        self.idle_level = None;
+        self.led_node.set_brightness(level.into())?;
+        self.config.brightness = level;
+        Ok(())
+    }
+
+    /// Estimate the LED draw from the brightness, the current effect and the
+    /// zones lit while awake. Software effects are taken as half lit.
+    pub(super) fn power_estimate(&self) -> PowerEstimate {
+        let lit: Vec<PowerZones> = if self.config.enabled.states.is_empty() {
+            vec![PowerZones::Keyboard]
+        } else {
+            self.config
+                .enabled
+                .states
+                .iter()
+                .filter(|state| state.awake)
+                .map(|state| state.zone)
+                .collect()
+        };
+        let load = if self.frame_stream.is_some() {
+            0.5
+        } else if let Some(effects) = self
+            .config
+            .multizone
+            .as_ref()
+            .and_then(|m| m.get(&self.config.current_mode))
+            .filter(|e| self.config.multizone_on && !e.is_empty())
+        {
+            effects.iter().map(power_estimate::effect_load).sum::<f32>() / effects.len() as f32
+        } else {
+            self.config
+                .builtins
+                .get(&self.config.current_mode)
+                .map_or(1.0, power_estimate::effect_load)
+        };
+        let calibration = self
+            .supported_data
+            .power_calibration
+            .clone()
+            .unwrap_or_default();
+        PowerEstimate::new(&calibration, &lit, self.config.brightness, load)
+    }
+
+    /// Step to the next brightness level, going from high back to off
+    pub(super) fn cycle_brightness(&mut self) -> Result<(), RogError> {
+        let level = self.config.brightness.next();
+        self.idle_level = None;
+        self.led_node.set_brightness(level.into())?;
+        self.config.brightness = level;
+        Ok(())
+    }
+
+    /// Switch to the next or previous builtin mode, wrapping around at the
+    /// ends
+    pub(super) fn cycle_mode(&mut self, forward: bool) -> Result<(), RogError> {
+        let modes: Vec<AuraModeNum> = self.config.builtins.keys().copied().collect();
+        if modes.is_empty() {
+            return Ok(());
+        }
+        let next = match modes.iter().position(|m| *m == self.config.current_mode) {
+            Some(i) if forward => (i + 1) % modes.len(),
+            Some(i) => (i + modes.len() - 1) % modes.len(),
+            None => 0,
+        };
+        self.stop_frame_stream();
+        self.temporary_effect = None;
+        self.config.raw_mode = None;
+        self.config.current_mode = modes[next];
+        self.write_current_config_mode()
+    }
+
+    /// Set the brightness of one zone. Zones are dimmed by scaling their
+    /// colours so this only works for zones the device addresses individually,
+    /// any other zone (including `AuraZone::None`) sets the global brightness.
+    pub(super) fn set_zone_brightness(
+        &mut self,
+        zone: AuraZone,
+        level: LedBrightness,
+    ) -> Result<(), RogError> {
+        if zone == AuraZone::None || !self.supported_data.basic_zones.contains(&zone) {
+            debug!("Zone {zone:?} has no separate brightness, setting global brightness");
+            self.led_node.set_brightness(level.into())?;
+            self.config.brightness = level;
+            return Ok(());
+        }
+
+        self.config.zone_brightness.insert(zone, level);
+        if self.config.multizone_on {
+            self.write_current_config_mode()?;
+        }
+        Ok(())
+    }
+
+    /// Called on every write to `kbd_rgb_mode`. The attribute can't be read
+    /// back, so what was written is unknown. A write shortly after our own
+    /// is the firmware resetting the mode, as some do on resume, and the
+    /// stored mode is written again. A later one is from another tool and is
+    /// left shown until the next change or resume. Returns `true` if the mode
+    /// was rewritten.
+    pub(super) fn reconcile_kbd_rgb_mode(&mut self) -> Result<bool, RogError> {
+        let since = self
+            .kbd_rgb_mode_written
+            .map_or(Duration::MAX, |t| t.elapsed());
+        if since < OWN_WRITE_WINDOW || !self.stored_mode_shown() {
+            return Ok(false);
+        }
+        if since >= REVERT_WINDOW {
+            info!("kbd_rgb_mode was set outside of asusd, leaving it shown");
+            return Ok(false);
+        }
+        info!("kbd_rgb_mode was reset after asusd wrote it, restoring the stored mode");
+        self.write_current_config_mode()?;
+        Ok(true)
+    }
+
+    /// Nothing is shown over the stored mode, such as a software effect
+    pub(super) fn stored_mode_shown(&self) -> bool {
+        !self.per_key_mode_active && self.frame_stream.is_none() && self.temporary_effect.is_none()
+    }
+
     pub(super) fn write_current_config_mode(&mut self) -> Result<(), RogError> {
+        if let Some((_, user)) = &self.user_override {
+            let effect = user.effect.clone();
+            self.write_mode(&effect)?;
+            return self.write_audio_indicator();
+        }
+        if let Some(name) = self.config.raw_mode.clone() {
+            match self.config.raw_modes.get(&name).cloned() {
+                Some(packets) => match self.write_effect_block(&packets) {
+                    Ok(_) => return Ok(()),
+                    Err(e) => warn!("Could not write raw mode {name}, using the stored mode: {e}"),
+                },
+                None => warn!("Raw mode {name} was removed, using the stored mode"),
+            }
+            self.config.raw_mode = None;
+        }
         if self.config.multizone_on {
             let mode = self.config.current_mode;
             let mut create = false;
@@ -312,7 +929,19 @@ impl CtrlKbdLed {
             }
         }
 
-        Ok(())
+        self.write_audio_indicator()
+    }
+
+    /// Light the audio indicator zone over the current mode. Skipped for
+    /// zones the device can't set on their own, where it would cover the
+    /// whole keyboard.
+    fn write_audio_indicator(&mut self) -> Result<(), RogError> {
+        match self.config.audio_indicator.effect(self.audio_output) {
+            Some(effect) if self.supported_data.basic_zones.contains(&effect.zone) => {
+                self.write_mode(&effect)
+            }
+            _ => Ok(()),
+        }
     }
 
     /// Create a default for the `current_mode` if multizone and no config
@@ -344,17 +973,456 @@ impl CtrlKbdLed {
     }
 }
 
+/// Scale the colours of an effect to a brightness level
+fn dim_effect(effect: &AuraEffect, level: LedBrightness) -> AuraEffect {
+    let scale = |c: Colour| {
+        let level = u8::from(level) as u16;
+        let max = u8::from(LedBrightness::High) as u16;
+        Colour {
+            r: (c.r as u16 * level / max) as u8,
+            g: (c.g as u16 * level / max) as u8,
+            b: (c.b as u16 * level / max) as u8,
+        }
+    };
+    AuraEffect {
+        colour1: scale(effect.colour1),
+        colour2: scale(effect.colour2),
+        ..effect.clone()
+    }
+}
+
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
+    use std::time::{Duration, Instant};
+
+    use rog_aura::audio_indicator::AudioOutput;
     use rog_aura::aura_detection::{LaptopLedData, PowerZones};
-    use rog_aura::{AuraDeviceType, AuraModeNum, AuraZone};
+    use rog_aura::colour_correction::ColourCorrection;
+    use rog_aura::keyboard::{AuraPowerState, LaptopAuraPower, LedCode, LedUsbPackets};
+    use rog_aura::usb::{LED_APPLY, LED_SET};
+    use rog_aura::user_override::AuraOverride;
+    use rog_aura::{
+        AuraDeviceType, AuraEffect, AuraModeNum, AuraZone, Colour, LedBrightness, Speed,
+    };
+    use rog_platform::aura_hid::MockHid;
     use rog_platform::hid_raw::HidRaw;
     use rog_platform::keyboard_led::KeyboardLed;
     use zbus::zvariant::OwnedObjectPath;
 
-    use super::CtrlKbdLed;
+    use super::{dim_effect, CtrlKbdLed, POWER_WRITE_ATTEMPTS};
+    use crate::ctrl_aura::backend::UsbHid;
     use crate::ctrl_aura::config::AuraConfig;
     use crate::ctrl_aura::controller::LEDNode;
+    use crate::ctrl_aura::tuf::Tuf;
+    use crate::error::RogError;
+    use crate::keys::BrightnessKey;
+
+    pub(crate) fn mock_controller(mock: &MockHid) -> CtrlKbdLed {
+        let mut config = AuraConfig::new("19b6");
+        config.brightness = LedBrightness::Med;
+        CtrlKbdLed {
+            led_type: AuraDeviceType::LaptopPost2021,
+            led_node: LEDNode::Mock(mock.clone()),
+            backend: Box::new(UsbHid::new(
+                AuraDeviceType::LaptopPost2021,
+                "19b6",
+                Box::new(mock.clone()),
+            )),
+            supported_data: LaptopLedData {
+                board_name: String::new(),
+                layout_name: "ga401".to_owned(),
+                basic_modes: vec![AuraModeNum::Static],
+                basic_zones: vec![AuraZone::Key1, AuraZone::Key2],
+                advanced_type: rog_aura::keyboard::AdvancedAuraType::PerKey,
+                power_zones: vec![PowerZones::Keyboard],
+                power_zone_descriptions: Default::default(),
+                zone_remap: [(AuraZone::Key1, AuraZone::Key2)].into(),
+                power_calibration: None,
+                colour_correction: None,
+            },
+            per_key_mode_active: false,
+            last_frame: None,
+            config,
+            dbus_path: OwnedObjectPath::default(),
+            frame_stream: None,
+            temporary_effect: None,
+            preview_owner: None,
+            kbd_rgb_mode_written: None,
+            audio_output: AudioOutput::Unknown,
+            led_rule: None,
+            woken_from: None,
+            tablet_mode: false,
+            defer_apply: false,
+            apply_pending: false,
+            idle_level: None,
+            user_override: None,
+            typing_trail: None,
+            idle_changed: Arc::default(),
+        }
+    }
+
+    #[test]
+    fn power_states_verified() {
+        let mock = MockHid::new("19b6");
+        let mut controller = mock_controller(&mock);
+        controller.config.enabled = LaptopAuraPower {
+            states: vec![AuraPowerState {
+                zone: PowerZones::Keyboard,
+                boot: true,
+                awake: true,
+                sleep: true,
+                shutdown: true,
+            }],
+        };
+        controller.set_power_states().unwrap();
+        let written = mock.take_writes();
+        // Firmware without the feature report is not checked
+        assert_eq!(written.len(), 3);
+
+        mock.set_feature_report(&written[0]);
+        controller.set_power_states().unwrap();
+        assert_eq!(mock.take_writes().len(), 3);
+
+        // Firmware that doesn't keep the states is written to again, then the
+        // mismatch returned
+        controller.config.enabled.states[0].awake = false;
+        let Err(RogError::PowerStateMismatch(_, read)) = controller.set_power_states() else {
+            panic!("the states read back differ from those written");
+        };
+        assert_eq!(read, written[0][3..7]);
+        assert_eq!(mock.take_writes().len(), 3 * POWER_WRITE_ATTEMPTS);
+    }
+
+    #[test]
+    fn mode_packets() {
+        let mock = MockHid::new("19b6");
+        let mut controller = mock_controller(&mock);
+        let effect = AuraEffect {
+            zone: AuraZone::Key1,
+            colour1: Colour {
+                r: 255,
+                g: 0,
+                b: 30,
+            },
+            speed: Speed::Med,
+            ..Default::default()
+        };
+        controller.write_mode(&effect).unwrap();
+
+        let mut expected = vec![0; rog_aura::LED_MSG_LEN];
+        // Key1 is remapped to the zone byte of Key2
+        expected[..9].copy_from_slice(&[0x5d, 0xb3, 0x02, 0x00, 255, 0, 30, 0xeb, 0x00]);
+        assert_eq!(
+            mock.take_writes(),
+            vec![expected, LED_SET.to_vec(), LED_APPLY.to_vec()]
+        );
+    }
+
+    #[test]
+    fn deferred_apply() {
+        let mock = MockHid::new("19b6");
+        let mut controller = mock_controller(&mock);
+        let effect = AuraEffect::default();
+        controller.defer_apply = true;
+        controller.write_mode(&effect).unwrap();
+        controller.write_mode(&effect).unwrap();
+        let written = mock.take_writes();
+        assert_eq!(written.len(), 4);
+        assert!(!written.contains(&LED_APPLY.to_vec()));
+
+        controller.defer_apply = false;
+        controller.flush_apply().unwrap();
+        assert_eq!(mock.take_writes(), vec![LED_APPLY.to_vec()]);
+        // Nothing is left to keep
+        controller.flush_apply().unwrap();
+        assert!(mock.take_writes().is_empty());
+    }
+
+    #[test]
+    fn colour_correction_applied() {
+        let mock = MockHid::new("19b6");
+        let mut controller = mock_controller(&mock);
+        controller.supported_data.colour_correction = Some(ColourCorrection::white_point(Colour {
+            r: 255,
+            g: 200,
+            b: 100,
+        }));
+        let white = Colour {
+            r: 255,
+            g: 255,
+            b: 255,
+        };
+        let effect = AuraEffect {
+            colour1: white,
+            ..Default::default()
+        };
+        controller.write_mode(&effect).unwrap();
+        assert_eq!(mock.take_writes()[0][4..7], [255, 200, 100]);
+
+        // The config replaces the correction of the model
+        controller.config.colour_correction = Some(ColourCorrection::default());
+        controller.write_mode(&effect).unwrap();
+        assert_eq!(mock.take_writes()[0][4..7], [255, 255, 255]);
+    }
+
+    #[test]
+    fn wake_for_effect() {
+        let mock = MockHid::new("19b6");
+        let mut controller = mock_controller(&mock);
+        controller.config.wake_brightness = Some(LedBrightness::High);
+        mock.set_brightness(0).unwrap();
+
+        // Not idle and not dimmed by a rule
+        controller.wake_for_effect(false).unwrap();
+        assert_eq!(mock.get_brightness().unwrap(), 0);
+
+        controller.wake_for_effect(true).unwrap();
+        assert_eq!(mock.get_brightness().unwrap(), 3);
+        controller.wake_for_effect(true).unwrap();
+        assert_eq!(controller.woken_from, Some(LedBrightness::Off));
+        controller.end_wake().unwrap();
+        assert_eq!(mock.get_brightness().unwrap(), 0);
+        assert!(controller.woken_from.is_none());
+    }
+
+    #[test]
+    fn off_in_tablet_mode() {
+        let mock = MockHid::new("19b6");
+        let mut controller = mock_controller(&mock);
+        mock.set_brightness(2).unwrap();
+        controller.set_tablet_mode(true).unwrap();
+        assert_eq!(mock.get_brightness().unwrap(), 0);
+        assert!(controller.brightness_overridden());
+        controller.set_tablet_mode(false).unwrap();
+        assert_eq!(mock.get_brightness().unwrap(), 2);
+
+        controller.config.on_in_tablet_mode = true;
+        controller.set_tablet_mode(true).unwrap();
+        assert_eq!(mock.get_brightness().unwrap(), 2);
+        assert!(!controller.brightness_overridden());
+    }
+
+    #[test]
+    fn user_override_shown_then_restored() {
+        let mock = MockHid::new("19b6");
+        let mut controller = mock_controller(&mock);
+        mock.set_brightness(2).unwrap();
+        let user = AuraOverride {
+            effect: AuraEffect {
+                colour1: Colour { r: 1, g: 2, b: 3 },
+                ..Default::default()
+            },
+            brightness: LedBrightness::High,
+        };
+        controller.set_user_override(Some((1000, user))).unwrap();
+        assert_eq!(mock.take_writes()[0][4..7], [1, 2, 3]);
+        assert_eq!(mock.get_brightness().unwrap(), 3);
+        assert!(controller.brightness_overridden());
+        // The stored brightness is left as it is
+        assert_eq!(controller.config.brightness, LedBrightness::Med);
+
+        controller.set_user_override(None).unwrap();
+        assert_eq!(mock.get_brightness().unwrap(), 2);
+        assert!(!controller.brightness_overridden());
+    }
+
+    #[test]
+    fn idle_fade_and_restore() {
+        let mock = MockHid::new("19b6");
+        let mut controller = mock_controller(&mock);
+        mock.set_brightness(2).unwrap();
+        controller.idle_step().unwrap();
+        assert_eq!(mock.get_brightness().unwrap(), 1);
+        assert!(controller.brightness_overridden());
+        controller.idle_step().unwrap();
+        controller.idle_step().unwrap();
+        assert_eq!(controller.idle_level, Some(LedBrightness::Off));
+        controller.end_idle().unwrap();
+        assert_eq!(mock.get_brightness().unwrap(), 2);
+
+        // A hotkey step while faded is from the stored level
+        controller.idle_step().unwrap();
+        controller.step_brightness(BrightnessKey::Up).unwrap();
+        assert_eq!(mock.get_brightness().unwrap(), 3);
+        assert!(controller.idle_level.is_none());
+        controller.end_idle().unwrap();
+        assert_eq!(mock.get_brightness().unwrap(), 3);
+    }
+
+    #[test]
+    fn tuf_mode_packet() {
+        let mock = MockHid::new("tuf");
+        let mut controller = mock_controller(&mock);
+        controller.led_type = AuraDeviceType::LaptopTuf;
+        controller.backend = Box::new(Tuf::with_writer(Box::new(mock.clone()), false));
+        let effect = AuraEffect {
+            colour1: Colour { r: 1, g: 2, b: 3 },
+            speed: Speed::High,
+            ..Default::default()
+        };
+        controller.write_mode(&effect).unwrap();
+        assert_eq!(mock.take_writes(), vec![vec![1, 0, 1, 2, 3, 2]]);
+        assert!(controller.kbd_rgb_mode_written.is_some());
+
+        // No kbd_rgb_state on this kernel
+        controller.set_power_states().unwrap();
+        assert!(mock.take_writes().is_empty());
+        let flash = AuraEffect {
+            mode: AuraModeNum::Flash,
+            ..Default::default()
+        };
+        assert!(controller.write_mode(&flash).is_err());
+
+        // Flash and the zones of the support data aren't listed for TUF
+        let mut data = controller.supported_data.clone();
+        data.basic_modes.push(AuraModeNum::Flash);
+        let data = controller.backend_support(data);
+        assert_eq!(data.basic_modes, vec![AuraModeNum::Static]);
+        assert!(data.basic_zones.is_empty());
+    }
+
+    #[test]
+    fn tuf_mode_reverted_only_after_own_write() {
+        let mock = MockHid::new("tuf");
+        let mut controller = mock_controller(&mock);
+        controller.led_type = AuraDeviceType::LaptopTuf;
+        controller.backend = Box::new(Tuf::with_writer(Box::new(mock.clone()), false));
+        controller
+            .config
+            .builtins
+            .insert(AuraModeNum::Static, AuraEffect::default());
+        let written_ago = |ms| Instant::now().checked_sub(Duration::from_millis(ms));
+
+        // The echo of our own write
+        controller.kbd_rgb_mode_written = written_ago(100);
+        assert!(!controller.reconcile_kbd_rgb_mode().unwrap());
+        // The firmware resetting the mode just after it was written
+        controller.kbd_rgb_mode_written = written_ago(1000);
+        assert!(controller.reconcile_kbd_rgb_mode().unwrap());
+        assert_eq!(mock.take_writes().len(), 1);
+        // Another tool, long after
+        controller.kbd_rgb_mode_written = written_ago(60_000);
+        assert!(!controller.reconcile_kbd_rgb_mode().unwrap());
+        assert!(mock.take_writes().is_empty());
+    }
+
+    #[test]
+    fn power_state_packets() {
+        let mock = MockHid::new("19b6");
+        let mut controller = mock_controller(&mock);
+        controller.config.enabled = LaptopAuraPower {
+            states: vec![AuraPowerState {
+                zone: PowerZones::Keyboard,
+                boot: true,
+                awake: true,
+                sleep: false,
+                shutdown: true,
+            }],
+        };
+        controller.set_power_states().unwrap();
+        assert_eq!(
+            mock.take_writes(),
+            vec![
+                vec![0x5d, 0xbd, 0x01, 0x8a, 0x00, 0x00, 0x00],
+                LED_SET.to_vec(),
+                LED_APPLY.to_vec()
+            ]
+        );
+    }
+
+    #[test]
+    fn per_key_init_sent_once() {
+        let mock = MockHid::new("19b6");
+        let mut controller = mock_controller(&mock);
+        let mut block = vec![vec![0u8; 64]; 2];
+        for (i, row) in block.iter_mut().enumerate() {
+            row[0] = 0x5d;
+            row[1] = 0xbc;
+            row[6] = (i as u8) << 4;
+        }
+
+        controller.write_effect_block(&block).unwrap();
+        let mut expected = vec![LedUsbPackets::get_init_msg().to_vec()];
+        expected.extend(block.iter().cloned());
+        assert_eq!(mock.take_writes(), expected);
+        assert!(controller.per_key_mode_active);
+
+        controller.write_effect_block(&block).unwrap();
+        assert_eq!(mock.take_writes(), block);
+    }
+
+    #[test]
+    fn frame_writes_changed_rows() {
+        let mock = MockHid::new("19b6");
+        let mut controller = mock_controller(&mock);
+        let mut frame = LedUsbPackets::new_per_key();
+        frame.set(LedCode::Esc, 255, 0, 0);
+
+        controller.write_frame(&frame).unwrap();
+        let mut expected = vec![LedUsbPackets::get_init_msg().to_vec()];
+        expected.extend(frame.get());
+        assert_eq!(mock.take_writes(), expected);
+
+        // Only the row of the key that changed
+        frame.set(LedCode::D, 0, 0, 255);
+        controller.write_frame(&frame).unwrap();
+        assert_eq!(mock.take_writes(), vec![frame.rows()[5].to_vec()]);
+        controller.write_frame(&frame).unwrap();
+        assert!(mock.take_writes().is_empty());
+
+        // All of it again once a builtin mode was shown
+        controller.write_mode(&AuraEffect::default()).unwrap();
+        mock.take_writes();
+        controller.write_frame(&frame).unwrap();
+        let mut expected = vec![LedUsbPackets::get_init_msg().to_vec()];
+        expected.extend(frame.get());
+        assert_eq!(mock.take_writes(), expected);
+    }
+
+    #[test]
+    fn raw_mode_shown_for_current_mode() {
+        let mock = MockHid::new("19b6");
+        let mut controller = mock_controller(&mock);
+        let mut packet = vec![0u8; 64];
+        packet[..3].copy_from_slice(&[0x5d, 0xb3, 0x0a]);
+        let packets = vec![packet];
+        controller
+            .config
+            .raw_modes
+            .insert("vendor".to_owned(), packets.clone());
+        controller.config.raw_mode = Some("vendor".to_owned());
+
+        controller.write_current_config_mode().unwrap();
+        assert_eq!(
+            mock.take_writes(),
+            vec![packets[0].clone(), LED_SET.to_vec()]
+        );
+
+        // A raw mode that is gone falls back to the stored mode
+        controller.config.raw_modes.clear();
+        controller.write_current_config_mode().unwrap();
+        assert!(controller.config.raw_mode.is_none());
+    }
+
+    #[test]
+    fn dim_zone_effect() {
+        let effect = AuraEffect {
+            zone: AuraZone::Logo,
+            colour1: Colour {
+                r: 255,
+                g: 0,
+                b: 30,
+            },
+            ..Default::default()
+        };
+        let dimmed = dim_effect(&effect, LedBrightness::Low);
+        assert_eq!(dimmed.colour1, Colour { r: 85, g: 0, b: 10 });
+        assert_eq!(dimmed.zone, AuraZone::Logo);
+        let off = dim_effect(&effect, LedBrightness::Off);
+        assert_eq!(off.colour1, Colour { r: 0, g: 0, b: 0 });
+        let full = dim_effect(&effect, LedBrightness::High);
+        assert_eq!(full.colour1, effect.colour1);
+    }
 
     #[test]
     #[ignore = "Unable to run in CI as the HIDRAW device is required"]
@@ -368,14 +1436,38 @@ mod tests {
             basic_zones: vec![],
             advanced_type: rog_aura::keyboard::AdvancedAuraType::None,
             power_zones: vec![PowerZones::Keyboard, PowerZones::RearGlow],
+            power_zone_descriptions: Default::default(),
+            zone_remap: Default::default(),
+            power_calibration: None,
+            colour_correction: None,
         };
         let mut controller = CtrlKbdLed {
             led_type: AuraDeviceType::LaptopPost2021,
-            led_node: LEDNode::Rog(KeyboardLed::default(), HidRaw::new("19b6").unwrap()),
+            led_node: LEDNode::KbdLed(KeyboardLed::default()),
+            backend: Box::new(UsbHid::new(
+                AuraDeviceType::LaptopPost2021,
+                "19b6",
+                Box::new(HidRaw::new("19b6").unwrap()),
+            )),
             supported_data: supported_basic_modes,
             per_key_mode_active: false,
+            last_frame: None,
             config,
             dbus_path: OwnedObjectPath::default(),
+            frame_stream: None,
+            temporary_effect: None,
+            preview_owner: None,
+            kbd_rgb_mode_written: None,
+            audio_output: AudioOutput::Unknown,
+            led_rule: None,
+            woken_from: None,
+            tablet_mode: false,
+            defer_apply: false,
+            apply_pending: false,
+            idle_level: None,
+            user_override: None,
+            typing_trail: None,
+            idle_changed: Arc::default(),
         };
 
         assert!(controller.config.multizone.is_none());
@@ -408,14 +1500,38 @@ mod tests {
             basic_zones: vec![AuraZone::Key1, AuraZone::Key2],
             advanced_type: rog_aura::keyboard::AdvancedAuraType::None,
             power_zones: vec![PowerZones::Keyboard, PowerZones::RearGlow],
+            power_zone_descriptions: Default::default(),
+            zone_remap: Default::default(),
+            power_calibration: None,
+            colour_correction: None,
         };
         let mut controller = CtrlKbdLed {
             led_type: AuraDeviceType::LaptopPost2021,
-            led_node: LEDNode::Rog(KeyboardLed::default(), HidRaw::new("19b6").unwrap()),
+            led_node: LEDNode::KbdLed(KeyboardLed::default()),
+            backend: Box::new(UsbHid::new(
+                AuraDeviceType::LaptopPost2021,
+                "19b6",
+                Box::new(HidRaw::new("19b6").unwrap()),
+            )),
             supported_data: supported_basic_modes,
             per_key_mode_active: false,
+            last_frame: None,
             config,
             dbus_path: OwnedObjectPath::default(),
+            frame_stream: None,
+            temporary_effect: None,
+            preview_owner: None,
+            kbd_rgb_mode_written: None,
+            audio_output: AudioOutput::Unknown,
+            led_rule: None,
+            woken_from: None,
+            tablet_mode: false,
+            defer_apply: false,
+            apply_pending: false,
+            idle_level: None,
+            user_override: None,
+            typing_trail: None,
+            idle_changed: Arc::default(),
         };
 
         assert!(controller.config.multizone.is_none());
@@ -0,0 +1,114 @@
+//! Turning the keyboard lighting off after a time without input. The
+//! keyboards, touchpads and ASUS hotkey devices are read straight from evdev,
+//! so this works the same on the console and without a desktop that reports
+//! idle sessions. The fade is a few steps down the brightness levels, and the
+//! first input after brings back the brightness it started from.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures_lite::stream::StreamExt;
+use inotify::{Inotify, WatchMask};
+use log::{info, warn};
+use serde_derive::{Deserialize, Serialize};
+use tokio::sync::Notify;
+
+use crate::keys;
+
+/// The kernel keyboard backlight has four levels, so a fade from `High` takes
+/// three steps
+pub const FADE_STEPS: u32 = 3;
+/// Where the nodes of input devices are made as they are plugged in
+const INPUT_DIR: &str = "/dev/input";
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct IdleOff {
+    /// Seconds without input before the lighting fades out, `0` to keep it on
+    pub timeout_secs: u32,
+    /// How long the fade to off takes
+    pub fade_ms: u32,
+}
+
+impl Default for IdleOff {
+    fn default() -> Self {
+        Self {
+            timeout_secs: 0,
+            fade_ms: 1500,
+        }
+    }
+}
+
+impl IdleOff {
+    /// `None` if the lighting is never turned off
+    pub fn timeout(&self) -> Option<Duration> {
+        (self.timeout_secs != 0).then(|| Duration::from_secs(self.timeout_secs as u64))
+    }
+
+    /// The time between each step down of the fade
+    pub fn fade_step(&self) -> Duration {
+        Duration::from_millis(self.fade_ms as u64) / FADE_STEPS
+    }
+}
+
+/// Every input device that counts as activity
+fn input_nodes() -> Vec<PathBuf> {
+    let mut nodes = keys::find_keyboard_devices();
+    nodes.extend(keys::find_touchpad_devices());
+    // Hotkeys handled by the firmware come from a device of their own
+    nodes.extend(keys::find_hotkey_devices());
+    nodes.sort();
+    nodes.dedup();
+    nodes
+}
+
+fn read_input(node: PathBuf, activity: Arc<Notify>) {
+    keys::spawn_event_reader(node, "Idle input", move |_, _, _| {
+        // A permit is stored if nothing waits, so input between checks isn't
+        // missed
+        activity.notify_one();
+        true
+    });
+}
+
+/// Start reading every input device that counts as activity, and each one
+/// plugged in later, notifying the returned `Notify` on each event
+pub fn watch_input() -> Arc<Notify> {
+    let activity = Arc::new(Notify::new());
+    let nodes = input_nodes();
+    if nodes.is_empty() {
+        info!("No keyboard or touchpad input device yet, idle off waits for one");
+    } else {
+        info!("Watching {} input devices for idle off", nodes.len());
+    }
+    for node in nodes {
+        read_input(node, activity.clone());
+    }
+
+    let hotplug = activity.clone();
+    tokio::spawn(async move {
+        let inotify = Inotify::init()?;
+        inotify
+            .watches()
+            .add(INPUT_DIR, WatchMask::CREATE)
+            .map_err(|e| warn!("Could not watch {INPUT_DIR} for new input devices: {e}"))
+            .ok();
+
+        let mut buffer = [0; 1024];
+        let mut events = inotify.into_event_stream(&mut buffer)?;
+        while let Some(event) = events.next().await {
+            let Some(name) = event.ok().and_then(|e| e.name) else {
+                continue;
+            };
+            // Only the new node, as the names of removed devices are reused
+            for node in input_nodes() {
+                if node.file_name() == Some(name.as_os_str()) {
+                    read_input(node, hotplug.clone());
+                }
+            }
+        }
+        Ok::<(), std::io::Error>(())
+    });
+    activity
+}
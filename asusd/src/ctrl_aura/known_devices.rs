@@ -0,0 +1,321 @@
+//! Aura devices that have been seen before, connected or not. Each device is
+//! kept with what it can do, so a client can still list and configure a
+//! device that is unplugged, such as an external keyboard. Settings made while
+//! a device is away go in to its own `aura_<prod_id>.ron` and are applied when
+//! the manager sees it plugged back in.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use config_traits::{StdConfig, StdConfigLoad};
+use log::info;
+use rog_aura::aura_detection::{LaptopLedData, PowerZones};
+use rog_aura::{AuraDeviceType, AuraEffect, AuraModeNum, AuraZone, LedBrightness};
+use serde_derive::{Deserialize, Serialize};
+use zbus::export::futures_util::lock::Mutex;
+use zbus::fdo::Error as FdoErr;
+use zbus::message::Header;
+use zbus::zvariant::Type;
+use zbus::{interface, Connection, SignalContext};
+
+use super::config::AuraConfig;
+use super::controller::CtrlKbdLed;
+use crate::error::RogError;
+use crate::polkit::{self, Action};
+
+pub const AURA_DEVICES_ZBUS_PATH: &str = "/org/asuslinux/AuraDevices";
+const CONFIG_NAME: &str = "aura_devices.ron";
+const CONFIG_VERSION: u32 = 1;
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[derive(Deserialize, Serialize, Type, Debug, Clone, PartialEq)]
+pub struct KnownAuraDevice {
+    pub prod_id: String,
+    pub device_type: AuraDeviceType,
+    pub connected: bool,
+    /// Object path of the device's `org.asuslinux.Aura` interface, empty
+    /// while it is not connected
+    pub dbus_path: String,
+    /// Seconds since the Unix epoch when the device was last plugged in or
+    /// removed
+    pub last_seen: u64,
+    pub basic_modes: Vec<AuraModeNum>,
+    pub basic_zones: Vec<AuraZone>,
+    pub power_zones: Vec<PowerZones>,
+    /// Settings were changed while the device was away and are waiting to be
+    /// applied
+    pub queued: bool,
+}
+
+#[derive(Deserialize, Serialize, Default, Debug, Clone, PartialEq)]
+pub struct KnownDevicesConfig {
+    /// Version of the file format, upgraded on load
+    #[serde(default)]
+    pub version: u32,
+    pub devices: BTreeMap<String, KnownAuraDevice>,
+}
+
+impl StdConfig for KnownDevicesConfig {
+    const VERSION: Option<u32> = Some(CONFIG_VERSION);
+
+    fn new() -> Self {
+        Self {
+            version: CONFIG_VERSION,
+            devices: BTreeMap::new(),
+        }
+    }
+
+    fn file_name(&self) -> String {
+        CONFIG_NAME.to_owned()
+    }
+
+    fn config_dir() -> std::path::PathBuf {
+        std::path::PathBuf::from(crate::CONFIG_PATH_BASE)
+    }
+
+    fn set_version(&mut self, version: u32) {
+        self.version = version;
+    }
+}
+
+impl StdConfigLoad for KnownDevicesConfig {}
+
+impl KnownDevicesConfig {
+    /// Record a device as connected at `dbus_path`. Returns `true` if it has
+    /// queued settings, which are then taken as applied.
+    pub fn seen(&mut self, prod_id: &str, dbus_path: &str, data: &LaptopLedData, now: u64) -> bool {
+        let previous = self.devices.insert(
+            prod_id.to_owned(),
+            KnownAuraDevice {
+                prod_id: prod_id.to_owned(),
+                device_type: AuraDeviceType::from(prod_id),
+                connected: true,
+                dbus_path: dbus_path.to_owned(),
+                last_seen: now,
+                basic_modes: data.basic_modes.clone(),
+                basic_zones: data.basic_zones.clone(),
+                power_zones: data.power_zones.clone(),
+                queued: false,
+            },
+        );
+        previous.is_some_and(|dev| dev.queued)
+    }
+
+    /// Record the device at `dbus_path` as removed
+    pub fn removed(&mut self, dbus_path: &str, now: u64) {
+        for dev in self.devices.values_mut() {
+            if dev.connected && dev.dbus_path == dbus_path {
+                dev.connected = false;
+                dev.dbus_path.clear();
+                dev.last_seen = now;
+            }
+        }
+    }
+
+    /// Nothing is connected until found again, used on start
+    fn disconnect_all(&mut self) {
+        for dev in self.devices.values_mut() {
+            dev.connected = false;
+            dev.dbus_path.clear();
+        }
+    }
+
+    /// A device that may be configured offline
+    fn offline(&mut self, prod_id: &str) -> Result<&mut KnownAuraDevice, FdoErr> {
+        match self.devices.get_mut(prod_id) {
+            Some(dev) if dev.connected => Err(FdoErr::Failed(format!(
+                "{prod_id} is connected, use {} instead",
+                dev.dbus_path
+            ))),
+            Some(dev) => Ok(dev),
+            None => Err(FdoErr::InvalidArgs(format!(
+                "{prod_id} has not been seen before"
+            ))),
+        }
+    }
+}
+
+/// Load the saved config of a device which is not connected, to be changed
+/// and written back
+fn offline_config(prod_id: &str) -> AuraConfig {
+    CtrlKbdLed::init_config(prod_id, &LaptopLedData::get_data())
+}
+
+#[derive(Clone)]
+pub struct CtrlAuraDevices {
+    devices: Arc<Mutex<KnownDevicesConfig>>,
+    signal_ctxt: SignalContext<'static>,
+}
+
+impl CtrlAuraDevices {
+    pub fn new(connection: &Connection) -> Result<Self, RogError> {
+        let mut config = KnownDevicesConfig::new().load();
+        config.disconnect_all();
+        Ok(Self {
+            devices: Arc::new(Mutex::new(config)),
+            signal_ctxt: SignalContext::new(connection, AURA_DEVICES_ZBUS_PATH)?,
+        })
+    }
+
+    /// Record a device as connected. Returns `true` if settings were queued
+    /// for it while it was away.
+    pub async fn device_added(&self, prod_id: &str, dbus_path: &str, data: &LaptopLedData) -> bool {
+        let mut devices = self.devices.lock().await;
+        let queued = devices.seen(prod_id, dbus_path, data, now_secs());
+        devices.write();
+        drop(devices);
+        if queued {
+            info!("Applying the settings queued for {prod_id} while it was away");
+        }
+        self.known_devices_changed(&self.signal_ctxt).await.ok();
+        queued
+    }
+
+    /// The devices connected now
+    pub async fn connected(&self) -> Vec<KnownAuraDevice> {
+        let devices = self.devices.lock().await;
+        devices
+            .devices
+            .values()
+            .filter(|d| d.connected)
+            .cloned()
+            .collect()
+    }
+
+    pub async fn device_removed(&self, dbus_path: &str) {
+        let mut devices = self.devices.lock().await;
+        devices.removed(dbus_path, now_secs());
+        devices.write();
+        drop(devices);
+        self.known_devices_changed(&self.signal_ctxt).await.ok();
+    }
+}
+
+#[interface(name = "org.asuslinux.AuraDevices")]
+impl CtrlAuraDevices {
+    /// Every Aura device seen so far, with what it supports. Devices that are
+    /// not connected can be set up with the `SetOffline` methods.
+    #[zbus(property)]
+    async fn known_devices(&self) -> Vec<KnownAuraDevice> {
+        self.devices
+            .lock()
+            .await
+            .devices
+            .values()
+            .cloned()
+            .collect()
+    }
+
+    /// Queue an effect for a device that is not connected. It becomes the
+    /// current mode when the device is plugged in again.
+    async fn set_offline_led_mode_data(
+        &self,
+        prod_id: &str,
+        effect: AuraEffect,
+        #[zbus(signal_context)] ctxt: SignalContext<'_>,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> Result<(), FdoErr> {
+        polkit::check(connection, &header, Action::ChangeLighting).await?;
+        let mut devices = self.devices.lock().await;
+        let dev = devices.offline(prod_id)?;
+        if !dev.basic_modes.contains(&effect.mode)
+            || effect.zone != AuraZone::None && !dev.basic_zones.contains(&effect.zone)
+        {
+            return Err(FdoErr::NotSupported(format!(
+                "The Aura effect is not supported by {prod_id}: {effect:?}"
+            )));
+        }
+        let mut config = offline_config(prod_id);
+        config.set_builtin(effect);
+        if config.brightness == LedBrightness::Off {
+            config.brightness = LedBrightness::Med;
+        }
+        config.write();
+        dev.queued = true;
+        devices.write();
+        drop(devices);
+        self.known_devices_changed(&ctxt).await.ok();
+        Ok(())
+    }
+
+    /// Queue a brightness for a device that is not connected
+    async fn set_offline_brightness(
+        &self,
+        prod_id: &str,
+        brightness: LedBrightness,
+        #[zbus(signal_context)] ctxt: SignalContext<'_>,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> Result<(), FdoErr> {
+        polkit::check(connection, &header, Action::ChangeLighting).await?;
+        let mut devices = self.devices.lock().await;
+        let dev = devices.offline(prod_id)?;
+        let mut config = offline_config(prod_id);
+        config.brightness = brightness;
+        config.write();
+        dev.queued = true;
+        devices.write();
+        drop(devices);
+        self.known_devices_changed(&ctxt).await.ok();
+        Ok(())
+    }
+
+    /// Forget a device that is not connected. Its config file is kept.
+    async fn remove_device(
+        &self,
+        prod_id: &str,
+        #[zbus(signal_context)] ctxt: SignalContext<'_>,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> Result<(), FdoErr> {
+        polkit::check(connection, &header, Action::ChangeLighting).await?;
+        let mut devices = self.devices.lock().await;
+        devices.offline(prod_id)?;
+        devices.devices.remove(prod_id);
+        devices.write();
+        drop(devices);
+        self.known_devices_changed(&ctxt).await.ok();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rog_aura::aura_detection::LaptopLedData;
+    use rog_aura::AuraModeNum;
+
+    use super::KnownDevicesConfig;
+
+    #[test]
+    fn queued_settings_survive_until_seen() {
+        let mut known = KnownDevicesConfig::default();
+        let data = LaptopLedData {
+            basic_modes: vec![AuraModeNum::Static, AuraModeNum::Breathe],
+            ..Default::default()
+        };
+        let path = "/org/asuslinux/19b6_3_1";
+        assert!(!known.seen("19b6", path, &data, 10));
+        assert!(known.offline("19b6").is_err());
+        assert!(known.offline("1866").is_err());
+
+        known.removed(path, 20);
+        let dev = known.offline("19b6").unwrap();
+        assert!(dev.dbus_path.is_empty());
+        assert_eq!(dev.last_seen, 20);
+        assert_eq!(dev.basic_modes.len(), 2);
+        dev.queued = true;
+
+        assert!(known.seen("19b6", path, &data, 30));
+        let dev = &known.devices["19b6"];
+        assert!(dev.connected && !dev.queued);
+        assert_eq!(dev.dbus_path, path);
+    }
+}
@@ -18,31 +18,60 @@ use zbus::zvariant::{ObjectPath, OwnedObjectPath};
 use zbus::Connection;
 
 use crate::ctrl_aura::controller::CtrlKbdLed;
+use crate::ctrl_aura::known_devices::{CtrlAuraDevices, AURA_DEVICES_ZBUS_PATH};
 use crate::ctrl_aura::trait_impls::{CtrlAuraZbus, AURA_ZBUS_PATH};
 use crate::error::RogError;
-use crate::{CtrlTask, Reloadable};
+use crate::{
+    ConfigChangeHook, CtrlTask, HotkeyHook, ProfileChangeListener, Reloadable, ShutdownHook,
+    TabletModeListener,
+};
 
 pub struct AuraManager {
     _connection: Connection,
 }
 
 impl AuraManager {
-    pub async fn new(connection: Connection) -> Result<Self, RogError> {
+    pub async fn new(
+        connection: Connection,
+        profile_listener: ProfileChangeListener,
+        hotkey_hook: HotkeyHook,
+        config_hook: ConfigChangeHook,
+        shutdown_hook: ShutdownHook,
+        tablet_mode: TabletModeListener,
+        dry_run: bool,
+    ) -> Result<Self, RogError> {
         let conn_copy = connection.clone();
         let data = LaptopLedData::get_data();
         let mut interfaces = HashSet::new();
+        let known = CtrlAuraDevices::new(&connection)?;
 
         // Do the initial keyboard detection:
         let all = CtrlKbdLed::find_all(&data)?;
-        for ctrl in all {
+        for mut ctrl in all {
+            if dry_run {
+                ctrl.use_dry_run();
+            }
             let path = ctrl.dbus_path.clone();
             interfaces.insert(path.clone()); // ensure we record the initial stuff
+            known
+                .device_added(ctrl.prod_id(), path.as_str(), &ctrl.supported_data)
+                .await;
             let sig_ctx = CtrlAuraZbus::signal_context(&connection)?;
             let sig_ctx2 = sig_ctx.clone();
             let zbus = CtrlAuraZbus::new(ctrl, sig_ctx);
+            zbus.listen_profile_changes(profile_listener.clone());
+            zbus.listen_hotkeys(hotkey_hook.subscribe());
+            zbus.listen_config_changes(config_hook.subscribe());
+            zbus.listen_shutdown(shutdown_hook.subscribe());
+            zbus.listen_tablet_mode(tablet_mode.clone());
             start_tasks(zbus, connection.clone(), sig_ctx2, path).await?;
         }
 
+        connection
+            .object_server()
+            .at(AURA_DEVICES_ZBUS_PATH, known.clone())
+            .await?;
+
         let manager = Self {
             _connection: connection,
         };
@@ -96,7 +125,9 @@ impl AuraManager {
                         if interfaces.remove(&path) {
                             info!("AuraManager removing: {path:?}");
                             let conn_copy = conn_copy.clone();
+                            let known = known.clone();
                             tokio::spawn(async move {
+                                known.device_removed(path.as_str()).await;
                                 let res = conn_copy
                                     .object_server()
                                     .remove::<CtrlAuraZbus, _>(&path)
@@ -135,15 +166,45 @@ impl AuraManager {
                                 if let Ok(mut ctrl) =
                                     CtrlKbdLed::from_hidraw(raw, path.clone(), &data)
                                 {
+                                    // Any settings queued while the device was away are
+                                    // in its config already
                                     ctrl.config = CtrlKbdLed::init_config(&id_product, &data);
+                                    if dry_run {
+                                        ctrl.use_dry_run();
+                                    }
                                     interfaces.insert(path.clone());
                                     info!("AuraManager starting device at: {dev_node:?}, {path:?}");
                                     let sig_ctx = CtrlAuraZbus::signal_context(&conn_copy)?;
-                                    let zbus = CtrlAuraZbus::new(ctrl, sig_ctx);
-                                    let sig_ctx = CtrlAuraZbus::signal_context(&conn_copy)?;
                                     let conn_copy = conn_copy.clone();
+                                    let known = known.clone();
+                                    let profile_listener = profile_listener.clone();
+                                    let hotkeys = hotkey_hook.subscribe();
+                                    let config_changes = config_hook.subscribe();
+                                    let shutdown = shutdown_hook.subscribe();
+                                    let tablet_mode = tablet_mode.clone();
+                                    let id_product = id_product.to_string();
                                     tokio::spawn(async move {
-                                        start_tasks(zbus, conn_copy.clone(), sig_ctx, path).await
+                                        let queued = known
+                                            .device_added(
+                                                &id_product,
+                                                path.as_str(),
+                                                &ctrl.supported_data,
+                                            )
+                                            .await;
+                                        if queued {
+                                            // The mode is written by reload, brightness is not
+                                            ctrl.led_node
+                                                .set_brightness(ctrl.config.brightness.into())
+                                                .map_err(|e| warn!("Queued brightness: {e}"))
+                                                .ok();
+                                        }
+                                        let zbus = CtrlAuraZbus::new(ctrl, sig_ctx.clone());
+                                        zbus.listen_profile_changes(profile_listener);
+                                        zbus.listen_hotkeys(hotkeys);
+                                        zbus.listen_config_changes(config_changes);
+                                        zbus.listen_shutdown(shutdown);
+                                        zbus.listen_tablet_mode(tablet_mode);
+                                        start_tasks(zbus, conn_copy, sig_ctx, path).await
                                     });
                                 }
                             }
@@ -1,5 +1,24 @@
+/// Writing to each kind of Aura device
+pub mod backend;
+/// Queued changes, applied in order
+pub mod commands;
 pub mod config;
 pub mod controller;
+/// Fading the lighting out while there is no input
+pub mod idle;
+/// Devices seen before and their offline configuration
+pub mod known_devices;
 pub mod manager;
+/// The sysfs backend of keyboards in the multicolor LED class
+pub mod multicolor;
+/// Runtime loaded effect generators
+#[cfg(feature = "plugins")]
+pub mod plugin;
+/// Socket streaming of software effect frames
+pub mod stream;
 /// Implements `CtrlTask`, `Reloadable`, `ZbusRun`
 pub mod trait_impls;
+/// The sysfs backend of TUF keyboards
+pub mod tuf;
+/// Keys fading out after being pressed
+pub mod typing_trail;
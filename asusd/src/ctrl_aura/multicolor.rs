@@ -0,0 +1,199 @@
+//! Keyboards with neither a ROG HID path nor `kbd_rgb_mode`, only a
+//! `kbd_backlight` in the multicolor LED class. The hardware has no modes of
+//! its own, so only `Static` is always there. `Breathe` and `Flash` are run by
+//! the kernel `pattern` and `timer` triggers if it has them. The whole
+//! keyboard is one colour and there are no power states.
+
+use log::info;
+use rog_aura::keyboard::LaptopAuraPower;
+use rog_aura::{AuraEffect, AuraModeNum, Colour, Speed};
+use rog_platform::aura_hid::{AuraHidWrite, MockHid};
+use rog_platform::multicolor_led::{LedTrigger, MulticolorLed};
+
+use super::backend::{AuraBackend, AuraCapabilities};
+use crate::error::RogError;
+
+pub const MULTICOLOR_PROD_ID: &str = "multicolor";
+
+/// Where the colour and trigger are written
+pub trait MulticolorWrite: std::fmt::Debug + Send {
+    fn write_colour(&self, colour: Colour) -> rog_platform::error::Result<()>;
+
+    fn write_trigger(&self, trigger: LedTrigger) -> rog_platform::error::Result<()>;
+}
+
+impl MulticolorWrite for MulticolorLed {
+    fn write_colour(&self, colour: Colour) -> rog_platform::error::Result<()> {
+        self.set_colour(colour.r, colour.g, colour.b)
+    }
+
+    fn write_trigger(&self, trigger: LedTrigger) -> rog_platform::error::Result<()> {
+        self.set_trigger(trigger)
+    }
+}
+
+/// Records the colour as `[r, g, b]` and the trigger as its name
+impl MulticolorWrite for MockHid {
+    fn write_colour(&self, colour: Colour) -> rog_platform::error::Result<()> {
+        self.write_bytes(&[colour.r, colour.g, colour.b])
+    }
+
+    fn write_trigger(&self, trigger: LedTrigger) -> rog_platform::error::Result<()> {
+        info!("MockHid {}: trigger {trigger:?}", self.prod_id());
+        self.write_bytes(trigger.name().as_bytes())
+    }
+}
+
+/// The modes that can be run with the triggers the kernel has
+pub fn modes_for_triggers(triggers: &[String]) -> Vec<AuraModeNum> {
+    let mut modes = vec![AuraModeNum::Static];
+    for (name, mode) in [
+        ("pattern", AuraModeNum::Breathe),
+        ("timer", AuraModeNum::Flash),
+    ] {
+        if triggers.iter().any(|t| t == name) {
+            modes.push(mode);
+        }
+    }
+    modes
+}
+
+/// The trigger that runs `mode`, `None` for modes it can't run
+fn trigger_for(mode: AuraModeNum, speed: Speed) -> Option<LedTrigger> {
+    match mode {
+        AuraModeNum::Static => Some(LedTrigger::None),
+        AuraModeNum::Breathe => Some(LedTrigger::Breathe {
+            period_ms: match speed {
+                Speed::Low => 4000,
+                Speed::Med => 2000,
+                Speed::High => 1000,
+            },
+        }),
+        AuraModeNum::Flash => {
+            let ms = match speed {
+                Speed::Low => 500,
+                Speed::Med => 250,
+                Speed::High => 100,
+            };
+            Some(LedTrigger::Blink {
+                on_ms: ms,
+                off_ms: ms,
+            })
+        }
+        _ => None,
+    }
+}
+
+#[derive(Debug)]
+pub struct Multicolor {
+    led: Box<dyn MulticolorWrite>,
+    modes: Vec<AuraModeNum>,
+}
+
+impl Multicolor {
+    pub fn new(led: &MulticolorLed) -> Self {
+        let triggers = led.get_triggers().unwrap_or_default();
+        Self::with_writer(Box::new(led.clone()), modes_for_triggers(&triggers))
+    }
+
+    /// Write the colour and trigger somewhere else, such as to a mock
+    pub fn with_writer(led: Box<dyn MulticolorWrite>, modes: Vec<AuraModeNum>) -> Self {
+        Self { led, modes }
+    }
+}
+
+impl AuraBackend for Multicolor {
+    fn prod_id(&self) -> &str {
+        MULTICOLOR_PROD_ID
+    }
+
+    fn capabilities(&self) -> AuraCapabilities {
+        AuraCapabilities {
+            modes: Some(self.modes.clone()),
+            zones: false,
+            power_states: false,
+            frames: true,
+        }
+    }
+
+    fn write_mode(&self, mode: &AuraEffect) -> Result<(), RogError> {
+        let trigger = trigger_for(mode.mode, mode.speed)
+            .filter(|_| self.modes.contains(&mode.mode))
+            .ok_or(RogError::AuraEffectNotSupported)?;
+        self.led.write_colour(mode.colour1)?;
+        self.led.write_trigger(trigger)?;
+        Ok(())
+    }
+
+    fn write_power(&self, _power: &LaptopAuraPower) -> Result<(), RogError> {
+        Err(RogError::MissingFunction(
+            "Multicolor LEDs have no power states".to_owned(),
+        ))
+    }
+
+    fn write_frame(
+        &self,
+        rows: &mut dyn Iterator<Item = &[u8]>,
+        start: bool,
+    ) -> Result<(), RogError> {
+        // The whole keyboard is one colour, so only the last row is shown
+        let Some(row) = rows.last() else {
+            return Ok(());
+        };
+        if start {
+            self.led.write_trigger(LedTrigger::None)?;
+        }
+        self.led.write_colour(Colour {
+            r: row[9],
+            g: row[10],
+            b: row[11],
+        })?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rog_aura::keyboard::LaptopAuraPower;
+    use rog_aura::{AuraEffect, AuraModeNum, Colour, Speed};
+    use rog_platform::aura_hid::MockHid;
+
+    use super::{modes_for_triggers, Multicolor};
+    use crate::ctrl_aura::backend::AuraBackend;
+
+    #[test]
+    fn multicolor_writes() {
+        let triggers = ["none".to_owned(), "pattern".to_owned()];
+        let modes = modes_for_triggers(&triggers);
+        assert_eq!(modes, vec![AuraModeNum::Static, AuraModeNum::Breathe]);
+
+        let led = MockHid::new("multicolor");
+        let multicolor = Multicolor::with_writer(Box::new(led.clone()), modes);
+        assert!(!multicolor.capabilities().has_mode(AuraModeNum::Flash));
+
+        multicolor
+            .write_mode(&AuraEffect {
+                mode: AuraModeNum::Breathe,
+                colour1: Colour { r: 1, g: 2, b: 3 },
+                speed: Speed::Low,
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(led.take_writes(), vec![vec![1, 2, 3], b"pattern".to_vec()]);
+
+        assert!(multicolor
+            .write_mode(&AuraEffect {
+                mode: AuraModeNum::Flash,
+                ..Default::default()
+            })
+            .is_err());
+        assert!(multicolor.write_power(&LaptopAuraPower::default()).is_err());
+
+        let mut row = [0u8; 64];
+        row[9..12].copy_from_slice(&[4, 5, 6]);
+        multicolor
+            .write_frame(&mut [row.as_slice()].into_iter(), true)
+            .unwrap();
+        assert_eq!(led.take_writes(), vec![b"none".to_vec(), vec![4, 5, 6]]);
+    }
+}
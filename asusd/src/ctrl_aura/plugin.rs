@@ -0,0 +1,207 @@
+//! Effect generator plugins loaded at runtime. A plugin is a `cdylib` placed
+//! in `PLUGIN_DIR` which exports `asusd_aura_plugin`, a function returning a
+//! pointer to a static `PluginVTable`. The daemon calls `frame` on its own
+//! thread and feeds the records it produces into a `FrameStream`, so a
+//! plugin effect behaves exactly like a client streaming frames.
+//!
+//! Plugins run inside asusd as root. Only files owned by root and not
+//! writable by group or others, in a directory with the same permissions,
+//! are ever loaded.
+
+use std::ffi::{c_char, c_void, CStr};
+use std::io::Write;
+use std::os::unix::fs::MetadataExt;
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use effect_metrics::Stage;
+use libloading::{Library, Symbol};
+use log::{info, warn};
+
+use super::stream::{FRAME_INTERVAL, MAX_RECORDS};
+use crate::error::RogError;
+
+/// The trusted directory plugins are loaded from
+pub const PLUGIN_DIR: &str = "/usr/lib/asusd/plugins";
+/// Bumped on any change to `PluginVTable`
+pub const PLUGIN_ABI_VERSION: u32 = 1;
+/// Name of the function every plugin exports
+const PLUGIN_ENTRY: &[u8] = b"asusd_aura_plugin\0";
+
+/// The functions a plugin provides. All of them may be called from a thread
+/// other than the one the library was loaded on.
+#[repr(C)]
+pub struct PluginVTable {
+    /// Must equal `PLUGIN_ABI_VERSION`
+    pub abi_version: u32,
+    /// Nul terminated name of the effect, may be null
+    pub name: *const c_char,
+    /// Create the effect state. `per_key` is false for zoned keyboards, which
+    /// only use the zone `LedCode`s.
+    pub create: unsafe extern "C" fn(per_key: bool) -> *mut c_void,
+    /// Write up to `max_records` 4-byte `[LedCode, r, g, b]` records for the
+    /// frame at `elapsed_ms` into `records`, returning the count written
+    pub frame: unsafe extern "C" fn(
+        state: *mut c_void,
+        elapsed_ms: u64,
+        records: *mut u8,
+        max_records: u32,
+    ) -> u32,
+    /// Free the state returned by `create`
+    pub destroy: unsafe extern "C" fn(state: *mut c_void),
+}
+
+type PluginEntry = unsafe extern "C" fn() -> *const PluginVTable;
+
+/// A loaded plugin, ready to be run
+pub struct Plugin {
+    name: String,
+    vtable: *const PluginVTable,
+    // Must outlive `vtable`
+    _library: Library,
+}
+
+// SAFETY: the ABI requires that the vtable functions can be called from any
+// thread, and a `Plugin` is only ever used by one thread at a time.
+unsafe impl Send for Plugin {}
+
+/// The plugin files in `PLUGIN_DIR`, by the name used to run them
+pub fn available_plugins() -> Vec<String> {
+    let Ok(dir) = std::fs::read_dir(PLUGIN_DIR) else {
+        return Vec::new();
+    };
+    let mut names: Vec<String> = dir
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            if path.extension()? != "so" {
+                return None;
+            }
+            Some(path.file_stem()?.to_string_lossy().into_owned())
+        })
+        .collect();
+    names.sort();
+    names
+}
+
+/// The path of the plugin called `name`, or `None` if the name could point
+/// outside of `PLUGIN_DIR`
+pub fn plugin_path(name: &str) -> Option<PathBuf> {
+    if name.is_empty() || name.contains(['/', '\0']) || name.starts_with('.') {
+        return None;
+    }
+    Some(Path::new(PLUGIN_DIR).join(format!("{name}.so")))
+}
+
+fn is_trusted(path: &Path) -> bool {
+    let check =
+        |p: &Path| std::fs::metadata(p).is_ok_and(|m| m.uid() == 0 && m.mode() & 0o022 == 0);
+    check(path) && path.parent().is_some_and(check)
+}
+
+impl Plugin {
+    pub fn load(path: &Path) -> Result<Self, RogError> {
+        let err = |reason: String| RogError::Plugin(path.display().to_string(), reason);
+        if !is_trusted(path) {
+            return Err(err(
+                "must be owned by root and not writable by others".to_owned()
+            ));
+        }
+
+        // SAFETY: the library's init code runs here. The file is root owned in
+        // a root owned directory so is as trusted as asusd itself.
+        let library = unsafe { Library::new(path) }.map_err(|e| err(e.to_string()))?;
+        let vtable = unsafe {
+            let entry: Symbol<'_, PluginEntry> =
+                library.get(PLUGIN_ENTRY).map_err(|e| err(e.to_string()))?;
+            entry()
+        };
+        if vtable.is_null() {
+            return Err(err("returned no vtable".to_owned()));
+        }
+        // SAFETY: checked non-null, and the vtable is static in the library
+        let table = unsafe { &*vtable };
+        if table.abi_version != PLUGIN_ABI_VERSION {
+            return Err(err(format!(
+                "ABI version is {}, asusd supports {PLUGIN_ABI_VERSION}",
+                table.abi_version
+            )));
+        }
+
+        let name = if table.name.is_null() {
+            path.file_stem()
+                .map(|s| s.to_string_lossy().into_owned())
+                .unwrap_or_default()
+        } else {
+            // SAFETY: the ABI requires a nul terminated string
+            unsafe { CStr::from_ptr(table.name) }
+                .to_string_lossy()
+                .into_owned()
+        };
+        info!("Loaded effect plugin {name} from {}", path.display());
+
+        Ok(Self {
+            name,
+            vtable,
+            _library: library,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Run the effect on a new thread, writing each frame to `socket` in the
+    /// `FrameStream` format. The thread ends, and the plugin is unloaded, once
+    /// the other end of the socket is closed.
+    pub fn run(self, mut socket: UnixStream, per_key: bool) -> Result<(), RogError> {
+        std::thread::Builder::new()
+            .name("Aura plugin".into())
+            .spawn(move || {
+                // SAFETY: for all calls below the ABI is as documented on
+                // `PluginVTable`, and the buffer is sized to `MAX_RECORDS`
+                let table = unsafe { &*self.vtable };
+                let state = unsafe { (table.create)(per_key) };
+                let start = Instant::now();
+                let mut records = vec![0u8; MAX_RECORDS * 4];
+                loop {
+                    let count = effect_metrics::timed("aura_plugin", Stage::Render, || unsafe {
+                        (table.frame)(
+                            state,
+                            start.elapsed().as_millis() as u64,
+                            records.as_mut_ptr(),
+                            MAX_RECORDS as u32,
+                        )
+                    }) as usize;
+                    if count > MAX_RECORDS {
+                        warn!("Aura plugin {} wrote too many records, stopping", self.name);
+                        break;
+                    }
+
+                    let mut frame = Vec::with_capacity(2 + count * 4);
+                    frame.extend_from_slice(&(count as u16).to_le_bytes());
+                    frame.extend_from_slice(&records[..count * 4]);
+                    if let Err(e) = socket.write_all(&frame) {
+                        info!("Aura plugin {} ended: {e}", self.name);
+                        break;
+                    }
+                    std::thread::sleep(FRAME_INTERVAL);
+                }
+                unsafe { (table.destroy)(state) };
+            })?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::plugin_path;
+
+    #[test]
+    fn plugin_names_stay_in_dir() {
+        assert!(plugin_path("rain").unwrap().ends_with("plugins/rain.so"));
+        assert!(plugin_path("").is_none());
+        assert!(plugin_path("../../tmp/evil").is_none());
+        assert!(plugin_path(".hidden").is_none());
+    }
+}
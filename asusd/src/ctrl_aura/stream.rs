@@ -0,0 +1,177 @@
+//! A socket based stream of per-key or zoned frames. D-Bus method calls are
+//! far too chatty for software effects running at 30+ FPS, so a client can
+//! instead request a session and write raw frames straight to a socket.
+//!
+//! Each frame is a little-endian `u16` record count followed by that many
+//! 4-byte `[LedCode, r, g, b]` records, where `LedCode` is the `u8`
+//! discriminant of `rog_aura::keyboard::LedCode`.
+
+use std::io::{self, Read};
+use std::net::Shutdown;
+use std::os::unix::net::UnixStream;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use log::{info, warn};
+use rog_aura::keyboard::{AdvancedAuraType, LedCode, LedUsbPackets};
+use zbus::names::OwnedUniqueName;
+
+use super::commands::{AuraCommand, AuraQueue};
+use crate::error::RogError;
+
+/// Minimum time between two frames being written to the device. Anything
+/// sent faster than this is held back by the socket buffer.
+pub(super) const FRAME_INTERVAL: Duration = Duration::from_millis(16);
+/// Upper bound on records in a frame to guard against garbage lengths
+pub(super) const MAX_RECORDS: usize = 256;
+
+static SESSION_ID: AtomicU64 = AtomicU64::new(0);
+
+/// An active frame stream owned by one D-Bus client, or run by asusd itself
+#[derive(Debug)]
+pub struct FrameStream {
+    id: u64,
+    /// `None` for streams run by asusd, which any client may replace
+    owner: Option<OwnedUniqueName>,
+    socket: UnixStream,
+}
+
+impl FrameStream {
+    /// Start a new stream session for `owner`. The returned `UnixStream` is the
+    /// client end and should be passed back over D-Bus as a file descriptor.
+    ///
+    /// The session ends when the client closes its end (including when the
+    /// client process dies), or when `stop()` is called. On end the stored
+    /// builtin mode is restored. Frames are written through `queue`, and only
+    /// while the stream is the one started with `AuraCommand::StartFrameStream`.
+    pub fn start(
+        queue: AuraQueue,
+        owner: Option<OwnedUniqueName>,
+        advanced_type: &AdvancedAuraType,
+    ) -> Result<(Self, UnixStream), RogError> {
+        let mut packets = match advanced_type {
+            AdvancedAuraType::PerKey => LedUsbPackets::new_per_key(),
+            AdvancedAuraType::Zoned(_) => LedUsbPackets::new_zoned(true),
+            AdvancedAuraType::None => return Err(RogError::AuraEffectNotSupported),
+        };
+
+        let (mut daemon_end, client_end) = UnixStream::pair()?;
+        let id = SESSION_ID.fetch_add(1, Ordering::SeqCst);
+        let session = Self {
+            id,
+            owner,
+            socket: daemon_end.try_clone()?,
+        };
+
+        std::thread::Builder::new()
+            .name("Aura frame stream".into())
+            .spawn(move || {
+                info!("Aura frame stream {id} started");
+                let mut last_write: Option<Instant> = None;
+                let mut records = Vec::new();
+                loop {
+                    if let Err(e) = read_frame(&mut daemon_end, &mut records, &mut packets) {
+                        info!("Aura frame stream {id} ended: {e}");
+                        break;
+                    }
+                    if let Some(last) = last_write {
+                        let elapsed = last.elapsed();
+                        if elapsed < FRAME_INTERVAL {
+                            std::thread::sleep(FRAME_INTERVAL - elapsed);
+                        }
+                    }
+
+                    // Fails once replaced or stopped while queued
+                    let frame = AuraCommand::WriteFrame(id, packets.clone());
+                    if let Err(e) = queue.run_blocking(frame) {
+                        warn!("Aura frame stream {id}: {e}");
+                        break;
+                    }
+                    last_write = Some(Instant::now());
+                }
+
+                queue
+                    .run_blocking(AuraCommand::EndFrameStream(id))
+                    .map_err(|e| warn!("Aura frame stream {id}: {e}"))
+                    .ok();
+            })?;
+
+        Ok((session, client_end))
+    }
+
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn owner(&self) -> Option<&OwnedUniqueName> {
+        self.owner.as_ref()
+    }
+
+    /// Close the daemon end of the socket which also ends the reading thread
+    pub fn stop(&self) {
+        self.socket.shutdown(Shutdown::Both).ok();
+    }
+}
+
+/// Read one frame in to `packets`, with `records` kept between frames to read
+/// the records in to
+fn read_frame(
+    socket: &mut UnixStream,
+    records: &mut Vec<u8>,
+    packets: &mut LedUsbPackets,
+) -> io::Result<()> {
+    let mut len = [0u8; 2];
+    socket.read_exact(&mut len)?;
+    let count = u16::from_le_bytes(len) as usize;
+    if count > MAX_RECORDS {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("frame has {count} records, max is {MAX_RECORDS}"),
+        ));
+    }
+
+    records.resize(count * 4, 0);
+    socket.read_exact(records)?;
+    for record in records.chunks_exact(4) {
+        match LedCode::try_from(record[0]) {
+            Ok(code) => packets.set(code, record[1], record[2], record[3]),
+            Err(e) => warn!("Aura frame stream: {e}: {}", record[0]),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::os::unix::net::UnixStream;
+
+    use rog_aura::keyboard::{LedCode, LedUsbPackets};
+
+    use super::read_frame;
+
+    #[test]
+    fn frame_to_packets() {
+        let (mut client, mut daemon) = UnixStream::pair().unwrap();
+        let mut frame = vec![2, 0];
+        frame.extend_from_slice(&[LedCode::Esc as u8, 255, 0, 0]);
+        frame.extend_from_slice(&[LedCode::D as u8, 0, 0, 255]);
+        client.write_all(&frame).unwrap();
+
+        let mut packets = LedUsbPackets::new_per_key();
+        read_frame(&mut daemon, &mut Vec::new(), &mut packets).unwrap();
+
+        let mut expected = LedUsbPackets::new_per_key();
+        expected.set(LedCode::Esc, 255, 0, 0);
+        expected.set(LedCode::D, 0, 0, 255);
+        assert_eq!(packets.get(), expected.get());
+    }
+
+    #[test]
+    fn frame_too_long() {
+        let (mut client, mut daemon) = UnixStream::pair().unwrap();
+        client.write_all(&u16::MAX.to_le_bytes()).unwrap();
+        let mut packets = LedUsbPackets::new_per_key();
+        assert!(read_frame(&mut daemon, &mut Vec::new(), &mut packets).is_err());
+    }
+}
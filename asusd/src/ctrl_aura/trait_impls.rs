@@ -1,38 +1,486 @@
 use std::collections::BTreeMap;
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 
 use config_traits::StdConfig;
 use log::{debug, error, info, warn};
+use logind_zbus::manager::{InhibitType, ManagerProxy};
+use rog_aura::audio_indicator::{AudioIndicator, AudioOutput};
 use rog_aura::aura_detection::PowerZones;
-use rog_aura::keyboard::{LaptopAuraPower, UsbPackets};
+use rog_aura::effects::TrailConfig;
+use rog_aura::keyboard::{
+    AdvancedAuraType, KeyGeometry, KeyLayout, LaptopAuraPower, LedUsbPackets, PowerPhase,
+    UsbPackets,
+};
+use rog_aura::user_override::AuraOverride;
 use rog_aura::{AuraDeviceType, AuraEffect, AuraModeNum, AuraZone, LedBrightness};
-use zbus::export::futures_util::lock::{Mutex, MutexGuard};
+use tokio::sync::watch;
+use tokio::task::AbortHandle;
+use zbus::export::futures_util::lock::Mutex;
 use zbus::export::futures_util::StreamExt;
-use zbus::fdo::Error as ZbErr;
-use zbus::{interface, SignalContext};
+use zbus::fdo::{Error as ZbErr, NameOwnerChangedStream};
+use zbus::message::Header;
+use zbus::names::{OwnedUniqueName, UniqueName};
+use zbus::zvariant::OwnedFd;
+use zbus::{interface, CacheProperties, Connection, SignalContext};
 
-use super::controller::CtrlKbdLed;
+use super::commands::{AuraCommand, AuraQueue, TemporaryEffect};
+use super::controller::{CtrlKbdLed, LEDNode};
+use super::idle;
+use super::stream::FrameStream;
+use crate::ctrl_events::Hotkey;
 use crate::error::RogError;
-use crate::CtrlTask;
+use crate::led_automation::LedRule;
+use crate::polkit::{self, Action};
+use crate::power_events::{self, PowerEventSource};
+use crate::{
+    charge_schedule, config_watch, keys, led_automation, shutdown, user_session,
+    ConfigChangeListener, CtrlTask, DebouncedWrite, HotkeyListener, ProfileChangeListener,
+    ShutdownListener, TabletModeListener,
+};
 
 pub const AURA_ZBUS_NAME: &str = "Aura";
 pub const AURA_ZBUS_PATH: &str = "/org/asuslinux";
+/// How long brightness must stay unchanged before it is saved
+const CONFIG_SAVE_DELAY: Duration = Duration::from_secs(2);
+/// Where the layouts of rog-aura are installed, in a `layouts` directory
+const LAYOUT_DATA_DIR: &str = "/usr/share/rog-gui/";
+/// How often the LED automation rules are checked against the time and light
+const LED_RULE_INTERVAL: Duration = Duration::from_secs(15);
+
+static TEMPORARY_EFFECT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Take a logind "delay" inhibitor for sleep so that the LEDs can be readied
+/// before the system suspends. The lock is held until the fd is dropped.
+/// Without logind the sleep hook waits for asusd instead.
+async fn take_sleep_inhibitor() -> Option<OwnedFd> {
+    let manager = power_events::logind_manager().await?;
+    manager
+        .inhibit(
+            InhibitType::Sleep,
+            "asusd",
+            "Set keyboard LED sleep state",
+            "delay",
+        )
+        .await
+        .map_err(|e| warn!("take_sleep_inhibitor: {e}"))
+        .ok()
+}
+
+/// True if logind reports every session as idle, such as once the screen has
+/// blanked
+async fn sessions_idle(connection: &Connection) -> bool {
+    if power_events::source().await != PowerEventSource::Logind {
+        return false;
+    }
+    let manager = ManagerProxy::builder(connection)
+        .cache_properties(CacheProperties::No)
+        .build()
+        .await;
+    match manager {
+        Ok(manager) => manager.idle_hint().await.unwrap_or_default(),
+        Err(e) => {
+            warn!("sessions_idle: {e}");
+            false
+        }
+    }
+}
+
+/// The unique name of the caller of `header`, and the changes to its owner so
+/// that it can be told when the caller leaves the bus
+async fn watch_caller(
+    connection: &Connection,
+    header: &Header<'_>,
+) -> Result<(UniqueName<'static>, NameOwnerChangedStream<'static>), ZbErr> {
+    let sender = header
+        .sender()
+        .map(|s| s.to_owned())
+        .ok_or_else(|| ZbErr::Failed("Could not determine the caller".into()))?;
+    let dbus = zbus::fdo::DBusProxy::new(connection).await?;
+    let owner_changed = dbus
+        .receive_name_owner_changed_with_args(&[(0, sender.as_str())])
+        .await?;
+    Ok((sender, owner_changed))
+}
+
+/// The task ending the temporary effect shown, aborted when another effect
+/// replaces it
+type EffectExpiry = Arc<std::sync::Mutex<Option<AbortHandle>>>;
 
 #[derive(Clone)]
-pub struct CtrlAuraZbus(Arc<Mutex<CtrlKbdLed>>, SignalContext<'static>);
+pub struct CtrlAuraZbus(
+    Arc<Mutex<CtrlKbdLed>>,
+    SignalContext<'static>,
+    AuraQueue,
+    EffectExpiry,
+);
 
 impl CtrlAuraZbus {
     pub fn new(controller: CtrlKbdLed, signal: SignalContext<'static>) -> Self {
-        Self(Arc::new(Mutex::new(controller)), signal)
+        let inner = Arc::new(Mutex::new(controller));
+        let queue = AuraQueue::start(inner.clone());
+        Self(inner, signal, queue, EffectExpiry::default())
+    }
+
+    /// Switch to the Aura mode associated with each throttle policy as it
+    /// becomes active
+    pub fn listen_profile_changes(&self, mut listener: ProfileChangeListener) {
+        let ctrl = self.clone();
+        tokio::spawn(async move {
+            while listener.changed().await.is_ok() {
+                let mode = listener
+                    .borrow_and_update()
+                    .as_ref()
+                    .and_then(|c| c.association.aura_mode);
+                let Some(mode) = mode else {
+                    continue;
+                };
+                if let Err(e) = ctrl.2.run(AuraCommand::ProfileMode(mode)).await {
+                    warn!("Profile Aura mode {mode:?}: {e}");
+                    continue;
+                }
+
+                ctrl.led_mode_changed(&ctrl.1).await.ok();
+                ctrl.led_mode_data_invalidate(&ctrl.1).await.ok();
+            }
+        });
+    }
+
+    /// Act on the Aura hotkeys that are set to be handled by asusd
+    pub fn listen_hotkeys(&self, mut listener: HotkeyListener) {
+        let ctrl = self.clone();
+        tokio::spawn(async move {
+            loop {
+                let key = match listener.recv().await {
+                    Ok(key) => key,
+                    Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+                };
+                if !matches!(
+                    key,
+                    Hotkey::KbdBrightnessUp
+                        | Hotkey::KbdBrightnessDown
+                        | Hotkey::KbdBrightnessCycle
+                        | Hotkey::AuraNext
+                        | Hotkey::AuraPrevious
+                ) {
+                    continue;
+                }
+                if let Err(e) = ctrl.2.run(AuraCommand::Hotkey(key)).await {
+                    warn!("Aura hotkey {key:?}: {e}");
+                    continue;
+                }
+
+                if matches!(key, Hotkey::AuraNext | Hotkey::AuraPrevious) {
+                    ctrl.led_mode_changed(&ctrl.1).await.ok();
+                    ctrl.led_mode_data_invalidate(&ctrl.1).await.ok();
+                } else {
+                    ctrl.brightness_changed(&ctrl.1).await.ok();
+                }
+            }
+        });
+    }
+
+    /// Turn the laptop keyboard lighting off in tablet mode. External
+    /// keyboards are left alone.
+    pub fn listen_tablet_mode(&self, mut listener: TabletModeListener) {
+        let ctrl = self.clone();
+        tokio::spawn(async move {
+            let led_type = ctrl.0.lock().await.led_type;
+            if !(led_type.is_new_laptop()
+                || led_type.is_old_laptop()
+                || led_type.is_tuf_laptop()
+                || led_type.is_multicolor_laptop())
+            {
+                return;
+            }
+            loop {
+                let tablet_mode = *listener.borrow_and_update();
+                if let Err(e) = ctrl.2.run(AuraCommand::TabletMode(tablet_mode)).await {
+                    warn!("Aura tablet mode: {e}");
+                }
+                ctrl.brightness_changed(&ctrl.1).await.ok();
+                if listener.changed().await.is_err() {
+                    break;
+                }
+            }
+        });
+    }
+
+    /// Fade the lighting out once there has been no input for the idle
+    /// timeout, and bring it back on the next input. The input devices are
+    /// only opened once a timeout is set, and the wait starts over when the
+    /// config changes.
+    fn listen_idle(&self) {
+        let ctrl = self.clone();
+        tokio::spawn(async move {
+            let changed = ctrl.0.lock().await.idle_changed.clone();
+            let mut input = None;
+            loop {
+                let idle = ctrl.0.lock().await.config.idle_off.clone();
+                let Some(timeout) = idle.timeout() else {
+                    changed.notified().await;
+                    continue;
+                };
+                let activity = input.get_or_insert_with(idle::watch_input);
+                tokio::select! {
+                    _ = activity.notified() => continue,
+                    _ = changed.notified() => continue,
+                    _ = tokio::time::sleep(timeout) => {}
+                }
+                debug!("No input for {timeout:?}, fading the keyboard lighting out");
+                let mut woke = false;
+                for _ in 0..idle::FADE_STEPS {
+                    if let Err(e) = ctrl.2.run(AuraCommand::IdleStep).await {
+                        warn!("Aura idle off: {e}");
+                    }
+                    ctrl.brightness_changed(&ctrl.1).await.ok();
+                    let step = tokio::time::timeout(idle.fade_step(), activity.notified());
+                    if step.await.is_ok() {
+                        woke = true;
+                        break;
+                    }
+                }
+                if !woke {
+                    activity.notified().await;
+                }
+                if let Err(e) = ctrl.2.run(AuraCommand::EndIdle).await {
+                    warn!("Aura idle off: {e}");
+                }
+                ctrl.brightness_changed(&ctrl.1).await.ok();
+            }
+        });
+    }
+
+    /// End the lighting of a user once their session is no longer the active
+    /// one, such as when switching to the greeter or another user
+    fn listen_active_user(&self) {
+        let ctrl = self.clone();
+        tokio::spawn(async move {
+            let connection = match Connection::system().await {
+                Ok(connection) => connection,
+                Err(e) => {
+                    warn!("Aura active user: {e}");
+                    return;
+                }
+            };
+            let Some(mut active) = user_session::watch_active_uid(&connection).await else {
+                return;
+            };
+            while active.changed().await.is_ok() {
+                let uid = *active.borrow_and_update();
+                if let Err(e) = ctrl.2.run(AuraCommand::ActiveUser(uid)).await {
+                    warn!("Aura active user: {e}");
+                }
+                ctrl.brightness_changed(&ctrl.1).await.ok();
+            }
+        });
+    }
+
+    /// Leave the keyboard showing its stored mode for each shutdown request,
+    /// with software and temporary effects stopped. `PowerStates` then writes
+    /// the power states, `AllOff` the boot state only at zero brightness.
+    pub fn listen_shutdown(&self, mut listener: ShutdownListener) {
+        let ctrl = self.clone();
+        tokio::spawn(async move {
+            // `done` is dropped at the end of each loop, once the LEDs are set
+            while let Some(shutdown) = shutdown::requested(&mut listener).await {
+                if let Err(e) = ctrl.2.run(AuraCommand::Shutdown(shutdown.policy)).await {
+                    warn!("Aura shutdown policy {:?}: {e}", shutdown.policy);
+                }
+            }
+        });
+    }
+
+    /// Apply this device's config each time it is edited outside of asusd
+    pub fn listen_config_changes(&self, mut listener: ConfigChangeListener) {
+        let ctrl = self.clone();
+        tokio::spawn(async move {
+            let file_name = ctrl.0.lock().await.config.file_name();
+            while config_watch::changed(&mut listener, &file_name).await {
+                let Some(edited) = ctrl.0.lock().await.config.read_new() else {
+                    continue;
+                };
+                info!("{file_name} was edited, applying it");
+                let edited = AuraCommand::ConfigEdited(Box::new(edited));
+                if let Err(e) = ctrl.2.run(edited).await {
+                    warn!("Could not apply the edited {file_name}: {e}");
+                }
+
+                ctrl.led_mode_changed(&ctrl.1).await.ok();
+                ctrl.led_mode_data_invalidate(&ctrl.1).await.ok();
+                ctrl.brightness_changed(&ctrl.1).await.ok();
+                ctrl.zone_brightness_changed(&ctrl.1).await.ok();
+                ctrl.led_power_changed(&ctrl.1).await.ok();
+                Self::notify_config_reloaded(&ctrl.1).await.ok();
+            }
+        });
+    }
+
+    /// Show the automation rule that matches the time and light now, if it
+    /// isn't shown already
+    async fn check_led_rules(&self) -> Result<(), ZbErr> {
+        let (rules, shown) = {
+            let ctrl = self.0.lock().await;
+            (ctrl.config.led_rules.clone(), ctrl.led_rule.clone())
+        };
+        // Only read the sensor for rules that need it
+        let lux = rules
+            .iter()
+            .any(|r| r.below_lux > 0)
+            .then(led_automation::ambient_lux)
+            .flatten();
+        let rule = led_automation::active_rule(
+            &rules,
+            charge_schedule::local_minute(),
+            lux,
+            shown.as_ref(),
+        )
+        .cloned();
+        if rule == shown {
+            return Ok(());
+        }
+        match &rule {
+            Some(rule) => info!("LED rule active: {rule:?}"),
+            None => info!("No LED rule is active, restoring the stored state"),
+        }
+        self.2.run(AuraCommand::ShowLedRule(rule)).await?;
+
+        self.brightness_changed(&self.1).await.ok();
+        self.active_led_rule_changed(&self.1).await.ok();
+        Ok(())
+    }
+
+    /// End the temporary effect or preview `id` after `ttl`, or once `sender`
+    /// leaves the bus. The task for the effect it replaced is stopped.
+    fn end_temporary_effect_after(
+        &self,
+        id: u64,
+        ttl: Duration,
+        sender: UniqueName<'static>,
+        mut owner_changed: NameOwnerChangedStream<'static>,
+    ) {
+        let zbus = self.clone();
+        let task = tokio::spawn(async move {
+            let disconnected = async {
+                while let Some(signal) = owner_changed.next().await {
+                    if signal
+                        .args()
+                        .map(|a| a.new_owner.is_none())
+                        .unwrap_or(false)
+                    {
+                        break;
+                    }
+                }
+            };
+            tokio::select! {
+                _ = tokio::time::sleep(ttl) => {}
+                _ = disconnected => debug!("Temporary effect owner {sender} disconnected"),
+            }
+            zbus.end_temporary_effect(AuraCommand::EndTemporaryEffect(id))
+                .await
+                .map_err(|e| warn!("Temporary effect restore: {e}"))
+                .ok();
+        });
+        if let Ok(mut expiry) = self.3.lock() {
+            if let Some(replaced) = expiry.replace(task.abort_handle()) {
+                replaced.abort();
+            }
+        }
+    }
+
+    /// Show `effect` until `ttl` passes or the caller leaves the bus, as the
+    /// caller's preview if `preview`
+    async fn show_temporary_effect(
+        &self,
+        effect: AuraEffect,
+        ttl: Duration,
+        preview: bool,
+        header: &Header<'_>,
+        connection: &Connection,
+    ) -> Result<(), ZbErr> {
+        polkit::check_streaming(connection, header, Action::ChangeLighting).await?;
+        let (sender, owner_changed) = watch_caller(connection, header).await?;
+
+        let id = TEMPORARY_EFFECT_ID.fetch_add(1, Ordering::SeqCst);
+        self.2
+            .run(AuraCommand::TemporaryEffect(TemporaryEffect {
+                id,
+                effect,
+                idle: sessions_idle(connection).await,
+                preview_owner: preview.then(|| sender.to_string()),
+            }))
+            .await?;
+        if self.0.lock().await.woken_from.is_some() {
+            self.brightness_changed(&self.1).await.ok();
+        }
+        self.end_temporary_effect_after(id, ttl, sender, owner_changed);
+        Ok(())
+    }
+
+    /// Run `EndTemporaryEffect` or `CancelPreview`, telling clients if the
+    /// brightness raised for the effect was restored
+    async fn end_temporary_effect(&self, command: AuraCommand) -> Result<(), ZbErr> {
+        let woken = self.0.lock().await.woken_from.is_some();
+        let res = self.2.run(command).await;
+        if woken && self.0.lock().await.woken_from.is_none() {
+            self.brightness_changed(&self.1).await.ok();
+        }
+        res
+    }
+
+    /// Start a frame stream owned by the sender of `header`, replacing any
+    /// stream they already own
+    async fn start_frame_stream(&self, header: &Header<'_>) -> Result<UnixStream, ZbErr> {
+        let owner = header
+            .sender()
+            .map(|s| OwnedUniqueName::from(s.to_owned()))
+            .ok_or_else(|| ZbErr::Failed("Could not determine the caller".into()))?;
+        self.start_stream(Some(owner)).await
+    }
+
+    /// Start a frame stream owned by `owner`, or by asusd if `None`
+    async fn start_stream(&self, owner: Option<OwnedUniqueName>) -> Result<UnixStream, ZbErr> {
+        let advanced_type = self.0.lock().await.supported_data.advanced_type.clone();
+        let (stream, client) = FrameStream::start(self.2.clone(), owner, &advanced_type)?;
+        self.2.run(AuraCommand::StartFrameStream(stream)).await?;
+        Ok(client)
     }
 
-    fn update_config(lock: &mut CtrlKbdLed) -> Result<(), RogError> {
-        let bright = lock.led_node.get_brightness()?;
-        lock.config.read();
-        lock.config.brightness = bright.into();
-        lock.config.write();
+    #[cfg(feature = "plugins")]
+    fn plugin_names() -> Vec<String> {
+        super::plugin::available_plugins()
+    }
+
+    #[cfg(not(feature = "plugins"))]
+    fn plugin_names() -> Vec<String> {
+        Vec::new()
+    }
+
+    #[cfg(feature = "plugins")]
+    async fn start_plugin(&self, name: &str, header: &Header<'_>) -> Result<(), ZbErr> {
+        let path = super::plugin::plugin_path(name)
+            .ok_or_else(|| ZbErr::InvalidArgs(format!("Invalid plugin name {name}")))?;
+        let plugin = super::plugin::Plugin::load(&path)?;
+        let per_key = {
+            let ctrl = self.0.lock().await;
+            ctrl.supported_data.advanced_type == AdvancedAuraType::PerKey
+        };
+        let client = self.start_frame_stream(header).await?;
+        info!("Running effect plugin {}", plugin.name());
+        plugin.run(client, per_key)?;
         Ok(())
     }
+
+    #[cfg(not(feature = "plugins"))]
+    async fn start_plugin(&self, _name: &str, _header: &Header<'_>) -> Result<(), ZbErr> {
+        Err(ZbErr::NotSupported(
+            "asusd was built without effect plugin support".to_owned(),
+        ))
+    }
 }
 
 /// The main interface for changing, reading, or notfying
@@ -56,9 +504,39 @@ impl CtrlAuraZbus {
 
     /// Set the keyboard brightness level (0-3)
     #[zbus(property)]
-    async fn set_brightness(&mut self, brightness: LedBrightness) -> Result<(), ZbErr> {
+    async fn set_brightness(
+        &mut self,
+        brightness: LedBrightness,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> Result<(), ZbErr> {
+        polkit::check(connection, &header, Action::ChangeLighting).await?;
+        self.2.run(AuraCommand::SetBrightness(brightness)).await
+    }
+
+    /// Brightness of each zone that has been set separately
+    #[zbus(property)]
+    async fn zone_brightness(&self) -> BTreeMap<AuraZone, LedBrightness> {
         let ctrl = self.0.lock().await;
-        Ok(ctrl.led_node.set_brightness(brightness.into())?)
+        ctrl.config.zone_brightness.clone()
+    }
+
+    /// Set the brightness of a single zone such as the logo or lightbar. On
+    /// devices without separately addressable zones this sets the global
+    /// brightness instead.
+    async fn set_zone_brightness(
+        &self,
+        zone: AuraZone,
+        level: LedBrightness,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> Result<(), ZbErr> {
+        polkit::check(connection, &header, Action::ChangeLighting).await?;
+        self.2
+            .run(AuraCommand::SetZoneBrightness(zone, level))
+            .await?;
+        self.zone_brightness_changed(&self.1).await.ok();
+        Ok(())
     }
 
     /// Total levels of brightness available
@@ -91,6 +569,13 @@ impl CtrlAuraZbus {
         Ok(ctrl.supported_data.power_zones.clone())
     }
 
+    /// A description of what each supported power zone controls on this model
+    #[zbus(property)]
+    async fn power_zone_descriptions(&self) -> BTreeMap<PowerZones, String> {
+        let ctrl = self.0.lock().await;
+        ctrl.supported_data.describe_power_zones()
+    }
+
     /// The current mode data
     #[zbus(property)]
     async fn led_mode(&self) -> Result<AuraModeNum, ZbErr> {
@@ -103,17 +588,14 @@ impl CtrlAuraZbus {
     /// On success the aura config file is read to refresh cached values, then
     /// the effect is stored and config written to disk.
     #[zbus(property)]
-    async fn set_led_mode(&mut self, num: AuraModeNum) -> Result<(), ZbErr> {
-        let mut ctrl = self.0.lock().await;
-        ctrl.config.current_mode = num;
-        ctrl.write_current_config_mode()?;
-        if ctrl.config.brightness == LedBrightness::Off {
-            ctrl.config.brightness = LedBrightness::Med;
-        }
-        ctrl.led_node
-            .set_brightness(ctrl.config.brightness.into())?;
-        ctrl.config.write();
-
+    async fn set_led_mode(
+        &mut self,
+        num: AuraModeNum,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> Result<(), ZbErr> {
+        polkit::check(connection, &header, Action::ChangeLighting).await?;
+        self.2.run(AuraCommand::SetLedMode(num)).await?;
         self.led_mode_data_invalidate(&self.1).await.ok();
         Ok(())
     }
@@ -134,27 +616,129 @@ impl CtrlAuraZbus {
     /// On success the aura config file is read to refresh cached values, then
     /// the effect is stored and config written to disk.
     #[zbus(property)]
-    async fn set_led_mode_data(&mut self, effect: AuraEffect) -> Result<(), ZbErr> {
-        let mut ctrl = self.0.lock().await;
-        if !ctrl.supported_data.basic_modes.contains(&effect.mode)
-            || effect.zone != AuraZone::None
-                && !ctrl.supported_data.basic_zones.contains(&effect.zone)
-        {
-            return Err(ZbErr::NotSupported(format!(
-                "The Aura effect is not supported: {effect:?}"
-            )));
-        }
+    async fn set_led_mode_data(
+        &mut self,
+        effect: AuraEffect,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> Result<(), ZbErr> {
+        polkit::check(connection, &header, Action::ChangeLighting).await?;
+        self.2.run(AuraCommand::SetLedModeData(effect)).await?;
+        self.led_mode_invalidate(&self.1).await.ok();
+        Ok(())
+    }
 
-        ctrl.write_mode(&effect)?;
-        if ctrl.config.brightness == LedBrightness::Off {
-            ctrl.config.brightness = LedBrightness::Med;
-        }
-        ctrl.led_node
-            .set_brightness(ctrl.config.brightness.into())?;
-        ctrl.config.set_builtin(effect);
-        ctrl.config.write();
+    /// The effect of each zone in the current mode, empty if the mode has no
+    /// multizone set
+    async fn get_zones(&self) -> Vec<AuraEffect> {
+        let ctrl = self.0.lock().await;
+        ctrl.config
+            .get_multizone(ctrl.config.current_mode)
+            .map(|effects| effects.to_vec())
+            .unwrap_or_default()
+    }
 
-        self.led_mode_invalidate(&self.1).await.ok();
+    /// Set the effect of one zone without resending the others. The zone of
+    /// `effect` is replaced by `zone`, and its mode becomes the current one
+    /// with the multizone set shown. Sends `NotifyZone` on success.
+    async fn set_zone(
+        &self,
+        zone: AuraZone,
+        mut effect: AuraEffect,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> Result<(), ZbErr> {
+        polkit::check(connection, &header, Action::ChangeLighting).await?;
+        self.2
+            .run(AuraCommand::SetZone(zone, effect.clone()))
+            .await?;
+        effect.zone = zone;
+        Self::notify_zone(&self.1, effect).await.ok();
+        self.led_mode_changed(&self.1).await.ok();
+        Ok(())
+    }
+
+    /// Apply an effect without storing it. The stored mode is restored after
+    /// `ttl_secs`, or as soon as the calling client disconnects, so scripts
+    /// and notifiers can't leave the keyboard in a temporary state.
+    ///
+    /// If `wake_brightness` is set in the config and the sessions are idle or
+    /// an automation rule has dimmed the keyboard, the brightness is raised to
+    /// it for as long as the effect is shown.
+    async fn set_temporary_effect(
+        &self,
+        effect: AuraEffect,
+        ttl_secs: u32,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> Result<(), ZbErr> {
+        let ttl = Duration::from_secs(ttl_secs.into());
+        self.show_temporary_effect(effect, ttl, false, &header, connection)
+            .await
+    }
+
+    /// Show an effect without storing it, for colour pickers and such. As
+    /// `SetTemporaryEffect`, but timed in milliseconds and also ended by
+    /// `CancelPreview`, so a cancelled picker never leaves the keyboard half
+    /// set. Each preview replaces the last and starts its own timeout.
+    /// `SetLedModeData` keeps the effect.
+    async fn preview_effect(
+        &self,
+        effect: AuraEffect,
+        timeout_ms: u32,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> Result<(), ZbErr> {
+        let ttl = Duration::from_millis(timeout_ms.into());
+        self.show_temporary_effect(effect, ttl, true, &header, connection)
+            .await
+    }
+
+    /// End the effect shown by `PreviewEffect` and restore the stored mode.
+    /// Nothing is done if no preview is shown, and only the client that
+    /// started the preview can end it.
+    async fn cancel_preview(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> Result<(), ZbErr> {
+        polkit::check(connection, &header, Action::ChangeLighting).await?;
+        let sender = header
+            .sender()
+            .map(|s| s.to_string())
+            .ok_or_else(|| ZbErr::Failed("Could not determine the caller".into()))?;
+        self.end_temporary_effect(AuraCommand::CancelPreview(sender))
+            .await
+    }
+
+    /// Show `lighting` in place of the stored mode and brightness while the
+    /// caller's session is the active one on the seat. For the session daemon
+    /// asusd-user, only the user with the active session may call it. The
+    /// stored lighting is left as it is and comes back when another user or
+    /// the greeter becomes active.
+    async fn set_user_override(
+        &self,
+        lighting: AuraOverride,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> Result<(), ZbErr> {
+        let uid = user_session::check_active_user(connection, &header).await?;
+        self.2
+            .run(AuraCommand::SetUserOverride(uid, lighting))
+            .await?;
+        self.brightness_changed(&self.1).await.ok();
+        Ok(())
+    }
+
+    /// Go back to the stored lighting, if the lighting shown is the caller's
+    async fn clear_user_override(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> Result<(), ZbErr> {
+        let uid = user_session::sender_uid(connection, &header).await?;
+        self.2.run(AuraCommand::ClearUserOverride(uid)).await?;
+        self.brightness_changed(&self.1).await.ok();
         Ok(())
     }
 
@@ -176,31 +760,430 @@ impl CtrlAuraZbus {
     ///
     /// For Modern ROG devices the "enabled" flag is ignored.
     #[zbus(property)]
-    async fn set_led_power(&mut self, options: LaptopAuraPower) -> Result<(), ZbErr> {
-        let mut ctrl = self.0.lock().await;
-        for opt in options.states {
-            let zone = opt.zone;
-            for config in ctrl.config.enabled.states.iter_mut() {
-                if config.zone == zone {
-                    *config = opt;
-                }
-            }
+    async fn set_led_power(
+        &mut self,
+        options: LaptopAuraPower,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> Result<(), ZbErr> {
+        polkit::check(connection, &header, Action::ChangeLighting).await?;
+        self.2.run(AuraCommand::SetLedPower(options)).await
+    }
+
+    /// Set if `zone` is lit during `phase`, leaving the other zones and
+    /// phases as they are
+    async fn set_led_power_state(
+        &self,
+        zone: PowerZones,
+        phase: PowerPhase,
+        enabled: bool,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> Result<(), ZbErr> {
+        polkit::check(connection, &header, Action::ChangeLighting).await?;
+        self.2
+            .run(AuraCommand::SetLedPowerState(zone, phase, enabled))
+            .await?;
+        self.led_power_changed(&self.1).await.ok();
+        Ok(())
+    }
+
+    /// The zone lit to show the active audio output, and its colours
+    #[zbus(property)]
+    async fn audio_indicator(&self) -> AudioIndicator {
+        let ctrl = self.0.lock().await;
+        ctrl.config.audio_indicator.clone()
+    }
+
+    #[zbus(property)]
+    async fn set_audio_indicator(
+        &mut self,
+        indicator: AudioIndicator,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> Result<(), ZbErr> {
+        polkit::check(connection, &header, Action::ChangeLighting).await?;
+        self.2.run(AuraCommand::SetAudioIndicator(indicator)).await
+    }
+
+    /// Rules changing the brightness and turning off power zones by time of
+    /// day and ambient light. The first that matches is shown in place of the
+    /// stored state, and brightness changes made while one is shown last until
+    /// it ends.
+    #[zbus(property)]
+    async fn led_rules(&self) -> Vec<LedRule> {
+        let ctrl = self.0.lock().await;
+        ctrl.config.led_rules.clone()
+    }
+
+    #[zbus(property)]
+    async fn set_led_rules(
+        &mut self,
+        rules: Vec<LedRule>,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> Result<(), ZbErr> {
+        polkit::check(connection, &header, Action::ChangeLighting).await?;
+        self.2.run(AuraCommand::SetLedRules(rules)).await?;
+        self.check_led_rules().await?;
+        // The position of the shown rule may have moved
+        self.active_led_rule_changed(&self.1).await.ok();
+        Ok(())
+    }
+
+    /// Position of the rule shown in `LedRules`, counting from 1, or 0 if no
+    /// rule is
+    #[zbus(property)]
+    async fn active_led_rule(&self) -> u32 {
+        let ctrl = self.0.lock().await;
+        ctrl.config
+            .led_rules
+            .iter()
+            .position(|r| Some(r) == ctrl.led_rule.as_ref())
+            .map_or(0, |i| i as u32 + 1)
+    }
+
+    /// The names of the saved presets
+    #[zbus(property)]
+    async fn presets(&self) -> Vec<String> {
+        let ctrl = self.0.lock().await;
+        ctrl.config.presets.keys().cloned().collect()
+    }
+
+    /// Save the brightness, mode, zone effects and power states as they are
+    /// now under `name`, replacing any preset already called that
+    async fn save_current_as_preset(
+        &self,
+        name: String,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> Result<(), ZbErr> {
+        polkit::check(connection, &header, Action::ChangeLighting).await?;
+        self.2.run(AuraCommand::SavePreset(name)).await?;
+        self.presets_changed(&self.1).await.ok();
+        Ok(())
+    }
+
+    /// Apply and store the lighting of a saved preset
+    async fn apply_preset(
+        &self,
+        name: String,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> Result<(), ZbErr> {
+        polkit::check(connection, &header, Action::ChangeLighting).await?;
+        self.2.run(AuraCommand::ApplyPreset(name)).await?;
+        self.brightness_changed(&self.1).await.ok();
+        self.led_mode_changed(&self.1).await.ok();
+        self.led_mode_data_invalidate(&self.1).await.ok();
+        self.led_power_changed(&self.1).await.ok();
+        self.zone_brightness_changed(&self.1).await.ok();
+        Ok(())
+    }
+
+    async fn remove_preset(
+        &self,
+        name: String,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> Result<(), ZbErr> {
+        polkit::check(connection, &header, Action::ChangeLighting).await?;
+        self.2.run(AuraCommand::RemovePreset(name)).await?;
+        self.presets_changed(&self.1).await.ok();
+        Ok(())
+    }
+
+    /// The names of the saved raw modes
+    #[zbus(property)]
+    async fn raw_modes(&self) -> Vec<String> {
+        let ctrl = self.0.lock().await;
+        ctrl.config.raw_modes.keys().cloned().collect()
+    }
+
+    /// Save raw factory mode packets, such as a captured vendor effect, under
+    /// `name`. They are written as with `DirectAddressingRaw` by
+    /// `ApplyRawMode`.
+    async fn save_raw_mode(
+        &self,
+        name: String,
+        packets: UsbPackets,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> Result<(), ZbErr> {
+        polkit::check(connection, &header, Action::ChangeLighting).await?;
+        self.2.run(AuraCommand::SaveRawMode(name, packets)).await?;
+        self.raw_modes_changed(&self.1).await.ok();
+        Ok(())
+    }
+
+    /// Write a saved raw mode. It is shown in place of the current mode, and
+    /// written again when asusd starts or the laptop wakes, until a mode is
+    /// set.
+    async fn apply_raw_mode(
+        &self,
+        name: String,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> Result<(), ZbErr> {
+        polkit::check(connection, &header, Action::ChangeLighting).await?;
+        self.2.run(AuraCommand::ApplyRawMode(name)).await
+    }
+
+    async fn remove_raw_mode(
+        &self,
+        name: String,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> Result<(), ZbErr> {
+        polkit::check(connection, &header, Action::ChangeLighting).await?;
+        self.2.run(AuraCommand::RemoveRawMode(name)).await?;
+        self.raw_modes_changed(&self.1).await.ok();
+        Ok(())
+    }
+
+    /// The audio output last reported with `SetAudioOutput`
+    #[zbus(property)]
+    async fn audio_output(&self) -> AudioOutput {
+        let ctrl = self.0.lock().await;
+        ctrl.audio_output
+    }
+
+    /// Called by the session helper in asusd-user when the default audio sink
+    /// changes, as asusd can't see the user's sound server. Only taken from
+    /// the user with the active session.
+    async fn set_audio_output(
+        &self,
+        output: AudioOutput,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> Result<(), ZbErr> {
+        user_session::check_active_user(connection, &header).await?;
+        debug!("Audio output is now {output:?}");
+        self.2.run(AuraCommand::SetAudioOutput(output)).await?;
+        self.audio_output_changed(&self.1).await.ok();
+        Ok(())
+    }
+
+    /// Rough estimate of the LED power draw in milliwatts. Changes are not
+    /// signalled, read it again on a change of `Brightness`, `LedModeData`,
+    /// `LedPower` or `ZoneBrightness`. The same goes for
+    /// `EstimatedZonePowerMw` and `PowerSavingSuggestions`.
+    #[zbus(property(emits_changed_signal = "false"))]
+    async fn estimated_power_mw(&self) -> u32 {
+        let ctrl = self.0.lock().await;
+        ctrl.power_estimate().total_mw()
+    }
+
+    /// The estimated draw of each zone lit while awake, in milliwatts
+    #[zbus(property(emits_changed_signal = "false"))]
+    async fn estimated_zone_power_mw(&self) -> BTreeMap<PowerZones, u32> {
+        let ctrl = self.0.lock().await;
+        ctrl.power_estimate().zones
+    }
+
+    /// Changes which would save the most power, e.g. disabling the lightbar
+    #[zbus(property(emits_changed_signal = "false"))]
+    async fn power_saving_suggestions(&self) -> Vec<String> {
+        let ctrl = self.0.lock().await;
+        ctrl.power_estimate().suggestions(ctrl.config.brightness)
+    }
+
+    /// Re-read the LED support database and the drop-ins in
+    /// `/etc/asusd/aura.d/`, then bring the config in line with the new data
+    /// and re-apply the current mode. For iterating on support files without
+    /// restarting asusd.
+    async fn reload_support_data(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> Result<(), ZbErr> {
+        polkit::check(connection, &header, Action::ChangeLighting).await?;
+        let before = self.0.lock().await.supported_data.clone();
+        self.2.run(AuraCommand::ReloadSupportData).await?;
+        if self.0.lock().await.supported_data == before {
+            return Ok(());
         }
-        ctrl.config.write();
-        Ok(ctrl.set_power_states().map_err(|e| {
-            warn!("{}", e);
-            e
-        })?)
+
+        self.supported_basic_modes_changed(&self.1).await.ok();
+        self.supported_basic_zones_changed(&self.1).await.ok();
+        self.supported_power_zones_changed(&self.1).await.ok();
+        self.power_zone_descriptions_changed(&self.1).await.ok();
+        self.led_mode_changed(&self.1).await.ok();
+        self.led_mode_data_invalidate(&self.1).await.ok();
+        self.led_power_changed(&self.1).await.ok();
+        Ok(())
     }
 
     /// On machine that have some form of either per-key keyboard or per-zone
     /// this can be used to write custom effects over dbus. The input is a
     /// nested `Vec<Vec<8>>` where `Vec<u8>` is a raw USB packet
-    async fn direct_addressing_raw(&self, data: UsbPackets) -> Result<(), ZbErr> {
-        let mut ctrl = self.0.lock().await;
-        ctrl.write_effect_block(&data)?;
-        Ok(())
+    async fn direct_addressing_raw(
+        &self,
+        data: UsbPackets,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> Result<(), ZbErr> {
+        polkit::check_streaming(connection, &header, Action::ChangeLighting).await?;
+        self.2.run(AuraCommand::WriteRaw(data)).await
+    }
+
+    /// Write a frame of per-key or zoned colours, such as one filled by
+    /// `AdvancedEffects::write_packets`. Only the rows that changed since the
+    /// last frame are written.
+    async fn direct_addressing_frame(
+        &self,
+        frame: LedUsbPackets,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> Result<(), ZbErr> {
+        polkit::check_streaming(connection, &header, Action::ChangeLighting).await?;
+        self.2.run(AuraCommand::WriteClientFrame(frame)).await
     }
+
+    /// Start a software effect stream and return a socket to write frames to.
+    ///
+    /// Each frame is a little-endian `u16` count followed by that many
+    /// `[LedCode, r, g, b]` records. Frames are written to the device at most
+    /// every 16ms. The session belongs to the caller and ends when the socket
+    /// is closed, or when a builtin mode is set. Only the owner may replace a
+    /// running session.
+    async fn stream_frames(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> Result<OwnedFd, ZbErr> {
+        polkit::check(connection, &header, Action::ChangeLighting).await?;
+        let client = self.start_frame_stream(&header).await?;
+        Ok(OwnedFd::from(std::os::fd::OwnedFd::from(client)))
+    }
+
+    /// The position and size of each LED of the keyboard, from the layout
+    /// named by `layout_name` in the LED support data. For clients drawing
+    /// per-key editors, each `led` is the `LedCode` used by `StreamFrames`.
+    async fn get_keyboard_layout(&self) -> Result<Vec<KeyGeometry>, ZbErr> {
+        let data = self.0.lock().await.supported_data.clone();
+        if data.layout_name.is_empty() {
+            return Err(ZbErr::NotSupported(
+                "No keyboard layout is known for this laptop".to_owned(),
+            ));
+        }
+        let layout = KeyLayout::find_layout(data, PathBuf::from(LAYOUT_DATA_DIR))
+            .map_err(|e| ZbErr::Failed(e.to_string()))?;
+        Ok(layout.geometry())
+    }
+
+    /// The effect plugins that can be run with `RunEffectPlugin`. Empty if
+    /// asusd was built without plugin support.
+    #[zbus(property)]
+    async fn effect_plugins(&self) -> Vec<String> {
+        Self::plugin_names()
+    }
+
+    /// Run an effect plugin by name. The plugin streams frames as if it were
+    /// a client of `StreamFrames` owned by the caller, and stops the same way.
+    async fn run_effect_plugin(
+        &self,
+        name: String,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> Result<(), ZbErr> {
+        polkit::check(connection, &header, Action::ChangeLighting).await?;
+        self.start_plugin(&name, &header).await
+    }
+
+    /// The gradient pressed keys fade through with `RunTypingTrail`, and how
+    /// long they take to
+    #[zbus(property)]
+    async fn typing_trail(&self) -> TrailConfig {
+        let ctrl = self.0.lock().await;
+        ctrl.config.typing_trail.clone()
+    }
+
+    #[zbus(property)]
+    async fn set_typing_trail(
+        &mut self,
+        trail: TrailConfig,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> Result<(), ZbErr> {
+        polkit::check(connection, &header, Action::ChangeLighting).await?;
+        self.2.run(AuraCommand::SetTypingTrail(trail)).await
+    }
+
+    /// Seconds without keyboard or touchpad input before the lighting fades
+    /// out, `0` to keep it on. Input brings back the brightness.
+    #[zbus(property)]
+    async fn idle_timeout(&self) -> u32 {
+        let ctrl = self.0.lock().await;
+        ctrl.config.idle_off.timeout_secs
+    }
+
+    #[zbus(property)]
+    async fn set_idle_timeout(
+        &mut self,
+        secs: u32,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> Result<(), ZbErr> {
+        polkit::check(connection, &header, Action::ChangeLighting).await?;
+        self.2.run(AuraCommand::SetIdleTimeout(secs)).await
+    }
+
+    /// How long the fade out after `IdleTimeout` takes, in ms
+    #[zbus(property)]
+    async fn idle_fade_ms(&self) -> u32 {
+        let ctrl = self.0.lock().await;
+        ctrl.config.idle_off.fade_ms
+    }
+
+    #[zbus(property)]
+    async fn set_idle_fade_ms(
+        &mut self,
+        ms: u32,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> Result<(), ZbErr> {
+        polkit::check(connection, &header, Action::ChangeLighting).await?;
+        self.2.run(AuraCommand::SetIdleFade(ms)).await
+    }
+
+    /// Light each key as it is pressed, fading it out through the gradient of
+    /// `TypingTrail`, until a builtin mode is set. Any client may replace it
+    /// with a stream of their own. Only on per-key keyboards.
+    async fn run_typing_trail(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> Result<(), ZbErr> {
+        polkit::check(connection, &header, Action::ChangeLighting).await?;
+        let (changes, config) = {
+            let ctrl = self.0.lock().await;
+            if ctrl.supported_data.advanced_type != AdvancedAuraType::PerKey {
+                return Err(ZbErr::NotSupported(
+                    "The typing trail needs a per-key keyboard".to_owned(),
+                ));
+            }
+            watch::channel(ctrl.config.typing_trail.clone())
+        };
+        let keyboards = keys::find_keyboard_devices();
+        if keyboards.is_empty() {
+            return Err(ZbErr::Failed("No keyboard input device found".to_owned()));
+        }
+        // Run by asusd rather than the caller, which may exit at once
+        let client = self.start_stream(None).await?;
+        info!("Running the typing trail");
+        super::typing_trail::run(client, config, keyboards)?;
+        self.2.run(AuraCommand::FollowTypingTrail(changes)).await
+    }
+
+    /// Sent once an edit of the device's config made outside of asusd has been
+    /// applied
+    #[zbus(signal)]
+    async fn notify_config_reloaded(ctxt: &SignalContext<'_>) -> zbus::Result<()>;
+
+    /// Sent with the new effect of a zone set by `SetZone`
+    #[zbus(signal)]
+    async fn notify_zone(ctxt: &SignalContext<'_>, effect: AuraEffect) -> zbus::Result<()>;
 }
 
 impl CtrlTask for CtrlAuraZbus {
@@ -209,47 +1192,44 @@ impl CtrlTask for CtrlAuraZbus {
     }
 
     async fn create_tasks(&self, _: SignalContext<'static>) -> Result<(), RogError> {
-        let load_save =
-            |start: bool, mut lock: MutexGuard<'_, CtrlKbdLed>| -> Result<(), RogError> {
-                // If waking up
-                if !start {
-                    info!("CtrlKbdLedTask reloading brightness and modes");
-                    lock.led_node
-                        .set_brightness(lock.config.brightness.into())
-                        .map_err(|e| {
-                            error!("CtrlKbdLedTask: {e}");
-                            e
-                        })?;
-                    lock.write_current_config_mode().map_err(|e| {
-                        error!("CtrlKbdLedTask: {e}");
-                        e
-                    })?;
-                } else if start {
-                    Self::update_config(&mut lock).map_err(|e| {
-                        error!("CtrlKbdLedTask: {e}");
-                        e
-                    })?;
-                }
-                Ok(())
-            };
-
-        let inner1 = self.0.clone();
-        let inner3 = self.0.clone();
+        let queue1 = self.2.clone();
+        let queue3 = self.2.clone();
+        let inhibitor = Arc::new(Mutex::new(take_sleep_inhibitor().await));
         self.create_sys_event_tasks(
             move |sleeping| {
-                let inner1 = inner1.clone();
+                let queue1 = queue1.clone();
+                let inhibitor = inhibitor.clone();
                 async move {
-                    let lock = inner1.lock().await;
-                    load_save(sleeping, lock).unwrap(); // unwrap as we want to
-                                                        // bomb out of the task
+                    let command = if sleeping {
+                        AuraCommand::Sleep
+                    } else {
+                        info!("CtrlKbdLedTask reloading brightness and modes");
+                        AuraCommand::Resume
+                    };
+                    if let Err(e) = queue1.run(command).await {
+                        error!("CtrlKbdLedTask: {e}");
+                    }
+
+                    // Dropping the fd lets logind continue with suspend, and a
+                    // fresh lock is needed for the next one after waking
+                    let mut fd = inhibitor.lock().await;
+                    if sleeping {
+                        fd.take();
+                    } else {
+                        *fd = take_sleep_inhibitor().await;
+                    }
                 }
             },
-            move |_shutting_down| {
-                let inner3 = inner3.clone();
+            move |shutting_down| {
+                let queue3 = queue3.clone();
                 async move {
-                    let lock = inner3.lock().await;
-                    load_save(false, lock).unwrap(); // unwrap as we want to
-                                                     // bomb out of the task
+                    // The shutdown policy is applied through `listen_shutdown`,
+                    // this only restores the LEDs if the shutdown is cancelled
+                    if !shutting_down {
+                        if let Err(e) = queue3.run(AuraCommand::Resume).await {
+                            error!("CtrlKbdLedTask: {e}");
+                        }
+                    }
                 }
             },
             move |_lid_closed| {
@@ -263,34 +1243,123 @@ impl CtrlTask for CtrlAuraZbus {
         )
         .await;
 
-        let ctrl2 = self.0.clone();
+        let save_inner = self.0.clone();
+        let save_config = DebouncedWrite::new(CONFIG_SAVE_DELAY, move || {
+            let inner = save_inner.clone();
+            async move {
+                inner.lock().await.config.write();
+            }
+        });
+
+        let ctrl2 = self.clone();
+        let save = save_config.clone();
         let ctrl = self.0.lock().await;
-        let watch = ctrl.led_node.monitor_brightness()?;
-        tokio::spawn(async move {
-            let mut buffer = [0; 32];
-            watch
-                .into_event_stream(&mut buffer)
-                .unwrap()
-                .for_each(|_| async {
-                    if let Some(lock) = ctrl2.try_lock() {
-                        load_save(true, lock).unwrap(); // unwrap as we want to
-                                                        // bomb out of the task
+        // A mock only changes brightness when asusd tells it to
+        if !matches!(ctrl.led_node, LEDNode::Mock(_)) {
+            let watch = ctrl.led_node.monitor_brightness()?;
+            tokio::spawn(async move {
+                let mut buffer = [0; 32];
+                watch
+                    .into_event_stream(&mut buffer)
+                    .unwrap()
+                    .for_each(|_| async {
+                        if let Err(e) = ctrl2.2.run(AuraCommand::SyncBrightness).await {
+                            error!("CtrlKbdLedTask: {e}");
+                        }
+                        // Saved later so that holding a hotkey doesn't write the
+                        // config on every step
+                        save.request();
+                        ctrl2.brightness_changed(&ctrl2.1).await.ok();
+                    })
+                    .await;
+            });
+        }
+
+        if !ctrl.led_type.is_scsi() && ctrl.led_type != AuraDeviceType::Unknown {
+            let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+            for node in keys::find_hotkey_devices() {
+                keys::spawn_reader(node, ctrl.config.brightness_keys.clone(), tx.clone());
+            }
+            drop(tx);
+            let ctrl4 = self.clone();
+            tokio::spawn(async move {
+                while let Some(key) = rx.recv().await {
+                    // Read for each key, so turning the handling on or off
+                    // needs no restart
+                    if !ctrl4.0.lock().await.config.brightness_keys.enabled {
+                        continue;
+                    }
+                    if let Err(e) = ctrl4.2.run(AuraCommand::StepBrightness(key)).await {
+                        warn!("CtrlKbdLedTask: {e}");
+                        continue;
                     }
-                })
-                .await;
+                    save_config.request();
+                    ctrl4.brightness_changed(&ctrl4.1).await.ok();
+                }
+            });
+        }
+
+        if ctrl.led_type.is_new_laptop()
+            || ctrl.led_type.is_old_laptop()
+            || ctrl.led_type.is_tuf_laptop()
+            || ctrl.led_type.is_multicolor_laptop()
+        {
+            self.listen_idle();
+        }
+        self.listen_active_user();
+
+        let ctrl5 = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(LED_RULE_INTERVAL);
+            loop {
+                interval.tick().await;
+                ctrl5
+                    .check_led_rules()
+                    .await
+                    .map_err(|e| warn!("LED rules: {e}"))
+                    .ok();
+            }
         });
 
+        // TUF keyboards have no HID path to go through, other tools and scripts
+        // write the mode straight to sysfs
+        if let (LEDNode::KbdLed(tuf), true) = (&ctrl.led_node, ctrl.led_type.is_tuf_laptop()) {
+            match tuf.monitor_kbd_rgb_mode() {
+                Ok(watch) => {
+                    let ctrl3 = self.clone();
+                    tokio::spawn(async move {
+                        let mut buffer = [0; 32];
+                        watch
+                            .into_event_stream(&mut buffer)
+                            .unwrap()
+                            .for_each(|_| async {
+                                let written = ctrl3.0.lock().await.kbd_rgb_mode_written;
+                                if let Err(e) = ctrl3.2.run(AuraCommand::ReconcileMode).await {
+                                    warn!("CtrlKbdLedTask: {e}");
+                                }
+                                // Written again if it was restored
+                                if ctrl3.0.lock().await.kbd_rgb_mode_written != written {
+                                    ctrl3.led_mode_data_changed(&ctrl3.1).await.ok();
+                                }
+                            })
+                            .await;
+                    });
+                }
+                Err(e) => info!("kbd_rgb_mode watch failed: {e}"),
+            }
+        }
+
         Ok(())
     }
 }
 
 impl crate::Reloadable for CtrlAuraZbus {
     async fn reload(&mut self) -> Result<(), RogError> {
-        let mut ctrl = self.0.lock().await;
-        debug!("reloading keyboard mode");
-        ctrl.write_current_config_mode()?;
-        debug!("reloading power states");
-        ctrl.set_power_states().map_err(|err| warn!("{err}")).ok();
+        debug!("reloading keyboard brightness, mode and power states");
+        self.2
+            .run(AuraCommand::Reload)
+            .await
+            .map_err(zbus::Error::from)?;
         Ok(())
     }
 }
@@ -0,0 +1,229 @@
+//! TUF keyboards have no HID path, the asus-wmi driver takes the mode and the
+//! power states as space separated values in `kbd_rgb_mode` and
+//! `kbd_rgb_state`. The whole keyboard is one zone, and per-key frames can
+//! only set the colour of all of it.
+
+use rog_aura::aura_detection::PowerZones;
+use rog_aura::keyboard::LaptopAuraPower;
+use rog_aura::{AuraDeviceType, AuraEffect, AuraModeNum, Speed};
+use rog_platform::aura_hid::{AuraHidWrite, MockHid};
+use rog_platform::keyboard_led::{KbdRgbCmd, KbdRgbMode, KbdRgbModeNum, KbdRgbState, KeyboardLed};
+
+use super::backend::{AuraBackend, AuraCapabilities};
+use crate::error::RogError;
+
+pub const TUF_PROD_ID: &str = "tuf";
+
+/// The modes the driver takes, any other is shown as `Pulse`
+const TUF_MODES: [AuraModeNum; 11] = [
+    AuraModeNum::Static,
+    AuraModeNum::Breathe,
+    AuraModeNum::Strobe,
+    AuraModeNum::Rainbow,
+    AuraModeNum::Star,
+    AuraModeNum::Rain,
+    AuraModeNum::Highlight,
+    AuraModeNum::Laser,
+    AuraModeNum::Ripple,
+    AuraModeNum::Pulse,
+    AuraModeNum::Comet,
+];
+
+/// The firmware mode of `mode`, `None` for those not in `TUF_MODES`
+const fn firmware_mode(mode: AuraModeNum) -> Option<KbdRgbModeNum> {
+    Some(match mode {
+        AuraModeNum::Static => KbdRgbModeNum::Static,
+        AuraModeNum::Breathe => KbdRgbModeNum::Breathe,
+        AuraModeNum::Strobe => KbdRgbModeNum::Strobe,
+        AuraModeNum::Rainbow => KbdRgbModeNum::Rainbow,
+        AuraModeNum::Star => KbdRgbModeNum::Star,
+        AuraModeNum::Rain => KbdRgbModeNum::Rain,
+        AuraModeNum::Highlight => KbdRgbModeNum::Highlight,
+        AuraModeNum::Laser => KbdRgbModeNum::Laser,
+        AuraModeNum::Ripple => KbdRgbModeNum::Ripple,
+        AuraModeNum::Pulse => KbdRgbModeNum::Pulse,
+        AuraModeNum::Comet => KbdRgbModeNum::Comet,
+        AuraModeNum::Flash => return None,
+    })
+}
+
+/// The driver takes the speed as an index, not the HID value
+const fn speed_index(speed: Speed) -> u8 {
+    match speed {
+        Speed::Low => 0,
+        Speed::Med => 1,
+        Speed::High => 2,
+    }
+}
+
+/// Where `kbd_rgb_mode` and `kbd_rgb_state` are written
+pub trait TufWrite: std::fmt::Debug + Send {
+    fn write_mode(&self, mode: &KbdRgbMode) -> rog_platform::error::Result<()>;
+
+    fn write_state(&self, state: &KbdRgbState) -> rog_platform::error::Result<()>;
+}
+
+impl TufWrite for KeyboardLed {
+    fn write_mode(&self, mode: &KbdRgbMode) -> rog_platform::error::Result<()> {
+        self.write_kbd_rgb_mode(mode)
+    }
+
+    fn write_state(&self, state: &KbdRgbState) -> rog_platform::error::Result<()> {
+        self.write_kbd_rgb_state(state)
+    }
+}
+
+/// Records the values of both as they are written to the attributes
+impl TufWrite for MockHid {
+    fn write_mode(&self, mode: &KbdRgbMode) -> rog_platform::error::Result<()> {
+        self.write_bytes(&mode.to_values())
+    }
+
+    fn write_state(&self, state: &KbdRgbState) -> rog_platform::error::Result<()> {
+        self.write_bytes(&state.to_values())
+    }
+}
+
+#[derive(Debug)]
+pub struct Tuf {
+    led: Box<dyn TufWrite>,
+    /// Older kernels have no `kbd_rgb_state`
+    power_states: bool,
+}
+
+impl Tuf {
+    pub fn new(led: &KeyboardLed) -> Self {
+        Self::with_writer(Box::new(led.clone()), led.has_kbd_rgb_state())
+    }
+
+    /// Write the values of `kbd_rgb_mode` and `kbd_rgb_state` somewhere else,
+    /// such as to a mock
+    pub fn with_writer(led: Box<dyn TufWrite>, power_states: bool) -> Self {
+        Self { led, power_states }
+    }
+}
+
+impl AuraBackend for Tuf {
+    fn prod_id(&self) -> &str {
+        TUF_PROD_ID
+    }
+
+    fn capabilities(&self) -> AuraCapabilities {
+        AuraCapabilities {
+            modes: Some(TUF_MODES.to_vec()),
+            zones: false,
+            power_states: self.power_states,
+            frames: true,
+        }
+    }
+
+    fn write_mode(&self, mode: &AuraEffect) -> Result<(), RogError> {
+        let c = mode.colour1;
+        let values = KbdRgbMode {
+            cmd: KbdRgbCmd::Save,
+            mode: firmware_mode(mode.mode).ok_or(RogError::AuraEffectNotSupported)?,
+            red: c.r,
+            green: c.g,
+            blue: c.b,
+            speed: speed_index(mode.speed),
+        };
+        self.led.write_mode(&values)?;
+        Ok(())
+    }
+
+    fn write_power(&self, power: &LaptopAuraPower) -> Result<(), RogError> {
+        if !self.power_states {
+            return Err(RogError::MissingFunction(
+                "kbd_rgb_state is not available".to_owned(),
+            ));
+        }
+        let keyboard = LaptopAuraPower {
+            states: power
+                .states
+                .iter()
+                .filter(|s| s.zone == PowerZones::Keyboard)
+                .cloned()
+                .collect(),
+        };
+        let bytes = keyboard.to_bytes(AuraDeviceType::LaptopTuf);
+        // The keyboard is left enabled as the awake state turns it off anyway
+        let values = KbdRgbState {
+            cmd: KbdRgbCmd::Save,
+            boot: bytes[0] != 0,
+            awake: bytes[1] != 0,
+            sleep: bytes[2] != 0,
+            keyboard: true,
+        };
+        self.led.write_state(&values)?;
+        Ok(())
+    }
+
+    fn write_frame(
+        &self,
+        rows: &mut dyn Iterator<Item = &[u8]>,
+        _start: bool,
+    ) -> Result<(), RogError> {
+        // Raw factory modes are for USB keyboards only, and left to the
+        // default `write_raw`
+        for row in rows {
+            let values = KbdRgbMode {
+                red: row[9],
+                green: row[10],
+                blue: row[11],
+                ..Default::default()
+            };
+            self.led.write_mode(&values)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rog_aura::aura_detection::PowerZones;
+    use rog_aura::keyboard::{AuraPowerState, LaptopAuraPower};
+    use rog_aura::{AuraEffect, AuraModeNum, Colour, Speed};
+    use rog_platform::aura_hid::MockHid;
+
+    use super::Tuf;
+    use crate::ctrl_aura::backend::AuraBackend;
+
+    #[test]
+    fn tuf_packets() {
+        let led = MockHid::new("tuf");
+        let tuf = Tuf::with_writer(Box::new(led.clone()), true);
+        assert!(tuf.capabilities().power_states);
+        assert!(!tuf.capabilities().has_mode(AuraModeNum::Flash));
+
+        tuf.write_mode(&AuraEffect {
+            mode: AuraModeNum::Breathe,
+            colour1: Colour { r: 1, g: 2, b: 3 },
+            speed: Speed::Low,
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(led.take_writes(), vec![vec![1, 1, 1, 2, 3, 0]]);
+        assert!(tuf
+            .write_mode(&AuraEffect {
+                mode: AuraModeNum::Flash,
+                ..Default::default()
+            })
+            .is_err());
+
+        tuf.write_power(&LaptopAuraPower {
+            states: vec![AuraPowerState {
+                zone: PowerZones::Keyboard,
+                boot: true,
+                awake: true,
+                sleep: false,
+                shutdown: false,
+            }],
+        })
+        .unwrap();
+        assert_eq!(led.take_writes(), vec![vec![1, 1, 1, 0, 1]]);
+
+        let no_state = Tuf::with_writer(Box::new(led), false);
+        assert!(!no_state.capabilities().power_states);
+        assert!(no_state.write_power(&LaptopAuraPower::default()).is_err());
+    }
+}
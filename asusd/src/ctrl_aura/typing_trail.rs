@@ -0,0 +1,85 @@
+//! Runs the `TypingTrail` effect of rog-aura from the key presses of the
+//! keyboards, writing its frames to a `FrameStream` the same way an effect
+//! plugin does. The pressed keys are only ever turned into an `LedCode` and
+//! are never logged.
+
+use std::io::Write;
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::time::Instant;
+
+use effect_metrics::Stage;
+use log::info;
+use rog_aura::effects::{led_for_key, TrailConfig, TypingTrail};
+use tokio::sync::watch;
+
+use super::stream::{FRAME_INTERVAL, MAX_RECORDS};
+use crate::error::RogError;
+use crate::keys;
+
+/// Light the keys pressed on `keyboards` until the other end of `socket` is
+/// closed. The first frame is empty, turning every key off until one is
+/// pressed. Changes sent to `config` are used from the next frame.
+pub fn run(
+    mut socket: UnixStream,
+    mut config: watch::Receiver<TrailConfig>,
+    keyboards: Vec<PathBuf>,
+) -> Result<(), RogError> {
+    let (tx, rx) = mpsc::channel();
+    for node in keyboards {
+        let tx = tx.clone();
+        keys::spawn_key_reader(node, "Typing trail", move |code, value| {
+            // Presses and kernel repeats, the trail stays lit while held
+            match led_for_key(code) {
+                Some(led) if value != 0 => tx.send(led).is_ok(),
+                _ => true,
+            }
+        });
+    }
+    drop(tx);
+
+    std::thread::Builder::new()
+        .name("Typing trail".into())
+        .spawn(move || {
+            let mut trail = TypingTrail::new(config.borrow_and_update().clone());
+            let mut last = Instant::now();
+            loop {
+                if config.has_changed().unwrap_or(false) {
+                    trail.set_config(config.borrow_and_update().clone());
+                }
+                for led in rx.try_iter() {
+                    trail.press(led);
+                }
+                let now = Instant::now();
+                let colours = effect_metrics::timed("aura_typing_trail", Stage::Render, || {
+                    trail.frame(now - last)
+                });
+                last = now;
+
+                let count = colours.len().min(MAX_RECORDS);
+                let mut frame = Vec::with_capacity(2 + count * 4);
+                frame.extend_from_slice(&(count as u16).to_le_bytes());
+                for (led, colour) in colours.iter().take(count) {
+                    frame.extend_from_slice(&[*led as u8, colour.r, colour.g, colour.b]);
+                }
+                if let Err(e) = socket.write_all(&frame) {
+                    info!("Typing trail ended: {e}");
+                    break;
+                }
+                if trail.is_idle() {
+                    // Nothing to fade, so wait for a key instead of sending
+                    // frames that change nothing. A closed stream is noticed
+                    // on the next press.
+                    let Ok(led) = rx.recv() else {
+                        break;
+                    };
+                    trail.press(led);
+                    last = Instant::now();
+                } else {
+                    std::thread::sleep(FRAME_INTERVAL);
+                }
+            }
+        })?;
+    Ok(())
+}
@@ -0,0 +1,413 @@
+//! Who changed what. Every change request made to an asusd interface is
+//! recorded against the sender's bus name, and the name a client gave with
+//! `RegisterClient` if it did, then announced with the `ChangedBy` signal.
+//! When a client overwrites a setting another client changed shortly before,
+//! `WriteConflict` is sent as well. The last write wins, but the overwritten
+//! client learns of it and can resync instead of showing a stale value.
+//!
+//! Requests are seen as they arrive, so one that later fails is recorded too.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use log::{debug, info, warn};
+use serde_derive::{Deserialize, Serialize};
+use zbus::export::futures_util::lock::Mutex;
+use zbus::export::futures_util::StreamExt;
+use zbus::fdo::Error as FdoErr;
+use zbus::message::{Header, Type as MessageType};
+use zbus::zvariant::{OwnedValue, Type};
+use zbus::{interface, Connection, MessageStream, SignalContext};
+
+use crate::error::RogError;
+use crate::CtrlTask;
+
+pub const CLIENTS_ZBUS_NAME: &str = "Clients";
+pub const CLIENTS_ZBUS_PATH: &str = "/org/asuslinux";
+const CLIENTS_IFACE: &str = "org.asuslinux.Clients";
+
+/// A write to a setting another client changed less than this long ago is a
+/// conflict
+const CONFLICT_WINDOW: Duration = Duration::from_secs(10);
+/// Method names which change state. Anything else is taken as a query.
+const CHANGE_PREFIXES: &[&str] = &[
+    "Set", "Next", "Prev", "Toggle", "Reset", "Reload", "Run", "Start", "Stop", "Clear", "Insert",
+    "Remove",
+];
+
+/// The last change made to one setting
+#[derive(Deserialize, Serialize, Type, Debug, Clone, PartialEq)]
+pub struct ChangeRecord {
+    pub interface: String,
+    /// The property or method name
+    pub property: String,
+    /// Unique bus name of the client, such as `:1.42`
+    pub sender: String,
+    /// The name the client registered, empty if it didn't
+    pub client: String,
+    pub seconds_ago: u64,
+}
+
+/// One change request, as sent with `ChangedBy`
+#[derive(Deserialize, Serialize, Type, Debug, Clone, PartialEq)]
+pub struct Change {
+    pub interface: String,
+    pub property: String,
+    pub sender: String,
+    pub client: String,
+}
+
+/// A change request made over another client's recent change, as sent with
+/// `WriteConflict`
+#[derive(Deserialize, Serialize, Type, Debug, Clone, PartialEq)]
+pub struct Conflict {
+    pub change: Change,
+    pub overwritten_sender: String,
+    pub overwritten_client: String,
+}
+
+#[derive(Debug, Clone)]
+struct Writer {
+    sender: String,
+    client: String,
+    at: Instant,
+}
+
+#[derive(Debug, Default)]
+struct ClientState {
+    /// Unique bus name to registered name
+    names: BTreeMap<String, String>,
+    last_writers: BTreeMap<(String, String), Writer>,
+}
+
+impl ClientState {
+    fn client_name(&self, sender: &str) -> String {
+        self.names.get(sender).cloned().unwrap_or_default()
+    }
+
+    /// Record `sender` as the last writer of `property`, returning the writer
+    /// it overwrote if that counts as a conflict
+    fn record(
+        &mut self,
+        interface: &str,
+        property: &str,
+        sender: &str,
+        now: Instant,
+    ) -> Option<Writer> {
+        let writer = Writer {
+            sender: sender.to_owned(),
+            client: self.client_name(sender),
+            at: now,
+        };
+        let previous = self
+            .last_writers
+            .insert((interface.to_owned(), property.to_owned()), writer)?;
+        (previous.sender != sender && now.duration_since(previous.at) < CONFLICT_WINDOW)
+            .then_some(previous)
+    }
+
+    fn changes(&self, now: Instant) -> Vec<ChangeRecord> {
+        self.last_writers
+            .iter()
+            .map(|((interface, property), w)| ChangeRecord {
+                interface: interface.clone(),
+                property: property.clone(),
+                sender: w.sender.clone(),
+                client: w.client.clone(),
+                seconds_ago: now.duration_since(w.at).as_secs(),
+            })
+            .collect()
+    }
+}
+
+/// The interface and setting a method call changes, if it changes anything
+fn change_target(
+    header: &Header<'_>,
+    body: impl FnOnce() -> Option<(String, String)>,
+) -> Option<(String, String)> {
+    if header.message_type() != MessageType::MethodCall {
+        return None;
+    }
+    let interface = header.interface()?.as_str();
+    let member = header.member()?.as_str();
+    if interface == "org.freedesktop.DBus.Properties" && member == "Set" {
+        return body()
+            .filter(|(iface, _)| iface.starts_with("org.asuslinux.") && iface != CLIENTS_IFACE);
+    }
+    if !interface.starts_with("org.asuslinux.") || interface == CLIENTS_IFACE {
+        return None;
+    }
+    method_setting(member).map(|setting| (interface.to_owned(), setting.to_owned()))
+}
+
+/// The setting a method changes, if it changes anything. A setter such as
+/// `SetLedMode` changes the property `LedMode`, so that it conflicts with a
+/// write of the property. Other methods are their own setting.
+fn method_setting(member: &str) -> Option<&str> {
+    // The prefix is a whole word, `SettingsSchema` is a query
+    let prefix = CHANGE_PREFIXES.iter().find(|prefix| {
+        member.strip_prefix(**prefix).map_or(false, |rest| {
+            rest.starts_with(|c: char| c.is_ascii_uppercase())
+        })
+    })?;
+    Some(if *prefix == "Set" {
+        &member[prefix.len()..]
+    } else {
+        member
+    })
+}
+
+#[derive(Clone, Default)]
+pub struct CtrlClients(Arc<Mutex<ClientState>>);
+
+impl CtrlClients {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn sender(header: &Header<'_>) -> Result<String, FdoErr> {
+        header
+            .sender()
+            .map(|s| s.to_string())
+            .ok_or_else(|| FdoErr::Failed("Could not determine the caller".into()))
+    }
+}
+
+#[interface(name = "org.asuslinux.Clients")]
+impl CtrlClients {
+    /// Give a name to the calling connection, such as `rog-control-center`,
+    /// which is then shown in the change records. Dropped when the
+    /// connection closes.
+    async fn register_client(
+        &self,
+        name: &str,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(signal_context)] ctxt: SignalContext<'_>,
+    ) -> Result<(), FdoErr> {
+        let sender = Self::sender(&header)?;
+        info!("Client {sender} registered as {name}");
+        self.0.lock().await.names.insert(sender, name.to_owned());
+        self.clients_changed(&ctxt).await.ok();
+        Ok(())
+    }
+
+    async fn unregister_client(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(signal_context)] ctxt: SignalContext<'_>,
+    ) -> Result<(), FdoErr> {
+        let sender = Self::sender(&header)?;
+        if self.0.lock().await.names.remove(&sender).is_some() {
+            self.clients_changed(&ctxt).await.ok();
+        }
+        Ok(())
+    }
+
+    /// Registered clients, unique bus name to client name
+    #[zbus(property)]
+    async fn clients(&self) -> BTreeMap<String, String> {
+        self.0.lock().await.names.clone()
+    }
+
+    /// The last client to change each setting that has been changed since
+    /// asusd started
+    async fn last_changes(&self) -> Vec<ChangeRecord> {
+        self.0.lock().await.changes(Instant::now())
+    }
+
+    /// Sent for each change request, naming the client that made it
+    #[zbus(signal)]
+    async fn changed_by(
+        ctxt: &SignalContext<'_>,
+        interface: &str,
+        property: &str,
+        sender: &str,
+        client: &str,
+    ) -> zbus::Result<()>;
+
+    /// Sent when a client overwrites a setting another client changed within
+    /// the last ten seconds
+    #[zbus(signal)]
+    async fn write_conflict(
+        ctxt: &SignalContext<'_>,
+        interface: &str,
+        property: &str,
+        overwritten_sender: &str,
+        overwritten_client: &str,
+        sender: &str,
+        client: &str,
+    ) -> zbus::Result<()>;
+}
+
+impl crate::ZbusRun for CtrlClients {
+    async fn add_to_server(self, server: &mut Connection) {
+        Self::add_to_server_helper(self, CLIENTS_ZBUS_PATH, server).await;
+    }
+}
+
+impl crate::Reloadable for CtrlClients {
+    async fn reload(&mut self) -> Result<(), RogError> {
+        Ok(())
+    }
+}
+
+impl CtrlTask for CtrlClients {
+    fn zbus_path() -> &'static str {
+        CLIENTS_ZBUS_PATH
+    }
+
+    async fn create_tasks(&self, signal_ctxt: SignalContext<'static>) -> Result<(), RogError> {
+        let connection = signal_ctxt.connection().clone();
+
+        let ctrl = self.clone();
+        let ctxt = signal_ctxt.clone();
+        let mut messages = MessageStream::from(&connection);
+        tokio::spawn(async move {
+            while let Some(msg) = messages.next().await {
+                let Ok(msg) = msg else {
+                    continue;
+                };
+                let header = msg.header();
+                let Some(sender) = header.sender().map(|s| s.to_string()) else {
+                    continue;
+                };
+                let target = change_target(&header, || {
+                    msg.body()
+                        .deserialize::<(String, String, OwnedValue)>()
+                        .ok()
+                        .map(|(iface, prop, _)| (iface, prop))
+                });
+                let Some((interface, property)) = target else {
+                    continue;
+                };
+
+                let mut state = ctrl.0.lock().await;
+                let change = Change {
+                    client: state.client_name(&sender),
+                    interface,
+                    property,
+                    sender,
+                };
+                let overwritten = state.record(
+                    &change.interface,
+                    &change.property,
+                    &change.sender,
+                    Instant::now(),
+                );
+                drop(state);
+
+                debug!(
+                    "{}.{} changed by {} {}",
+                    change.interface, change.property, change.sender, change.client
+                );
+                Self::changed_by(
+                    &ctxt,
+                    &change.interface,
+                    &change.property,
+                    &change.sender,
+                    &change.client,
+                )
+                .await
+                .ok();
+                if let Some(prev) = overwritten {
+                    let conflict = Conflict {
+                        change,
+                        overwritten_sender: prev.sender,
+                        overwritten_client: prev.client,
+                    };
+                    Self::write_conflict(
+                        &ctxt,
+                        &conflict.change.interface,
+                        &conflict.change.property,
+                        &conflict.overwritten_sender,
+                        &conflict.overwritten_client,
+                        &conflict.change.sender,
+                        &conflict.change.client,
+                    )
+                    .await
+                    .ok();
+                }
+            }
+        });
+
+        // Registrations end with the connection
+        let dbus = zbus::fdo::DBusProxy::new(&connection).await?;
+        let mut owner_changes = dbus.receive_name_owner_changed().await?;
+        let ctrl = self.clone();
+        tokio::spawn(async move {
+            while let Some(change) = owner_changes.next().await {
+                let Ok(args) = change.args() else {
+                    continue;
+                };
+                if args.new_owner().is_some() {
+                    continue;
+                }
+                let name = args.name().to_string();
+                if ctrl.0.lock().await.names.remove(&name).is_some() {
+                    debug!("Client {name} left the bus");
+                    ctrl.clients_changed(&signal_ctxt)
+                        .await
+                        .map_err(|e| warn!("clients_changed: {e}"))
+                        .ok();
+                }
+            }
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::{method_setting, ClientState};
+
+    #[test]
+    fn conflicts_only_between_clients_in_window() {
+        let mut state = ClientState::default();
+        state.names.insert(":1.1".to_owned(), "gui".to_owned());
+        let start = Instant::now();
+        let at = |s| start + Duration::from_secs(s);
+        let iface = "org.asuslinux.Aura";
+
+        assert!(state.record(iface, "Brightness", ":1.1", at(0)).is_none());
+        // The same client writing again is not a conflict
+        assert!(state.record(iface, "Brightness", ":1.1", at(1)).is_none());
+        let prev = state.record(iface, "Brightness", ":1.2", at(2)).unwrap();
+        assert_eq!(prev.client, "gui");
+        // Nor is a write long after the last
+        assert!(state.record(iface, "Brightness", ":1.1", at(30)).is_none());
+        assert!(state.record(iface, "LedMode", ":1.2", at(31)).is_none());
+
+        let changes = state.changes(at(32));
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].property, "Brightness");
+        assert_eq!(changes[0].client, "gui");
+        assert_eq!(changes[0].seconds_ago, 2);
+        assert_eq!(changes[1].client, "");
+    }
+
+    #[test]
+    fn setters_recorded_as_their_property() {
+        assert_eq!(method_setting("SetLedMode"), Some("LedMode"));
+        assert_eq!(method_setting("SetLedModeData"), Some("LedModeData"));
+        assert_eq!(
+            method_setting("NextThrottleThermalPolicy"),
+            Some("NextThrottleThermalPolicy")
+        );
+        assert_eq!(
+            method_setting("ResetProfileCurves"),
+            Some("ResetProfileCurves")
+        );
+        assert_eq!(method_setting("SettingsSchema"), None);
+        assert_eq!(method_setting("LastChanges"), None);
+
+        // A setter and a write of its property are the same setting
+        let mut state = ClientState::default();
+        let now = Instant::now();
+        let iface = "org.asuslinux.Aura";
+        state.record(iface, method_setting("SetLedMode").unwrap(), ":1.1", now);
+        assert!(state.record(iface, "LedMode", ":1.2", now).is_some());
+    }
+}
@@ -0,0 +1,301 @@
+//! Fn-key hotkeys read from the ASUS input devices and sent out as the
+//! `NotifyHotkey` signal. Each key is either left to the desktop, which can
+//! bind it from the signal, or handled by asusd itself. Keys handled by asusd
+//! are passed on to the other controllers through the `HotkeyHook`.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use config_traits::StdConfig;
+use log::{info, warn};
+use serde_derive::{Deserialize, Serialize};
+use zbus::export::futures_util::lock::Mutex;
+use zbus::fdo::Error as FdoErr;
+use zbus::message::Header;
+use zbus::zvariant::Type;
+use zbus::{interface, Connection, SignalContext};
+
+use crate::config::Config;
+use crate::error::RogError;
+use crate::polkit::{self, Action};
+use crate::{keys, CtrlTask, HotkeyHook};
+
+pub const EVENTS_ZBUS_NAME: &str = "Events";
+pub const EVENTS_ZBUS_PATH: &str = "/org/asuslinux/Events";
+
+#[derive(Deserialize, Serialize, Type, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[zvariant(signature = "s")]
+pub enum Hotkey {
+    /// The ROG or Armoury Crate key
+    RogKey,
+    AuraPrevious,
+    AuraNext,
+    KbdBrightnessUp,
+    KbdBrightnessDown,
+    /// Single key which steps through the brightness levels
+    KbdBrightnessCycle,
+    MicMute,
+    /// Fn+F5 on most models
+    FanMode,
+    /// On an ASUS keyboard, or the device of a userspace touchpad numpad
+    /// driver. Handled by asusd it toggles the touchpad numpad light.
+    NumLock,
+}
+
+impl Hotkey {
+    pub const ALL: [Hotkey; 9] = [
+        Hotkey::RogKey,
+        Hotkey::AuraPrevious,
+        Hotkey::AuraNext,
+        Hotkey::KbdBrightnessUp,
+        Hotkey::KbdBrightnessDown,
+        Hotkey::KbdBrightnessCycle,
+        Hotkey::MicMute,
+        Hotkey::FanMode,
+        Hotkey::NumLock,
+    ];
+
+    /// The key code `asus-wmi` or `hid-asus` reports for the key
+    pub const fn code(self) -> u16 {
+        match self {
+            Hotkey::RogKey => keys::KEY_PROG1,
+            Hotkey::AuraPrevious => keys::KEY_PROG2,
+            Hotkey::AuraNext => keys::KEY_PROG3,
+            Hotkey::KbdBrightnessUp => keys::KEY_KBDILLUMUP,
+            Hotkey::KbdBrightnessDown => keys::KEY_KBDILLUMDOWN,
+            Hotkey::KbdBrightnessCycle => keys::KEY_KBDILLUMTOGGLE,
+            Hotkey::MicMute => keys::KEY_MICMUTE,
+            Hotkey::FanMode => keys::KEY_FN_F5,
+            Hotkey::NumLock => keys::KEY_NUMLOCK,
+        }
+    }
+
+    pub fn from_code(code: u16) -> Option<Self> {
+        Self::ALL.into_iter().find(|key| key.code() == code)
+    }
+
+    /// Whether asusd has anything to do for the key. The others can only be
+    /// left to the desktop.
+    pub const fn daemon_can_handle(self) -> bool {
+        !matches!(self, Hotkey::RogKey | Hotkey::MicMute)
+    }
+}
+
+impl std::str::FromStr for Hotkey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|key| format!("{key:?}").eq_ignore_ascii_case(s.trim()))
+            .ok_or_else(|| format!("{s} is not a known hotkey"))
+    }
+}
+
+#[derive(Deserialize, Serialize, Type, Debug, Clone, Copy, PartialEq, Eq)]
+#[zvariant(signature = "s")]
+pub enum KeyAction {
+    Press,
+    /// Sent by the kernel every 33ms or so while the key is held
+    Repeat,
+    Release,
+}
+
+impl KeyAction {
+    /// From the value of an `EV_KEY` event
+    pub const fn from_value(value: i32) -> Option<Self> {
+        match value {
+            0 => Some(KeyAction::Release),
+            1 => Some(KeyAction::Press),
+            2 => Some(KeyAction::Repeat),
+            _ => None,
+        }
+    }
+}
+
+/// One press, repeat or release, as sent with `NotifyHotkey`
+#[derive(Deserialize, Serialize, Type, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HotkeyEvent {
+    pub key: Hotkey,
+    pub action: KeyAction,
+    /// asusd acted on the key
+    pub handled: bool,
+}
+
+/// Who acts on a hotkey. The signal is sent either way.
+#[derive(Deserialize, Serialize, Type, Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd)]
+#[zvariant(signature = "s")]
+pub enum HotkeyHandling {
+    /// asusd only sends the signal, so the key can be bound in the desktop
+    #[default]
+    Desktop,
+    /// asusd acts on the key itself, e.g. stepping the keyboard brightness
+    Daemon,
+}
+
+impl std::str::FromStr for HotkeyHandling {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().trim() {
+            "desktop" => Ok(Self::Desktop),
+            "daemon" => Ok(Self::Daemon),
+            _ => Err(format!("{s} is not one of desktop, daemon")),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct CtrlEvents {
+    config: Arc<Mutex<Config>>,
+    hotkey_hook: HotkeyHook,
+    supported: Vec<Hotkey>,
+}
+
+impl CtrlEvents {
+    pub fn new(config: Arc<Mutex<Config>>, hotkey_hook: HotkeyHook) -> Result<Self, RogError> {
+        let supported: Vec<Hotkey> = Hotkey::ALL
+            .into_iter()
+            .filter(|key| !keys::find_devices_with_any(&[key.code()]).is_empty())
+            .collect();
+        if supported.is_empty() {
+            return Err(RogError::MissingFunction(
+                "Hotkey events: no ASUS input device with hotkeys".to_owned(),
+            ));
+        }
+        info!("Hotkeys found: {supported:?}");
+        Ok(Self {
+            config,
+            hotkey_hook,
+            supported,
+        })
+    }
+
+    async fn handling(&self, key: Hotkey) -> HotkeyHandling {
+        self.config
+            .lock()
+            .await
+            .hotkeys
+            .get(&key)
+            .copied()
+            .unwrap_or_default()
+    }
+}
+
+#[interface(name = "org.asuslinux.Events")]
+impl CtrlEvents {
+    /// The hotkeys this laptop has
+    #[zbus(property)]
+    fn supported_hotkeys(&self) -> Vec<Hotkey> {
+        self.supported.clone()
+    }
+
+    /// How each hotkey is handled. Keys not listed are left to the desktop.
+    #[zbus(property)]
+    async fn hotkey_handling(&self) -> BTreeMap<Hotkey, HotkeyHandling> {
+        self.config.lock().await.hotkeys.clone()
+    }
+
+    /// Choose whether asusd or the desktop acts on `key`
+    async fn set_hotkey_handling(
+        &self,
+        key: Hotkey,
+        handling: HotkeyHandling,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(signal_context)] ctxt: SignalContext<'_>,
+    ) -> Result<(), FdoErr> {
+        polkit::check(connection, &header, Action::ChangePlatformSettings).await?;
+        if handling == HotkeyHandling::Daemon && !key.daemon_can_handle() {
+            return Err(FdoErr::NotSupported(format!(
+                "asusd has no action for {key:?}, it can only be left to the desktop"
+            )));
+        }
+        let mut config = self.config.lock().await;
+        config.hotkeys.insert(key, handling);
+        config.write();
+        drop(config);
+        self.hotkey_handling_changed(&ctxt).await.ok();
+        Ok(())
+    }
+
+    /// Sent for every press, repeat and release of a hotkey. `handled` is
+    /// true if asusd acted on the key.
+    #[zbus(signal)]
+    async fn notify_hotkey(
+        ctxt: &SignalContext<'_>,
+        key: Hotkey,
+        action: KeyAction,
+        handled: bool,
+    ) -> zbus::Result<()>;
+}
+
+impl crate::ZbusRun for CtrlEvents {
+    async fn add_to_server(self, server: &mut Connection) {
+        Self::add_to_server_helper(self, EVENTS_ZBUS_PATH, server).await;
+    }
+}
+
+impl crate::Reloadable for CtrlEvents {
+    async fn reload(&mut self) -> Result<(), RogError> {
+        Ok(())
+    }
+}
+
+impl CtrlTask for CtrlEvents {
+    fn zbus_path() -> &'static str {
+        EVENTS_ZBUS_PATH
+    }
+
+    async fn create_tasks(&self, signal_ctxt: SignalContext<'static>) -> Result<(), RogError> {
+        let codes: Vec<u16> = self.supported.iter().map(|key| key.code()).collect();
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        for node in keys::find_devices_with_any(&codes) {
+            let tx = tx.clone();
+            keys::spawn_key_reader(node, "Hotkey events", move |code, value| {
+                match (Hotkey::from_code(code), KeyAction::from_value(value)) {
+                    (Some(key), Some(action)) => tx.send((key, action)).is_ok(),
+                    _ => true,
+                }
+            });
+        }
+        drop(tx);
+
+        let ctrl = self.clone();
+        tokio::spawn(async move {
+            while let Some((key, action)) = rx.recv().await {
+                let event = HotkeyEvent {
+                    key,
+                    action,
+                    handled: ctrl.handling(key).await == HotkeyHandling::Daemon,
+                };
+                if event.handled && action == KeyAction::Press {
+                    // Errors only if nothing is listening for this key
+                    ctrl.hotkey_hook.send(key).ok();
+                }
+                Self::notify_hotkey(&signal_ctxt, event.key, event.action, event.handled)
+                    .await
+                    .map_err(|e| warn!("notify_hotkey: {e}"))
+                    .ok();
+            }
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Hotkey, KeyAction};
+
+    #[test]
+    fn key_codes_round_trip() {
+        for key in Hotkey::ALL {
+            assert_eq!(Hotkey::from_code(key.code()), Some(key));
+        }
+        assert_eq!(Hotkey::from_code(0x1d6), Some(Hotkey::FanMode));
+        assert_eq!(Hotkey::from_code(30), None);
+        assert_eq!("rogkey".parse(), Ok(Hotkey::RogKey));
+        assert_eq!(KeyAction::from_value(2), Some(KeyAction::Repeat));
+        assert_eq!(KeyAction::from_value(3), None);
+    }
+}
@@ -10,22 +10,31 @@ use rog_profiles::fan_curve_set::CurveData;
 use rog_profiles::{find_fan_curve_node, FanCurvePU, FanCurveProfiles};
 use serde_derive::{Deserialize, Serialize};
 use tokio::sync::Mutex;
+use zbus::message::Header;
 use zbus::{interface, Connection, SignalContext};
 
+use crate::config_watch;
 use crate::error::RogError;
-use crate::{CtrlTask, CONFIG_PATH_BASE};
+use crate::first_run::note_fresh_config;
+use crate::polkit::{self, Action};
+use crate::{ConfigChangeListener, CtrlTask, ProfileChangeListener, Reloadable, CONFIG_PATH_BASE};
 
 pub const FAN_CURVE_ZBUS_NAME: &str = "FanCurves";
 pub const FAN_CURVE_ZBUS_PATH: &str = "/org/asuslinux";
 
 #[derive(Deserialize, Serialize, Debug, Default)]
 pub struct FanCurveConfig {
+    /// Version of the file format, upgraded on load
+    #[serde(default)]
+    pub version: u32,
     pub profiles: FanCurveProfiles,
     #[serde(skip)]
     pub current: u8,
 }
 
 impl StdConfig for FanCurveConfig {
+    const VERSION: Option<u32> = Some(1);
+
     /// Create a new config. The defaults are zeroed so the device must be read
     /// to get the actual device defaults.
     fn new() -> Self {
@@ -39,6 +48,10 @@ impl StdConfig for FanCurveConfig {
     fn config_dir() -> std::path::PathBuf {
         PathBuf::from(CONFIG_PATH_BASE)
     }
+
+    fn set_version(&mut self, version: u32) {
+        self.version = version;
+    }
 }
 
 impl StdConfigLoad for FanCurveConfig {}
@@ -57,7 +70,7 @@ impl CtrlFanCurveZbus {
             info!("Device has profile control available");
             find_fan_curve_node()?;
             info!("Device has fan curves available");
-            let mut config = FanCurveConfig::new().load();
+            let mut config = note_fresh_config(FanCurveConfig::new()).load();
             let mut fan_curves = FanCurveProfiles::default();
 
             // Only do defaults if the config doesn't already exist\
@@ -98,6 +111,64 @@ impl CtrlFanCurveZbus {
 
         Err(ProfileError::NotSupported.into())
     }
+
+    /// Apply the fan curves associated with each throttle policy as it becomes
+    /// active. The curves are stored as that policy's curves so that the
+    /// D-Bus view stays correct.
+    pub fn listen_profile_changes(&self, mut listener: ProfileChangeListener) {
+        let config = self.config.clone();
+        tokio::spawn(async move {
+            while listener.changed().await.is_ok() {
+                let change = listener.borrow_and_update().clone();
+                let Some((policy, curves)) =
+                    change.and_then(|c| Some((c.policy, c.association.fan_curves?)))
+                else {
+                    continue;
+                };
+
+                let mut config = config.lock().await;
+                for curve in curves {
+                    if let Err(e) = curve.validate() {
+                        warn!("Profile {policy:?} {:?} fan curve: {e}", curve.fan);
+                        continue;
+                    }
+                    config.profiles.save_fan_curve(curve, policy).ok();
+                }
+                if let Ok(mut device) = find_fan_curve_node() {
+                    config
+                        .profiles
+                        .write_profile_curve_to_platform(policy, &mut device)
+                        .map_err(|e| warn!("write_profile_curve_to_platform, {}", e))
+                        .ok();
+                }
+                config.write();
+            }
+        });
+    }
+
+    /// Apply `fan_curves.ron` each time it is edited outside of asusd
+    pub fn listen_config_changes(
+        &self,
+        mut listener: ConfigChangeListener,
+        signal_ctxt: SignalContext<'static>,
+    ) {
+        let mut ctrl = self.clone();
+        tokio::spawn(async move {
+            let file_name = ctrl.config.lock().await.file_name();
+            while config_watch::changed(&mut listener, &file_name).await {
+                let Some(edited) = ctrl.config.lock().await.read_new() else {
+                    continue;
+                };
+                info!("{file_name} was edited, applying the fan curves");
+                ctrl.config.lock().await.profiles = edited.profiles;
+                ctrl.reload()
+                    .await
+                    .map_err(|e| warn!("Could not apply the edited {file_name}: {e}"))
+                    .ok();
+                Self::notify_config_reloaded(&signal_ctxt).await.ok();
+            }
+        });
+    }
 }
 
 #[interface(name = "org.asuslinux.FanCurves")]
@@ -108,7 +179,10 @@ impl CtrlFanCurveZbus {
         &mut self,
         profile: ThrottlePolicy,
         enabled: bool,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
     ) -> zbus::fdo::Result<()> {
+        polkit::check(connection, &header, Action::ChangePowerProfile).await?;
         self.config
             .lock()
             .await
@@ -130,7 +204,10 @@ impl CtrlFanCurveZbus {
         profile: ThrottlePolicy,
         fan: FanCurvePU,
         enabled: bool,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
     ) -> zbus::fdo::Result<()> {
+        polkit::check(connection, &header, Action::ChangePowerProfile).await?;
         self.config
             .lock()
             .await
@@ -166,7 +243,10 @@ impl CtrlFanCurveZbus {
         &mut self,
         profile: ThrottlePolicy,
         curve: CurveData,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
     ) -> zbus::fdo::Result<()> {
+        polkit::check(connection, &header, Action::ChangePowerProfile).await?;
         self.config
             .lock()
             .await
@@ -189,7 +269,13 @@ impl CtrlFanCurveZbus {
     ///
     /// Each platform_profile has a different default and the default can be
     /// read only for the currently active profile.
-    async fn set_curves_to_defaults(&mut self, profile: ThrottlePolicy) -> zbus::fdo::Result<()> {
+    async fn set_curves_to_defaults(
+        &mut self,
+        profile: ThrottlePolicy,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> zbus::fdo::Result<()> {
+        polkit::check(connection, &header, Action::ChangePowerProfile).await?;
         let active = self.platform.get_throttle_thermal_policy()?;
         self.platform.set_throttle_thermal_policy(profile.into())?;
         self.config
@@ -207,7 +293,13 @@ impl CtrlFanCurveZbus {
     ///
     /// Each platform_profile has a different default and the defualt can be
     /// read only for the currently active profile.
-    async fn reset_profile_curves(&self, profile: ThrottlePolicy) -> zbus::fdo::Result<()> {
+    async fn reset_profile_curves(
+        &self,
+        profile: ThrottlePolicy,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> zbus::fdo::Result<()> {
+        polkit::check(connection, &header, Action::ChangePowerProfile).await?;
         let active = self.platform.get_throttle_thermal_policy()?;
 
         self.platform.set_throttle_thermal_policy(profile.into())?;
@@ -221,6 +313,11 @@ impl CtrlFanCurveZbus {
         self.config.lock().await.write();
         Ok(())
     }
+
+    /// Sent once an edit of `fan_curves.ron` made outside of asusd has been
+    /// applied
+    #[zbus(signal)]
+    async fn notify_config_reloaded(ctxt: &SignalContext<'_>) -> zbus::Result<()>;
 }
 
 impl crate::ZbusRun for CtrlFanCurveZbus {
@@ -0,0 +1,207 @@
+//! dGPU power and MUX control through the asus-wmi `dgpu_disable` and
+//! `gpu_mux_mode` attributes. A MUX change is only applied by the firmware at
+//! the next boot, so clients are told with the `RebootRequired` signal.
+
+use std::fs;
+
+use log::{info, warn};
+use rog_platform::platform::{GpuMuxMode, RogPlatform};
+use zbus::fdo::Error as FdoErr;
+use zbus::message::Header;
+use zbus::{interface, Connection, SignalContext};
+
+use crate::error::RogError;
+use crate::polkit::{self, Action};
+use crate::{task_watch_item_notify, CtrlTask};
+
+pub const GPU_ZBUS_NAME: &str = "Gpu";
+pub const GPU_ZBUS_PATH: &str = "/org/asuslinux";
+
+/// Connectors built in to the laptop, these never count as external
+const INTERNAL_CONNECTORS: &[&str] = &["eDP", "LVDS", "DSI"];
+
+#[derive(Debug, Clone)]
+pub struct CtrlGpu {
+    platform: RogPlatform,
+}
+
+impl CtrlGpu {
+    task_watch_item_notify!(dgpu_disable platform);
+
+    pub fn new() -> Result<Self, RogError> {
+        let platform = RogPlatform::new()?;
+        if !platform.has_gpu_mux_mode() && !platform.has_dgpu_disable() {
+            return Err(RogError::MissingFunction(
+                "dGPU control: no gpu_mux_mode or dgpu_disable".to_owned(),
+            ));
+        }
+        Ok(Self { platform })
+    }
+
+    fn get_mux_mode(&self) -> Result<GpuMuxMode, FdoErr> {
+        if !self.platform.has_gpu_mux_mode() {
+            return Err(FdoErr::NotSupported(
+                "CtrlGpu: gpu_mux_mode not supported".to_owned(),
+            ));
+        }
+        self.platform
+            .get_gpu_mux_mode()
+            .map(GpuMuxMode::from)
+            .map_err(|err| FdoErr::Failed(format!("CtrlGpu: gpu_mux_mode: {err}")))
+    }
+}
+
+/// Connected external displays on a discrete GPU, as DRM connector names. A
+/// card is taken to be discrete if the firmware did not boot on it.
+fn dgpu_external_displays() -> Vec<String> {
+    let Ok(dir) = fs::read_dir("/sys/class/drm") else {
+        return Vec::new();
+    };
+    let mut connected: Vec<String> = dir
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let (card, connector) = name.split_once('-')?;
+            if INTERNAL_CONNECTORS.iter().any(|c| connector.starts_with(c)) {
+                return None;
+            }
+            let boot_vga = fs::read_to_string(format!("/sys/class/drm/{card}/device/boot_vga"));
+            if boot_vga.ok()?.trim() != "0" {
+                return None;
+            }
+            let status = fs::read_to_string(entry.path().join("status")).ok()?;
+            (status.trim() == "connected").then_some(name)
+        })
+        .collect();
+    connected.sort();
+    connected
+}
+
+/// Switch the MUX, refused while the dGPU is disabled, and send
+/// `RebootRequired`. Also used by the deprecated `GpuMuxMode` property of the
+/// Platform interface, so its clients are told too.
+pub(crate) async fn write_mux_mode(
+    platform: &RogPlatform,
+    mode: GpuMuxMode,
+    ctxt: &SignalContext<'_>,
+) -> Result<(), FdoErr> {
+    if !platform.has_gpu_mux_mode() {
+        return Err(FdoErr::NotSupported(
+            "CtrlGpu: gpu_mux_mode not supported".to_owned(),
+        ));
+    }
+    if mode == GpuMuxMode::Dedicated && platform.get_dgpu_disable().unwrap_or_default() {
+        return Err(FdoErr::Failed(
+            "The dGPU is disabled, enable it before switching the MUX to it".to_owned(),
+        ));
+    }
+    platform.set_gpu_mux_mode(mode.into()).map_err(|err| {
+        warn!("CtrlGpu: set_gpu_mux_mode {err}");
+        FdoErr::Failed(format!("CtrlGpu: set_gpu_mux_mode: {err}"))
+    })?;
+    info!("GPU MUX set to {mode}, applies after reboot");
+    CtrlGpu::reboot_required(ctxt, &format!("GPU MUX mode changed to {mode}"))
+        .await
+        .ok();
+    Ok(())
+}
+
+#[interface(name = "org.asuslinux.Gpu")]
+impl CtrlGpu {
+    /// The MUX mode the laptop booted with. A change made since then is not
+    /// shown until the next boot.
+    #[zbus(property)]
+    fn mux_mode(&self) -> Result<GpuMuxMode, FdoErr> {
+        self.get_mux_mode()
+    }
+
+    #[zbus(property)]
+    fn dgpu_disable(&self) -> Result<bool, FdoErr> {
+        if !self.platform.has_dgpu_disable() {
+            return Err(FdoErr::NotSupported(
+                "CtrlGpu: dgpu_disable not supported".to_owned(),
+            ));
+        }
+        self.platform
+            .get_dgpu_disable()
+            .map_err(|err| FdoErr::Failed(format!("CtrlGpu: dgpu_disable: {err}")))
+    }
+
+    /// Switch the MUX. The dGPU can't be disabled while it is selected, and
+    /// the change applies after a reboot.
+    async fn set_mux_mode(
+        &self,
+        mode: GpuMuxMode,
+        #[zbus(signal_context)] ctxt: SignalContext<'_>,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> Result<(), FdoErr> {
+        polkit::check(connection, &header, Action::ChangeGpuMode).await?;
+        write_mux_mode(&self.platform, mode, &ctxt).await
+    }
+
+    /// Power the dGPU off or on. Refused while the dGPU drives the internal
+    /// panel or has an external display connected.
+    async fn set_dgpu_disable(
+        &self,
+        disable: bool,
+        #[zbus(signal_context)] ctxt: SignalContext<'_>,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> Result<(), FdoErr> {
+        polkit::check(connection, &header, Action::ChangeGpuMode).await?;
+        if !self.platform.has_dgpu_disable() {
+            return Err(FdoErr::NotSupported(
+                "CtrlGpu: dgpu_disable not supported".to_owned(),
+            ));
+        }
+        if disable {
+            if self.platform.has_gpu_mux_mode() && self.get_mux_mode()? == GpuMuxMode::Dedicated {
+                return Err(FdoErr::Failed(
+                    "The MUX is set to the dGPU, switch it to Integrated first".to_owned(),
+                ));
+            }
+            let displays = dgpu_external_displays();
+            if !displays.is_empty() {
+                return Err(FdoErr::Failed(format!(
+                    "External displays are active on the dGPU: {}",
+                    displays.join(", ")
+                )));
+            }
+        }
+        self.platform.set_dgpu_disable(disable).map_err(|err| {
+            warn!("CtrlGpu: set_dgpu_disable {err}");
+            FdoErr::Failed(format!("CtrlGpu: set_dgpu_disable: {err}"))
+        })?;
+        info!("dGPU disable set to {disable}");
+        self.dgpu_disable_changed(&ctxt).await.ok();
+        Ok(())
+    }
+
+    /// Sent when a change has been made that only applies after a reboot
+    #[zbus(signal)]
+    async fn reboot_required(ctxt: &SignalContext<'_>, reason: &str) -> zbus::Result<()>;
+}
+
+impl crate::ZbusRun for CtrlGpu {
+    async fn add_to_server(self, server: &mut Connection) {
+        Self::add_to_server_helper(self, GPU_ZBUS_PATH, server).await;
+    }
+}
+
+impl crate::Reloadable for CtrlGpu {
+    async fn reload(&mut self) -> Result<(), RogError> {
+        Ok(())
+    }
+}
+
+impl CtrlTask for CtrlGpu {
+    fn zbus_path() -> &'static str {
+        GPU_ZBUS_PATH
+    }
+
+    async fn create_tasks(&self, signal_ctxt: SignalContext<'static>) -> Result<(), RogError> {
+        self.watch_dgpu_disable(signal_ctxt).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,187 @@
+//! The light of the numpad on the touchpad of Vivobook and Zenbook models.
+//! asusd only sets the light, the stored state is written again on start and
+//! after resume as the touchpad forgets it.
+
+use std::sync::Arc;
+
+use config_traits::StdConfig;
+use log::{info, warn};
+use rog_aura::LedBrightness;
+use rog_platform::touchpad_numpad::TouchpadNumpad;
+use serde_derive::{Deserialize, Serialize};
+use zbus::export::futures_util::lock::Mutex;
+use zbus::fdo::Error as FdoErr;
+use zbus::message::Header;
+use zbus::{interface, Connection, SignalContext};
+
+use crate::config::Config;
+use crate::ctrl_events::Hotkey;
+use crate::error::RogError;
+use crate::polkit::{self, Action};
+use crate::{CtrlTask, HotkeyListener, Reloadable};
+
+pub const NUMPAD_ZBUS_NAME: &str = "Numpad";
+pub const NUMPAD_ZBUS_PATH: &str = "/org/asuslinux";
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct NumpadConfig {
+    pub enabled: bool,
+    /// The level used while enabled, never `Off`
+    pub brightness: LedBrightness,
+}
+
+impl Default for NumpadConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            brightness: LedBrightness::High,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct CtrlNumpad {
+    numpad: Arc<TouchpadNumpad>,
+    config: Arc<Mutex<Config>>,
+}
+
+impl CtrlNumpad {
+    pub fn new(config: Arc<Mutex<Config>>) -> Result<Self, RogError> {
+        Ok(Self {
+            numpad: Arc::new(TouchpadNumpad::new()?),
+            config,
+        })
+    }
+
+    fn write_light(&self, numpad: &NumpadConfig) -> Result<(), RogError> {
+        let level = if numpad.enabled {
+            numpad.brightness
+        } else {
+            LedBrightness::Off
+        };
+        Ok(self.numpad.set_level(level.into())?)
+    }
+
+    /// Apply `change` to the stored state and light the numpad to match. The
+    /// config is only written if the touchpad took it and it changed.
+    async fn update(&self, change: impl FnOnce(&mut NumpadConfig)) -> Result<(), RogError> {
+        let mut config = self.config.lock().await;
+        let mut numpad = config.numpad.clone();
+        change(&mut numpad);
+        self.write_light(&numpad)?;
+        if config.numpad != numpad {
+            config.numpad = numpad;
+            config.write();
+        }
+        Ok(())
+    }
+
+    /// Toggle the light on the NumLock hotkey when it is set to be handled by
+    /// asusd
+    pub fn listen_hotkeys(
+        &self,
+        mut listener: HotkeyListener,
+        signal_ctxt: SignalContext<'static>,
+    ) {
+        let ctrl = self.clone();
+        tokio::spawn(async move {
+            loop {
+                match listener.recv().await {
+                    Ok(Hotkey::NumLock) => {}
+                    Ok(_) | Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+                }
+                if let Err(e) = ctrl.update(|numpad| numpad.enabled = !numpad.enabled).await {
+                    warn!("NumLock hotkey: {e}");
+                    continue;
+                }
+                ctrl.enabled_changed(&signal_ctxt).await.ok();
+            }
+        });
+    }
+}
+
+#[interface(name = "org.asuslinux.Numpad")]
+impl CtrlNumpad {
+    /// Whether the numpad is lit
+    #[zbus(property)]
+    async fn enabled(&self) -> bool {
+        self.config.lock().await.numpad.enabled
+    }
+
+    #[zbus(property)]
+    async fn set_enabled(
+        &mut self,
+        enabled: bool,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> Result<(), FdoErr> {
+        polkit::check(connection, &header, Action::ChangeLighting).await?;
+        Ok(self.update(|numpad| numpad.enabled = enabled).await?)
+    }
+
+    /// The brightness while lit, one of `Low`, `Med` or `High`
+    #[zbus(property)]
+    async fn brightness(&self) -> LedBrightness {
+        self.config.lock().await.numpad.brightness
+    }
+
+    #[zbus(property)]
+    async fn set_brightness(
+        &mut self,
+        brightness: LedBrightness,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> Result<(), FdoErr> {
+        polkit::check(connection, &header, Action::ChangeLighting).await?;
+        if brightness == LedBrightness::Off {
+            return Err(FdoErr::InvalidArgs(
+                "Use Enabled to turn the numpad light off".to_owned(),
+            ));
+        }
+        Ok(self.update(|numpad| numpad.brightness = brightness).await?)
+    }
+}
+
+impl crate::ZbusRun for CtrlNumpad {
+    async fn add_to_server(self, server: &mut Connection) {
+        Self::add_to_server_helper(self, NUMPAD_ZBUS_PATH, server).await;
+    }
+}
+
+impl Reloadable for CtrlNumpad {
+    async fn reload(&mut self) -> Result<(), RogError> {
+        let numpad = self.config.lock().await.numpad.clone();
+        self.write_light(&numpad)
+    }
+}
+
+impl CtrlTask for CtrlNumpad {
+    fn zbus_path() -> &'static str {
+        NUMPAD_ZBUS_PATH
+    }
+
+    async fn create_tasks(&self, _: SignalContext<'static>) -> Result<(), RogError> {
+        let ctrl = self.clone();
+        self.create_sys_event_tasks(
+            move |sleeping| {
+                let mut ctrl = ctrl.clone();
+                async move {
+                    if !sleeping {
+                        info!("CtrlNumpad reloading the numpad light");
+                        ctrl.reload()
+                            .await
+                            .map_err(|e| warn!("CtrlNumpad: {e}"))
+                            .ok();
+                    }
+                }
+            },
+            move |_shutting_down| async move {},
+            move |_lid_closed| async move {},
+            move |_power_plugged| async move {},
+        )
+        .await;
+        Ok(())
+    }
+}
@@ -1,26 +1,41 @@
-use std::path::Path;
 use std::process::Command;
 use std::sync::Arc;
+use std::time::Duration;
 
 use config_traits::StdConfig;
 use log::{debug, error, info, warn};
 use rog_platform::cpu::{CPUControl, CPUGovernor, CPUEPP};
-use rog_platform::platform::{GpuMode, Properties, RogPlatform, ThrottlePolicy};
+use rog_platform::platform::{GpuMode, GpuMuxMode, Properties, RogPlatform, ThrottlePolicy};
 use rog_platform::power::AsusPower;
 use zbus::export::futures_util::lock::Mutex;
 use zbus::fdo::Error as FdoErr;
+use zbus::message::Header;
 use zbus::{interface, Connection, ObjectServer, SignalContext};
 
-use crate::config::Config;
+use crate::charge_schedule::{self, ChargeRule};
+use crate::config::{Config, CONFIG_FILE};
+use crate::config_watch;
 use crate::ctrl_anime::trait_impls::{CtrlAnimeZbus, ANIME_ZBUS_NAME, ANIME_ZBUS_PATH};
+use crate::ctrl_aura::known_devices::{CtrlAuraDevices, AURA_DEVICES_ZBUS_PATH};
 use crate::ctrl_aura::trait_impls::{CtrlAuraZbus, AURA_ZBUS_NAME, AURA_ZBUS_PATH};
+use crate::ctrl_clients::{CtrlClients, CLIENTS_ZBUS_NAME, CLIENTS_ZBUS_PATH};
+use crate::ctrl_events::{CtrlEvents, Hotkey, EVENTS_ZBUS_NAME, EVENTS_ZBUS_PATH};
 use crate::ctrl_fancurves::{CtrlFanCurveZbus, FAN_CURVE_ZBUS_NAME, FAN_CURVE_ZBUS_PATH};
+use crate::ctrl_gpu::{self, CtrlGpu, GPU_ZBUS_NAME, GPU_ZBUS_PATH};
+use crate::ctrl_numpad::{CtrlNumpad, NUMPAD_ZBUS_NAME, NUMPAD_ZBUS_PATH};
 use crate::ctrl_slash::trait_impls::{CtrlSlashZbus, SLASH_ZBUS_NAME, SLASH_ZBUS_PATH};
 use crate::error::RogError;
-use crate::{task_watch_item, task_watch_item_notify, CtrlTask, ReloadAndNotify};
+use crate::first_run::{self, FirstRunState, SuggestedDefaults};
+use crate::polkit::{self, Action};
+use crate::settings_schema::{self, SettingSchema};
+use crate::{
+    task_watch_item, task_watch_item_notify, ConfigChangeListener, CtrlTask, HotkeyListener,
+    ProfileChange, ProfileChangeHook, ReloadAndNotify, TabletModeListener,
+};
 
 const PLATFORM_ZBUS_NAME: &str = "Platform";
 const PLATFORM_ZBUS_PATH: &str = "/org/asuslinux";
+const CHARGE_SCHEDULE_INTERVAL: Duration = Duration::from_secs(60);
 
 macro_rules! platform_get_value {
     ($self:ident, $property:tt, $prop_name:literal) => {
@@ -91,13 +106,17 @@ pub struct CtrlPlatform {
     platform: RogPlatform,
     cpu_control: Option<CPUControl>,
     config: Arc<Mutex<Config>>,
+    profile_hook: Arc<ProfileChangeHook>,
+    tablet_mode: TabletModeListener,
 }
 
 impl CtrlPlatform {
     pub fn new(
         config: Arc<Mutex<Config>>,
-        config_path: &Path,
+        mut config_changes: ConfigChangeListener,
         signal_context: SignalContext<'static>,
+        profile_hook: ProfileChangeHook,
+        tablet_mode: TabletModeListener,
     ) -> Result<Self, RogError> {
         let platform = RogPlatform::new()?;
         let power = AsusPower::new()?;
@@ -108,12 +127,13 @@ impl CtrlPlatform {
         }
 
         let config1 = config.clone();
-        let config_path = config_path.to_owned();
 
         let ret_self = CtrlPlatform {
             power,
             platform,
             config,
+            profile_hook: Arc::new(profile_hook),
+            tablet_mode,
             cpu_control: CPUControl::new()
                 .map_err(|e| error!("Couldn't get CPU control sysfs: {e}"))
                 .ok(),
@@ -121,51 +141,15 @@ impl CtrlPlatform {
         let mut inotify_self = ret_self.clone();
 
         tokio::spawn(async move {
-            use zbus::export::futures_util::StreamExt;
-            info!("Starting inotify watch for asusd config file");
-
-            let mut buffer = [0; 32];
-            loop {
-                // vi and vim do stupid shit causing the file watch to be removed
-                let inotify = inotify::Inotify::init().unwrap();
-                inotify
-                    .watches()
-                    .add(
-                        &config_path,
-                        inotify::WatchMask::MODIFY
-                            | inotify::WatchMask::CLOSE_WRITE
-                            | inotify::WatchMask::ATTRIB
-                            | inotify::WatchMask::CREATE,
-                    )
-                    .map_err(|e| {
-                        if e.kind() == std::io::ErrorKind::NotFound {
-                            error!("Not found: {:?}", config_path);
-                        } else {
-                            error!("Could not set asusd config inotify: {:?}", config_path);
-                        }
-                        e
-                    })
-                    .ok();
-                let mut events = inotify.into_event_stream(&mut buffer).unwrap();
-
-                while let Some(ev) = events.next().await {
-                    if let Ok(ev) = ev {
-                        if ev.mask == inotify::EventMask::IGNORED {
-                            warn!(
-                                "Something modified asusd.ron vi/vim style. Now need to reload \
-                                 inotify watch"
-                            );
-                            break;
-                        }
-                    }
-
-                    let res = config1.lock().await.read_new();
-                    if let Some(new_cfg) = res {
-                        inotify_self
-                            .reload_and_notify(&signal_context, new_cfg)
-                            .await
-                            .unwrap();
-                    }
+            while config_watch::changed(&mut config_changes, CONFIG_FILE).await {
+                let res = config1.lock().await.read_new();
+                if let Some(new_cfg) = res {
+                    inotify_self
+                        .reload_and_notify(&signal_context, new_cfg)
+                        .await
+                        .map_err(|e| warn!("Could not apply the edited {CONFIG_FILE}: {e}"))
+                        .ok();
+                    Self::notify_config_reloaded(&signal_context).await.ok();
                 }
             }
         });
@@ -173,17 +157,6 @@ impl CtrlPlatform {
         Ok(ret_self)
     }
 
-    fn set_gfx_mode(&self, mode: GpuMode) -> Result<(), RogError> {
-        self.platform.set_gpu_mux_mode(mode.to_mux_attr())?;
-        // self.update_initramfs(enable)?;
-        if mode == GpuMode::Ultimate {
-            info!("Set system-level graphics mode: Dedicated Nvidia");
-        } else {
-            info!("Set system-level graphics mode: Optimus");
-        }
-        Ok(())
-    }
-
     async fn run_ac_or_bat_cmd(&self, power_plugged: bool) {
         let prog: Vec<String> = if power_plugged {
             // AC ONLINE
@@ -219,6 +192,30 @@ impl CtrlPlatform {
         }
     }
 
+    /// Let the other controllers apply what is associated with `policy`.
+    /// Nothing is sent if `policy` is the same as last time.
+    async fn notify_profile_change(&self, policy: ThrottlePolicy) {
+        let association = self
+            .config
+            .lock()
+            .await
+            .profile_associations
+            .get(&policy)
+            .cloned()
+            .unwrap_or_default();
+        self.profile_hook.send_if_modified(|last| {
+            if last.as_ref().is_some_and(|c| c.policy == policy) {
+                return false;
+            }
+            debug!("Platform: sending profile change to {policy:?}");
+            *last = Some(ProfileChange {
+                policy,
+                association,
+            });
+            true
+        });
+    }
+
     fn check_and_set_epp(&self, enegy_pref: CPUEPP, change_epp: bool) {
         if !change_epp {
             info!("ThrottlePolicy unlinked from EPP");
@@ -261,6 +258,169 @@ impl CtrlPlatform {
             .ok();
         self.check_and_set_epp(epp, change_epp);
     }
+
+    /// Write the charge limit the schedule wants right now, if the battery
+    /// isn't already set to it. Ends a one time full charge once the battery
+    /// is full, in which case `config` needs to be saved. True if a new limit
+    /// was written.
+    fn apply_charge_limit(&self, config: &mut Config) -> Result<bool, RogError> {
+        if !self.power.has_charge_control_end_threshold() {
+            return Ok(false);
+        }
+        if config.charge_full_once
+            && self
+                .power
+                .get_status()
+                .is_ok_and(|status| status.trim() == "Full")
+        {
+            info!("Battery is full, ending the one time full charge");
+            config.charge_full_once = false;
+        }
+        let limit = charge_schedule::effective_limit(
+            config.charge_control_end_threshold,
+            config.charge_full_once,
+            &config.charge_schedule,
+            charge_schedule::local_minute(),
+            self.power.get_online().unwrap_or_default() > 0,
+            charge_schedule::dock_connected(),
+        );
+        if self.power.get_charge_control_end_threshold()? != limit {
+            info!("Setting charge limit to {limit}%");
+            self.power.set_charge_control_end_threshold(limit)?;
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    /// `apply_charge_limit`, telling clients of the new limit if it changed
+    async fn apply_charge_limit_and_notify(&self, ctxt: &SignalContext<'_>) {
+        let mut config = self.config.lock().await;
+        let full_once = config.charge_full_once;
+        let changed = self
+            .apply_charge_limit(&mut config)
+            .map_err(|e| warn!("Charge schedule: {e}"))
+            .unwrap_or_default();
+        if full_once != config.charge_full_once {
+            config.write();
+        }
+        drop(config);
+        if changed {
+            self.charge_control_end_threshold_changed(ctxt).await.ok();
+        }
+    }
+
+    /// Re-check the charge schedule every minute, on top of the checks made
+    /// on power changes
+    fn spawn_charge_scheduler(&self, signal_ctxt: SignalContext<'static>) {
+        if !self.power.has_charge_control_end_threshold() {
+            return;
+        }
+        let ctrl = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(CHARGE_SCHEDULE_INTERVAL);
+            loop {
+                interval.tick().await;
+                ctrl.apply_charge_limit_and_notify(&signal_ctxt).await;
+            }
+        });
+    }
+
+    /// Send `TabletMode` changes on as they are seen
+    pub fn listen_tablet_mode(&self, signal_ctxt: SignalContext<'static>) {
+        let ctrl = self.clone();
+        let mut listener = self.tablet_mode.clone();
+        tokio::spawn(async move {
+            while listener.changed().await.is_ok() {
+                ctrl.tablet_mode_changed(&signal_ctxt).await.ok();
+            }
+        });
+    }
+
+    /// Step to the next throttle policy, for the fan mode hotkey and
+    /// `NextThrottleThermalPolicy`
+    async fn step_throttle_thermal_policy(
+        &mut self,
+        ctxt: &SignalContext<'_>,
+    ) -> Result<(), FdoErr> {
+        let policy: ThrottlePolicy =
+            platform_get_value!(self, throttle_thermal_policy, "throttle_thermal_policy")
+                .map(|n| n.into())?;
+        let policy = ThrottlePolicy::next(policy);
+
+        if self.platform.has_throttle_thermal_policy() {
+            let change_epp = self.config.lock().await.throttle_policy_linked_epp;
+            let epp = self.get_config_epp_for_throttle(policy).await;
+            self.check_and_set_epp(epp, change_epp);
+            self.platform
+                .set_throttle_thermal_policy(policy.into())
+                .map_err(|err| {
+                    warn!("throttle_thermal_policy {}", err);
+                    FdoErr::Failed(format!("RogPlatform: throttle_thermal_policy: {err}"))
+                })?;
+            Ok(self.throttle_thermal_policy_changed(ctxt).await?)
+        } else {
+            Err(FdoErr::NotSupported(
+                "RogPlatform: throttle_thermal_policy not supported".to_owned(),
+            ))
+        }
+    }
+
+    async fn write_throttle_thermal_policy(
+        &mut self,
+        policy: ThrottlePolicy,
+    ) -> Result<(), FdoErr> {
+        // TODO: watch for external changes
+        if self.platform.has_throttle_thermal_policy() {
+            let change_epp = self.config.lock().await.throttle_policy_linked_epp;
+            let epp = self.get_config_epp_for_throttle(policy).await;
+            self.check_and_set_epp(epp, change_epp);
+            self.config.lock().await.write();
+            self.platform
+                .set_throttle_thermal_policy(policy.into())
+                .map_err(|err| {
+                    warn!("throttle_thermal_policy {}", err);
+                    FdoErr::Failed(format!("RogPlatform: throttle_thermal_policy: {err}"))
+                })
+        } else {
+            Err(FdoErr::NotSupported(
+                "RogPlatform: throttle_thermal_policy not supported".to_owned(),
+            ))
+        }
+    }
+
+    async fn write_charge_control_end_threshold(&mut self, limit: u8) -> Result<(), FdoErr> {
+        if !(20..=100).contains(&limit) {
+            return Err(RogError::ChargeLimit(limit))?;
+        }
+        let mut config = self.config.lock().await;
+        config.charge_control_end_threshold = limit;
+        self.apply_charge_limit(&mut config)?;
+        config.write();
+        Ok(())
+    }
+
+    /// Step the throttle policy on the fan mode hotkey when it is set to be
+    /// handled by asusd
+    pub fn listen_hotkeys(
+        &self,
+        mut listener: HotkeyListener,
+        signal_ctxt: SignalContext<'static>,
+    ) {
+        let mut ctrl = self.clone();
+        tokio::spawn(async move {
+            loop {
+                match listener.recv().await {
+                    Ok(Hotkey::FanMode) => {}
+                    Ok(_) | Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+                }
+                ctrl.step_throttle_thermal_policy(&signal_ctxt)
+                    .await
+                    .map_err(|e| warn!("Fan mode hotkey: {e}"))
+                    .ok();
+            }
+        });
+    }
 }
 
 #[interface(name = "org.asuslinux.Platform")]
@@ -270,6 +430,80 @@ impl CtrlPlatform {
         crate::VERSION.to_string()
     }
 
+    /// True while a convertible is folded in to tablet mode. Always false on
+    /// laptops without a tablet mode switch.
+    #[zbus(property)]
+    async fn tablet_mode(&self) -> bool {
+        *self.tablet_mode.borrow()
+    }
+
+    /// Frame render and write time histograms of the software effects, in
+    /// the Prometheus text format
+    async fn effect_metrics(&self) -> String {
+        effect_metrics::metrics_text()
+    }
+
+    /// Whether the config was just created, what was found, and the defaults
+    /// a first run wizard can suggest. The interfaces found are given by
+    /// `SupportedInterfaces`.
+    #[zbus(property)]
+    async fn first_run_state(&self) -> FirstRunState {
+        FirstRunState {
+            pending: self.config.lock().await.first_run_pending,
+            fresh_configs: first_run::fresh_configs(),
+            properties: self.supported_properties().await,
+            suggested: SuggestedDefaults::new(self.power.has_charge_control_end_threshold()),
+        }
+    }
+
+    /// Apply each suggested default this laptop supports
+    async fn apply_suggested_defaults(
+        &mut self,
+        #[zbus(signal_context)] ctxt: SignalContext<'_>,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> Result<(), FdoErr> {
+        polkit::check(connection, &header, Action::ChangePlatformSettings).await?;
+        let suggested = SuggestedDefaults::new(self.power.has_charge_control_end_threshold());
+        if self.platform.has_throttle_thermal_policy() {
+            let on_ac = self.power.get_online().unwrap_or_default() > 0;
+            let mut config = self.config.lock().await;
+            config.throttle_policy_on_ac = suggested.throttle_policy_on_ac;
+            config.throttle_policy_on_battery = suggested.throttle_policy_on_battery;
+            drop(config);
+            let policy = if on_ac {
+                suggested.throttle_policy_on_ac
+            } else {
+                suggested.throttle_policy_on_battery
+            };
+            self.write_throttle_thermal_policy(policy).await?;
+            self.throttle_thermal_policy_changed(&ctxt).await.ok();
+        }
+        if suggested.charge_limit != 0 {
+            self.write_charge_control_end_threshold(suggested.charge_limit)
+                .await?;
+            self.charge_control_end_threshold_changed(&ctxt).await.ok();
+        }
+        self.config.lock().await.write();
+        Ok(())
+    }
+
+    /// Mark the first run as done so clients stop offering their wizard
+    async fn complete_first_run(
+        &self,
+        #[zbus(signal_context)] ctxt: SignalContext<'_>,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> Result<(), FdoErr> {
+        polkit::check(connection, &header, Action::ChangePlatformSettings).await?;
+        let mut config = self.config.lock().await;
+        config.first_run_pending = false;
+        config.write();
+        drop(config);
+        self.first_run_state_changed(&ctxt).await.ok();
+        Ok(())
+    }
+
     /// Returns a list of property names that this system supports
     async fn supported_properties(&self) -> Vec<Properties> {
         let mut supported = Vec::new();
@@ -345,6 +579,30 @@ impl CtrlPlatform {
         {
             interfaces.push(FAN_CURVE_ZBUS_NAME.to_owned());
         }
+        if server.interface::<_, CtrlGpu>(GPU_ZBUS_PATH).await.is_ok() {
+            interfaces.push(GPU_ZBUS_NAME.to_owned());
+        }
+        if server
+            .interface::<_, CtrlNumpad>(NUMPAD_ZBUS_PATH)
+            .await
+            .is_ok()
+        {
+            interfaces.push(NUMPAD_ZBUS_NAME.to_owned());
+        }
+        if server
+            .interface::<_, CtrlClients>(CLIENTS_ZBUS_PATH)
+            .await
+            .is_ok()
+        {
+            interfaces.push(CLIENTS_ZBUS_NAME.to_owned());
+        }
+        if server
+            .interface::<_, CtrlEvents>(EVENTS_ZBUS_PATH)
+            .await
+            .is_ok()
+        {
+            interfaces.push(EVENTS_ZBUS_NAME.to_owned());
+        }
         if server
             .interface::<_, CtrlPlatform>(PLATFORM_ZBUS_PATH)
             .await
@@ -362,6 +620,41 @@ impl CtrlPlatform {
         interfaces
     }
 
+    /// The settings this laptop has and the values each takes, for clients to
+    /// build their options from
+    async fn settings_schema(
+        &self,
+        #[zbus(object_server)] server: &ObjectServer,
+    ) -> Vec<SettingSchema> {
+        let mut schema = Vec::new();
+        if self.platform.has_throttle_thermal_policy() {
+            schema.push(settings_schema::profile(PLATFORM_ZBUS_PATH));
+        }
+        if let Ok(devices) = server
+            .interface::<_, CtrlAuraDevices>(AURA_DEVICES_ZBUS_PATH)
+            .await
+        {
+            for device in devices.get().await.connected().await {
+                schema.extend(settings_schema::aura(&device));
+            }
+        }
+        if server
+            .interface::<_, CtrlNumpad>(NUMPAD_ZBUS_PATH)
+            .await
+            .is_ok()
+        {
+            schema.push(settings_schema::numpad(NUMPAD_ZBUS_PATH));
+        }
+        if server
+            .interface::<_, CtrlSlashZbus>(SLASH_ZBUS_PATH)
+            .await
+            .is_ok()
+        {
+            schema.push(settings_schema::slash(SLASH_ZBUS_PATH));
+        }
+        schema
+    }
+
     #[zbus(property)]
     fn charge_control_end_threshold(&self) -> Result<u8, FdoErr> {
         let limit = self.power.get_charge_control_end_threshold()?;
@@ -369,13 +662,63 @@ impl CtrlPlatform {
     }
 
     #[zbus(property)]
-    async fn set_charge_control_end_threshold(&mut self, limit: u8) -> Result<(), FdoErr> {
-        if !(20..=100).contains(&limit) {
-            return Err(RogError::ChargeLimit(limit))?;
+    async fn set_charge_control_end_threshold(
+        &mut self,
+        limit: u8,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> Result<(), FdoErr> {
+        polkit::check(connection, &header, Action::ChangeChargeLimit).await?;
+        self.write_charge_control_end_threshold(limit).await
+    }
+
+    /// The charge limit rules, in the order they are checked
+    async fn charge_schedule(&self) -> Vec<ChargeRule> {
+        self.config.lock().await.charge_schedule.clone()
+    }
+
+    /// Replace the charge limit rules. The first rule that matches the time
+    /// and power source sets the limit, the charge control end threshold is
+    /// used if none do.
+    async fn set_charge_schedule(
+        &self,
+        rules: Vec<ChargeRule>,
+        #[zbus(signal_context)] ctxt: SignalContext<'_>,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> Result<(), FdoErr> {
+        polkit::check(connection, &header, Action::ChangeChargeLimit).await?;
+        for rule in &rules {
+            rule.validate().map_err(FdoErr::InvalidArgs)?;
         }
-        self.power.set_charge_control_end_threshold(limit)?;
-        self.config.lock().await.charge_control_end_threshold = limit;
-        self.config.lock().await.write();
+        let mut config = self.config.lock().await;
+        config.charge_schedule = rules;
+        self.apply_charge_limit(&mut config)?;
+        config.write();
+        drop(config);
+        self.charge_control_end_threshold_changed(&ctxt).await.ok();
+        Ok(())
+    }
+
+    /// Charge to 100% until the battery is next full, then go back to the
+    /// scheduled or configured limit
+    #[zbus(property)]
+    async fn charge_full_once(&self) -> bool {
+        self.config.lock().await.charge_full_once
+    }
+
+    #[zbus(property)]
+    async fn set_charge_full_once(
+        &mut self,
+        full_once: bool,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> Result<(), FdoErr> {
+        polkit::check(connection, &header, Action::ChangeChargeLimit).await?;
+        let mut config = self.config.lock().await;
+        config.charge_full_once = full_once;
+        self.apply_charge_limit(&mut config)?;
+        config.write();
         Ok(())
     }
 
@@ -387,20 +730,19 @@ impl CtrlPlatform {
         })
     }
 
+    /// Deprecated, use `SetMuxMode` on `org.asuslinux.Gpu`. Takes the same
+    /// checks, and a `GpuMode` where only `Ultimate` selects the dGPU.
     #[zbus(property)]
-    async fn set_gpu_mux_mode(&mut self, mode: u8) -> Result<(), FdoErr> {
-        if self.platform.has_gpu_mux_mode() {
-            self.set_gfx_mode(mode.into()).map_err(|err| {
-                warn!("set_gpu_mux_mode {}", err);
-                FdoErr::Failed(format!("RogPlatform: set_gpu_mux_mode: {err}"))
-            })?;
-            self.config.lock().await.write();
-        } else {
-            return Err(FdoErr::NotSupported(
-                "RogPlatform: set_gpu_mux_mode not supported".to_owned(),
-            ));
-        }
-        Ok(())
+    async fn set_gpu_mux_mode(
+        &mut self,
+        mode: u8,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> Result<(), FdoErr> {
+        polkit::check(connection, &header, Action::ChangeGpuMode).await?;
+        let mode = GpuMuxMode::from(GpuMode::from(mode).to_mux_attr());
+        let ctxt = CtrlGpu::signal_context(connection)?;
+        ctrl_gpu::write_mux_mode(&self.platform, mode, &ctxt).await
     }
 
     /// Toggle to next platform_profile. Names provided by `Profiles`.
@@ -408,28 +750,11 @@ impl CtrlPlatform {
     async fn next_throttle_thermal_policy(
         &mut self,
         #[zbus(signal_context)] ctxt: SignalContext<'_>,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
     ) -> Result<(), FdoErr> {
-        let policy: ThrottlePolicy =
-            platform_get_value!(self, throttle_thermal_policy, "throttle_thermal_policy")
-                .map(|n| n.into())?;
-        let policy = ThrottlePolicy::next(policy);
-
-        if self.platform.has_throttle_thermal_policy() {
-            let change_epp = self.config.lock().await.throttle_policy_linked_epp;
-            let epp = self.get_config_epp_for_throttle(policy).await;
-            self.check_and_set_epp(epp, change_epp);
-            self.platform
-                .set_throttle_thermal_policy(policy.into())
-                .map_err(|err| {
-                    warn!("throttle_thermal_policy {}", err);
-                    FdoErr::Failed(format!("RogPlatform: throttle_thermal_policy: {err}"))
-                })?;
-            Ok(self.throttle_thermal_policy_changed(&ctxt).await?)
-        } else {
-            Err(FdoErr::NotSupported(
-                "RogPlatform: throttle_thermal_policy not supported".to_owned(),
-            ))
-        }
+        polkit::check(connection, &header, Action::ChangePowerProfile).await?;
+        self.step_throttle_thermal_policy(&ctxt).await
     }
 
     #[zbus(property)]
@@ -439,24 +764,14 @@ impl CtrlPlatform {
     }
 
     #[zbus(property)]
-    async fn set_throttle_thermal_policy(&mut self, policy: ThrottlePolicy) -> Result<(), FdoErr> {
-        // TODO: watch for external changes
-        if self.platform.has_throttle_thermal_policy() {
-            let change_epp = self.config.lock().await.throttle_policy_linked_epp;
-            let epp = self.get_config_epp_for_throttle(policy).await;
-            self.check_and_set_epp(epp, change_epp);
-            self.config.lock().await.write();
-            self.platform
-                .set_throttle_thermal_policy(policy.into())
-                .map_err(|err| {
-                    warn!("throttle_thermal_policy {}", err);
-                    FdoErr::Failed(format!("RogPlatform: throttle_thermal_policy: {err}"))
-                })
-        } else {
-            Err(FdoErr::NotSupported(
-                "RogPlatform: throttle_thermal_policy not supported".to_owned(),
-            ))
-        }
+    async fn set_throttle_thermal_policy(
+        &mut self,
+        policy: ThrottlePolicy,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> Result<(), FdoErr> {
+        polkit::check(connection, &header, Action::ChangePowerProfile).await?;
+        self.write_throttle_thermal_policy(policy).await
     }
 
     #[zbus(property)]
@@ -465,7 +780,13 @@ impl CtrlPlatform {
     }
 
     #[zbus(property)]
-    async fn set_throttle_policy_linked_epp(&self, linked: bool) -> Result<(), zbus::Error> {
+    async fn set_throttle_policy_linked_epp(
+        &self,
+        linked: bool,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> Result<(), zbus::Error> {
+        polkit::check(connection, &header, Action::ChangePowerProfile).await?;
         self.config.lock().await.throttle_policy_linked_epp = linked;
         self.config.lock().await.write();
         Ok(())
@@ -480,9 +801,12 @@ impl CtrlPlatform {
     async fn set_throttle_policy_on_battery(
         &mut self,
         policy: ThrottlePolicy,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
     ) -> Result<(), FdoErr> {
+        polkit::check(connection, &header, Action::ChangePowerProfile).await?;
         self.config.lock().await.throttle_policy_on_battery = policy;
-        self.set_throttle_thermal_policy(policy).await?;
+        self.write_throttle_thermal_policy(policy).await?;
         self.config.lock().await.write();
         Ok(())
     }
@@ -493,9 +817,15 @@ impl CtrlPlatform {
     }
 
     #[zbus(property)]
-    async fn set_throttle_policy_on_ac(&mut self, policy: ThrottlePolicy) -> Result<(), FdoErr> {
+    async fn set_throttle_policy_on_ac(
+        &mut self,
+        policy: ThrottlePolicy,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> Result<(), FdoErr> {
+        polkit::check(connection, &header, Action::ChangePowerProfile).await?;
         self.config.lock().await.throttle_policy_on_ac = policy;
-        self.set_throttle_thermal_policy(policy).await?;
+        self.write_throttle_thermal_policy(policy).await?;
         self.config.lock().await.write();
         Ok(())
     }
@@ -508,7 +838,13 @@ impl CtrlPlatform {
     }
 
     #[zbus(property)]
-    async fn set_throttle_quiet_epp(&mut self, epp: CPUEPP) -> Result<(), FdoErr> {
+    async fn set_throttle_quiet_epp(
+        &mut self,
+        epp: CPUEPP,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> Result<(), FdoErr> {
+        polkit::check(connection, &header, Action::ChangePowerProfile).await?;
         let change_pp = self.config.lock().await.throttle_policy_linked_epp;
         self.config.lock().await.throttle_quiet_epp = epp;
         self.check_and_set_epp(epp, change_pp);
@@ -524,7 +860,13 @@ impl CtrlPlatform {
     }
 
     #[zbus(property)]
-    async fn set_throttle_balanced_epp(&mut self, epp: CPUEPP) -> Result<(), FdoErr> {
+    async fn set_throttle_balanced_epp(
+        &mut self,
+        epp: CPUEPP,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> Result<(), FdoErr> {
+        polkit::check(connection, &header, Action::ChangePowerProfile).await?;
         let change_pp = self.config.lock().await.throttle_policy_linked_epp;
         self.config.lock().await.throttle_balanced_epp = epp;
         self.check_and_set_epp(epp, change_pp);
@@ -540,7 +882,13 @@ impl CtrlPlatform {
     }
 
     #[zbus(property)]
-    async fn set_throttle_performance_epp(&mut self, epp: CPUEPP) -> Result<(), FdoErr> {
+    async fn set_throttle_performance_epp(
+        &mut self,
+        epp: CPUEPP,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> Result<(), FdoErr> {
+        polkit::check(connection, &header, Action::ChangePowerProfile).await?;
         let change_pp = self.config.lock().await.throttle_policy_linked_epp;
         self.config.lock().await.throttle_performance_epp = epp;
         self.check_and_set_epp(epp, change_pp);
@@ -556,7 +904,13 @@ impl CtrlPlatform {
     }
 
     #[zbus(property)]
-    async fn set_panel_od(&mut self, overdrive: bool) -> Result<(), FdoErr> {
+    async fn set_panel_od(
+        &mut self,
+        overdrive: bool,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> Result<(), FdoErr> {
+        polkit::check(connection, &header, Action::ChangePlatformSettings).await?;
         platform_set_value!(self, panel_od, "panel_od", overdrive)?;
         self.config.lock().await.write();
         Ok(())
@@ -570,7 +924,13 @@ impl CtrlPlatform {
     }
 
     #[zbus(property)]
-    async fn set_boot_sound(&mut self, on: bool) -> Result<(), FdoErr> {
+    async fn set_boot_sound(
+        &mut self,
+        on: bool,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> Result<(), FdoErr> {
+        polkit::check(connection, &header, Action::ChangePlatformSettings).await?;
         platform_set_value!(self, boot_sound, "boot_sound", on)?;
         self.config.lock().await.write();
         Ok(())
@@ -584,13 +944,21 @@ impl CtrlPlatform {
     }
 
     #[zbus(property)]
-    async fn set_mini_led_mode(&mut self, on: bool) -> Result<(), FdoErr> {
+    async fn set_mini_led_mode(
+        &mut self,
+        on: bool,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> Result<(), FdoErr> {
+        polkit::check(connection, &header, Action::ChangePlatformSettings).await?;
         platform_set_value!(self, mini_led_mode, "mini_led_mode", on)?;
         self.config.lock().await.write();
         Ok(())
     }
 
-    #[zbus(property)]
+    /// Deprecated, use `DgpuDisable` on `org.asuslinux.Gpu`, which also
+    /// signals changes
+    #[zbus(property(emits_changed_signal = "false"))]
     fn dgpu_disable(&self) -> Result<bool, FdoErr> {
         platform_get_value!(self, dgpu_disable, "dgpu_disable")
     }
@@ -610,7 +978,13 @@ impl CtrlPlatform {
     }
 
     #[zbus(property)]
-    async fn set_ppt_pl1_spl(&mut self, value: u8) -> Result<(), FdoErr> {
+    async fn set_ppt_pl1_spl(
+        &mut self,
+        value: u8,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> Result<(), FdoErr> {
+        polkit::check(connection, &header, Action::ChangePowerProfile).await?;
         platform_ppt_set_value!(self, ppt_pl1_spl, "ppt_pl1_spl", value)?;
         self.config.lock().await.write();
         Ok(())
@@ -625,7 +999,13 @@ impl CtrlPlatform {
     }
 
     #[zbus(property)]
-    async fn set_ppt_pl2_sppt(&mut self, value: u8) -> Result<(), FdoErr> {
+    async fn set_ppt_pl2_sppt(
+        &mut self,
+        value: u8,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> Result<(), FdoErr> {
+        polkit::check(connection, &header, Action::ChangePowerProfile).await?;
         platform_ppt_set_value!(self, ppt_pl2_sppt, "ppt_pl2_sppt", value)?;
         self.config.lock().await.write();
         Ok(())
@@ -639,7 +1019,13 @@ impl CtrlPlatform {
     }
 
     #[zbus(property)]
-    async fn set_ppt_fppt(&mut self, value: u8) -> Result<(), FdoErr> {
+    async fn set_ppt_fppt(
+        &mut self,
+        value: u8,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> Result<(), FdoErr> {
+        polkit::check(connection, &header, Action::ChangePowerProfile).await?;
         platform_ppt_set_value!(self, ppt_fppt, "ppt_fppt", value)?;
         self.config.lock().await.write();
         Ok(())
@@ -653,7 +1039,13 @@ impl CtrlPlatform {
     }
 
     #[zbus(property)]
-    async fn set_ppt_apu_sppt(&mut self, value: u8) -> Result<(), FdoErr> {
+    async fn set_ppt_apu_sppt(
+        &mut self,
+        value: u8,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> Result<(), FdoErr> {
+        polkit::check(connection, &header, Action::ChangePowerProfile).await?;
         platform_ppt_set_value!(self, ppt_apu_sppt, "ppt_apu_sppt", value)?;
         self.config.lock().await.write();
         Ok(())
@@ -667,7 +1059,13 @@ impl CtrlPlatform {
     }
 
     #[zbus(property)]
-    async fn set_ppt_platform_sppt(&mut self, value: u8) -> Result<(), FdoErr> {
+    async fn set_ppt_platform_sppt(
+        &mut self,
+        value: u8,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> Result<(), FdoErr> {
+        polkit::check(connection, &header, Action::ChangePowerProfile).await?;
         platform_ppt_set_value!(self, ppt_platform_sppt, "ppt_platform_sppt", value)?;
         self.config.lock().await.write();
         Ok(())
@@ -681,7 +1079,13 @@ impl CtrlPlatform {
     }
 
     #[zbus(property)]
-    async fn set_nv_dynamic_boost(&mut self, value: u8) -> Result<(), FdoErr> {
+    async fn set_nv_dynamic_boost(
+        &mut self,
+        value: u8,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> Result<(), FdoErr> {
+        polkit::check(connection, &header, Action::ChangePowerProfile).await?;
         platform_ppt_set_value!(self, nv_dynamic_boost, "nv_dynamic_boost", value)?;
         self.config.lock().await.write();
         Ok(())
@@ -695,11 +1099,22 @@ impl CtrlPlatform {
     }
 
     #[zbus(property)]
-    async fn set_nv_temp_target(&mut self, value: u8) -> Result<(), FdoErr> {
+    async fn set_nv_temp_target(
+        &mut self,
+        value: u8,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> Result<(), FdoErr> {
+        polkit::check(connection, &header, Action::ChangePowerProfile).await?;
         platform_ppt_set_value!(self, nv_temp_target, "nv_temp_target", value)?;
         self.config.lock().await.write();
         Ok(())
     }
+
+    /// Sent once an edit of `asusd.ron` made outside of asusd has been
+    /// applied
+    #[zbus(signal)]
+    async fn notify_config_reloaded(ctxt: &SignalContext<'_>) -> zbus::Result<()>;
 }
 
 impl crate::ZbusRun for CtrlPlatform {
@@ -714,15 +1129,14 @@ impl ReloadAndNotify for CtrlPlatform {
     async fn reload_and_notify(
         &mut self,
         signal_context: &SignalContext<'static>,
-        data: Self::Data,
+        mut data: Self::Data,
     ) -> Result<(), RogError> {
         let mut config = self.config.lock().await;
         if *config != data {
             info!("asusd.ron updated externally, reloading and updating internal copy");
 
             if self.power.has_charge_control_end_threshold() {
-                self.power
-                    .set_charge_control_end_threshold(data.charge_control_end_threshold)?;
+                self.apply_charge_limit(&mut data)?;
                 self.charge_control_end_threshold_changed(signal_context)
                     .await?;
             }
@@ -816,11 +1230,7 @@ impl crate::Reloadable for CtrlPlatform {
         ppt_reload!(nv_dynamic_boost, "nv_dynamic_boost");
         ppt_reload!(nv_temp_target, "nv_temp_target");
 
-        if self.power.has_charge_control_end_threshold() {
-            self.power.set_charge_control_end_threshold(
-                self.config.lock().await.charge_control_end_threshold,
-            )?;
-        }
+        self.apply_charge_limit(&mut *self.config.lock().await)?;
 
         if let Ok(power_plugged) = self.power.get_online() {
             self.config.lock().await.last_power_plugged = power_plugged;
@@ -845,8 +1255,6 @@ impl CtrlPlatform {
 
     task_watch_item_notify!(boot_sound platform);
 
-    task_watch_item_notify!(dgpu_disable platform);
-
     task_watch_item_notify!(egpu_enable platform);
 
     // NOTE: see note further below
@@ -876,9 +1284,12 @@ impl CtrlTask for CtrlPlatform {
         let platform1 = self.clone();
         let platform2 = self.clone();
         let platform3 = self.clone();
+        let ctxt1 = signal_ctxt.clone();
+        let ctxt3 = signal_ctxt.clone();
         self.create_sys_event_tasks(
             move |sleeping| {
                 let platform1 = platform1.clone();
+                let ctxt1 = ctxt1.clone();
                 async move {
                     info!("RogPlatform reloading panel_od");
                     if !sleeping && platform1.platform.has_panel_od() {
@@ -892,17 +1303,16 @@ impl CtrlTask for CtrlPlatform {
                             .ok();
                     }
                     if sleeping && platform1.power.has_charge_control_end_threshold() {
-                        platform1.config.lock().await.charge_control_end_threshold = platform1
-                            .power
-                            .get_charge_control_end_threshold()
-                            .unwrap_or(100);
-                    } else if !sleeping && platform1.power.has_charge_control_end_threshold() {
-                        platform1
-                            .power
-                            .set_charge_control_end_threshold(
-                                platform1.config.lock().await.charge_control_end_threshold,
-                            )
-                            .ok();
+                        let mut config = platform1.config.lock().await;
+                        // A scheduled limit must not replace the configured one
+                        if config.charge_schedule.is_empty() && !config.charge_full_once {
+                            config.charge_control_end_threshold = platform1
+                                .power
+                                .get_charge_control_end_threshold()
+                                .unwrap_or(100);
+                        }
+                    } else if !sleeping {
+                        platform1.apply_charge_limit_and_notify(&ctxt1).await;
                     }
                     if let Ok(power_plugged) = platform1.power.get_online() {
                         if platform1.config.lock().await.last_power_plugged != power_plugged {
@@ -944,8 +1354,10 @@ impl CtrlTask for CtrlPlatform {
             },
             move |power_plugged| {
                 let platform3 = platform3.clone();
+                let ctxt3 = ctxt3.clone();
                 // power change
                 async move {
+                    platform3.apply_charge_limit_and_notify(&ctxt3).await;
                     if platform3.platform.has_throttle_thermal_policy() {
                         let change_epp = platform3.config.lock().await.throttle_policy_linked_epp;
                         platform3
@@ -958,6 +1370,8 @@ impl CtrlTask for CtrlPlatform {
         )
         .await;
 
+        self.spawn_charge_scheduler(signal_ctxt.clone());
+
         // This spawns a new task for every item.
         // TODO: find a better way to manage this
         self.watch_panel_od(signal_ctxt.clone()).await?;
@@ -965,7 +1379,6 @@ impl CtrlTask for CtrlPlatform {
         self.watch_charge_control_end_threshold(signal_ctxt.clone())
             .await?;
 
-        self.watch_dgpu_disable(signal_ctxt.clone()).await?;
         self.watch_egpu_enable(signal_ctxt.clone()).await?;
 
         // NOTE: Can't have this as a watch because on a write to it, it reverts back to
@@ -1002,6 +1415,9 @@ impl CtrlTask for CtrlPlatform {
                         let change_epp = ctrl.config.lock().await.throttle_policy_linked_epp;
                         let epp = ctrl.get_config_epp_for_throttle(profile).await;
                         ctrl.check_and_set_epp(epp, change_epp);
+                        // Every change of policy, from D-Bus, Fn+F5, or power
+                        // source, ends up here
+                        ctrl.notify_profile_change(profile).await;
                     }
                 }
             }
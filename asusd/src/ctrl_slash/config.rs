@@ -3,27 +3,43 @@ use rog_slash::{DeviceState, SlashMode};
 use serde_derive::{Deserialize, Serialize};
 
 const CONFIG_FILE: &str = "slash.ron";
+const CONFIG_VERSION: u32 = 1;
 
 /// Config for base system actions for the anime display
 #[derive(Deserialize, Serialize, Debug)]
 pub struct SlashConfig {
+    /// Version of the file format, upgraded on load
+    #[serde(default)]
+    pub version: u32,
     pub slash_enabled: bool,
     pub slash_brightness: u8,
     pub slash_interval: u8,
     pub slash_mode: SlashMode,
+    /// Turn the Slash off while the laptop is suspended
+    #[serde(default)]
+    pub off_when_suspended: bool,
+    /// Save the Slash as off when the laptop powers down, so it stays dark
+    /// through boot until asusd starts
+    #[serde(default)]
+    pub off_at_boot: bool,
 }
 
 impl Default for SlashConfig {
     fn default() -> Self {
         SlashConfig {
+            version: CONFIG_VERSION,
             slash_enabled: true,
             slash_brightness: 255,
             slash_interval: 0,
             slash_mode: SlashMode::Bounce,
+            off_when_suspended: true,
+            off_at_boot: false,
         }
     }
 }
 impl StdConfig for SlashConfig {
+    const VERSION: Option<u32> = Some(CONFIG_VERSION);
+
     fn new() -> Self {
         Self::default()
     }
@@ -35,6 +51,10 @@ impl StdConfig for SlashConfig {
     fn config_dir() -> std::path::PathBuf {
         std::path::PathBuf::from(crate::CONFIG_PATH_BASE)
     }
+
+    fn set_version(&mut self, version: u32) {
+        self.version = version;
+    }
 }
 
 impl StdConfigLoad for SlashConfig {}
@@ -1,11 +1,11 @@
 pub mod config;
 pub mod trait_impls;
 
+use log::info;
 use rog_platform::hid_raw::HidRaw;
 use rog_platform::usb_raw::USBRaw;
-use rog_slash::error::SlashError;
-use rog_slash::usb::{get_slash_type, pkt_set_mode, pkt_set_options, pkts_for_init};
-use rog_slash::{SlashMode, SlashType};
+use rog_slash::usb::{get_slash_type, pkt_save, pkt_set_mode, pkt_set_options, pkts_for_init};
+use rog_slash::SlashMode;
 
 use crate::ctrl_slash::config::SlashConfig;
 use crate::error::RogError;
@@ -54,10 +54,10 @@ impl CtrlSlash {
             return Err(RogError::NotSupported);
         };
 
+        // The USB device is there, an unknown board is shown with the GA403
+        // layout
         let slash_type = get_slash_type()?;
-        if slash_type == SlashType::Unknown {
-            return Err(RogError::Slash(SlashError::NoDevice));
-        }
+        info!("Slash device {slash_type:?} found");
 
         let ctrl = CtrlSlash {
             node,
@@ -97,6 +97,23 @@ impl CtrlSlash {
         Ok(())
     }
 
+    /// Show the Slash with the stored options, or turn it off without
+    /// changing them
+    pub fn show(&self, on: bool) -> Result<(), RogError> {
+        self.set_options(
+            on && self.config.slash_enabled,
+            self.config.slash_brightness,
+            self.config.slash_interval,
+        )
+    }
+
+    /// Turn the Slash off and save that to the device, which keeps it over a
+    /// power cycle
+    pub fn save_off(&self) -> Result<(), RogError> {
+        self.show(false)?;
+        self.node.write_bytes(&pkt_save())
+    }
+
     pub fn set_slash_mode(&self, slash_mode: SlashMode) -> Result<(), RogError> {
         let command_packets = pkt_set_mode(slash_mode);
         self.node.write_bytes(&command_packets[0])?;
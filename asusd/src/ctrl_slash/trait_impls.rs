@@ -1,14 +1,19 @@
 use std::sync::Arc;
 
 use config_traits::StdConfig;
-use log::warn;
+use log::{info, warn};
 use rog_slash::usb::{pkt_set_mode, pkt_set_options};
 use rog_slash::{DeviceState, SlashMode};
 use zbus::export::futures_util::lock::Mutex;
+use zbus::message::Header;
 use zbus::{interface, Connection, SignalContext};
 
+use crate::config_watch;
 use crate::ctrl_slash::CtrlSlash;
 use crate::error::RogError;
+use crate::polkit::{self, Action};
+use crate::shutdown::{self, ShutdownPolicy};
+use crate::{ConfigChangeListener, ShutdownListener};
 
 pub const SLASH_ZBUS_NAME: &str = "Slash";
 pub const SLASH_ZBUS_PATH: &str = "/org/asuslinux";
@@ -23,6 +28,59 @@ impl crate::ZbusRun for CtrlSlashZbus {
     }
 }
 
+impl CtrlSlashZbus {
+    /// Apply `slash.ron` each time it is edited outside of asusd
+    pub fn listen_config_changes(
+        &self,
+        mut listener: ConfigChangeListener,
+        signal_ctxt: SignalContext<'static>,
+    ) {
+        let ctrl = self.clone();
+        tokio::spawn(async move {
+            let file_name = ctrl.0.lock().await.config.file_name();
+            while config_watch::changed(&mut listener, &file_name).await {
+                let mut lock = ctrl.0.lock().await;
+                let Some(edited) = lock.config.read_new() else {
+                    continue;
+                };
+                info!("{file_name} was edited, applying it");
+                lock.config = edited;
+                lock.set_options(
+                    lock.config.slash_enabled,
+                    lock.config.slash_brightness,
+                    lock.config.slash_interval,
+                )
+                .and_then(|_| lock.set_slash_mode(lock.config.slash_mode))
+                .map_err(|e| warn!("Could not apply the edited {file_name}: {e}"))
+                .ok();
+                drop(lock);
+                Self::notify_config_reloaded(&signal_ctxt).await.ok();
+            }
+        });
+    }
+
+    /// Turn the Slash off for the `AllOff` shutdown policy, without changing
+    /// `slash.ron`. It has no power states of its own to write otherwise.
+    pub fn listen_shutdown(&self, mut listener: ShutdownListener) {
+        let ctrl = self.clone();
+        tokio::spawn(async move {
+            while let Some(shutdown) = shutdown::requested(&mut listener).await {
+                if shutdown.policy != ShutdownPolicy::AllOff {
+                    continue;
+                }
+                let lock = ctrl.0.lock().await;
+                lock.set_options(
+                    false,
+                    lock.config.slash_brightness,
+                    lock.config.slash_interval,
+                )
+                .map_err(|e| warn!("ctrl_slash::listen_shutdown {e}"))
+                .ok();
+            }
+        });
+    }
+}
+
 #[interface(name = "org.asuslinux.Slash")]
 impl CtrlSlashZbus {
     /// Get enabled or not
@@ -33,7 +91,14 @@ impl CtrlSlashZbus {
     }
 
     /// Set enabled true or false
-    async fn set_enabled(&self, enabled: bool) {
+    #[zbus(property)]
+    async fn set_enabled(
+        &self,
+        enabled: bool,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> zbus::fdo::Result<()> {
+        polkit::check(connection, &header, Action::ChangeLighting).await?;
         let mut lock = self.0.lock().await;
         let brightness = if enabled && lock.config.slash_brightness == 0 {
             0x88
@@ -54,6 +119,7 @@ impl CtrlSlashZbus {
         lock.config.slash_enabled = enabled;
         lock.config.slash_brightness = brightness;
         lock.config.write();
+        Ok(())
     }
 
     /// Get brightness level
@@ -64,7 +130,14 @@ impl CtrlSlashZbus {
     }
 
     /// Set brightness level
-    async fn set_brightness(&self, brightness: u8) {
+    #[zbus(property)]
+    async fn set_brightness(
+        &self,
+        brightness: u8,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> zbus::fdo::Result<()> {
+        polkit::check(connection, &header, Action::ChangeLighting).await?;
         let mut lock = self.0.lock().await;
         let enabled = brightness > 0;
         lock.node
@@ -81,6 +154,7 @@ impl CtrlSlashZbus {
         lock.config.slash_enabled = enabled;
         lock.config.slash_brightness = brightness;
         lock.config.write();
+        Ok(())
     }
 
     #[zbus(property)]
@@ -90,7 +164,14 @@ impl CtrlSlashZbus {
     }
 
     /// Set interval between slash animations (0-255)
-    async fn set_interval(&self, interval: u8) {
+    #[zbus(property)]
+    async fn set_interval(
+        &self,
+        interval: u8,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> zbus::fdo::Result<()> {
+        polkit::check(connection, &header, Action::ChangeLighting).await?;
         let mut lock = self.0.lock().await;
         lock.node
             .write_bytes(&pkt_set_options(
@@ -105,16 +186,24 @@ impl CtrlSlashZbus {
 
         lock.config.slash_interval = interval;
         lock.config.write();
+        Ok(())
     }
 
     #[zbus(property)]
-    async fn slash_mode(&self) -> u8 {
+    async fn slash_mode(&self) -> SlashMode {
         let lock = self.0.lock().await;
-        lock.config.slash_interval
+        lock.config.slash_mode
     }
 
-    /// Set interval between slash animations (0-255)
-    async fn set_slash_mode(&self, slash_mode: SlashMode) {
+    /// Set the animation played
+    #[zbus(property)]
+    async fn set_slash_mode(
+        &self,
+        slash_mode: SlashMode,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> zbus::fdo::Result<()> {
+        polkit::check(connection, &header, Action::ChangeLighting).await?;
         let mut lock = self.0.lock().await;
 
         let command_packets = pkt_set_mode(slash_mode);
@@ -134,6 +223,50 @@ impl CtrlSlashZbus {
 
         lock.config.slash_mode = slash_mode;
         lock.config.write();
+        Ok(())
+    }
+
+    #[zbus(property)]
+    async fn off_when_suspended(&self) -> bool {
+        let lock = self.0.lock().await;
+        lock.config.off_when_suspended
+    }
+
+    /// Set if to turn the Slash off when the laptop is suspended
+    #[zbus(property)]
+    async fn set_off_when_suspended(
+        &self,
+        enabled: bool,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> zbus::fdo::Result<()> {
+        polkit::check(connection, &header, Action::ChangeLighting).await?;
+        let mut lock = self.0.lock().await;
+        lock.config.off_when_suspended = enabled;
+        lock.config.write();
+        Ok(())
+    }
+
+    #[zbus(property)]
+    async fn off_at_boot(&self) -> bool {
+        let lock = self.0.lock().await;
+        lock.config.off_at_boot
+    }
+
+    /// Set if the Slash stays off through boot, until asusd starts. This is
+    /// saved to the device when the laptop powers down.
+    #[zbus(property)]
+    async fn set_off_at_boot(
+        &self,
+        enabled: bool,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> zbus::fdo::Result<()> {
+        polkit::check(connection, &header, Action::ChangeLighting).await?;
+        let mut lock = self.0.lock().await;
+        lock.config.off_at_boot = enabled;
+        lock.config.write();
+        Ok(())
     }
 
     /// Get the device state as stored by asusd
@@ -142,6 +275,10 @@ impl CtrlSlashZbus {
         let lock = self.0.lock().await;
         DeviceState::from(&lock.config)
     }
+
+    /// Sent once an edit of `slash.ron` made outside of asusd has been applied
+    #[zbus(signal)]
+    async fn notify_config_reloaded(ctxt: &SignalContext<'_>) -> zbus::Result<()>;
 }
 
 impl crate::CtrlTask for CtrlSlashZbus {
@@ -150,6 +287,46 @@ impl crate::CtrlTask for CtrlSlashZbus {
     }
 
     async fn create_tasks(&self, _: SignalContext<'static>) -> Result<(), RogError> {
+        let inner1 = self.0.clone();
+        let inner2 = self.0.clone();
+        self.create_sys_event_tasks(
+            move |sleeping| {
+                let inner = inner1.clone();
+                async move {
+                    let lock = inner.lock().await;
+                    if lock.config.off_when_suspended {
+                        lock.show(!sleeping)
+                            .map_err(|err| {
+                                warn!("create_sys_event_tasks::off_when_suspended {}", err);
+                            })
+                            .ok();
+                    }
+                }
+            },
+            move |shutting_down| {
+                let inner = inner2.clone();
+                async move {
+                    let lock = inner.lock().await;
+                    if !lock.config.off_at_boot {
+                        return;
+                    }
+                    // Shown again if the shutdown is cancelled
+                    let res = if shutting_down {
+                        lock.save_off()
+                    } else {
+                        lock.show(true)
+                    };
+                    res.map_err(|err| {
+                        warn!("create_sys_event_tasks::off_at_boot {}", err);
+                    })
+                    .ok();
+                }
+            },
+            move |_| async {},
+            move |_| async {},
+        )
+        .await;
+
         Ok(())
     }
 }
@@ -1,19 +1,29 @@
 use std::env;
 use std::error::Error;
+use std::fs;
 use std::sync::Arc;
 
 use ::zbus::export::futures_util::lock::Mutex;
 use ::zbus::Connection;
 use asusd::config::Config;
+use asusd::config_watch;
 use asusd::ctrl_anime::config::AnimeConfig;
 use asusd::ctrl_anime::trait_impls::CtrlAnimeZbus;
 use asusd::ctrl_anime::CtrlAnime;
 use asusd::ctrl_aura::manager::AuraManager;
+use asusd::ctrl_clients::CtrlClients;
+use asusd::ctrl_events::CtrlEvents;
 use asusd::ctrl_fancurves::CtrlFanCurveZbus;
+use asusd::ctrl_gpu::CtrlGpu;
+use asusd::ctrl_numpad::CtrlNumpad;
 use asusd::ctrl_platform::CtrlPlatform;
 use asusd::ctrl_slash::config::SlashConfig;
 use asusd::ctrl_slash::trait_impls::CtrlSlashZbus;
 use asusd::ctrl_slash::CtrlSlash;
+use asusd::first_run::note_fresh_config;
+use asusd::power_events;
+use asusd::shutdown;
+use asusd::tablet_mode;
 use asusd::{print_board_info, start_tasks, CtrlTask, DBUS_NAME};
 use config_traits::{StdConfig, StdConfigLoad, StdConfigLoad2, StdConfigLoad3};
 use log::{error, info};
@@ -34,11 +44,19 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         None => false,
     };
 
-    if !is_service {
+    // Aura packets are logged instead of written, other devices are written as
+    // usual
+    let aura_dry_run = env::args().any(|arg| arg == "--aura-dry-run");
+    // Started by an init other than systemd, such as runit or OpenRC
+    let foreground = env::args().any(|arg| arg == "--foreground");
+    let pidfile = arg_value("--pidfile");
+
+    if !is_service && !foreground {
         println!("asusd schould be only run from the right systemd service");
         println!(
             "do not run in your terminal, if you need an logs please use journalctl -b -u asusd"
         );
+        println!("other inits can start it with --foreground");
         println!("asusd will now exit");
         return Ok(());
     }
@@ -50,12 +68,32 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     info!(" rog-profiles v{}", rog_profiles::VERSION);
     info!("rog-platform v{}", rog_platform::VERSION);
 
-    start_daemon().await?;
-    Ok(())
+    if let Some(path) = &pidfile {
+        fs::write(path, format!("{}\n", std::process::id()))?;
+    }
+    let res = start_daemon(aura_dry_run).await;
+    if let Some(path) = &pidfile {
+        fs::remove_file(path).ok();
+    }
+    res
+}
+
+/// The value of `--name value` or `--name=value`
+fn arg_value(name: &str) -> Option<String> {
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == name {
+            return args.next();
+        }
+        if let Some(value) = arg.strip_prefix(name).and_then(|v| v.strip_prefix('=')) {
+            return Some(value.to_owned());
+        }
+    }
+    None
 }
 
 /// The actual main loop for the daemon
-async fn start_daemon() -> Result<(), Box<dyn Error>> {
+async fn start_daemon(aura_dry_run: bool) -> Result<(), Box<dyn Error>> {
     // let supported = SupportedFunctions::get_supported();
     print_board_info();
     // println!("{:?}", supported.supported_functions());
@@ -68,15 +106,37 @@ async fn start_daemon() -> Result<(), Box<dyn Error>> {
         .await
         .unwrap();
 
-    let config = Config::new().load();
-    let cfg_path = config.file_path();
+    let config = note_fresh_config(Config::new()).load();
     let config = Arc::new(Mutex::new(config));
 
     // supported.add_to_server(&mut connection).await;
 
+    // Lets the platform controller tell the others about throttle policy changes
+    let (profile_hook, profile_listener) = tokio::sync::watch::channel(None);
+    // Lets the events controller pass on the hotkeys asusd handles itself
+    let (hotkey_hook, _) = tokio::sync::broadcast::channel(16);
+    // Passes on the configs edited outside of asusd to the controllers owning them
+    let (config_hook, _) = tokio::sync::broadcast::channel(16);
+    config_watch::watch_config_dir(config_hook.clone());
+    config_watch::reload_on_sighup(config_hook.clone());
+    // Sleep and wake come from logind, elogind, or sysfs and the sleep hook
+    power_events::start().await;
+    // Asks the LED controllers to apply the shutdown policy
+    let (shutdown_hook, _) = tokio::sync::broadcast::channel(4);
+    shutdown::watch_prepare_for_shutdown(config.clone(), shutdown_hook.clone());
+    // Tells the platform and LED controllers when a convertible is folded
+    let (tablet_mode_hook, tablet_mode) = tokio::sync::watch::channel(false);
+    tablet_mode::watch_tablet_mode(config.clone(), tablet_mode_hook);
+
+    let clients = CtrlClients::new();
+    let sig_ctx = CtrlClients::signal_context(&connection)?;
+    start_tasks(clients, &mut connection, sig_ctx).await?;
+
     match CtrlFanCurveZbus::new() {
         Ok(ctrl) => {
+            ctrl.listen_profile_changes(profile_listener.clone());
             let sig_ctx = CtrlFanCurveZbus::signal_context(&connection)?;
+            ctrl.listen_config_changes(config_hook.subscribe(), sig_ctx.clone());
             start_tasks(ctrl, &mut connection, sig_ctx).await?;
         }
         Err(err) => {
@@ -86,11 +146,15 @@ async fn start_daemon() -> Result<(), Box<dyn Error>> {
 
     match CtrlPlatform::new(
         config.clone(),
-        &cfg_path,
+        config_hook.subscribe(),
         CtrlPlatform::signal_context(&connection)?,
+        profile_hook,
+        tablet_mode.clone(),
     ) {
         Ok(ctrl) => {
             let sig_ctx = CtrlPlatform::signal_context(&connection)?;
+            ctrl.listen_hotkeys(hotkey_hook.subscribe(), sig_ctx.clone());
+            ctrl.listen_tablet_mode(sig_ctx.clone());
             start_tasks(ctrl, &mut connection, sig_ctx).await?;
         }
         Err(err) => {
@@ -98,10 +162,43 @@ async fn start_daemon() -> Result<(), Box<dyn Error>> {
         }
     }
 
-    match CtrlAnime::new(AnimeConfig::new().load()) {
+    match CtrlEvents::new(config.clone(), hotkey_hook.clone()) {
+        Ok(ctrl) => {
+            let sig_ctx = CtrlEvents::signal_context(&connection)?;
+            start_tasks(ctrl, &mut connection, sig_ctx).await?;
+        }
+        Err(err) => {
+            info!("Hotkey events: {}", err);
+        }
+    }
+
+    match CtrlGpu::new() {
+        Ok(ctrl) => {
+            let sig_ctx = CtrlGpu::signal_context(&connection)?;
+            start_tasks(ctrl, &mut connection, sig_ctx).await?;
+        }
+        Err(err) => {
+            info!("dGPU control: {}", err);
+        }
+    }
+
+    match CtrlNumpad::new(config.clone()) {
+        Ok(ctrl) => {
+            let sig_ctx = CtrlNumpad::signal_context(&connection)?;
+            ctrl.listen_hotkeys(hotkey_hook.subscribe(), sig_ctx.clone());
+            start_tasks(ctrl, &mut connection, sig_ctx).await?;
+        }
+        Err(err) => {
+            info!("Touchpad numpad: {}", err);
+        }
+    }
+
+    match CtrlAnime::new(note_fresh_config(AnimeConfig::new()).load()) {
         Ok(ctrl) => {
             let zbus = CtrlAnimeZbus(Arc::new(Mutex::new(ctrl)));
             let sig_ctx = CtrlAnimeZbus::signal_context(&connection)?;
+            zbus.listen_config_changes(config_hook.subscribe(), sig_ctx.clone());
+            zbus.listen_shutdown(shutdown_hook.subscribe());
             start_tasks(zbus, &mut connection, sig_ctx).await?;
         }
         Err(err) => {
@@ -109,28 +206,45 @@ async fn start_daemon() -> Result<(), Box<dyn Error>> {
         }
     }
 
-    match CtrlSlash::new(SlashConfig::new().load()) {
+    match CtrlSlash::new(note_fresh_config(SlashConfig::new()).load()) {
         Ok(ctrl) => {
             let zbus = CtrlSlashZbus(Arc::new(Mutex::new(ctrl)));
-            // Currently, the Slash has no need for a loop watching power events, however,
-            // it could be cool to have the slash do some power-on/off animation
-            // (It has a built-in power on animation which plays when u plug in the power
-            // supply)
+            // The power events only turn the Slash off or on. It could be cool to have
+            // the slash do some power-on/off animation (It has a built-in power on
+            // animation which plays when u plug in the power supply)
             let sig_ctx = CtrlSlashZbus::signal_context(&connection)?;
+            zbus.listen_config_changes(config_hook.subscribe(), sig_ctx.clone());
+            zbus.listen_shutdown(shutdown_hook.subscribe());
             start_tasks(zbus, &mut connection, sig_ctx).await?;
         }
         Err(err) => {
-            info!("AniMe control: {}", err);
+            info!("Slash control: {}", err);
         }
     }
 
-    let _ = AuraManager::new(connection.clone()).await?;
+    let _ = AuraManager::new(
+        connection.clone(),
+        profile_listener,
+        hotkey_hook,
+        config_hook,
+        shutdown_hook.clone(),
+        tablet_mode,
+        aura_dry_run,
+    )
+    .await?;
 
     // Request dbus name after finishing initalizing all functions
     connection.request_name(DBUS_NAME).await?;
 
-    loop {
-        // This is just a blocker to idle and ensure the reator reacts
-        connection.executor().tick().await;
+    let run = async {
+        loop {
+            // This is just a blocker to idle and ensure the reator reacts
+            connection.executor().tick().await;
+        }
+    };
+    tokio::select! {
+        _ = run => {}
+        _ = shutdown::until_terminated(config, shutdown_hook) => info!("asusd stopping"),
     }
+    Ok(())
 }
@@ -38,6 +38,11 @@ pub enum RogError {
     SystemdUnitWaitTimeout(String),
     Command(String, std::io::Error),
     ParseRon(ron::Error),
+    /// (plugin path, reason)
+    Plugin(String, String),
+    /// The power state bytes (written, read back) of a device whose firmware
+    /// didn't keep them
+    PowerStateMismatch(Vec<u8>, Vec<u8>),
 }
 
 impl fmt::Display for RogError {
@@ -88,6 +93,12 @@ impl fmt::Display for RogError {
             }
             RogError::Command(func, error) => write!(f, "Command exec error: {}: {}", func, error),
             RogError::ParseRon(error) => write!(f, "Parse config error: {}", error),
+            RogError::Plugin(path, reason) => write!(f, "Effect plugin {}: {}", path, reason),
+            RogError::PowerStateMismatch(written, read) => write!(
+                f,
+                "The LED power states were not kept, wrote {:02x?} but read back {:02x?}",
+                written, read
+            ),
         }
     }
 }
@@ -137,8 +148,62 @@ impl From<ron::Error> for RogError {
 }
 
 impl From<RogError> for zbus::fdo::Error {
+    /// Mapped to the closest D-Bus error so that clients can tell an
+    /// unsupported request from a missing device or a failed write
     #[inline]
     fn from(err: RogError) -> Self {
-        zbus::fdo::Error::Failed(format!("{}", err))
+        use zbus::fdo::Error;
+        let msg = format!("{}", err);
+        match err {
+            RogError::NotSupported
+            | RogError::AuraEffectNotSupported
+            | RogError::MissingFunction(_) => Error::NotSupported(msg),
+            RogError::NoAuraKeyboard
+            | RogError::NoAuraNode
+            | RogError::MissingLedBrightNode(..)
+            | RogError::NotFound(_) => Error::FileNotFound(msg),
+            RogError::ChargeLimit(_) => Error::InvalidArgs(msg),
+            RogError::Path(..)
+            | RogError::Read(..)
+            | RogError::Write(..)
+            | RogError::Io(_)
+            | RogError::PowerStateMismatch(..) => Error::IOError(msg),
+            RogError::Platform(err) => err.into(),
+            RogError::Zbus(zbus::Error::FDO(err)) => *err,
+            _ => Error::Failed(msg),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rog_platform::error::PlatformError;
+    use zbus::fdo::Error;
+
+    use super::RogError;
+
+    #[test]
+    fn errors_map_to_dbus_kinds() {
+        assert!(matches!(
+            Error::from(RogError::AuraEffectNotSupported),
+            Error::NotSupported(_)
+        ));
+        assert!(matches!(
+            Error::from(RogError::NoAuraNode),
+            Error::FileNotFound(_)
+        ));
+        let write = PlatformError::IoPath(
+            "/dev/hidraw0".into(),
+            std::io::Error::from(std::io::ErrorKind::BrokenPipe),
+        );
+        let Error::IOError(msg) = Error::from(RogError::Platform(write)) else {
+            panic!("a failed HID write should be an IOError");
+        };
+        assert!(msg.contains("/dev/hidraw0"));
+        assert!(matches!(
+            Error::from(RogError::PowerStateMismatch(vec![0xff], vec![0])),
+            Error::IOError(_)
+        ));
+        assert!(matches!(Error::from(RogError::ParseLed), Error::Failed(_)));
     }
 }
@@ -0,0 +1,71 @@
+//! State for first run wizards in clients. asusd notes which config files it
+//! had to create when it started, and a fresh `asusd.ron` is marked as
+//! pending a first run until a client calls `CompleteFirstRun`. Configs
+//! written by older versions don't have the flag and are taken as set up.
+
+use std::sync::Mutex;
+
+use config_traits::StdConfig;
+use rog_platform::platform::{Properties, ThrottlePolicy};
+use serde_derive::{Deserialize, Serialize};
+use zbus::zvariant::Type;
+
+/// The charge limit suggested to new users, which is kinder to the battery
+/// on a laptop that is mostly plugged in
+const SUGGESTED_CHARGE_LIMIT: u8 = 80;
+
+static FRESH_CONFIGS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// Note `config` as freshly created if its file doesn't exist yet. Must be
+/// called before `load()`, which writes the file.
+pub fn note_fresh_config<C: StdConfig>(config: C) -> C {
+    if !config.file_path().exists() {
+        if let Ok(mut fresh) = FRESH_CONFIGS.lock() {
+            fresh.push(config.file_name());
+        }
+    }
+    config
+}
+
+/// Config files created with defaults when asusd started
+pub fn fresh_configs() -> Vec<String> {
+    FRESH_CONFIGS
+        .lock()
+        .map(|fresh| fresh.clone())
+        .unwrap_or_default()
+}
+
+/// Defaults a wizard can offer. Only the parts this laptop supports are
+/// applied by `ApplySuggestedDefaults`.
+#[derive(Deserialize, Serialize, Type, Debug, Clone, PartialEq)]
+pub struct SuggestedDefaults {
+    pub throttle_policy_on_ac: ThrottlePolicy,
+    pub throttle_policy_on_battery: ThrottlePolicy,
+    /// Zero if the charge limit can't be set
+    pub charge_limit: u8,
+}
+
+impl SuggestedDefaults {
+    pub fn new(has_charge_limit: bool) -> Self {
+        Self {
+            throttle_policy_on_ac: ThrottlePolicy::Balanced,
+            throttle_policy_on_battery: ThrottlePolicy::Quiet,
+            charge_limit: if has_charge_limit {
+                SUGGESTED_CHARGE_LIMIT
+            } else {
+                0
+            },
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Type, Debug, Clone, PartialEq)]
+pub struct FirstRunState {
+    /// No client has completed a first run since `asusd.ron` was created
+    pub pending: bool,
+    /// Config files asusd created with defaults at this start
+    pub fresh_configs: Vec<String>,
+    /// The platform properties found, as in `SupportedProperties`
+    pub properties: Vec<Properties>,
+    pub suggested: SuggestedDefaults,
+}
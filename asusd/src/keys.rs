@@ -0,0 +1,297 @@
+//! Keyboard brightness hotkeys read straight from the input devices. Laptops
+//! with a USB keyboard forward these keys to userspace rather than handling
+//! them in firmware, so asusd can step the brightness itself.
+//!
+//! The kernel sends a repeat event roughly every 33ms while a key is held,
+//! those are thinned out here to follow a `KeyRepeat` curve.
+
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use log::{info, warn};
+use serde_derive::{Deserialize, Serialize};
+use tokio::sync::mpsc::UnboundedSender;
+
+const EV_KEY: u16 = 0x01;
+pub const EV_SW: u16 = 0x05;
+pub const KEY_A: u16 = 30;
+pub const KEY_SPACE: u16 = 57;
+pub const KEY_NUMLOCK: u16 = 69;
+pub const KEY_PROG1: u16 = 148;
+pub const KEY_PROG2: u16 = 149;
+pub const KEY_PROG3: u16 = 202;
+pub const KEY_KBDILLUMTOGGLE: u16 = 228;
+pub const KEY_KBDILLUMDOWN: u16 = 229;
+pub const KEY_KBDILLUMUP: u16 = 230;
+pub const KEY_MICMUTE: u16 = 248;
+pub const KEY_FN_F5: u16 = 0x1d6;
+const BTN_TOOL_FINGER: u16 = 0x145;
+const BTN_TOUCH: u16 = 0x14a;
+/// `struct input_event`, a `timeval` followed by type, code and value
+#[cfg(target_pointer_width = "64")]
+const EVENT_SIZE: usize = 24;
+#[cfg(not(target_pointer_width = "64"))]
+const EVENT_SIZE: usize = 16;
+
+/// A single brightness step to make
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BrightnessKey {
+    Up,
+    Down,
+}
+
+/// How the brightness steps while a hotkey is held. The first repeat comes
+/// after `delay_ms`, then each interval is the last multiplied by
+/// `acceleration` until it reaches `min_interval_ms`.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct KeyRepeat {
+    /// Handle the hotkeys in asusd. Leave this off if the desktop already
+    /// changes the keyboard brightness on these keys.
+    pub enabled: bool,
+    pub delay_ms: u64,
+    pub interval_ms: u64,
+    pub min_interval_ms: u64,
+    pub acceleration: f32,
+}
+
+impl Default for KeyRepeat {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            delay_ms: 400,
+            interval_ms: 300,
+            min_interval_ms: 80,
+            acceleration: 0.7,
+        }
+    }
+}
+
+/// Tracks one held key against a `KeyRepeat` curve
+#[derive(Debug, Default)]
+pub struct RepeatState {
+    next: Option<Instant>,
+    interval: Duration,
+}
+
+impl RepeatState {
+    /// Feed in the value of a key event, 1 for press, 2 for a kernel repeat
+    /// and 0 for release. Returns `true` if the key should step now.
+    pub fn event(&mut self, value: i32, now: Instant, curve: &KeyRepeat) -> bool {
+        match value {
+            1 => {
+                self.next = Some(now + Duration::from_millis(curve.delay_ms));
+                self.interval = Duration::from_millis(curve.interval_ms);
+                true
+            }
+            2 => match self.next {
+                Some(next) if now >= next => {
+                    self.next = Some(now + self.interval);
+                    self.interval = self
+                        .interval
+                        .mul_f32(curve.acceleration.clamp(0.0, 1.0))
+                        .max(Duration::from_millis(curve.min_interval_ms));
+                    true
+                }
+                _ => false,
+            },
+            _ => {
+                self.next = None;
+                false
+            }
+        }
+    }
+}
+
+/// Check a sysfs capabilities bitmap such as `capabilities/key` for `code`.
+/// The bitmap is printed as space separated `long`s, most significant first.
+fn has_key(caps: &str, code: u16) -> bool {
+    let bits = usize::BITS as u16;
+    let word = (code / bits) as usize;
+    caps.split_whitespace()
+        .rev()
+        .nth(word)
+        .and_then(|w| usize::from_str_radix(w, 16).ok())
+        .is_some_and(|w| w & (1 << (code % bits)) != 0)
+}
+
+/// Event nodes of input devices whose sysfs directory passes `filter`
+fn find_event_devices(filter: impl Fn(&Path) -> bool) -> Vec<PathBuf> {
+    let Ok(dir) = fs::read_dir("/sys/class/input") else {
+        return Vec::new();
+    };
+    let mut nodes: Vec<PathBuf> = dir
+        .flatten()
+        .filter(|entry| entry.file_name().to_string_lossy().starts_with("event"))
+        .filter(|entry| filter(&entry.path().join("device")))
+        .map(|entry| Path::new("/dev/input").join(entry.file_name()))
+        .collect();
+    nodes.sort();
+    nodes
+}
+
+/// Event nodes of ASUS input devices whose key capabilities pass `filter`
+pub fn find_asus_key_devices(filter: impl Fn(&str) -> bool) -> Vec<PathBuf> {
+    find_event_devices(|device| {
+        let name = fs::read_to_string(device.join("name")).unwrap_or_default();
+        let caps = fs::read_to_string(device.join("capabilities/key")).unwrap_or_default();
+        name.to_lowercase().contains("asus") && filter(&caps)
+    })
+}
+
+/// Event nodes of any input device with the switch `code`. Switches such as
+/// the tablet mode one are often on a platform device of another vendor,
+/// e.g. `intel-vbtn`.
+pub fn find_switch_devices(code: u16) -> Vec<PathBuf> {
+    find_event_devices(|device| {
+        let caps = fs::read_to_string(device.join("capabilities/sw")).unwrap_or_default();
+        has_key(&caps, code)
+    })
+}
+
+/// Event nodes of every keyboard, of any vendor since some laptops have the
+/// keys on an `AT Translated Set 2 keyboard` and only the hotkeys on an ASUS
+/// device
+pub fn find_keyboard_devices() -> Vec<PathBuf> {
+    find_event_devices(|device| {
+        let caps = fs::read_to_string(device.join("capabilities/key")).unwrap_or_default();
+        has_key(&caps, KEY_A) && has_key(&caps, KEY_SPACE)
+    })
+}
+
+/// Event nodes of touchpads, which report a finger on them as well as
+/// touches
+pub fn find_touchpad_devices() -> Vec<PathBuf> {
+    find_event_devices(|device| {
+        let caps = fs::read_to_string(device.join("capabilities/key")).unwrap_or_default();
+        has_key(&caps, BTN_TOOL_FINGER) && has_key(&caps, BTN_TOUCH)
+    })
+}
+
+/// Event nodes of ASUS input devices which have the brightness hotkeys
+pub fn find_hotkey_devices() -> Vec<PathBuf> {
+    find_asus_key_devices(|caps| has_key(caps, KEY_KBDILLUMUP) && has_key(caps, KEY_KBDILLUMDOWN))
+}
+
+/// Event nodes of ASUS input devices with any of `codes`
+pub fn find_devices_with_any(codes: &[u16]) -> Vec<PathBuf> {
+    find_asus_key_devices(|caps| codes.iter().any(|code| has_key(caps, *code)))
+}
+
+/// Read key events from `node` on a new thread named `name`, calling
+/// `on_key(code, value)` for each. The thread ends when the device goes away
+/// or `on_key` returns `false`.
+pub fn spawn_key_reader(
+    node: PathBuf,
+    name: &str,
+    mut on_key: impl FnMut(u16, i32) -> bool + Send + 'static,
+) {
+    spawn_event_reader(node, name, move |kind, code, value| {
+        kind != EV_KEY || on_key(code, value)
+    });
+}
+
+/// As `spawn_key_reader`, but for events of every type, calling
+/// `on_event(type, code, value)`
+pub fn spawn_event_reader(
+    node: PathBuf,
+    name: &str,
+    mut on_event: impl FnMut(u16, u16, i32) -> bool + Send + 'static,
+) {
+    let mut file = match File::open(&node) {
+        Ok(file) => file,
+        Err(e) => {
+            warn!("{name}: could not open {}: {e}", node.display());
+            return;
+        }
+    };
+    let name = name.to_owned();
+    std::thread::Builder::new()
+        .name(name.clone())
+        .spawn(move || {
+            info!("{name}: reading {}", node.display());
+            let mut event = [0u8; EVENT_SIZE];
+            while file.read_exact(&mut event).is_ok() {
+                let kind = u16::from_ne_bytes([event[EVENT_SIZE - 8], event[EVENT_SIZE - 7]]);
+                let code = u16::from_ne_bytes([event[EVENT_SIZE - 6], event[EVENT_SIZE - 5]]);
+                let value = i32::from_ne_bytes([
+                    event[EVENT_SIZE - 4],
+                    event[EVENT_SIZE - 3],
+                    event[EVENT_SIZE - 2],
+                    event[EVENT_SIZE - 1],
+                ]);
+                if !on_event(kind, code, value) {
+                    break;
+                }
+            }
+            info!("{name}: stopped reading {}", node.display());
+        })
+        .map_err(|e| warn!("Could not start input thread: {e}"))
+        .ok();
+}
+
+/// Read brightness hotkeys from `node`, sending a step for each press and
+/// for each repeat the curve allows
+pub fn spawn_reader(node: PathBuf, curve: KeyRepeat, steps: UnboundedSender<BrightnessKey>) {
+    let mut up = RepeatState::default();
+    let mut down = RepeatState::default();
+    spawn_key_reader(node, "Brightness hotkeys", move |code, value| {
+        let step = match code {
+            KEY_KBDILLUMUP if up.event(value, Instant::now(), &curve) => BrightnessKey::Up,
+            KEY_KBDILLUMDOWN if down.event(value, Instant::now(), &curve) => BrightnessKey::Down,
+            _ => return true,
+        };
+        steps.send(step).is_ok()
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::{has_key, KeyRepeat, RepeatState, KEY_KBDILLUMDOWN, KEY_KBDILLUMUP};
+
+    #[test]
+    fn repeat_accelerates() {
+        let curve = KeyRepeat {
+            enabled: true,
+            delay_ms: 400,
+            interval_ms: 200,
+            min_interval_ms: 100,
+            acceleration: 0.5,
+        };
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let mut state = RepeatState::default();
+
+        assert!(state.event(1, at(0), &curve));
+        // Held, but not for the delay
+        assert!(!state.event(2, at(250), &curve));
+        assert!(state.event(2, at(400), &curve));
+        assert!(!state.event(2, at(550), &curve));
+        assert!(state.event(2, at(600), &curve));
+        // Now at the minimum interval
+        assert!(!state.event(2, at(650), &curve));
+        assert!(state.event(2, at(700), &curve));
+        assert!(state.event(2, at(800), &curve));
+
+        assert!(!state.event(0, at(820), &curve));
+        // Repeats after release are ignored until a press
+        assert!(!state.event(2, at(2000), &curve));
+    }
+
+    #[test]
+    fn key_capabilities() {
+        // Bits 229 and 230 are bits 37 and 38 of the 4th word from the right
+        let caps = "0 0 0 0 60 0 0 0";
+        assert!(!has_key(caps, KEY_KBDILLUMUP));
+        let caps = "0 0 0 0 60 0 0 0 0 0 0 0";
+        assert!(!has_key(caps, KEY_KBDILLUMDOWN));
+        let caps = "0 0 0 0 6000000000 0 0 0";
+        assert!(has_key(caps, KEY_KBDILLUMUP));
+        assert!(has_key(caps, KEY_KBDILLUMDOWN));
+        assert!(!has_key("", KEY_KBDILLUMUP));
+    }
+}
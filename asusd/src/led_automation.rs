@@ -0,0 +1,150 @@
+//! Rules that change the keyboard brightness and turn off power zones by time
+//! of day or ambient light, e.g. off between 23:00 and 07:00, or low in a dark
+//! room. The first rule that matches is shown in place of the stored
+//! brightness and power states, which are restored once no rule does.
+
+use std::fs;
+use std::path::Path;
+
+use rog_aura::aura_detection::PowerZones;
+use rog_aura::LedBrightness;
+use serde_derive::{Deserialize, Serialize};
+use zbus::zvariant::Type;
+
+use crate::charge_schedule::{self, MINUTES_PER_DAY};
+
+const IIO_DEVICES: &str = "/sys/bus/iio/devices";
+/// An active dark room rule ends once the light is this much above its limit,
+/// so that it doesn't flicker on and off at dusk
+const LUX_HYSTERESIS: f64 = 1.25;
+
+#[derive(Deserialize, Serialize, Type, Debug, Clone, PartialEq)]
+pub struct LedRule {
+    pub brightness: LedBrightness,
+    /// Local time the rule starts, in minutes after midnight
+    pub start: u16,
+    /// Local time the rule ends, see [`charge_schedule::in_window`]
+    pub end: u16,
+    /// Only while the ambient light is below this many lux, 0 for any light
+    pub below_lux: u32,
+    /// Power zones turned off while the rule is active
+    pub zones_off: Vec<PowerZones>,
+}
+
+impl LedRule {
+    /// `lux` is `None` without a light sensor, when only rules for any light
+    /// can match. `active` is set if this rule is the one active now.
+    pub fn matches(&self, minute: u16, lux: Option<f64>, active: bool) -> bool {
+        let light = match (self.below_lux, lux) {
+            (0, _) => true,
+            (_, None) => false,
+            (below, Some(lux)) if active => lux < f64::from(below) * LUX_HYSTERESIS,
+            (below, Some(lux)) => lux < f64::from(below),
+        };
+        light && charge_schedule::in_window(self.start, self.end, minute)
+    }
+
+    /// Check the times are in range
+    pub fn validate(&self) -> Result<(), String> {
+        if self.start >= MINUTES_PER_DAY || self.end >= MINUTES_PER_DAY {
+            return Err(format!(
+                "Times must be less than {MINUTES_PER_DAY} minutes after midnight"
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// The rule that should be shown right now, `active` being the one shown now
+pub fn active_rule<'a>(
+    rules: &'a [LedRule],
+    minute: u16,
+    lux: Option<f64>,
+    active: Option<&LedRule>,
+) -> Option<&'a LedRule> {
+    rules
+        .iter()
+        .find(|rule| rule.matches(minute, lux, active == Some(*rule)))
+}
+
+/// The ambient light in lux from the first iio light sensor, if there is one
+pub fn ambient_lux() -> Option<f64> {
+    let dir = fs::read_dir(IIO_DEVICES).ok()?;
+    dir.flatten().find_map(|entry| read_lux(&entry.path()))
+}
+
+/// Drivers either give lux, or a raw value with the offset and scale to get
+/// it
+fn read_lux(device: &Path) -> Option<f64> {
+    let read = |name: &str| -> Option<f64> {
+        fs::read_to_string(device.join(name))
+            .ok()?
+            .trim()
+            .parse()
+            .ok()
+    };
+    if let Some(lux) = read("in_illuminance_input") {
+        return Some(lux);
+    }
+    let raw = read("in_illuminance_raw")?;
+    let offset = read("in_illuminance_offset").unwrap_or(0.0);
+    Some((raw + offset) * read("in_illuminance_scale").unwrap_or(1.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use rog_aura::aura_detection::PowerZones;
+    use rog_aura::LedBrightness;
+
+    use super::{active_rule, read_lux, LedRule};
+    use crate::charge_schedule::parse_time;
+
+    fn rule(brightness: LedBrightness, start: &str, end: &str, below_lux: u32) -> LedRule {
+        LedRule {
+            brightness,
+            start: parse_time(start).unwrap(),
+            end: parse_time(end).unwrap(),
+            below_lux,
+            zones_off: vec![PowerZones::Lightbar],
+        }
+    }
+
+    #[test]
+    fn first_match_wins() {
+        let rules = [
+            rule(LedBrightness::Off, "23:00", "07:00", 0),
+            rule(LedBrightness::Low, "00:00", "00:00", 10),
+        ];
+        let night = parse_time("23:30").unwrap();
+        let noon = parse_time("12:00").unwrap();
+        assert_eq!(active_rule(&rules, night, Some(1.0), None), Some(&rules[0]));
+        assert_eq!(active_rule(&rules, noon, Some(5.0), None), Some(&rules[1]));
+        assert_eq!(active_rule(&rules, noon, Some(50.0), None), None);
+        // Without a sensor only the time rule can match
+        assert_eq!(active_rule(&rules, noon, None, None), None);
+        assert_eq!(active_rule(&rules, night, None, None), Some(&rules[0]));
+    }
+
+    #[test]
+    fn dark_rule_hysteresis() {
+        let dark = rule(LedBrightness::Low, "00:00", "00:00", 10);
+        assert!(!dark.matches(0, Some(11.0), false));
+        assert!(dark.matches(0, Some(11.0), true));
+        assert!(!dark.matches(0, Some(13.0), true));
+        assert!(rule(LedBrightness::Low, "01:00", "02:00", 0)
+            .validate()
+            .is_ok());
+    }
+
+    #[test]
+    fn lux_from_raw() {
+        let dir = std::env::temp_dir().join(format!("asusd-iio-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("in_illuminance_raw"), "200\n").unwrap();
+        std::fs::write(dir.join("in_illuminance_scale"), "0.5\n").unwrap();
+        assert_eq!(read_lux(&dir), Some(100.0));
+        std::fs::write(dir.join("in_illuminance_input"), "42.5\n").unwrap();
+        assert_eq!(read_lux(&dir), Some(42.5));
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -1,30 +1,65 @@
 #![deny(unused_must_use)]
+/// Charge limit rules by time and power source
+pub mod charge_schedule;
 /// Configuration loading, saving
 pub mod config;
+/// Reloading of configs edited outside of asusd
+pub mod config_watch;
 /// Control of anime matrix display
 pub mod ctrl_anime;
 /// Keyboard LED brightness control, RGB, and LED display modes
 pub mod ctrl_aura;
+/// Attribution of changes to the clients that made them
+pub mod ctrl_clients;
+/// Hotkey events from the ASUS input devices
+pub mod ctrl_events;
 /// Control platform profiles + fan-curves if available
 pub mod ctrl_fancurves;
+/// dGPU power and MUX switching
+pub mod ctrl_gpu;
+/// Light of the numpad on the touchpad
+pub mod ctrl_numpad;
 /// Control ASUS bios function such as boot sound, Optimus/Dedicated gfx mode
 pub mod ctrl_platform;
 /// Control of Slash led bar
 pub mod ctrl_slash;
 
 pub mod error;
+/// Support for first run wizards
+pub mod first_run;
+/// Brightness hotkeys read from the keyboard input devices
+pub mod keys;
+/// Keyboard brightness rules by time and ambient light
+pub mod led_automation;
+/// Frame timing histograms of the software effects
+/// Polkit authorization of changes made through D-Bus
+pub mod polkit;
+/// Sleep, wake, lid and AC events from logind or sysfs
+pub mod power_events;
+/// Description of the settings for clients to build their options from
+pub mod settings_schema;
+/// LED state when powering down or stopping
+pub mod shutdown;
+/// The tablet mode switch of convertibles
+pub mod tablet_mode;
+/// The user with the active session, whose own lighting may be shown
+pub mod user_session;
 
 use std::future::Future;
+use std::sync::Arc;
 use std::time::Duration;
 
 use dmi_id::DMIID;
 use futures_lite::stream::StreamExt;
 use log::{debug, info, warn};
-use logind_zbus::manager::ManagerProxy;
+use rog_platform::platform::ThrottlePolicy;
+use tokio::sync::broadcast::error::RecvError;
 use tokio::time::sleep;
 use zbus::zvariant::ObjectPath;
-use zbus::{CacheProperties, Connection, SignalContext};
+use zbus::{Connection, SignalContext};
 
+use crate::config::ProfileAssociation;
+use crate::ctrl_events::Hotkey;
 use crate::error::RogError;
 
 const CONFIG_PATH_BASE: &str = "/etc/asusd/";
@@ -131,6 +166,74 @@ pub fn print_board_info() {
     info!("Board name: {}", dmi.board_name);
 }
 
+/// Sent by the platform controller each time the throttle policy changes,
+/// along with what the config associates with the new policy
+#[derive(Debug, Clone)]
+pub struct ProfileChange {
+    pub policy: ThrottlePolicy,
+    pub association: ProfileAssociation,
+}
+
+/// The hook the platform controller uses to tell other controllers about a
+/// throttle policy change. Controllers listen on a `Receiver` from
+/// `subscribe()`.
+pub type ProfileChangeHook = tokio::sync::watch::Sender<Option<ProfileChange>>;
+pub type ProfileChangeListener = tokio::sync::watch::Receiver<Option<ProfileChange>>;
+
+/// The hook the events controller uses to pass on each hotkey press that is
+/// set to be handled by asusd. Controllers listen on a `Receiver` from
+/// `subscribe()`.
+pub type HotkeyHook = tokio::sync::broadcast::Sender<Hotkey>;
+pub type HotkeyListener = tokio::sync::broadcast::Receiver<Hotkey>;
+
+/// The hook the config watcher uses to pass on the name of each file in the
+/// config dir that was edited outside of asusd. Controllers listen on a
+/// `Receiver` from `subscribe()`.
+pub type ConfigChangeHook = tokio::sync::broadcast::Sender<String>;
+pub type ConfigChangeListener = tokio::sync::broadcast::Receiver<String>;
+
+/// The hook used to ask the LED controllers to apply the shutdown policy on
+/// `SIGTERM` or `PrepareForShutdown`. Controllers listen on a `Receiver` from
+/// `subscribe()`, and drop the `done` sender when finished.
+pub type ShutdownHook = tokio::sync::broadcast::Sender<shutdown::Shutdown>;
+pub type ShutdownListener = tokio::sync::broadcast::Receiver<shutdown::Shutdown>;
+
+/// The hook the tablet mode watcher uses to tell other controllers when a
+/// convertible is folded in to or out of tablet mode, `true` while in it.
+/// Controllers listen on a `Receiver` from `subscribe()`.
+pub type TabletModeHook = tokio::sync::watch::Sender<bool>;
+pub type TabletModeListener = tokio::sync::watch::Receiver<bool>;
+
+/// Coalesces a burst of config changes, such as a held hotkey, in to one
+/// write. `request()` is cheap enough to call on every change, and `write`
+/// runs once no request has been made for the delay.
+#[derive(Clone)]
+pub struct DebouncedWrite(Arc<tokio::sync::Notify>);
+
+impl DebouncedWrite {
+    /// Spawn the writer task. Must be called from within the tokio runtime.
+    pub fn new<F, Fut>(delay: Duration, write: F) -> Self
+    where
+        F: Fn() -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send,
+    {
+        let notify = Arc::new(tokio::sync::Notify::new());
+        let waiter = notify.clone();
+        tokio::spawn(async move {
+            loop {
+                waiter.notified().await;
+                while tokio::time::timeout(delay, waiter.notified()).await.is_ok() {}
+                write().await;
+            }
+        });
+        Self(notify)
+    }
+
+    pub fn request(&self) {
+        self.0.notify_one();
+    }
+}
+
 pub trait Reloadable {
     fn reload(&mut self) -> impl Future<Output = Result<(), RogError>> + Send;
 }
@@ -226,48 +329,57 @@ pub trait CtrlTask {
         Fut4: Future<Output = ()> + Send,
     {
         async {
-            let connection = Connection::system()
-                .await
-                .expect("Controller could not create dbus connection");
-
-            let manager = ManagerProxy::builder(&connection)
-                .cache_properties(CacheProperties::No)
-                .build()
-                .await
-                .expect("Controller could not create ManagerProxy");
-
-            let manager1 = manager.clone();
-            tokio::spawn(async move {
-                if let Ok(mut notif) = manager1.receive_prepare_for_shutdown().await {
-                    while let Some(event) = notif.next().await {
-                        // blocks thread :|
-                        if let Ok(args) = event.args() {
-                            debug!("Doing on_prepare_for_shutdown({})", args.start);
-                            on_prepare_for_shutdown(args.start).await;
+            let logind = power_events::logind_manager().await;
+            if let Some(manager) = logind.clone() {
+                let manager1 = manager.clone();
+                tokio::spawn(async move {
+                    if let Ok(mut notif) = manager1.receive_prepare_for_shutdown().await {
+                        while let Some(event) = notif.next().await {
+                            // blocks thread :|
+                            if let Ok(args) = event.args() {
+                                debug!("Doing on_prepare_for_shutdown({})", args.start);
+                                on_prepare_for_shutdown(args.start).await;
+                            }
                         }
                     }
-                }
-            });
-
-            let manager2 = manager.clone();
-            tokio::spawn(async move {
-                if let Ok(mut notif) = manager2.receive_prepare_for_sleep().await {
-                    while let Some(event) = notif.next().await {
-                        // blocks thread :|
-                        if let Ok(args) = event.args() {
-                            debug!("Doing on_prepare_for_sleep({})", args.start);
-                            on_prepare_for_sleep(args.start).await;
+                });
+
+                tokio::spawn(async move {
+                    if let Ok(mut notif) = manager.receive_prepare_for_sleep().await {
+                        while let Some(event) = notif.next().await {
+                            // blocks thread :|
+                            if let Ok(args) = event.args() {
+                                debug!("Doing on_prepare_for_sleep({})", args.start);
+                                on_prepare_for_sleep(args.start).await;
+                            }
                         }
                     }
-                }
-            });
+                });
+            } else {
+                // Shutdown is only seen as SIGTERM, see `shutdown::until_terminated`
+                let mut events = power_events::sysfs_sleep_events();
+                tokio::spawn(async move {
+                    loop {
+                        match events.recv().await {
+                            Ok(start) => {
+                                debug!("Doing on_prepare_for_sleep({})", start);
+                                on_prepare_for_sleep(start).await;
+                            }
+                            Err(RecvError::Lagged(_)) => continue,
+                            Err(RecvError::Closed) => break,
+                        }
+                    }
+                });
+            }
 
-            let manager3 = manager.clone();
+            let manager = logind.clone();
             tokio::spawn(async move {
-                let mut last_power = manager3.on_external_power().await.unwrap_or_default();
+                let mut last_power = power_events::on_external_power(manager.as_ref())
+                    .await
+                    .unwrap_or_default();
 
                 loop {
-                    if let Ok(next) = manager3.on_external_power().await {
+                    if let Some(next) = power_events::on_external_power(manager.as_ref()).await {
                         if next != last_power {
                             last_power = next;
                             on_external_power_change(next).await;
@@ -278,10 +390,12 @@ pub trait CtrlTask {
             });
 
             tokio::spawn(async move {
-                let mut last_lid = manager.lid_closed().await.unwrap_or_default();
+                let mut last_lid = power_events::lid_closed(logind.as_ref())
+                    .await
+                    .unwrap_or_default();
                 // need to loop on these as they don't emit signals
                 loop {
-                    if let Ok(next) = manager.lid_closed().await {
+                    if let Some(next) = power_events::lid_closed(logind.as_ref()).await {
                         if next != last_lid {
                             last_lid = next;
                             on_lid_change(next).await;
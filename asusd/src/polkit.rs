@@ -0,0 +1,281 @@
+//! Polkit authorization of the changes made through D-Bus. Each kind of
+//! change is its own action, so that a polkit rule can let anyone change the
+//! lighting while keeping the GPU MUX or charge limit to admins. The policy
+//! shipped lets the user of the active local session make any change without
+//! a password, bar the GPU mode which needs an admin.
+//!
+//! Without polkit every caller let through by the D-Bus policy is allowed, as
+//! before. If polkit can't be looked for the change is refused, and it is
+//! looked for again on the next one.
+//!
+//! Each change is checked with polkit, so that `auth_admin_keep` and the
+//! session being active are honoured. Only the calls made once per frame use
+//! `check_streaming`, which remembers an authorized caller for a few seconds.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use log::{info, warn};
+use tokio::sync::OnceCell;
+use zbus::fdo::{DBusProxy, Error as ZbErr};
+use zbus::message::Header;
+use zbus::names::BusName;
+use zbus::zvariant::Value;
+use zbus::{proxy, Connection};
+
+const POLKIT: &str = "org.freedesktop.PolicyKit1";
+/// Lets the polkit agent of the caller ask for a password
+const ALLOW_USER_INTERACTION: u32 = 1;
+/// Authorized callers are forgotten past this many
+const MAX_AUTHORIZED: usize = 256;
+/// How long `check_streaming` remembers an authorized caller
+const AUTHORIZED_TTL: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Action {
+    /// Aura, Slash and numpad lighting
+    ChangeLighting,
+    /// Throttle policies, EPP, PPT limits and fan curves
+    ChangePowerProfile,
+    /// The charge limit and its schedule
+    ChangeChargeLimit,
+    /// The GPU MUX and dGPU power
+    ChangeGpuMode,
+    /// Panel overdrive, boot sound, mini-LED and such firmware settings
+    ChangePlatformSettings,
+    /// Anything shown on the AniMe matrix
+    FlashAnime,
+}
+
+impl Action {
+    /// The action id in `org.asuslinux.Daemon.policy`
+    pub const fn id(self) -> &'static str {
+        match self {
+            Action::ChangeLighting => "org.asuslinux.Daemon.change-lighting",
+            Action::ChangePowerProfile => "org.asuslinux.Daemon.change-power-profile",
+            Action::ChangeChargeLimit => "org.asuslinux.Daemon.change-charge-limit",
+            Action::ChangeGpuMode => "org.asuslinux.Daemon.change-gpu-mode",
+            Action::ChangePlatformSettings => "org.asuslinux.Daemon.change-platform-settings",
+            Action::FlashAnime => "org.asuslinux.Daemon.flash-anime",
+        }
+    }
+}
+
+#[proxy(
+    interface = "org.freedesktop.PolicyKit1.Authority",
+    default_service = "org.freedesktop.PolicyKit1",
+    default_path = "/org/freedesktop/PolicyKit1/Authority"
+)]
+trait Authority {
+    /// Returns if the subject is authorized, if it could be by authenticating,
+    /// and details of the result
+    fn check_authorization(
+        &self,
+        subject: &(&str, HashMap<&str, Value<'_>>),
+        action_id: &str,
+        details: &HashMap<&str, &str>,
+        flags: u32,
+        cancellation_id: &str,
+    ) -> zbus::Result<(bool, bool, HashMap<String, String>)>;
+}
+
+/// Callers authorized for an action by `check_streaming` and when. Keyed by
+/// unique bus name, which is never reused.
+#[derive(Debug)]
+struct AuthorizedCache {
+    entries: BTreeMap<(String, Action), Instant>,
+    ttl: Duration,
+    max: usize,
+}
+
+impl AuthorizedCache {
+    const fn new(ttl: Duration, max: usize) -> Self {
+        Self {
+            entries: BTreeMap::new(),
+            ttl,
+            max,
+        }
+    }
+
+    /// The caller was authorized for the action within the TTL. The GPU mode
+    /// is never remembered.
+    fn contains(&mut self, key: &(String, Action), now: Instant) -> bool {
+        match self.entries.get(key) {
+            Some(at) if now.duration_since(*at) < self.ttl => true,
+            Some(_) => {
+                self.entries.remove(key);
+                false
+            }
+            None => false,
+        }
+    }
+
+    fn insert(&mut self, key: (String, Action), now: Instant) {
+        if key.1 == Action::ChangeGpuMode {
+            return;
+        }
+        let ttl = self.ttl;
+        self.entries.retain(|_, at| now.duration_since(*at) < ttl);
+        if self.entries.len() >= self.max {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, at)| **at)
+                .map(|(k, _)| k.clone());
+            if let Some(oldest) = oldest {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.entries.insert(key, now);
+    }
+}
+
+static AVAILABLE: OnceCell<bool> = OnceCell::const_new();
+static AUTHORIZED: Mutex<AuthorizedCache> =
+    Mutex::new(AuthorizedCache::new(AUTHORIZED_TTL, MAX_AUTHORIZED));
+
+/// polkitd is running or can be started. Found once, but a failed lookup is
+/// not kept and refuses the change.
+async fn available(connection: &Connection) -> Result<bool, ZbErr> {
+    AVAILABLE
+        .get_or_try_init(|| async {
+            let dbus = DBusProxy::new(connection).await?;
+            let name = BusName::try_from(POLKIT).map_err(zbus::Error::from)?;
+            let found = dbus.name_has_owner(name).await?
+                || dbus
+                    .list_activatable_names()
+                    .await?
+                    .iter()
+                    .any(|n| n.as_str() == POLKIT);
+            if !found {
+                info!("No polkit, changes are allowed for all callers");
+            }
+            Ok::<bool, ZbErr>(found)
+        })
+        .await
+        .copied()
+        .map_err(|e| {
+            warn!("Could not look for polkit, refusing the change: {e}");
+            ZbErr::Failed(format!("Could not look for polkit: {e}"))
+        })
+}
+
+/// The unique name of the sender of `header`
+fn sender(header: &Header<'_>) -> Result<String, ZbErr> {
+    Ok(header
+        .sender()
+        .ok_or_else(|| ZbErr::Failed("The message has no sender".to_owned()))?
+        .to_string())
+}
+
+/// `AccessDenied` unless polkit authorizes the sender of `header` for
+/// `action`. The caller's agent may ask them for a password first.
+pub async fn check(
+    connection: &Connection,
+    header: &Header<'_>,
+    action: Action,
+) -> Result<(), ZbErr> {
+    if !available(connection).await? {
+        return Ok(());
+    }
+    authorize(connection, &sender(header)?, action).await
+}
+
+/// `check` for calls made once per frame, such as by the software effects of
+/// asusd-user. The caller is remembered for a few seconds once authorized.
+pub async fn check_streaming(
+    connection: &Connection,
+    header: &Header<'_>,
+    action: Action,
+) -> Result<(), ZbErr> {
+    if !available(connection).await? {
+        return Ok(());
+    }
+    let key = (sender(header)?, action);
+    let now = Instant::now();
+    if AUTHORIZED.lock().is_ok_and(|mut a| a.contains(&key, now)) {
+        return Ok(());
+    }
+    authorize(connection, &key.0, action).await?;
+    if let Ok(mut authorized) = AUTHORIZED.lock() {
+        authorized.insert(key, now);
+    }
+    Ok(())
+}
+
+async fn authorize(connection: &Connection, sender: &str, action: Action) -> Result<(), ZbErr> {
+    let subject = (
+        "system-bus-name",
+        HashMap::from([("name", Value::from(sender))]),
+    );
+    let (authorized, challenge, _) = AuthorityProxy::new(connection)
+        .await?
+        .check_authorization(
+            &subject,
+            action.id(),
+            &HashMap::new(),
+            ALLOW_USER_INTERACTION,
+            "",
+        )
+        .await
+        .map_err(|e| {
+            warn!("polkit check of {} failed: {e}", action.id());
+            ZbErr::Failed(format!("Could not check authorization: {e}"))
+        })?;
+    if !authorized {
+        let reason = if challenge {
+            "Authentication is required"
+        } else {
+            "Not authorized"
+        };
+        return Err(ZbErr::AccessDenied(format!("{reason} for {}", action.id())));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::{Action, AuthorizedCache};
+
+    #[test]
+    fn cache_expires() {
+        let mut cache = AuthorizedCache::new(Duration::from_secs(5), 8);
+        let now = Instant::now();
+        let key = (":1.10".to_owned(), Action::ChangeLighting);
+        assert!(!cache.contains(&key, now));
+        cache.insert(key.clone(), now);
+        assert!(cache.contains(&key, now + Duration::from_secs(4)));
+        assert!(!cache.contains(&(":1.10".to_owned(), Action::FlashAnime), now));
+        assert!(!cache.contains(&(":1.11".to_owned(), Action::ChangeLighting), now));
+        assert!(!cache.contains(&key, now + Duration::from_secs(5)));
+        assert!(cache.entries.is_empty());
+
+        let gpu = (":1.10".to_owned(), Action::ChangeGpuMode);
+        cache.insert(gpu.clone(), now);
+        assert!(!cache.contains(&gpu, now));
+    }
+
+    #[test]
+    fn cache_evicts() {
+        let mut cache = AuthorizedCache::new(Duration::from_secs(5), 2);
+        let now = Instant::now();
+        let key = |n: u32| (format!(":1.{n}"), Action::ChangeLighting);
+        cache.insert(key(1), now);
+        cache.insert(key(2), now + Duration::from_secs(1));
+        cache.insert(key(3), now + Duration::from_secs(2));
+        assert_eq!(cache.entries.len(), 2);
+        assert!(!cache.contains(&key(1), now + Duration::from_secs(2)));
+        assert!(cache.contains(&key(2), now + Duration::from_secs(2)));
+
+        // Expired callers go first
+        cache.insert(
+            key(4),
+            now + Duration::from_secs(6) + Duration::from_millis(500),
+        );
+        assert!(cache.contains(&key(3), now + Duration::from_secs(6)));
+        assert_eq!(cache.entries.len(), 2);
+    }
+}
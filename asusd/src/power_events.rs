@@ -0,0 +1,209 @@
+//! Where the sleep, wake, lid and external power events come from. logind
+//! gives them on systemd distros, and elogind has the same D-Bus API on the
+//! others which run it. With neither, such as on a plain runit or OpenRC
+//! system, the wake is seen through `/sys/power/state`, as the write which
+//! suspends the machine only closes the file once it has woken. The suspend
+//! itself is only seen ahead of time if the sleep hook sends asusd `SIGUSR1`.
+//! The lid and AC state are then read from ACPI and the power supply class.
+
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use futures_lite::stream::StreamExt;
+use inotify::{Inotify, WatchMask};
+use log::{debug, info, warn};
+use logind_zbus::manager::ManagerProxy;
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::{broadcast, OnceCell};
+use zbus::fdo::DBusProxy;
+use zbus::names::BusName;
+use zbus::{CacheProperties, Connection};
+
+const LOGIN1: &str = "org.freedesktop.login1";
+const SYS_POWER_STATE: &str = "/sys/power/state";
+const LID_DIR: &str = "/proc/acpi/button/lid";
+const POWER_SUPPLY_DIR: &str = "/sys/class/power_supply";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PowerEventSource {
+    /// systemd-logind or elogind
+    Logind,
+    /// `/sys/power/state`, the sleep hook, ACPI and the power supply class
+    Sysfs,
+}
+
+static SOURCE: OnceCell<PowerEventSource> = OnceCell::const_new();
+/// Sleep events when there is no logind, `true` before a suspend and `false`
+/// after waking
+static SLEEP: OnceLock<broadcast::Sender<bool>> = OnceLock::new();
+
+/// The source of the power events, found once
+pub async fn source() -> PowerEventSource {
+    *SOURCE.get_or_init(find_source).await
+}
+
+async fn find_source() -> PowerEventSource {
+    let found = async {
+        let connection = Connection::system().await?;
+        let dbus = DBusProxy::new(&connection).await?;
+        let name = BusName::try_from(LOGIN1)?;
+        // elogind may only be started on the first call
+        Ok::<bool, zbus::Error>(
+            dbus.name_has_owner(name).await?
+                || dbus
+                    .list_activatable_names()
+                    .await?
+                    .iter()
+                    .any(|n| n.as_str() == LOGIN1),
+        )
+    };
+    match found.await {
+        Ok(true) => PowerEventSource::Logind,
+        Ok(false) => {
+            info!("No logind or elogind, power events are read from sysfs");
+            PowerEventSource::Sysfs
+        }
+        Err(e) => {
+            warn!("Could not look for logind, power events are read from sysfs: {e}");
+            PowerEventSource::Sysfs
+        }
+    }
+}
+
+/// A logind manager proxy, `None` if there is no logind
+pub async fn logind_manager() -> Option<ManagerProxy<'static>> {
+    if source().await != PowerEventSource::Logind {
+        return None;
+    }
+    let connection = Connection::system()
+        .await
+        .map_err(|e| warn!("logind_manager: {e}"))
+        .ok()?;
+    ManagerProxy::builder(&connection)
+        .cache_properties(CacheProperties::No)
+        .build()
+        .await
+        .map_err(|e| warn!("logind_manager: {e}"))
+        .ok()
+}
+
+/// Find the source, and without logind start watching for sleep. `SIGUSR1`
+/// is always taken so that a sleep hook left behind can't stop asusd.
+pub async fn start() {
+    let source = source().await;
+    let sender = SLEEP.get_or_init(|| broadcast::channel(4).0).clone();
+
+    let tx = sender.clone();
+    tokio::spawn(async move {
+        let mut usr1 = signal(SignalKind::user_defined1())?;
+        while usr1.recv().await.is_some() {
+            if source == PowerEventSource::Logind {
+                debug!("Ignoring SIGUSR1, sleep is announced by logind");
+                continue;
+            }
+            debug!("Sleep hook ran, preparing for sleep");
+            tx.send(true).ok();
+        }
+        Ok::<(), std::io::Error>(())
+    });
+
+    if source != PowerEventSource::Sysfs {
+        return;
+    }
+    tokio::spawn(async move {
+        let inotify = Inotify::init()?;
+        inotify
+            .watches()
+            .add(SYS_POWER_STATE, WatchMask::CLOSE_WRITE)?;
+        let mut buffer = [0; 64];
+        let mut events = inotify.into_event_stream(&mut buffer)?;
+        while events.next().await.is_some() {
+            debug!("{SYS_POWER_STATE} was written, woke from sleep");
+            sender.send(false).ok();
+        }
+        Ok::<(), std::io::Error>(())
+    });
+}
+
+/// Sleep events seen without logind, see `start`
+pub fn sysfs_sleep_events() -> broadcast::Receiver<bool> {
+    SLEEP.get_or_init(|| broadcast::channel(4).0).subscribe()
+}
+
+/// `manager` is from `logind_manager`, kept by callers which poll. `None` if
+/// logind couldn't be asked.
+pub async fn lid_closed(manager: Option<&ManagerProxy<'_>>) -> Option<bool> {
+    match manager {
+        Some(manager) => manager.lid_closed().await.ok(),
+        None => Some(read_lid_closed(Path::new(LID_DIR))),
+    }
+}
+
+pub async fn on_external_power(manager: Option<&ManagerProxy<'_>>) -> Option<bool> {
+    match manager {
+        Some(manager) => manager.on_external_power().await.ok(),
+        None => Some(read_on_external_power(Path::new(POWER_SUPPLY_DIR))),
+    }
+}
+
+/// Each lid has a `state` file such as `state:      closed`
+fn read_lid_closed(dir: &Path) -> bool {
+    let Ok(lids) = fs::read_dir(dir) else {
+        return false;
+    };
+    lids.flatten().any(|lid| {
+        fs::read_to_string(lid.path().join("state")).is_ok_and(|state| state.contains("closed"))
+    })
+}
+
+/// Like logind, without a mains supply to ask the machine is taken to be on
+/// external power
+fn read_on_external_power(dir: &Path) -> bool {
+    let Ok(supplies) = fs::read_dir(dir) else {
+        return true;
+    };
+    let mut mains = supplies
+        .flatten()
+        .map(|supply| supply.path())
+        .filter(|supply| fs::read_to_string(supply.join("type")).is_ok_and(|t| t.trim() == "Mains"))
+        .peekable();
+    if mains.peek().is_none() {
+        return true;
+    }
+    mains.any(|supply| {
+        fs::read_to_string(supply.join("online")).is_ok_and(|online| online.trim() == "1")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{read_lid_closed, read_on_external_power};
+
+    #[test]
+    fn sysfs_lid_and_power() {
+        let dir = std::env::temp_dir().join(format!("asusd-power-{}", std::process::id()));
+        let lid = dir.join("lid/LID0");
+        let ac = dir.join("supply/ACAD");
+        let bat = dir.join("supply/BAT0");
+        for d in [&lid, &ac, &bat] {
+            fs::create_dir_all(d).unwrap();
+        }
+        fs::write(lid.join("state"), "state:      open\n").unwrap();
+        assert!(!read_lid_closed(&dir.join("lid")));
+        fs::write(lid.join("state"), "state:      closed\n").unwrap();
+        assert!(read_lid_closed(&dir.join("lid")));
+
+        fs::write(bat.join("type"), "Battery\n").unwrap();
+        // A battery alone says nothing of the AC
+        assert!(read_on_external_power(&dir.join("supply")));
+        fs::write(ac.join("type"), "Mains\n").unwrap();
+        fs::write(ac.join("online"), "0\n").unwrap();
+        assert!(!read_on_external_power(&dir.join("supply")));
+        fs::write(ac.join("online"), "1\n").unwrap();
+        assert!(read_on_external_power(&dir.join("supply")));
+        fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,158 @@
+//! What can be set on this laptop and the values each setting takes. Clients
+//! such as asusctl build their options from this at runtime, so a mode or
+//! setting added to asusd shows up in them without a new client release.
+
+use rog_aura::LedBrightness;
+use rog_platform::platform::ThrottlePolicy;
+use rog_slash::SlashMode;
+use serde_derive::{Deserialize, Serialize};
+use zbus::zvariant::Type;
+
+use crate::ctrl_aura::known_devices::KnownAuraDevice;
+
+pub const SETTING_PROFILE: &str = "profile";
+pub const SETTING_LED_MODE: &str = "led-mode";
+pub const SETTING_LED_BRIGHTNESS: &str = "led-brightness";
+pub const SETTING_AURA_ZONE: &str = "aura-zone";
+pub const SETTING_NUMPAD_BRIGHTNESS: &str = "numpad-brightness";
+pub const SETTING_SLASH_MODE: &str = "slash-mode";
+
+const BRIGHTNESS: [LedBrightness; 4] = [
+    LedBrightness::Off,
+    LedBrightness::Low,
+    LedBrightness::Med,
+    LedBrightness::High,
+];
+
+#[derive(Deserialize, Serialize, Type, Debug, Clone, PartialEq, Eq)]
+pub struct SettingValue {
+    /// The name clients show and take, such as `quiet`
+    pub name: String,
+    /// What is written to the property for it
+    pub value: u32,
+}
+
+#[derive(Deserialize, Serialize, Type, Debug, Clone, PartialEq, Eq)]
+pub struct SettingSchema {
+    /// Short name used by clients, such as `led-mode`
+    pub name: String,
+    pub description: String,
+    /// Object path of the interface holding the setting
+    pub path: String,
+    pub interface: String,
+    /// The property to set, empty for values only taken as part of another
+    /// setting, such as the zone of an effect
+    pub property: String,
+    pub values: Vec<SettingValue>,
+}
+
+impl SettingSchema {
+    pub fn value(&self, name: &str) -> Option<&SettingValue> {
+        self.values
+            .iter()
+            .find(|v| v.name.eq_ignore_ascii_case(name))
+    }
+
+    pub fn value_names(&self) -> Vec<String> {
+        self.values.iter().map(|v| v.name.clone()).collect()
+    }
+}
+
+fn value(name: impl Into<String>, value: u32) -> SettingValue {
+    SettingValue {
+        name: name.into(),
+        value,
+    }
+}
+
+pub fn profile(path: &str) -> SettingSchema {
+    SettingSchema {
+        name: SETTING_PROFILE.to_owned(),
+        description: "Throttle policy, the platform profile".to_owned(),
+        path: path.to_owned(),
+        interface: "org.asuslinux.Platform".to_owned(),
+        property: "ThrottleThermalPolicy".to_owned(),
+        values: ThrottlePolicy::list()
+            .iter()
+            .map(|p| value(p.to_string().to_lowercase(), *p as u32))
+            .collect(),
+    }
+}
+
+/// The settings of a connected Aura device
+pub fn aura(device: &KnownAuraDevice) -> Vec<SettingSchema> {
+    let setting = |name: &str, description: &str, property: &str, values| SettingSchema {
+        name: name.to_owned(),
+        description: description.to_owned(),
+        path: device.dbus_path.clone(),
+        interface: "org.asuslinux.Aura".to_owned(),
+        property: property.to_owned(),
+        values,
+    };
+    let mut schema = vec![
+        setting(
+            SETTING_LED_MODE,
+            "Builtin keyboard LED mode",
+            "LedMode",
+            device
+                .basic_modes
+                .iter()
+                .map(|m| value(<&str>::from(m).to_lowercase(), *m as u32))
+                .collect(),
+        ),
+        setting(
+            SETTING_LED_BRIGHTNESS,
+            "Keyboard LED brightness",
+            "Brightness",
+            BRIGHTNESS
+                .iter()
+                .map(|b| value(format!("{b:?}").to_lowercase(), *b as u32))
+                .collect(),
+        ),
+    ];
+    if !device.basic_zones.is_empty() {
+        schema.push(setting(
+            SETTING_AURA_ZONE,
+            "Zones an LED mode can be set on",
+            "",
+            device
+                .basic_zones
+                .iter()
+                .map(|z| value(z.name(), *z as u32))
+                .collect(),
+        ));
+    }
+    schema
+}
+
+/// The touchpad numpad light, which is turned off with its `Enabled` property
+/// rather than a brightness
+pub fn numpad(path: &str) -> SettingSchema {
+    SettingSchema {
+        name: SETTING_NUMPAD_BRIGHTNESS.to_owned(),
+        description: "Brightness of the touchpad numpad while lit".to_owned(),
+        path: path.to_owned(),
+        interface: "org.asuslinux.Numpad".to_owned(),
+        property: "Brightness".to_owned(),
+        values: BRIGHTNESS[1..]
+            .iter()
+            .map(|b| value(format!("{b:?}").to_lowercase(), *b as u32))
+            .collect(),
+    }
+}
+
+/// The animations of the Slash lid lighting
+pub fn slash(path: &str) -> SettingSchema {
+    SettingSchema {
+        name: SETTING_SLASH_MODE.to_owned(),
+        description: "Slash lid animation".to_owned(),
+        path: path.to_owned(),
+        interface: "org.asuslinux.Slash".to_owned(),
+        property: "SlashMode".to_owned(),
+        values: SlashMode::list()
+            .iter()
+            .filter_map(|name| name.parse::<SlashMode>().ok())
+            .map(|m| value(m.to_string().to_lowercase(), m as u32))
+            .collect(),
+    }
+}
@@ -0,0 +1,194 @@
+//! What the LEDs are left showing when the machine powers down or asusd is
+//! stopped, such as for an upgrade. logind's `PrepareForShutdown`, or the
+//! `SIGTERM` asusd gets on stop, passes the policy from `asusd.ron` to each
+//! LED controller and waits a short time for them to finish. The policy is
+//! applied once, whichever comes first.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures_lite::stream::StreamExt;
+use log::{info, warn};
+use logind_zbus::manager::{InhibitType, ManagerProxy};
+use serde_derive::{Deserialize, Serialize};
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::mpsc;
+use zbus::export::futures_util::lock::Mutex;
+use zbus::zvariant::OwnedFd;
+
+use crate::config::Config;
+use crate::power_events;
+use crate::{ShutdownHook, ShutdownListener};
+
+/// How long the controllers get to write their LEDs
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Set once the policy is applied for a shutdown, so the `SIGTERM` that
+/// follows `PrepareForShutdown` doesn't apply it again. Cleared if the
+/// shutdown is cancelled.
+static APPLIED: AtomicBool = AtomicBool::new(false);
+
+#[derive(Deserialize, Serialize, Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd)]
+pub enum ShutdownPolicy {
+    /// Write nothing, whatever is showing stays
+    LeaveAsIs,
+    /// Stop software effects and write the power states, so the devices show
+    /// their shutdown state. AniMe plays its shutdown animation.
+    #[default]
+    PowerStates,
+    /// Turn off every LED. The stored settings are kept and applied again
+    /// when asusd starts.
+    AllOff,
+}
+
+/// Sent through the `ShutdownHook`
+#[derive(Debug, Clone)]
+pub struct Shutdown {
+    pub policy: ShutdownPolicy,
+    /// Each controller drops its copy once done
+    pub done: mpsc::Sender<()>,
+}
+
+/// Wait for the next shutdown, `None` once the hook is gone
+pub async fn requested(listener: &mut ShutdownListener) -> Option<Shutdown> {
+    loop {
+        match listener.recv().await {
+            Ok(shutdown) => return Some(shutdown),
+            Err(RecvError::Lagged(_)) => continue,
+            Err(RecvError::Closed) => return None,
+        }
+    }
+}
+
+/// Tell the controllers to apply the shutdown policy and wait for them
+async fn apply(config: &Mutex<Config>, hook: &ShutdownHook) {
+    let policy = config.lock().await.shutdown_policy;
+    info!("Applying the shutdown policy {policy:?}");
+    let (done, mut finished) = mpsc::channel(1);
+    // Nothing is listening if there are no LED devices
+    hook.send(Shutdown { policy, done }).ok();
+    // Ends once every copy of `done` is dropped
+    if tokio::time::timeout(SHUTDOWN_TIMEOUT, finished.recv())
+        .await
+        .is_err()
+    {
+        warn!("Not every controller finished the shutdown policy in time");
+    }
+}
+
+/// Delay shutdown until the policy has been applied
+async fn take_shutdown_inhibitor(manager: &ManagerProxy<'_>) -> Option<OwnedFd> {
+    manager
+        .inhibit(
+            InhibitType::Shutdown,
+            "asusd",
+            "Set the LED shutdown state",
+            "delay",
+        )
+        .await
+        .map_err(|e| warn!("take_shutdown_inhibitor: {e}"))
+        .ok()
+}
+
+/// Apply the policy each time logind announces a shutdown. Without logind
+/// the init sends `SIGTERM`, which `until_terminated` handles.
+pub fn watch_prepare_for_shutdown(config: Arc<Mutex<Config>>, hook: ShutdownHook) {
+    tokio::spawn(async move {
+        let Some(manager) = power_events::logind_manager().await else {
+            return Ok(());
+        };
+        let mut inhibitor = take_shutdown_inhibitor(&manager).await;
+        let mut events = manager.receive_prepare_for_shutdown().await?;
+        while let Some(event) = events.next().await {
+            match event.args() {
+                Ok(args) if args.start => {
+                    if !APPLIED.swap(true, Ordering::SeqCst) {
+                        apply(&config, &hook).await;
+                    }
+                    // Lets logind carry on with the shutdown
+                    inhibitor.take();
+                }
+                // The shutdown was cancelled
+                Ok(_) => {
+                    APPLIED.store(false, Ordering::SeqCst);
+                    inhibitor = take_shutdown_inhibitor(&manager).await;
+                }
+                Err(_) => {}
+            }
+        }
+        Ok::<(), zbus::Error>(())
+    });
+}
+
+/// Wait for `SIGTERM`, then apply the policy unless `PrepareForShutdown`
+/// already did. asusd should exit once this returns.
+pub async fn until_terminated(config: Arc<Mutex<Config>>, hook: ShutdownHook) {
+    match signal(SignalKind::terminate()) {
+        Ok(mut term) => {
+            term.recv().await;
+            if !APPLIED.swap(true, Ordering::SeqCst) {
+                apply(&config, &hook).await;
+            }
+        }
+        Err(e) => {
+            warn!("Could not listen for SIGTERM: {e}");
+            std::future::pending::<()>().await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use tokio::sync::{broadcast, mpsc};
+    use zbus::export::futures_util::lock::Mutex;
+
+    use super::{apply, requested, Shutdown, ShutdownPolicy, SHUTDOWN_TIMEOUT};
+    use crate::config::Config;
+
+    #[tokio::test]
+    async fn requested_skips_lagged() {
+        let (hook, mut listener) = broadcast::channel(1);
+        let (done, _finished) = mpsc::channel(1);
+        for policy in [ShutdownPolicy::LeaveAsIs, ShutdownPolicy::AllOff] {
+            hook.send(Shutdown {
+                policy,
+                done: done.clone(),
+            })
+            .unwrap();
+        }
+        let shutdown = requested(&mut listener).await.unwrap();
+        assert_eq!(shutdown.policy, ShutdownPolicy::AllOff);
+
+        drop(hook);
+        assert!(requested(&mut listener).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn apply_waits_for_controllers() {
+        let config = Mutex::new(Config {
+            shutdown_policy: ShutdownPolicy::AllOff,
+            ..Default::default()
+        });
+        let (hook, mut listener) = broadcast::channel(1);
+        let controller = tokio::spawn(async move {
+            let shutdown = requested(&mut listener).await.unwrap();
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            shutdown.policy
+        });
+        let start = Instant::now();
+        apply(&config, &hook).await;
+        // `done` is only dropped once the controller has finished
+        assert!(start.elapsed() >= Duration::from_millis(50));
+        assert_eq!(controller.await.unwrap(), ShutdownPolicy::AllOff);
+
+        // Returns at once with no controllers
+        let (hook, _) = broadcast::channel(1);
+        let start = Instant::now();
+        apply(&config, &hook).await;
+        assert!(start.elapsed() < SHUTDOWN_TIMEOUT);
+    }
+}
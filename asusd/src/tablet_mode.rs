@@ -0,0 +1,153 @@
+//! The `SW_TABLET_MODE` switch of convertibles, set by `asus-nb-wmi` or
+//! `intel-vbtn` while the screen is folded back over the keyboard. The state
+//! is passed on through the `TabletModeHook`, so the keyboard lighting can be
+//! turned off while the keyboard is face down and can't be used.
+
+use std::fs::{self, File};
+use std::os::fd::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use log::{info, warn};
+use zbus::export::futures_util::lock::Mutex;
+
+use crate::config::Config;
+use crate::keys::{self, EV_SW};
+use crate::TabletModeHook;
+
+const SW_TABLET_MODE: u16 = 0x01;
+const LEDS: &str = "/sys/class/leds";
+/// The suffixes of the LED class names the input core gives keyboard LEDs
+const INPUT_LEDS: [&str; 3] = ["::capslock", "::numlock", "::scrolllock"];
+
+/// `EVIOCGSW(len)` from `linux/input.h`
+const fn eviocgsw(len: usize) -> u64 {
+    const IOC_READ: u64 = 2;
+    (IOC_READ << 30) | ((len as u64) << 16) | ((b'E' as u64) << 8) | 0x1b
+}
+
+/// The state of the tablet mode switch of `node`, which only reports changes
+/// as events
+fn read_switch(node: &Path) -> Option<bool> {
+    let file = File::open(node).ok()?;
+    // Room for every switch up to SW_MAX
+    let mut state = [0u8; 8];
+    // SAFETY: the length in the request is that of `state`, which lives past
+    // the call
+    let res = unsafe {
+        libc::ioctl(
+            file.as_raw_fd(),
+            eviocgsw(state.len()) as _,
+            state.as_mut_ptr(),
+        )
+    };
+    (res >= 0).then_some(state[0] & (1 << SW_TABLET_MODE) != 0)
+}
+
+/// The trigger shown selected in the `trigger` file of an LED, such as
+/// `kbd-capslock` from `none [kbd-capslock] kbd-numlock`
+fn selected_trigger(triggers: &str) -> Option<&str> {
+    let start = triggers.find('[')? + 1;
+    let end = start + triggers[start..].find(']')?;
+    Some(&triggers[start..end])
+}
+
+/// The keyboard lock LEDs turned off for tablet mode, with the trigger each
+/// had to restore after
+#[derive(Debug, Default)]
+struct InputLeds(Vec<(PathBuf, String)>);
+
+impl InputLeds {
+    fn turn_off(&mut self) {
+        let Ok(dir) = fs::read_dir(LEDS) else {
+            return;
+        };
+        for led in dir.flatten().map(|entry| entry.path()) {
+            let name = led.file_name().unwrap_or_default().to_string_lossy();
+            if !INPUT_LEDS.iter().any(|suffix| name.ends_with(suffix)) {
+                continue;
+            }
+            let triggers = fs::read_to_string(led.join("trigger")).unwrap_or_default();
+            let Some(trigger) = selected_trigger(&triggers) else {
+                continue;
+            };
+            // The lock state drives the LED through its trigger, which has to
+            // be removed for it to stay off
+            let res = fs::write(led.join("trigger"), "none")
+                .and_then(|_| fs::write(led.join("brightness"), "0"));
+            match res {
+                Ok(_) => self.0.push((led.clone(), trigger.to_owned())),
+                Err(e) => warn!("Could not turn off {}: {e}", led.display()),
+            }
+        }
+    }
+
+    fn restore(&mut self) {
+        for (led, trigger) in self.0.drain(..) {
+            fs::write(led.join("trigger"), &trigger)
+                .map_err(|e| warn!("Could not restore {}: {e}", led.display()))
+                .ok();
+        }
+    }
+}
+
+/// Watch the tablet mode switches and keep `hook` set to their state
+pub fn watch_tablet_mode(config: Arc<Mutex<Config>>, hook: TabletModeHook) {
+    let nodes = keys::find_switch_devices(SW_TABLET_MODE);
+    if nodes.is_empty() {
+        info!("No tablet mode switch found");
+        return;
+    }
+    let tablet = nodes.iter().any(|node| read_switch(node).unwrap_or(false));
+
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    tx.send(tablet).ok();
+    for node in nodes {
+        let tx = tx.clone();
+        keys::spawn_event_reader(node, "Tablet mode switch", move |kind, code, value| {
+            if kind == EV_SW && code == SW_TABLET_MODE {
+                return tx.send(value != 0).is_ok();
+            }
+            true
+        });
+    }
+    drop(tx);
+
+    tokio::spawn(async move {
+        let mut input_leds = InputLeds::default();
+        while let Some(tablet) = rx.recv().await {
+            // Each switch device reports the fold
+            if tablet == *hook.borrow() {
+                continue;
+            }
+            info!("Tablet mode {}", if tablet { "on" } else { "off" });
+            hook.send_replace(tablet);
+            if !tablet {
+                input_leds.restore();
+            } else if config.lock().await.tablet_mode_input_leds_off {
+                input_leds.turn_off();
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{eviocgsw, selected_trigger};
+
+    #[test]
+    fn switch_ioctl_matches_kernel_header() {
+        // EVIOCGSW(8) as computed by the C macros
+        assert_eq!(eviocgsw(8), 0x8008_451b);
+    }
+
+    #[test]
+    fn trigger_parsing() {
+        assert_eq!(
+            selected_trigger("none [kbd-capslock] kbd-numlock"),
+            Some("kbd-capslock")
+        );
+        assert_eq!(selected_trigger("[none] timer"), Some("none"));
+        assert_eq!(selected_trigger("none timer"), None);
+    }
+}
@@ -0,0 +1,139 @@
+//! The user whose session is active on `seat0`. Only the session daemon of
+//! that user may show its own lighting over the stored lighting, so that a
+//! user switched away from, or the greeter, gets the system settings. Taken
+//! from logind, without which no user is taken as active.
+
+use futures_lite::stream::StreamExt;
+use log::{info, warn};
+use tokio::sync::watch;
+use zbus::fdo::{DBusProxy, Error as ZbErr};
+use zbus::message::Header;
+use zbus::names::BusName;
+use zbus::zvariant::OwnedObjectPath;
+use zbus::{proxy, CacheProperties, Connection};
+
+use crate::power_events::{self, PowerEventSource};
+
+const SEAT: &str = "seat0";
+
+#[proxy(
+    interface = "org.freedesktop.login1.Manager",
+    default_service = "org.freedesktop.login1",
+    default_path = "/org/freedesktop/login1"
+)]
+pub trait Login1Manager {
+    /// The id, uid, user name, seat and path of each session
+    fn list_sessions(&self) -> zbus::Result<Vec<(String, u32, String, String, OwnedObjectPath)>>;
+}
+
+#[proxy(
+    interface = "org.freedesktop.login1.Session",
+    default_service = "org.freedesktop.login1"
+)]
+pub trait Login1Session {
+    #[zbus(property)]
+    fn active(&self) -> zbus::Result<bool>;
+
+    /// `user`, or `greeter`, `lock-screen` and such for sessions run by the
+    /// display manager
+    #[zbus(property)]
+    fn class(&self) -> zbus::Result<String>;
+}
+
+#[proxy(
+    interface = "org.freedesktop.login1.Seat",
+    default_service = "org.freedesktop.login1",
+    default_path = "/org/freedesktop/login1/seat/seat0"
+)]
+pub trait Login1Seat {
+    /// The id and path of the session in the foreground
+    #[zbus(property)]
+    fn active_session(&self) -> zbus::Result<(String, OwnedObjectPath)>;
+}
+
+/// The uid of the user with the active session on `seat0`, `None` at the
+/// greeter or without logind
+pub async fn active_uid(connection: &Connection) -> zbus::Result<Option<u32>> {
+    if power_events::source().await != PowerEventSource::Logind {
+        return Ok(None);
+    }
+    let manager = Login1ManagerProxy::new(connection).await?;
+    for (_, uid, _, seat, path) in manager.list_sessions().await? {
+        if seat != SEAT {
+            continue;
+        }
+        let session = Login1SessionProxy::builder(connection)
+            .path(path)?
+            .cache_properties(CacheProperties::No)
+            .build()
+            .await?;
+        if session.active().await? && session.class().await? == "user" {
+            return Ok(Some(uid));
+        }
+    }
+    Ok(None)
+}
+
+/// Keep the returned receiver set to the uid from `active_uid` as the
+/// session in the foreground of `seat0` changes. `None` without logind.
+pub async fn watch_active_uid(connection: &Connection) -> Option<watch::Receiver<Option<u32>>> {
+    if power_events::source().await != PowerEventSource::Logind {
+        return None;
+    }
+    let seat = Login1SeatProxy::new(connection)
+        .await
+        .map_err(|e| warn!("watch_active_uid: {e}"))
+        .ok()?;
+    let mut changes = seat.receive_active_session_changed().await;
+    let first = active_uid(connection)
+        .await
+        .map_err(|e| warn!("watch_active_uid: {e}"))
+        .unwrap_or_default();
+    let (tx, rx) = watch::channel(first);
+
+    let connection = connection.clone();
+    tokio::spawn(async move {
+        // The seat is kept alive for its property stream
+        let _seat = seat;
+        while changes.next().await.is_some() {
+            let uid = match active_uid(&connection).await {
+                Ok(uid) => uid,
+                Err(e) => {
+                    warn!("Could not find the active session: {e}");
+                    continue;
+                }
+            };
+            if tx.send_if_modified(|active| std::mem::replace(active, uid) != uid) {
+                info!("The active user is now {uid:?}");
+            }
+        }
+    });
+    Some(rx)
+}
+
+/// The uid of the sender of `header`
+pub async fn sender_uid(connection: &Connection, header: &Header<'_>) -> Result<u32, ZbErr> {
+    let sender = header
+        .sender()
+        .ok_or_else(|| ZbErr::Failed("The message has no sender".to_owned()))?;
+    DBusProxy::new(connection)
+        .await?
+        .get_connection_unix_user(BusName::from(sender.to_owned()))
+        .await
+}
+
+/// The uid of the sender of `header`, if it is the user with the active
+/// session. `AccessDenied` for anyone else.
+pub async fn check_active_user(connection: &Connection, header: &Header<'_>) -> Result<u32, ZbErr> {
+    let uid = sender_uid(connection, header).await?;
+    let active = active_uid(connection).await.map_err(|e| {
+        warn!("Could not find the active session: {e}");
+        ZbErr::Failed(format!("Could not find the active session: {e}"))
+    })?;
+    if active != Some(uid) {
+        return Err(ZbErr::AccessDenied(format!(
+            "uid {uid} does not have the active session"
+        )));
+    }
+    Ok(uid)
+}
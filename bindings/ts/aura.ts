@@ -5,15 +5,7 @@
 /** Represents the per-key raw USB packets */
 export type UsbPackets = number[][];
 
-/**
- * A `UsbPackets` contains all data to change the full set of keyboard
- * key colours individually.
- * 
- * Each row of the internal array is a full HID packet that can be sent
- * to the keyboard EC. One row controls one group of keys, these keys are not
- * necessarily all on the same row of the keyboard, with some splitting between
- * two rows.
- */
+/** The form of `LedUsbPackets` for D-Bus, files and the bindings */
 export interface LedUsbPackets {
 	/** The packet data used to send data to the USB keyboard */
 	usb_packets: UsbPackets;
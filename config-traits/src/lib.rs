@@ -7,16 +7,186 @@
 //! and includes the ability to add commenting, and is less verbose than `json`.
 //! Currently the crate will also try to parse from `json` and `toml` if the
 //! `ron` parsing fails, then update to `ron` format.
-
+//!
+//! Configs may also record the version of their format in a `version` field,
+//! see `StdConfig::VERSION`. A file from an older version is then upgraded one
+//! version at a time by `StdConfig::upgrade` before it is parsed, and the
+//! original is kept as `<file>-v<version>.bak`.
+//!
+//! Files are replaced atomically on write, and reads and writes take an
+//! advisory lock (`flock`) so that other processes using this crate never see
+//! a file half written. Edits made outside of this crate can be told apart
+//! with `is_own_write`.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
+use std::ffi::OsString;
 use std::fs::{self, create_dir, File, OpenOptions};
-use std::io::{Read, Write};
-use std::path::PathBuf;
+use std::hash::{Hash, Hasher};
+use std::io::{self, Read, Write};
+use std::os::fd::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
 
 use log::{error, warn};
 pub use ron;
 use ron::ser::PrettyConfig;
 use serde::de::DeserializeOwned;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+
+/// Hash of the data last written to each config file by this process
+static OWN_WRITES: Mutex<BTreeMap<PathBuf, u64>> = Mutex::new(BTreeMap::new());
+
+fn data_hash(data: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    data.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Whether `data` is what this process last wrote to `path`. A file watcher
+/// can use this to ignore its own writes and react only to external edits.
+pub fn is_own_write(path: &Path, data: &[u8]) -> bool {
+    OWN_WRITES
+        .lock()
+        .map(|w| w.get(path) == Some(&data_hash(data)))
+        .unwrap_or_default()
+}
+
+/// Replace the file at `path` with `data`. The data is written and synced to
+/// a temporary file beside it first, which is then renamed over `path`, so a
+/// crash part way leaves either the old file or the new one.
+pub fn write_atomic(path: &Path, data: &[u8]) -> io::Result<()> {
+    let mut tmp_name = OsString::from(".");
+    tmp_name.push(path.file_name().unwrap_or_default());
+    tmp_name.push(".tmp");
+    let tmp = path.with_file_name(tmp_name);
+
+    let res = File::create(&tmp)
+        .and_then(|mut file| {
+            file.write_all(data)?;
+            file.sync_all()
+        })
+        .and_then(|_| fs::rename(&tmp, path));
+    if res.is_err() {
+        fs::remove_file(&tmp).ok();
+        return res;
+    }
+    // Make the rename itself durable
+    if let Some(dir) = path.parent() {
+        File::open(dir).and_then(|dir| dir.sync_all()).ok();
+    }
+    if let Ok(mut writes) = OWN_WRITES.lock() {
+        writes.insert(path.to_owned(), data_hash(data));
+    }
+    Ok(())
+}
+
+/// An advisory lock on a config file, released when dropped
+pub struct ConfigLock(File);
+
+impl ConfigLock {
+    /// Lock the file at `path`, waiting for any other holder. Writers need an
+    /// `exclusive` lock, readers share.
+    pub fn new(path: &Path, exclusive: bool) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        let op = if exclusive {
+            libc::LOCK_EX
+        } else {
+            libc::LOCK_SH
+        };
+        loop {
+            // SAFETY: the fd is owned by `file` and open for the whole call
+            if unsafe { libc::flock(file.as_raw_fd(), op) } == 0 {
+                return Ok(Self(file));
+            }
+            let err = io::Error::last_os_error();
+            if err.kind() != io::ErrorKind::Interrupted {
+                return Err(err);
+            }
+        }
+    }
+}
+
+impl Drop for ConfigLock {
+    fn drop(&mut self) {
+        // SAFETY: as above, closing the file would also release it
+        unsafe { libc::flock(self.0.as_raw_fd(), libc::LOCK_UN) };
+    }
+}
+
+fn read_file<T: StdConfig>(config: &T) -> Option<T> {
+    if let Ok(data) = fs::read_to_string(config.file_path()) {
+        if data.is_empty() {
+            warn!("File is empty {:?}", config.file_path());
+        } else if let Ok(data) = ron::from_str(&data) {
+            return Some(data);
+        } else {
+            warn!("Could not deserialise {:?}", config.file_path());
+        }
+    }
+    None
+}
+
+fn write_file<T: StdConfig>(config: &T) {
+    let ron = match ron::ser::to_string_pretty(config, PrettyConfig::new().depth_limit(4)) {
+        Ok(data) => data,
+        Err(e) => {
+            error!("Parse {:?} to RON failed, error: {e}", config.file_path());
+            return;
+        }
+    };
+    write_atomic(&config.file_path(), ron.as_bytes()).unwrap_or_else(|e| {
+        error!(
+            "Couldn't overwrite config {:?}, error: {e}",
+            config.file_path()
+        );
+    });
+}
+
+/// Only the version of a config file. Any other fields are ignored.
+#[derive(Deserialize)]
+struct FileVersion {
+    #[serde(default)]
+    version: u32,
+}
+
+/// The version a config file was written at. Files from before the config
+/// was versioned, or not in `ron` format, are version 0.
+pub fn file_version(data: &str) -> u32 {
+    ron::from_str::<FileVersion>(data)
+        .map(|v| v.version)
+        .unwrap_or_default()
+}
+
+/// Parse `data` as `Old` and write it back out as `New`, the usual way to
+/// make one step of `StdConfig::upgrade`
+pub fn upgrade_with<Old, New>(data: &str) -> Result<String, String>
+where
+    Old: DeserializeOwned + Into<New>,
+    New: Serialize,
+{
+    let old: Old = ron::from_str(data).map_err(|e| e.to_string())?;
+    ron::to_string(&old.into()).map_err(|e| e.to_string())
+}
+
+/// Upgrade the contents of a `T` file from `from` to `T::VERSION`, one
+/// version at a time
+pub fn upgrade_to_current<T: StdConfig>(from: u32, data: &str) -> Result<String, String> {
+    let Some(current) = T::VERSION else {
+        return Ok(data.to_owned());
+    };
+    let mut data = data.to_owned();
+    for version in from..current {
+        data = T::upgrade(version, &data)
+            .map_err(|e| format!("upgrade from version {version} failed: {e}"))?;
+    }
+    Ok(data)
+}
 
 /// Config file helper traits. Only `new()` and `file_name()` are required to be
 /// implemented, the rest are intended to be free methods.
@@ -33,6 +203,22 @@ where
     /// Return the full path to the directory the config file resides in
     fn config_dir() -> PathBuf;
 
+    /// The version of the file format for configs with a `version` field, or
+    /// `None` if the config is not versioned. Bump it, and add a step to
+    /// `upgrade`, for any change older files would not parse as or which
+    /// changes what a field means.
+    const VERSION: Option<u32> = None;
+
+    /// Turn the contents of a file at `version` into those of a file at
+    /// `version + 1`. Only called for versions from 1, files from before
+    /// versioning are left to the `StdConfigLoad` conversions.
+    fn upgrade(version: u32, _data: &str) -> Result<String, String> {
+        Err(format!("no upgrade from version {version}"))
+    }
+
+    /// Record `version` in the `version` field
+    fn set_version(&mut self, _version: u32) {}
+
     /// Return the full path to the config file
     fn file_path(&self) -> PathBuf {
         let mut config = Self::config_dir();
@@ -97,54 +283,63 @@ where
             .unwrap_or_else(|e| panic!("Could not open {:?} {e}", self.file_path()))
     }
 
+    /// The advisory lock of the config file. It is a separate `.<file>.lock`
+    /// file as writes replace the config file itself. Failing to lock is
+    /// logged, and the caller goes ahead unlocked.
+    fn lock(&self, exclusive: bool) -> Option<ConfigLock> {
+        let path = self
+            .file_path()
+            .with_file_name(format!(".{}.lock", self.file_name()));
+        ConfigLock::new(&path, exclusive)
+            .map_err(|e| warn!("Could not lock {}: {e}", self.file_name()))
+            .ok()
+    }
+
     /// Open and parse the config file to self from ron format
     fn read(&mut self) {
-        if let Ok(data) = fs::read_to_string(self.file_path()) {
-            if data.is_empty() {
-                warn!("File is empty {:?}", self.file_path());
-            } else if let Ok(data) = ron::from_str(&data) {
-                *self = data;
-            } else {
-                warn!("Could not deserialise {:?}", self.file_path());
-            }
+        let _lock = self.lock(false);
+        if let Some(data) = read_file(self) {
+            *self = data;
         }
     }
 
     /// Open and parse the config file to self from ron format
     fn read_new(&self) -> Option<Self> {
-        if let Ok(data) = fs::read_to_string(self.file_path()) {
-            if data.is_empty() {
-                warn!("File is empty {:?}", self.file_path());
-            } else if let Ok(data) = ron::from_str(&data) {
-                return Some(data);
-            } else {
-                warn!("Could not deserialise {:?}", self.file_path());
-            }
-        }
-        None
+        let _lock = self.lock(false);
+        read_file(self)
     }
 
     /// Write the config file data to pretty ron format
     fn write(&self) {
-        let mut file = match File::create(self.file_path()) {
-            Ok(data) => data,
-            Err(e) => {
-                error!(
-                    "Couldn't overwrite config {:?}, error: {e}",
-                    self.file_path()
-                );
-                return;
-            }
-        };
-        let ron = match ron::ser::to_string_pretty(&self, PrettyConfig::new().depth_limit(4)) {
-            Ok(data) => data,
-            Err(e) => {
-                error!("Parse {:?} to RON failed, error: {e}", self.file_path());
-                return;
-            }
-        };
-        file.write_all(ron.as_bytes())
-            .unwrap_or_else(|err| error!("Could not write config: {}", err));
+        let _lock = self.lock(true);
+        write_file(self);
+    }
+
+    /// Read the file in to self, apply `f`, and write the result back while
+    /// holding the lock throughout. Changes made to the file since it was
+    /// last read are kept, rather than overwritten with a stale copy.
+    fn update(&mut self, f: impl FnOnce(&mut Self)) {
+        let _lock = self.lock(true);
+        if let Some(data) = read_file(self) {
+            *self = data;
+        }
+        f(self);
+        write_file(self);
+    }
+
+    /// Copy the existing file to `<file>-v<version>.bak`, unless there is
+    /// already a backup of that version
+    fn backup_file(&self, version: u32) {
+        let mut backup = self.file_path().into_os_string();
+        backup.push(format!("-v{version}.bak"));
+        let backup = PathBuf::from(backup);
+        if backup.exists() {
+            return;
+        }
+        warn!("Keeping a copy of {} as {backup:?}", self.file_name());
+        std::fs::copy(self.file_path(), &backup)
+            .map_err(|e| error!("Could not back up {}: {e}", self.file_name()))
+            .ok();
     }
 
     /// Renames the existing file to `<file>-old`
@@ -216,8 +411,31 @@ macro_rules! std_config_load {
             fn load(mut self) -> Self {
                 let mut file = self.file_open();
                 let mut buf = String::new();
-                if let Ok(read_len) = file.read_to_string(&mut buf) {
+                let lock = self.lock(false);
+                let read = file.read_to_string(&mut buf);
+                drop(lock);
+                if let Ok(read_len) = read {
                     if read_len != 0 {
+                        if let Some(current) = Self::VERSION {
+                            let version = $crate::file_version(&buf);
+                            if version != current {
+                                self.backup_file(version);
+                            }
+                            if version > current {
+                                log::warn!(
+                                    "{} is version {version}, newer than {current}. Unknown settings will be dropped",
+                                    self.file_name()
+                                );
+                            } else if version > 0 && version < current {
+                                match $crate::upgrade_to_current::<Self>(version, &buf) {
+                                    Ok(data) => {
+                                        log::info!("Upgraded {} from version {version} to {current}", self.file_name());
+                                        buf = data;
+                                    }
+                                    Err(e) => error!("Config {}: {e}", self.file_name()),
+                                }
+                            }
+                        }
                         if let Ok(data) = ron::from_str(&buf) {
                             self = data;
                             log::info!("Parsed RON for {:?}", std::any::type_name::<Self>());
@@ -237,13 +455,16 @@ macro_rules! std_config_load {
                             self = data.into();
                             log::info!("Newvious version failed, trying previous: Parsed TOML for {:?}", std::any::type_name::<$generic>());
                         })* else {
+                            // `self` is still the default it was called on
                             self.rename_file_old();
-                            self = Self::new();
                         }
                     } else {
                         error!("Config file {} zero read length", self.file_name());
                     }
                 }
+                if let Some(current) = Self::VERSION {
+                    self.set_version(current);
+                }
                 self.write();
                 self
             }
@@ -340,4 +561,151 @@ mod tests {
 
         impl crate::StdConfigLoad3<Old1, Old2, Old3> for Test {}
     }
+
+    #[test]
+    fn upgrade_versions_in_order() {
+        use crate::{StdConfig, StdConfigLoad};
+
+        #[derive(serde::Deserialize, serde::Serialize, Debug)]
+        struct V1 {
+            version: u32,
+            speed: u8,
+        }
+
+        #[derive(serde::Deserialize, serde::Serialize, Debug)]
+        struct V2 {
+            version: u32,
+            fan_speed: u8,
+        }
+
+        #[derive(serde::Deserialize, serde::Serialize, Debug, Default, PartialEq)]
+        struct Test {
+            #[serde(default)]
+            version: u32,
+            fan_speed: u8,
+            fan_count: u8,
+        }
+
+        impl From<V1> for V2 {
+            fn from(v: V1) -> Self {
+                Self {
+                    version: 2,
+                    fan_speed: v.speed,
+                }
+            }
+        }
+
+        impl From<V2> for Test {
+            fn from(v: V2) -> Self {
+                Self {
+                    version: 3,
+                    fan_speed: v.fan_speed,
+                    fan_count: 1,
+                }
+            }
+        }
+
+        impl crate::StdConfig for Test {
+            const VERSION: Option<u32> = Some(3);
+
+            fn new() -> Self {
+                Self::default()
+            }
+
+            fn file_name(&self) -> String {
+                "upgrade.ron".to_owned()
+            }
+
+            fn config_dir() -> PathBuf {
+                std::env::temp_dir().join(format!("config-traits-{}", std::process::id()))
+            }
+
+            fn upgrade(version: u32, data: &str) -> Result<String, String> {
+                match version {
+                    1 => crate::upgrade_with::<V1, V2>(data),
+                    2 => crate::upgrade_with::<V2, Test>(data),
+                    _ => Err("unknown".to_owned()),
+                }
+            }
+
+            fn set_version(&mut self, version: u32) {
+                self.version = version;
+            }
+        }
+
+        impl crate::StdConfigLoad for Test {}
+
+        let dir = Test::config_dir();
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("upgrade.ron");
+        std::fs::write(&path, "(version: 1, speed: 40)").unwrap();
+
+        let config = Test::new().load();
+        assert_eq!(
+            config,
+            Test {
+                version: 3,
+                fan_speed: 40,
+                fan_count: 1
+            }
+        );
+        let backup = std::fs::read_to_string(dir.join("upgrade.ron-v1.bak")).unwrap();
+        assert_eq!(backup, "(version: 1, speed: 40)");
+        let written = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(crate::file_version(&written), 3);
+        assert_eq!(crate::file_version("(speed: 40)"), 0);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn update_keeps_external_edits() {
+        use crate::StdConfig;
+
+        #[derive(serde::Deserialize, serde::Serialize, Debug, Default, PartialEq)]
+        struct Test {
+            brightness: u8,
+            mode: u8,
+        }
+
+        impl crate::StdConfig for Test {
+            fn new() -> Self {
+                Self::default()
+            }
+
+            fn file_name(&self) -> String {
+                "update.ron".to_owned()
+            }
+
+            fn config_dir() -> PathBuf {
+                std::env::temp_dir().join(format!("config-traits-update-{}", std::process::id()))
+            }
+        }
+
+        let dir = Test::config_dir();
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("update.ron");
+
+        let mut config = Test::new();
+        config.write();
+        let written = std::fs::read(&path).unwrap();
+        assert!(crate::is_own_write(&path, &written));
+        assert!(!dir.join(".update.ron.tmp").exists());
+
+        // Edited by something else since it was written
+        std::fs::write(&path, "(brightness: 0, mode: 3)").unwrap();
+        assert!(!crate::is_own_write(&path, b"(brightness: 0, mode: 3)"));
+
+        config.update(|c| c.brightness = 2);
+        assert_eq!(
+            config,
+            Test {
+                brightness: 2,
+                mode: 3
+            }
+        );
+        assert_eq!(config.read_new(), Some(config));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
 }
@@ -104,6 +104,7 @@ desktop-file-validate %{buildroot}/%{_datadir}/applications/rog-control-center.d
 #%dir %{_sysconfdir}/asusd/
 %{_datadir}/asusd/aura_support.ron
 %{_datadir}/dbus-1/system.d/asusd.conf
+%{_datadir}/polkit-1/actions/org.asuslinux.Daemon.policy
 %{_datadir}/icons/hicolor/512x512/apps/asus_notif_yellow.png
 %{_datadir}/icons/hicolor/512x512/apps/asus_notif_green.png
 %{_datadir}/icons/hicolor/512x512/apps/asus_notif_red.png
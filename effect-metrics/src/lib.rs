@@ -0,0 +1,181 @@
+//! Frame timing of the software effects, kept as fixed bucket histograms per
+//! effect type and exported in the Prometheus text format. Render time is
+//! what the daemon spends building a frame, write time is the device write.
+//!
+//! Everything is in memory only and starts from zero when the daemon starts.
+//! asusd and asusd-user each keep their own.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Upper bounds of the histogram buckets in seconds. A frame at 60 FPS has
+/// about 16ms, so the buckets are finest below that.
+pub const BUCKETS: [f64; 10] = [
+    0.0005, 0.001, 0.002, 0.004, 0.008, 0.016, 0.033, 0.066, 0.125, 0.25,
+];
+
+/// Which part of producing a frame was timed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Stage {
+    Render,
+    Write,
+}
+
+impl Stage {
+    fn metric(self) -> &'static str {
+        match self {
+            Stage::Render => "asusd_effect_render_seconds",
+            Stage::Write => "asusd_effect_write_seconds",
+        }
+    }
+
+    fn help(self) -> &'static str {
+        match self {
+            Stage::Render => "Time taken to render one frame of a software effect",
+            Stage::Write => "Time taken to write one frame of a software effect to the device",
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Histogram {
+    /// Observations in each bucket, not cumulative. The last is for anything
+    /// over the largest bound.
+    counts: [u64; BUCKETS.len() + 1],
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    pub fn observe(&mut self, seconds: f64) {
+        let bucket = BUCKETS
+            .iter()
+            .position(|le| seconds <= *le)
+            .unwrap_or(BUCKETS.len());
+        self.counts[bucket] += 1;
+        self.sum += seconds;
+        self.count += 1;
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    fn write_text(&self, out: &mut String, metric: &str, effect: &str) {
+        let mut cumulative = 0;
+        for (le, count) in BUCKETS.iter().zip(self.counts) {
+            cumulative += count;
+            writeln!(
+                out,
+                "{metric}_bucket{{effect=\"{effect}\",le=\"{le}\"}} {cumulative}"
+            )
+            .ok();
+        }
+        writeln!(
+            out,
+            "{metric}_bucket{{effect=\"{effect}\",le=\"+Inf\"}} {}",
+            self.count
+        )
+        .ok();
+        writeln!(out, "{metric}_sum{{effect=\"{effect}\"}} {}", self.sum).ok();
+        writeln!(out, "{metric}_count{{effect=\"{effect}\"}} {}", self.count).ok();
+    }
+}
+
+static FRAME_TIMES: Mutex<BTreeMap<(Stage, &'static str), Histogram>> = Mutex::new(BTreeMap::new());
+
+/// Add one frame time for `effect`
+pub fn record(effect: &'static str, stage: Stage, time: Duration) {
+    if let Ok(mut times) = FRAME_TIMES.lock() {
+        times
+            .entry((stage, effect))
+            .or_default()
+            .observe(time.as_secs_f64());
+    }
+}
+
+/// Run `f` and record how long it took
+pub fn timed<T>(effect: &'static str, stage: Stage, f: impl FnOnce() -> T) -> T {
+    let start = Instant::now();
+    let res = f();
+    record(effect, stage, start.elapsed());
+    res
+}
+
+fn histograms_text(times: &BTreeMap<(Stage, &'static str), Histogram>) -> String {
+    let mut out = String::new();
+    let mut last_stage = None;
+    for ((stage, effect), hist) in times {
+        if last_stage != Some(*stage) {
+            writeln!(out, "# HELP {} {}", stage.metric(), stage.help()).ok();
+            writeln!(out, "# TYPE {} histogram", stage.metric()).ok();
+            last_stage = Some(*stage);
+        }
+        hist.write_text(&mut out, stage.metric(), effect);
+    }
+    out
+}
+
+/// All recorded frame times in the Prometheus text exposition format
+pub fn metrics_text() -> String {
+    FRAME_TIMES
+        .lock()
+        .map(|times| histograms_text(&times))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::{histograms_text, Histogram, Stage, BUCKETS};
+
+    #[test]
+    fn observations_land_in_buckets() {
+        let mut hist = Histogram::default();
+        hist.observe(0.0001);
+        hist.observe(0.001);
+        hist.observe(0.0011);
+        hist.observe(10.0);
+        assert_eq!(hist.counts[0], 1);
+        assert_eq!(hist.counts[1], 1);
+        assert_eq!(hist.counts[2], 1);
+        assert_eq!(hist.counts[BUCKETS.len()], 1);
+        assert_eq!(hist.count(), 4);
+    }
+
+    #[test]
+    fn text_is_cumulative() {
+        let mut render = Histogram::default();
+        render.observe(0.001953125);
+        render.observe(0.00390625);
+        let mut write = Histogram::default();
+        write.observe(0.5);
+        let mut times = BTreeMap::new();
+        times.insert((Stage::Render, "aura_plugin"), render);
+        times.insert((Stage::Write, "aura_stream"), write);
+
+        let text = histograms_text(&times);
+        assert!(text.starts_with(
+            "# HELP asusd_effect_render_seconds Time taken to render one frame of a software \
+             effect\n# TYPE asusd_effect_render_seconds histogram\n"
+        ));
+        assert!(text
+            .contains("asusd_effect_render_seconds_bucket{effect=\"aura_plugin\",le=\"0.001\"} 0"));
+        assert!(text
+            .contains("asusd_effect_render_seconds_bucket{effect=\"aura_plugin\",le=\"0.002\"} 1"));
+        assert!(text
+            .contains("asusd_effect_render_seconds_bucket{effect=\"aura_plugin\",le=\"0.004\"} 2"));
+        assert!(
+            text.contains("asusd_effect_render_seconds_sum{effect=\"aura_plugin\"} 0.005859375")
+        );
+        assert!(text.contains("# TYPE asusd_effect_write_seconds histogram"));
+        assert!(text
+            .contains("asusd_effect_write_seconds_bucket{effect=\"aura_stream\",le=\"0.25\"} 0"));
+        assert!(text
+            .contains("asusd_effect_write_seconds_bucket{effect=\"aura_stream\",le=\"+Inf\"} 1"));
+        assert!(text.contains("asusd_effect_write_seconds_count{effect=\"aura_stream\"} 1"));
+    }
+}
@@ -13,6 +13,7 @@ pub enum AnimeError {
     Png(PngError),
     Gif(DecodingError),
     Format,
+    UnknownImageFormat,
     /// The input was incorrect size, expected size is `IncorrectSize(width,
     /// height)`
     IncorrectSize(u32, u32),
@@ -37,6 +38,7 @@ impl fmt::Display for AnimeError {
             AnimeError::Png(e) => write!(f, "PNG error: {}", e),
             AnimeError::Gif(e) => write!(f, "GIF error: {}", e),
             AnimeError::Format => write!(f, "PNG file is not 8bit greyscale"),
+            AnimeError::UnknownImageFormat => write!(f, "The file is not a GIF or PNG"),
             AnimeError::IncorrectSize(width, height) => write!(
                 f,
                 "The input image size is incorrect, expected {}x{}",
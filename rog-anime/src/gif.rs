@@ -19,6 +19,11 @@ pub struct AnimeFrame {
 }
 
 impl AnimeFrame {
+    #[inline]
+    pub fn new(data: AnimeDataBuffer, delay: Duration) -> Self {
+        Self { data, delay }
+    }
+
     /// Get the inner data buffer of the gif frame
     #[inline]
     pub fn frame(&self) -> &AnimeDataBuffer {
@@ -56,6 +56,108 @@ impl AnimeGrid {
         }
     }
 
+    /// Set a position in the grid, ignoring anything outside of it
+    #[inline]
+    pub fn pixel(&mut self, x: i32, y: i32, b: u8) {
+        if x >= 0 && y >= 0 && (x as usize) < WIDTH && (y as usize) < HEIGHT {
+            self.data[y as usize][x as usize] = b;
+        }
+    }
+
+    /// Draw a line between two points (Bresenham). Parts of the line outside
+    /// the grid are clipped.
+    pub fn line(&mut self, x0: i32, y0: i32, x1: i32, y1: i32, b: u8) {
+        self.line_i64(x0.into(), y0.into(), x1.into(), y1.into(), b);
+    }
+
+    /// `line` in a type wide enough for the corners of any `rect`. The line is
+    /// clipped before it is walked so that far off points are cheap.
+    fn line_i64(&mut self, x0: i64, y0: i64, x1: i64, y1: i64, b: u8) {
+        let Some((x0, y0, x1, y1)) = clip_line(x0, y0, x1, y1) else {
+            return;
+        };
+        let dx = (x1 - x0).abs();
+        let dy = -(y1 - y0).abs();
+        let sx = if x0 < x1 { 1 } else { -1 };
+        let sy = if y0 < y1 { 1 } else { -1 };
+        let mut err = dx + dy;
+        let (mut x, mut y) = (x0, y0);
+        loop {
+            self.data[y as usize][x as usize] = b;
+            if x == x1 && y == y1 {
+                break;
+            }
+            let e2 = 2 * err;
+            if e2 >= dy {
+                err += dy;
+                x += sx;
+            }
+            if e2 <= dx {
+                err += dx;
+                y += sy;
+            }
+        }
+    }
+
+    /// Draw a rectangle outline with the top-left corner at `x`,`y`, or a
+    /// solid rectangle if `fill` is set
+    pub fn rect(&mut self, x: i32, y: i32, w: i32, h: i32, b: u8, fill: bool) {
+        if w <= 0 || h <= 0 {
+            return;
+        }
+        let (x, y) = (i64::from(x), i64::from(y));
+        let (x1, y1) = (x + i64::from(w) - 1, y + i64::from(h) - 1);
+        if fill {
+            let cols = x.max(0)..=x1.min(WIDTH as i64 - 1);
+            for row in y.max(0)..=y1.min(HEIGHT as i64 - 1) {
+                for col in cols.clone() {
+                    self.data[row as usize][col as usize] = b;
+                }
+            }
+        } else {
+            self.line_i64(x, y, x1, y, b);
+            self.line_i64(x, y1, x1, y1, b);
+            self.line_i64(x, y, x, y1, b);
+            self.line_i64(x1, y, x1, y1, b);
+        }
+    }
+
+    /// Copy a sprite that is `width` columns wide in to the grid with the
+    /// top-left corner at `x`,`y`. Zero values in the sprite are transparent.
+    pub fn blit(&mut self, x: i32, y: i32, width: usize, sprite: &[u8]) {
+        if width == 0 {
+            return;
+        }
+        for (i, b) in sprite.iter().enumerate() {
+            let col = i64::from(x) + (i % width) as i64;
+            let row = i64::from(y) + (i / width) as i64;
+            if *b != 0 && (0..WIDTH as i64).contains(&col) && (0..HEIGHT as i64).contains(&row) {
+                self.data[row as usize][col as usize] = *b;
+            }
+        }
+    }
+
+    /// Reduce the grid to `levels` brightness steps using a 4x4 ordered
+    /// dither. Anything less than 2 levels leaves the grid untouched.
+    pub fn dither(&mut self, levels: u8) {
+        const BAYER: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+        if levels < 2 {
+            return;
+        }
+        let step = 255.0 / (levels - 1) as f32;
+        for (y, row) in self.data.iter_mut().enumerate() {
+            for (x, v) in row.iter_mut().enumerate() {
+                let threshold = (BAYER[y % 4][x % 4] as f32 + 0.5) / 16.0;
+                let scaled = *v as f32 / step;
+                let mut level = scaled.floor();
+                if scaled - level > threshold {
+                    level += 1.0;
+                }
+                *v = (level * step).round().min(255.0) as u8;
+            }
+        }
+    }
+
     // pub fn debug_print(&self) {
     //     // this is the index from right. It is used to progressively shorten rows
     //     let mut prog_row_len = WIDTH - 2;
@@ -90,6 +192,67 @@ impl AnimeGrid {
     // }
 }
 
+const INSIDE: u8 = 0;
+const LEFT: u8 = 1;
+const RIGHT: u8 = 2;
+const TOP: u8 = 4;
+const BOTTOM: u8 = 8;
+
+/// Which sides of the grid a point is beyond
+fn outcode(x: i64, y: i64) -> u8 {
+    let mut code = INSIDE;
+    if x < 0 {
+        code |= LEFT;
+    } else if x >= WIDTH as i64 {
+        code |= RIGHT;
+    }
+    if y < 0 {
+        code |= TOP;
+    } else if y >= HEIGHT as i64 {
+        code |= BOTTOM;
+    }
+    code
+}
+
+/// `a * b / c`, where the product of two `i32` spans can overflow `i64`
+fn scale(a: i64, b: i64, c: i64) -> i64 {
+    (i128::from(a) * i128::from(b) / i128::from(c)) as i64
+}
+
+/// Clip a line to the grid (Cohen-Sutherland), `None` if none of it is on
+/// the grid. Points are `i64` so that the maths can't overflow for `i32`
+/// input.
+fn clip_line(mut x0: i64, mut y0: i64, mut x1: i64, mut y1: i64) -> Option<(i64, i64, i64, i64)> {
+    let (max_x, max_y) = (WIDTH as i64 - 1, HEIGHT as i64 - 1);
+    let mut code0 = outcode(x0, y0);
+    let mut code1 = outcode(x1, y1);
+    loop {
+        if code0 | code1 == INSIDE {
+            return Some((x0, y0, x1, y1));
+        }
+        if code0 & code1 != INSIDE {
+            return None;
+        }
+        let code = if code0 != INSIDE { code0 } else { code1 };
+        let (x, y) = if code & TOP != 0 {
+            (x0 + scale(x1 - x0, -y0, y1 - y0), 0)
+        } else if code & BOTTOM != 0 {
+            (x0 + scale(x1 - x0, max_y - y0, y1 - y0), max_y)
+        } else if code & LEFT != 0 {
+            (0, y0 + scale(y1 - y0, -x0, x1 - x0))
+        } else {
+            (max_x, y0 + scale(y1 - y0, max_x - x0, x1 - x0))
+        };
+        if code == code0 {
+            (x0, y0) = (x, y);
+            code0 = outcode(x0, y0);
+        } else {
+            (x1, y1) = (x, y);
+            code1 = outcode(x1, y1);
+        }
+    }
+}
+
 impl TryFrom<AnimeGrid> for AnimeDataBuffer {
     type Error = AnimeError;
 
@@ -116,6 +279,75 @@ impl TryFrom<AnimeGrid> for AnimeDataBuffer {
 mod tests {
     use crate::grid::*;
 
+    #[test]
+    fn draw_clipped() {
+        let mut grid = AnimeGrid::new(AnimeType::GA402);
+        grid.pixel(-1, 0, 0xff);
+        grid.pixel(0, HEIGHT as i32, 0xff);
+        grid.line(-5, 2, 2, 2, 0xff);
+        assert_eq!(grid.get()[2][..4], [0xff, 0xff, 0xff, 0]);
+
+        grid.fill_with(0);
+        grid.rect(1, 1, 3, 3, 0x80, false);
+        assert_eq!(grid.get()[1][1..4], [0x80, 0x80, 0x80]);
+        assert_eq!(grid.get()[2][1..4], [0x80, 0, 0x80]);
+        grid.rect(1, 1, 3, 3, 0x80, true);
+        assert_eq!(grid.get()[2][1..4], [0x80, 0x80, 0x80]);
+
+        grid.fill_with(0);
+        grid.blit(WIDTH as i32 - 1, 0, 2, &[1, 2, 0, 4]);
+        assert_eq!(grid.get()[0][WIDTH - 1], 1);
+        assert_eq!(grid.get()[1][WIDTH - 1], 0);
+    }
+
+    #[test]
+    fn draw_extreme_coordinates() {
+        let mut grid = AnimeGrid::new(AnimeType::GA402);
+        grid.line(i32::MIN, 0, i32::MAX, 0, 0xff);
+        assert!(grid.get()[0].iter().all(|v| *v == 0xff));
+        grid.line(i32::MIN, i32::MIN, i32::MAX, i32::MAX, 0x10);
+        grid.line(i32::MAX, i32::MIN, i32::MAX, i32::MAX, 0x20);
+        grid.line(-10, -10, -1, -1, 0x30);
+        assert!(grid
+            .get()
+            .iter()
+            .flatten()
+            .all(|v| *v != 0x20 && *v != 0x30));
+
+        grid.fill_with(0);
+        grid.rect(i32::MIN, i32::MIN, i32::MAX, i32::MAX, 0x40, false);
+        grid.rect(i32::MAX, i32::MAX, i32::MAX, i32::MAX, 0x40, true);
+        assert!(grid.get().iter().flatten().all(|v| *v == 0));
+        grid.rect(i32::MIN, i32::MIN, i32::MAX, i32::MAX, 0x40, true);
+        assert!(grid.get().iter().flatten().all(|v| *v == 0));
+        grid.rect(-1, -1, i32::MAX, i32::MAX, 0x40, true);
+        assert!(grid.get().iter().flatten().all(|v| *v == 0x40));
+
+        grid.blit(i32::MAX, i32::MAX, 2, &[1, 2, 3, 4]);
+        grid.blit(i32::MIN, 0, usize::MAX, &[1, 2, 3, 4]);
+        assert!(grid.get().iter().flatten().all(|v| *v == 0x40));
+    }
+
+    #[test]
+    fn dither_levels() {
+        let mut grid = AnimeGrid::new(AnimeType::GA402);
+        grid.fill_with(0x80);
+        grid.pixel(0, 0, 0xff);
+        grid.dither(2);
+        assert_eq!(grid.get()[0][0], 0xff);
+        for row in grid.get() {
+            for v in row {
+                assert!(*v == 0 || *v == 0xff);
+            }
+        }
+        let lit = grid.get()[4..8]
+            .iter()
+            .flatten()
+            .filter(|v| **v == 0xff)
+            .count();
+        assert!(lit > 0 && lit < 4 * WIDTH);
+    }
+
     #[test]
     fn check_data_alignment() {
         let mut matrix = AnimeGrid::new(AnimeType::GA401);
@@ -0,0 +1,306 @@
+//! Decoding of GIF and APNG files in to frames for any display type. Each
+//! frame is flattened to greyscale, scaled to fit the display keeping its
+//! aspect ratio, dithered, then sampled at the position of every LED.
+
+use std::path::Path;
+use std::time::Duration;
+
+use crate::error::{AnimeError, Result};
+use crate::{AnimeDataBuffer, AnimeFrame, AnimeGrid, AnimeType};
+
+const PNG_MAGIC: &[u8] = b"\x89PNG";
+const GIF_MAGIC: &[u8] = b"GIF8";
+/// Pixels with less alpha than this are transparent
+const ALPHA_CUTOFF: u8 = 128;
+/// Used for frames which have no delay set, as browsers do
+const DEFAULT_DELAY: Duration = Duration::from_millis(100);
+
+/// A decoded frame, composited over the frames before it
+#[derive(Debug, Clone, PartialEq)]
+pub struct GreyFrame {
+    pub width: usize,
+    pub height: usize,
+    /// Brightness of each pixel, row major
+    pub pixels: Vec<u8>,
+    pub delay: Duration,
+}
+
+/// Decode a GIF or APNG (or a plain PNG as a single frame). The format is
+/// found from the file contents rather than the name.
+pub fn decode_animation(path: &Path) -> Result<Vec<GreyFrame>> {
+    let data = std::fs::read(path)?;
+    let frames = if data.starts_with(GIF_MAGIC) {
+        decode_gif(&data)?
+    } else if data.starts_with(PNG_MAGIC) {
+        decode_png(&data)?
+    } else {
+        return Err(AnimeError::UnknownImageFormat);
+    };
+    if frames.is_empty() {
+        return Err(AnimeError::NoFrames);
+    }
+    Ok(frames)
+}
+
+/// Decode `path` and convert every frame for `anime_type`. `dither_levels`
+/// is passed to `AnimeGrid::dither`.
+pub fn load_animation(
+    path: &Path,
+    anime_type: AnimeType,
+    brightness: f32,
+    dither_levels: u8,
+) -> Result<Vec<AnimeFrame>> {
+    if !(0.0..=1.0).contains(&brightness) {
+        return Err(AnimeError::InvalidBrightness(brightness));
+    }
+    decode_animation(path)?
+        .iter()
+        .map(|frame| {
+            let mut grid = fit_to_grid(frame, anime_type, brightness);
+            grid.dither(dither_levels);
+            Ok(AnimeFrame::new(
+                AnimeDataBuffer::try_from(grid)?,
+                frame.delay,
+            ))
+        })
+        .collect()
+}
+
+fn luma(r: u8, g: u8, b: u8) -> u8 {
+    ((r as u32 * 299 + g as u32 * 587 + b as u32 * 114) / 1000) as u8
+}
+
+fn delay_or_default(millis: u64) -> Duration {
+    if millis == 0 {
+        DEFAULT_DELAY
+    } else {
+        Duration::from_millis(millis)
+    }
+}
+
+fn decode_gif(data: &[u8]) -> Result<Vec<GreyFrame>> {
+    let mut options = gif::DecodeOptions::new();
+    options.set_color_output(gif::ColorOutput::RGBA);
+    let mut decoder = options.read_info(data)?;
+    let width = decoder.width() as usize;
+    let height = decoder.height() as usize;
+
+    let mut canvas = vec![0u8; width * height];
+    let mut frames = Vec::new();
+    while let Some(frame) = decoder.read_next_frame()? {
+        let previous = canvas.clone();
+        let left = frame.left as usize;
+        let top = frame.top as usize;
+        for (y, row) in frame.buffer.chunks(frame.width as usize * 4).enumerate() {
+            for (x, px) in row.chunks(4).enumerate() {
+                let (cx, cy) = (left + x, top + y);
+                if px[3] >= ALPHA_CUTOFF && cx < width && cy < height {
+                    canvas[cy * width + cx] = luma(px[0], px[1], px[2]);
+                }
+            }
+        }
+        frames.push(GreyFrame {
+            width,
+            height,
+            pixels: canvas.clone(),
+            delay: delay_or_default(frame.delay as u64 * 10),
+        });
+
+        match frame.dispose {
+            gif::DisposalMethod::Background => {
+                for y in top..(top + frame.height as usize).min(height) {
+                    for x in left..(left + frame.width as usize).min(width) {
+                        canvas[y * width + x] = 0;
+                    }
+                }
+            }
+            gif::DisposalMethod::Previous => canvas = previous,
+            _ => {}
+        }
+    }
+    Ok(frames)
+}
+
+fn decode_png(data: &[u8]) -> Result<Vec<GreyFrame>> {
+    let mut frames = Vec::new();
+    for step in png_pong::Decoder::new(std::io::Cursor::new(data))?.into_steps() {
+        let png_pong::Step { raster, delay } = step?;
+        let (width, height, pixels) = match raster {
+            png_pong::PngRaster::Gray8(ras) => grey_from_8bit(&ras, true),
+            png_pong::PngRaster::Graya8(ras) => grey_from_8bit(&ras, true),
+            png_pong::PngRaster::Rgb8(ras) => grey_from_8bit(&ras, false),
+            png_pong::PngRaster::Rgba8(ras) => grey_from_8bit(&ras, false),
+            png_pong::PngRaster::Gray16(ras) => grey_from_16bit(&ras, true),
+            png_pong::PngRaster::Graya16(ras) => grey_from_16bit(&ras, true),
+            png_pong::PngRaster::Rgb16(ras) => grey_from_16bit(&ras, false),
+            png_pong::PngRaster::Rgba16(ras) => grey_from_16bit(&ras, false),
+            png_pong::PngRaster::Palette(..) => return Err(AnimeError::Format),
+        };
+        frames.push(GreyFrame {
+            width,
+            height,
+            pixels,
+            delay: delay_or_default(delay as u64),
+        });
+    }
+    Ok(frames)
+}
+
+fn grey_from_8bit<P>(ras: &pix::Raster<P>, grey: bool) -> (usize, usize, Vec<u8>)
+where
+    P: pix::el::Pixel<Chan = pix::chan::Ch8>,
+{
+    let pixels = ras
+        .pixels()
+        .iter()
+        .map(|px| {
+            if u8::from(px.alpha()) < ALPHA_CUTOFF {
+                0
+            } else if grey {
+                u8::from(px.one())
+            } else {
+                luma(px.one().into(), px.two().into(), px.three().into())
+            }
+        })
+        .collect();
+    (ras.width() as usize, ras.height() as usize, pixels)
+}
+
+fn grey_from_16bit<P>(ras: &pix::Raster<P>, grey: bool) -> (usize, usize, Vec<u8>)
+where
+    P: pix::el::Pixel<Chan = pix::chan::Ch16>,
+{
+    let high = |c: pix::chan::Ch16| (u16::from(c) >> 8) as u8;
+    let pixels = ras
+        .pixels()
+        .iter()
+        .map(|px| {
+            if high(px.alpha()) < ALPHA_CUTOFF {
+                0
+            } else if grey {
+                high(px.one())
+            } else {
+                luma(high(px.one()), high(px.two()), high(px.three()))
+            }
+        })
+        .collect();
+    (ras.width() as usize, ras.height() as usize, pixels)
+}
+
+/// Scale `frame` to fit the display, centred, and sample it for each grid
+/// position. Grid rows are half an LED apart and odd rows are shifted half an
+/// LED left, so the sample points match where the LEDs physically are.
+pub fn fit_to_grid(frame: &GreyFrame, anime_type: AnimeType, brightness: f32) -> AnimeGrid {
+    let mut grid = AnimeGrid::new(anime_type);
+    if frame.width == 0 || frame.height == 0 {
+        return grid;
+    }
+    let rows = grid.get().len();
+    let cols = grid.get()[0].len();
+    let display_w = cols as f32;
+    let display_h = rows as f32 / 2.0;
+    let scale = (display_w / frame.width as f32).min(display_h / frame.height as f32);
+    let off_x = (display_w - frame.width as f32 * scale) / 2.0;
+    let off_y = (display_h - frame.height as f32 * scale) / 2.0;
+
+    for (gy, row) in grid.get_mut().iter_mut().enumerate() {
+        let py = (gy as f32 + 0.5) / 2.0;
+        let shift = if gy % 2 == 1 { 0.5 } else { 0.0 };
+        let iy = ((py - off_y) / scale).floor();
+        if iy < 0.0 || iy as usize >= frame.height {
+            continue;
+        }
+        for (gx, led) in row.iter_mut().enumerate() {
+            let px = gx as f32 + 0.5 - shift;
+            let ix = ((px - off_x) / scale).floor();
+            if ix < 0.0 || ix as usize >= frame.width {
+                continue;
+            }
+            let v = frame.pixels[iy as usize * frame.width + ix as usize];
+            *led = (v as f32 * brightness) as u8;
+        }
+    }
+    grid
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+    use std::time::Duration;
+
+    use super::{decode_gif, fit_to_grid, GreyFrame, DEFAULT_DELAY};
+    use crate::AnimeType;
+
+    fn frame(width: usize, height: usize, pixels: Vec<u8>) -> GreyFrame {
+        GreyFrame {
+            width,
+            height,
+            pixels,
+            delay: DEFAULT_DELAY,
+        }
+    }
+
+    #[test]
+    fn fit_keeps_aspect() {
+        // A square is limited by the height, 27.5 LEDs, leaving 2.75 at each side
+        let grid = fit_to_grid(&frame(2, 2, vec![255; 4]), AnimeType::GA401, 1.0);
+        let data = grid.get();
+        assert_eq!(data[0][2], 0);
+        assert_eq!(data[0][3], 255);
+        assert_eq!(data[0][30], 0);
+        assert_eq!(data[54][3], 255);
+
+        let grid = fit_to_grid(&frame(2, 2, vec![255; 4]), AnimeType::GA401, 0.5);
+        assert_eq!(grid.get()[10][10], 127);
+    }
+
+    #[test]
+    fn fit_offsets_odd_rows() {
+        // 66x55 fits exactly at two pixels per LED across and per row down
+        let mut pixels = vec![0; 66 * 55];
+        for y in 0..55 {
+            pixels[y * 66 + 1] = 255;
+        }
+        let grid = fit_to_grid(&frame(66, 55, pixels), AnimeType::GA401, 1.0);
+        let data = grid.get();
+        // Even rows sample at x 0.5, odd rows at x 0.0
+        assert_eq!(data[0][0], 255);
+        assert_eq!(data[1][0], 0);
+        assert_eq!(data[2][0], 255);
+        assert_eq!(data[0][1], 0);
+    }
+
+    #[test]
+    fn gif_frames_composite() {
+        let mut data = Vec::new();
+        {
+            let palette = [0, 0, 0, 255, 255, 255];
+            let mut encoder = gif::Encoder::new(&mut data, 2, 2, &palette).unwrap();
+            encoder
+                .write_frame(&gif::Frame {
+                    width: 2,
+                    height: 2,
+                    delay: 5,
+                    buffer: Cow::Borrowed(&[1, 0, 0, 1]),
+                    ..Default::default()
+                })
+                .unwrap();
+            encoder
+                .write_frame(&gif::Frame {
+                    left: 1,
+                    width: 1,
+                    height: 1,
+                    buffer: Cow::Borrowed(&[1]),
+                    ..Default::default()
+                })
+                .unwrap();
+        }
+
+        let frames = decode_gif(&data).unwrap();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].pixels, vec![255, 0, 0, 255]);
+        assert_eq!(frames[0].delay, Duration::from_millis(50));
+        assert_eq!(frames[1].pixels, vec![255, 255, 0, 255]);
+        assert_eq!(frames[1].delay, DEFAULT_DELAY);
+    }
+}
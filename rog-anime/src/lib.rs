@@ -21,11 +21,19 @@ pub use diagonal::*;
 mod gif;
 pub use crate::gif::*;
 
+/// Decode GIF and APNG files of any size to frames for the display
+mod import;
+pub use import::*;
+
 /// A container of images/grids/gifs/pauses which can be iterated over to
 /// generate cool effects
 mod sequencer;
 pub use sequencer::*;
 
+/// Daemon drawn widgets and the small font they use
+mod widget;
+pub use widget::*;
+
 /// Base errors that are possible
 pub mod error;
 
@@ -0,0 +1,143 @@
+use serde_derive::{Deserialize, Serialize};
+use typeshare::typeshare;
+#[cfg(feature = "dbus")]
+use zbus::zvariant::{OwnedValue, Type, Value};
+
+use crate::AnimeGrid;
+
+/// Height in pixels of a line of widget text
+pub const GLYPH_HEIGHT: i32 = 5;
+/// Width of one character including the gap after it
+pub const GLYPH_ADVANCE: i32 = 4;
+
+/// Things that asusd can draw on the display by itself, on top of anything
+/// else being shown
+#[cfg_attr(
+    feature = "dbus",
+    derive(Type, Value, OwnedValue),
+    zvariant(signature = "s")
+)]
+#[typeshare]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum AnimeWidget {
+    /// Local time as `HH:MM`
+    Clock,
+    /// CPU package temperature
+    CpuTemp,
+    /// dGPU or iGPU temperature
+    GpuTemp,
+    /// Title of the playing media, scrolled if too long
+    Media,
+}
+
+impl std::str::FromStr for AnimeWidget {
+    type Err = crate::error::AnimeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "clock" => Ok(Self::Clock),
+            "cputemp" | "cpu" => Ok(Self::CpuTemp),
+            "gputemp" | "gpu" => Ok(Self::GpuTemp),
+            "media" => Ok(Self::Media),
+            _ => Err(crate::error::AnimeError::ParseError(format!("widget {s}"))),
+        }
+    }
+}
+
+/// A 3x5 glyph, one `u8` per row with the left pixel in bit 2. Lowercase is
+/// drawn as uppercase and anything unknown as `?`.
+fn glyph(c: char) -> [u8; 5] {
+    match c.to_ascii_uppercase() {
+        '0' => [7, 5, 5, 5, 7],
+        '1' => [2, 6, 2, 2, 7],
+        '2' => [7, 1, 7, 4, 7],
+        '3' => [7, 1, 7, 1, 7],
+        '4' => [5, 5, 7, 1, 1],
+        '5' => [7, 4, 7, 1, 7],
+        '6' => [7, 4, 7, 5, 7],
+        '7' => [7, 1, 1, 1, 1],
+        '8' => [7, 5, 7, 5, 7],
+        '9' => [7, 5, 7, 1, 7],
+        'A' => [2, 5, 7, 5, 5],
+        'B' => [6, 5, 6, 5, 6],
+        'C' => [3, 4, 4, 4, 3],
+        'D' => [6, 5, 5, 5, 6],
+        'E' => [7, 4, 6, 4, 7],
+        'F' => [7, 4, 6, 4, 4],
+        'G' => [3, 4, 5, 5, 3],
+        'H' => [5, 5, 7, 5, 5],
+        'I' => [7, 2, 2, 2, 7],
+        'J' => [1, 1, 1, 5, 2],
+        'K' => [5, 5, 6, 5, 5],
+        'L' => [4, 4, 4, 4, 7],
+        'M' => [5, 7, 7, 5, 5],
+        'N' => [6, 5, 5, 5, 5],
+        'O' => [2, 5, 5, 5, 2],
+        'P' => [6, 5, 6, 4, 4],
+        'Q' => [2, 5, 5, 6, 3],
+        'R' => [6, 5, 6, 5, 5],
+        'S' => [3, 4, 2, 1, 6],
+        'T' => [7, 2, 2, 2, 2],
+        'U' => [5, 5, 5, 5, 7],
+        'V' => [5, 5, 5, 5, 2],
+        'W' => [5, 5, 7, 7, 5],
+        'X' => [5, 5, 2, 5, 5],
+        'Y' => [5, 5, 2, 2, 2],
+        'Z' => [7, 1, 2, 4, 7],
+        ' ' => [0, 0, 0, 0, 0],
+        ':' => [0, 2, 0, 2, 0],
+        '.' => [0, 0, 0, 0, 2],
+        ',' => [0, 0, 0, 2, 4],
+        '-' => [0, 0, 7, 0, 0],
+        '%' => [5, 1, 2, 4, 5],
+        '/' => [1, 1, 2, 4, 4],
+        '\'' => [2, 2, 0, 0, 0],
+        '!' => [2, 2, 2, 0, 2],
+        _ => [7, 1, 2, 0, 2],
+    }
+}
+
+/// Width in pixels of `text` when drawn with `AnimeGrid::text`
+pub fn text_width(text: &str) -> i32 {
+    (text.chars().count() as i32 * GLYPH_ADVANCE - 1).max(0)
+}
+
+impl AnimeGrid {
+    /// Draw `text` with the top-left corner at `x`,`y` using the built in 3x5
+    /// font, clipped to the grid
+    pub fn text(&mut self, x: i32, y: i32, text: &str, b: u8) {
+        for (i, c) in text.chars().enumerate() {
+            let left = x + i as i32 * GLYPH_ADVANCE;
+            for (row, bits) in glyph(c).iter().enumerate() {
+                for col in 0..3 {
+                    if bits & (4 >> col) != 0 {
+                        self.pixel(left + col, y + row as i32, b);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::text_width;
+    use crate::{AnimeGrid, AnimeType};
+
+    #[test]
+    fn draw_text() {
+        let mut grid = AnimeGrid::new(AnimeType::GA401);
+        grid.text(0, 0, "1-", 255);
+        let data = grid.get();
+        // "1" is [2, 6, 2, 2, 7]
+        assert_eq!(&data[0][..3], &[0, 255, 0]);
+        assert_eq!(&data[1][..3], &[255, 255, 0]);
+        assert_eq!(&data[4][..3], &[255, 255, 255]);
+        // "-" starts after one column of space
+        assert_eq!(&data[2][3..7], &[0, 255, 255, 255]);
+        assert_eq!(data[0][4..7], [0, 0, 0]);
+
+        assert_eq!(text_width(""), 0);
+        assert_eq!(text_width("12:34"), 19);
+    }
+}
@@ -0,0 +1,114 @@
+//! Per-key packets of a software effect, as built for each frame. Run with
+//! `cargo bench -p rog_aura`. The allocations and rows written per frame are
+//! printed before the timings.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::hint::black_box;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use rog_aura::effects::{AdvancedEffects, Breathe, Effect, Static};
+use rog_aura::keyboard::{KeyLayout, LedCode, LedUsbPackets};
+use rog_aura::{Colour, Speed};
+
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+const FRAMES: usize = 600;
+const RED: Colour = Colour { r: 255, g: 0, b: 0 };
+const BLUE: Colour = Colour { r: 0, g: 0, b: 255 };
+
+/// A few breathing keys over a static backdrop, as a typical effect where
+/// most of the keyboard stays the same between frames
+fn effects() -> AdvancedEffects {
+    let mut effects = AdvancedEffects::new(false);
+    for key in [LedCode::Esc, LedCode::F1, LedCode::F2, LedCode::F3] {
+        effects.push(Effect::Static(Static::new(key, BLUE)));
+    }
+    for key in [LedCode::W, LedCode::A, LedCode::S, LedCode::D] {
+        effects.push(Effect::Breathe(Breathe::new(key, RED, BLUE, Speed::Med)));
+    }
+    effects
+}
+
+/// Allocations and rows written over `FRAMES` frames, building new packets
+/// for each and writing all rows, then reusing one set and writing the rows
+/// that changed
+fn report(layout: &KeyLayout) {
+    let mut seq = effects();
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    let mut rows = 0;
+    for _ in 0..FRAMES {
+        seq.next_state(layout);
+        rows += black_box(seq.create_packets()).len();
+    }
+    let allocs = ALLOCATIONS.load(Ordering::Relaxed) - before;
+    println!("create_packets: {allocs} allocations, {rows} rows written for {FRAMES} frames");
+
+    let mut seq = effects();
+    let mut packets = seq.new_packets();
+    let mut last = seq.new_packets();
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    let mut rows = 0;
+    for _ in 0..FRAMES {
+        seq.next_state(layout);
+        seq.write_packets(&mut packets);
+        rows += packets.changed_rows(&last).count();
+        last.clone_from(&packets);
+    }
+    let allocs = ALLOCATIONS.load(Ordering::Relaxed) - before;
+    println!("write_packets: {allocs} allocations, {rows} rows written for {FRAMES} frames");
+}
+
+fn packets(c: &mut Criterion) {
+    let layout = KeyLayout::default_layout();
+    report(&layout);
+
+    c.bench_function("create_packets", |b| {
+        let mut seq = effects();
+        b.iter(|| {
+            seq.next_state(&layout);
+            black_box(seq.create_packets());
+        })
+    });
+
+    c.bench_function("write_packets", |b| {
+        let mut seq = effects();
+        let mut packets = seq.new_packets();
+        b.iter(|| {
+            seq.next_state(&layout);
+            seq.write_packets(&mut packets);
+            black_box(&packets);
+        })
+    });
+
+    c.bench_function("changed_rows", |b| {
+        let mut seq = effects();
+        let mut packets = seq.new_packets();
+        let mut last = LedUsbPackets::new_per_key();
+        b.iter(|| {
+            seq.next_state(&layout);
+            seq.write_packets(&mut packets);
+            black_box(packets.changed_rows(&last).count());
+            last.clone_from(&packets);
+        })
+    });
+}
+
+criterion_group!(benches, packets);
+criterion_main!(benches);
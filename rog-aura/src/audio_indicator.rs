@@ -0,0 +1,93 @@
+//! A zone lit in a colour showing which audio output is in use, so sound
+//! going to the wrong sink is noticed. The output is found by a helper in the
+//! user session and passed to asusd, which only sees the system bus.
+
+use serde::{Deserialize, Serialize};
+use typeshare::typeshare;
+#[cfg(feature = "dbus")]
+use zbus::zvariant::{OwnedValue, Type, Value};
+
+use crate::{AuraEffect, AuraModeNum, AuraZone, Colour, BLUE, GREEN, ORANGE};
+
+/// The kind of the default audio sink
+#[typeshare]
+#[cfg_attr(
+    feature = "dbus",
+    derive(Type, Value, OwnedValue),
+    zvariant(signature = "u")
+)]
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub enum AudioOutput {
+    /// Not known yet, or a sink that is none of the others
+    #[default]
+    Unknown = 0,
+    Speakers = 1,
+    Headphones = 2,
+    Hdmi = 3,
+}
+
+#[typeshare]
+#[cfg_attr(feature = "dbus", derive(Type, Value, OwnedValue))]
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct AudioIndicator {
+    pub enabled: bool,
+    /// The zone lit, it must be one the device addresses on its own
+    pub zone: AuraZone,
+    pub speakers: Colour,
+    pub headphones: Colour,
+    pub hdmi: Colour,
+}
+
+impl Default for AudioIndicator {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            zone: AuraZone::Logo,
+            speakers: GREEN,
+            headphones: BLUE,
+            hdmi: ORANGE,
+        }
+    }
+}
+
+impl AudioIndicator {
+    /// The effect to write over the current mode for `output`, `None` if the
+    /// indicator is off or the output is unknown
+    pub fn effect(&self, output: AudioOutput) -> Option<AuraEffect> {
+        let colour1 = match output {
+            _ if !self.enabled => return None,
+            AudioOutput::Unknown => return None,
+            AudioOutput::Speakers => self.speakers,
+            AudioOutput::Headphones => self.headphones,
+            AudioOutput::Hdmi => self.hdmi,
+        };
+        Some(AuraEffect {
+            mode: AuraModeNum::Static,
+            zone: self.zone,
+            colour1,
+            ..Default::default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AudioIndicator, AudioOutput};
+    use crate::{AuraModeNum, AuraZone, BLUE};
+
+    #[test]
+    fn indicator_effect_follows_output() {
+        let mut indicator = AudioIndicator {
+            zone: AuraZone::Key4,
+            ..Default::default()
+        };
+        assert!(indicator.effect(AudioOutput::Headphones).is_none());
+
+        indicator.enabled = true;
+        assert!(indicator.effect(AudioOutput::Unknown).is_none());
+        let effect = indicator.effect(AudioOutput::Headphones).unwrap();
+        assert_eq!(effect.mode, AuraModeNum::Static);
+        assert_eq!(effect.zone, AuraZone::Key4);
+        assert_eq!(effect.colour1, BLUE);
+    }
+}
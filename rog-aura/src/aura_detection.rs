@@ -1,16 +1,23 @@
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+
 use dmi_id::DMIID;
 use log::{error, info, warn};
 use serde_derive::{Deserialize, Serialize};
 use typeshare::typeshare;
 use zbus::zvariant::{OwnedValue, Type, Value};
 
+use crate::colour_correction::ColourCorrection;
+use crate::error::Error;
 use crate::keyboard::AdvancedAuraType;
+use crate::power_estimate::PowerCalibration;
 use crate::{AuraModeNum, AuraZone};
 
 pub const ASUS_LED_MODE_CONF: &str = "/usr/share/asusd/aura_support.ron";
 pub const ASUS_LED_MODE_USER_CONF: &str = "/etc/asusd/asusd_user_ledmodes.ron";
+pub const ASUS_LED_MODE_DROPIN_DIR: &str = "/etc/asusd/aura.d";
 
-#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
 pub struct LedSupportFile(Vec<LaptopLedData>);
 
 /// The powerr zones this laptop supports
@@ -20,7 +27,9 @@ pub struct LedSupportFile(Vec<LaptopLedData>);
     derive(Type, Value, OwnedValue),
     zvariant(signature = "u")
 )]
-#[derive(Serialize, Deserialize, PartialEq, Eq, Hash, Debug, Default, Copy, Clone)]
+#[derive(
+    Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Default, Copy, Clone,
+)]
 pub enum PowerZones {
     /// The logo on some laptop lids
     #[default]
@@ -37,7 +46,22 @@ pub enum PowerZones {
     KeyboardAndLightbar = 5,
 }
 
-#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+impl PowerZones {
+    /// A generic description of the zone, used where the support data has no
+    /// model specific description
+    pub const fn description(&self) -> &'static str {
+        match self {
+            PowerZones::Logo => "The logo on the lid",
+            PowerZones::Keyboard => "The keyboard backlight",
+            PowerZones::Lightbar => "The lightbar, typically along the front edge",
+            PowerZones::Lid => "LEDs around the edge of the lid",
+            PowerZones::RearGlow => "The LED strip on the rear of the laptop",
+            PowerZones::KeyboardAndLightbar => "The keyboard and lightbar together",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
 pub struct LaptopLedData {
     /// Found via `cat /sys/class/dmi/id/board_name`, e.g `GU603ZW`.
     /// The match doesn't have to be the complete model number as it is
@@ -52,22 +76,167 @@ pub struct LaptopLedData {
     pub basic_zones: Vec<AuraZone>,
     pub advanced_type: AdvancedAuraType,
     pub power_zones: Vec<PowerZones>,
+    /// What each power zone physically controls on this model, for zones where
+    /// the generic name is ambiguous
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub power_zone_descriptions: BTreeMap<PowerZones, String>,
+    /// Logical zone to the zone ID the hardware actually uses, for models
+    /// where the zones are not in the usual order
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub zone_remap: BTreeMap<AuraZone, AuraZone>,
+    /// Measured LED power draw, used by the power estimate in place of the
+    /// typical figures
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub power_calibration: Option<PowerCalibration>,
+    /// Makes up for the colour response of the LEDs on this model
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub colour_correction: Option<ColourCorrection>,
 }
 
 impl LaptopLedData {
+    /// Find the support data for this laptop, with any drop-ins from
+    /// `/etc/asusd/aura.d/*.toml` merged over it
     pub fn get_data() -> Self {
         let dmi = DMIID::new().unwrap_or_default();
         // let prod_family = dmi.product_family().expect("Could not get
         // product_family");
 
-        if let Some(modes) = LedSupportFile::load_from_supoprt_db() {
-            if let Some(data) = modes.matcher(&dmi.board_name) {
-                return data;
-            }
+        let support = LedSupportFile::load_from_supoprt_db();
+        let overrides = LedSupportOverride::load_dir(Path::new(ASUS_LED_MODE_DROPIN_DIR));
+        if let Some(data) = Self::merged(&dmi.board_name, support, &overrides) {
+            return data;
         }
         info!("Using generic LED control for keyboard brightness only");
         LaptopLedData::default()
     }
+
+    /// Match the board in the support data then apply each override for the
+    /// board in order. An override can add a board that isn't in the support
+    /// data at all.
+    pub fn merged(
+        board_name: &str,
+        support: Option<LedSupportFile>,
+        overrides: &[LedSupportOverride],
+    ) -> Option<Self> {
+        let mut data = support.and_then(|s| s.matcher(board_name));
+        for o in overrides
+            .iter()
+            .filter(|o| !o.board_name.is_empty() && board_name.contains(&o.board_name))
+        {
+            info!("LedSupport: applying drop-in for {}", o.board_name);
+            o.apply(data.get_or_insert_with(LaptopLedData::default));
+        }
+        data
+    }
+
+    /// The zone ID to write to the device for a logical `zone`
+    pub fn hardware_zone(&self, zone: AuraZone) -> AuraZone {
+        self.zone_remap.get(&zone).copied().unwrap_or(zone)
+    }
+
+    /// Describe every supported power zone. Model specific descriptions from
+    /// the support data are used where available.
+    pub fn describe_power_zones(&self) -> BTreeMap<PowerZones, String> {
+        self.power_zones
+            .iter()
+            .map(|zone| {
+                let desc = self
+                    .power_zone_descriptions
+                    .get(zone)
+                    .cloned()
+                    .unwrap_or_else(|| zone.description().to_owned());
+                (*zone, desc)
+            })
+            .collect()
+    }
+}
+
+/// A partial `LaptopLedData` from a drop-in file in `/etc/asusd/aura.d/`.
+///
+/// It is merged over the support data of any board matching `board_name`:
+/// modes and zones are added to, while `layout_name` and `advanced_type`
+/// replace the existing value if set, as does `colour_correction`. Each
+/// `zone_remap` entry replaces the entry for the same logical zone.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct LedSupportOverride {
+    pub board_name: String,
+    pub layout_name: Option<String>,
+    pub basic_modes: Vec<AuraModeNum>,
+    pub basic_zones: Vec<AuraZone>,
+    pub advanced_type: Option<AdvancedAuraType>,
+    pub power_zones: Vec<PowerZones>,
+    pub power_zone_descriptions: BTreeMap<PowerZones, String>,
+    pub zone_remap: BTreeMap<AuraZone, AuraZone>,
+    pub power_calibration: Option<PowerCalibration>,
+    pub colour_correction: Option<ColourCorrection>,
+}
+
+impl LedSupportOverride {
+    /// Load every `*.toml` file in `dir` in filename order. Files which fail to
+    /// parse are logged and skipped.
+    pub fn load_dir(dir: &Path) -> Vec<Self> {
+        let mut paths: Vec<PathBuf> = match std::fs::read_dir(dir) {
+            Ok(entries) => entries
+                .filter_map(|e| e.ok().map(|e| e.path()))
+                .filter(|p| p.extension().map_or(false, |e| e == "toml"))
+                .collect(),
+            Err(_) => return Vec::new(),
+        };
+        paths.sort();
+
+        paths
+            .iter()
+            .filter_map(|path| {
+                let text = std::fs::read_to_string(path)
+                    .map_err(|e| warn!("{}: {e}", path.display()))
+                    .ok()?;
+                toml::from_str::<Self>(&text)
+                    .map_err(|e| error!("{}: {e}. The file will be ignored", path.display()))
+                    .ok()
+            })
+            .collect()
+    }
+
+    /// Merge this override in to `data`
+    pub fn apply(&self, data: &mut LaptopLedData) {
+        if data.board_name.is_empty() {
+            data.board_name = self.board_name.clone();
+        }
+        if let Some(layout_name) = &self.layout_name {
+            data.layout_name = layout_name.clone();
+        }
+        for mode in &self.basic_modes {
+            if !data.basic_modes.contains(mode) {
+                data.basic_modes.push(*mode);
+            }
+        }
+        for zone in &self.basic_zones {
+            if !data.basic_zones.contains(zone) {
+                data.basic_zones.push(*zone);
+            }
+        }
+        if let Some(advanced_type) = &self.advanced_type {
+            data.advanced_type = advanced_type.clone();
+        }
+        for zone in &self.power_zones {
+            if !data.power_zones.contains(zone) {
+                data.power_zones.push(*zone);
+            }
+        }
+        data.power_zone_descriptions
+            .extend(self.power_zone_descriptions.clone());
+        data.zone_remap.extend(self.zone_remap.clone());
+        if let Some(calibration) = &self.power_calibration {
+            data.power_calibration
+                .get_or_insert_with(PowerCalibration::default)
+                .zone_mw
+                .extend(calibration.zone_mw.clone());
+        }
+        if self.colour_correction.is_some() {
+            data.colour_correction = self.colour_correction;
+        }
+    }
 }
 
 impl LedSupportFile {
@@ -87,6 +256,97 @@ impl LedSupportFile {
         None
     }
 
+    /// Parse the text of a LED support file and check each entry for mistakes
+    /// that deserialising alone doesn't catch. A syntax error is returned as
+    /// `Err` including the line and column, otherwise the parsed data is
+    /// returned with a list of problems naming the board and field.
+    pub fn validate(text: &str) -> Result<(Self, Vec<String>), Error> {
+        let data: LedSupportFile = ron::from_str(text)?;
+        let mut issues = Vec::new();
+        let mut boards = HashSet::new();
+
+        for (i, entry) in data.0.iter().enumerate() {
+            let name = if entry.board_name.is_empty() {
+                issues.push(format!("entry {i}: board_name is empty"));
+                format!("entry {i}")
+            } else {
+                if !boards.insert(entry.board_name.as_str()) {
+                    issues.push(format!("{}: board_name is duplicated", entry.board_name));
+                }
+                entry.board_name.clone()
+            };
+
+            if entry.layout_name.is_empty() {
+                issues.push(format!("{name}: layout_name is empty"));
+            }
+            if entry.basic_modes.is_empty() {
+                issues.push(format!("{name}: basic_modes is empty"));
+            }
+            let mut modes = HashSet::new();
+            for mode in &entry.basic_modes {
+                if !modes.insert(*mode) {
+                    issues.push(format!("{name}: basic_modes has {mode:?} more than once"));
+                }
+            }
+            if entry.basic_zones.contains(&AuraZone::None) {
+                issues.push(format!("{name}: basic_zones should not contain None"));
+            }
+            if entry.power_zones.is_empty() {
+                issues.push(format!("{name}: power_zones is empty"));
+            }
+            for zone in entry.power_zone_descriptions.keys() {
+                if !entry.power_zones.contains(zone) {
+                    issues.push(format!(
+                        "{name}: power_zone_descriptions has {zone:?} which is not in power_zones"
+                    ));
+                }
+            }
+            let mut targets = HashSet::new();
+            for (from, to) in &entry.zone_remap {
+                if *from == AuraZone::None || *to == AuraZone::None {
+                    issues.push(format!("{name}: zone_remap should not contain None"));
+                }
+                if !targets.insert(*to) {
+                    issues.push(format!(
+                        "{name}: zone_remap maps more than one zone to {to:?}"
+                    ));
+                }
+            }
+            if let AdvancedAuraType::Zoned(codes) = &entry.advanced_type {
+                if codes.is_empty() {
+                    issues.push(format!("{name}: advanced_type is Zoned with no LED codes"));
+                }
+            }
+            if let Some(problem) = entry.colour_correction.as_ref().and_then(|c| c.check()) {
+                issues.push(format!("{name}: colour_correction {problem}"));
+            }
+        }
+
+        Ok((data, issues))
+    }
+
+    /// Read and validate a single support file. Any problem is logged, and a
+    /// file which can't be parsed is skipped rather than being fatal.
+    fn load_file(path: &str) -> Option<Self> {
+        let file = std::fs::read_to_string(path).ok()?;
+        if file.is_empty() {
+            warn!("{path} is empty");
+            return None;
+        }
+        match Self::validate(&file) {
+            Ok((data, issues)) => {
+                for issue in issues {
+                    warn!("{path}: {issue}");
+                }
+                Some(data)
+            }
+            Err(e) => {
+                error!("{path}: {e}. The file will be ignored");
+                None
+            }
+        }
+    }
+
     /// Load `LedSupportFile` from the `aura_support.ron` file at
     /// `/usr/share/asusd/aura_support.ron` and append with data from
     /// `/etc/asusd/asusd_user_ledmodes.ron` if that file is available.
@@ -96,34 +356,21 @@ impl LedSupportFile {
         let mut loaded = false;
         let mut data = LedSupportFile::default();
         // Load user configs first so they are first to be checked
-        if let Ok(file) = std::fs::read_to_string(ASUS_LED_MODE_USER_CONF) {
-            if file.is_empty() {
-                warn!("{} is empty", ASUS_LED_MODE_USER_CONF);
-            } else {
-                if let Ok(mut tmp) = ron::from_str::<LedSupportFile>(&file) {
-                    data.0.append(&mut tmp.0);
-                }
-                info!(
-                    "Loaded user-defined LED support data from {}",
-                    ASUS_LED_MODE_USER_CONF
-                );
-            }
+        if let Some(mut tmp) = Self::load_file(ASUS_LED_MODE_USER_CONF) {
+            data.0.append(&mut tmp.0);
+            info!(
+                "Loaded user-defined LED support data from {}",
+                ASUS_LED_MODE_USER_CONF
+            );
         }
         // Load and append the default LED support data
-        if let Ok(file) = std::fs::read_to_string(ASUS_LED_MODE_CONF) {
-            if file.is_empty() {
-                warn!("{} is empty", ASUS_LED_MODE_CONF);
-            } else {
-                let mut tmp: LedSupportFile = ron::from_str(&file)
-                    .map_err(|e| error!("{e}"))
-                    .unwrap_or_else(|_| panic!("Could not deserialise {}", ASUS_LED_MODE_CONF));
-                data.0.append(&mut tmp.0);
-                loaded = true;
-                info!(
-                    "Loaded default LED support data from {}",
-                    ASUS_LED_MODE_CONF
-                );
-            }
+        if let Some(mut tmp) = Self::load_file(ASUS_LED_MODE_CONF) {
+            data.0.append(&mut tmp.0);
+            loaded = true;
+            info!(
+                "Loaded default LED support data from {}",
+                ASUS_LED_MODE_CONF
+            );
         }
         data.0.sort_by(|a, b| a.board_name.cmp(&b.board_name));
 
@@ -131,13 +378,14 @@ impl LedSupportFile {
             return Some(data);
         }
 
-        warn!("Does {} exist?", ASUS_LED_MODE_USER_CONF);
+        warn!("Does {} exist?", ASUS_LED_MODE_CONF);
         None
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::collections::BTreeMap;
     use std::fs::OpenOptions;
     use std::io::Write;
     use std::path::PathBuf;
@@ -145,7 +393,8 @@ mod tests {
     use ron::ser::PrettyConfig;
 
     use super::LaptopLedData;
-    use crate::aura_detection::{LedSupportFile, PowerZones};
+    use crate::aura_detection::{LedSupportFile, LedSupportOverride, PowerZones};
+    use crate::colour_correction::ColourCorrection;
     use crate::keyboard::{AdvancedAuraType, LedCode};
     // use crate::zoned::Zone;
     use crate::{AuraModeNum, AuraZone};
@@ -159,12 +408,134 @@ mod tests {
             basic_zones: vec![AuraZone::Key1, AuraZone::Logo, AuraZone::BarLeft],
             advanced_type: AdvancedAuraType::Zoned(vec![LedCode::LightbarRight]),
             power_zones: vec![PowerZones::Keyboard, PowerZones::RearGlow],
+            power_zone_descriptions: BTreeMap::from([(
+                PowerZones::RearGlow,
+                "Lid outer ring".to_owned(),
+            )]),
+            zone_remap: BTreeMap::new(),
+            power_calibration: None,
+            colour_correction: None,
         };
 
         assert!(ron::to_string(&led).is_ok());
+        let desc = led.describe_power_zones();
+        assert_eq!(desc[&PowerZones::RearGlow], "Lid outer ring");
+        assert_eq!(
+            desc[&PowerZones::Keyboard],
+            PowerZones::Keyboard.description()
+        );
         // assert_eq!(json, String::new());
     }
 
+    #[test]
+    fn validate_led_data() {
+        let err = LedSupportFile::validate("([\n    (board_name: 401),\n])").unwrap_err();
+        assert!(err.to_string().contains("2:"));
+
+        let text = r#"([
+            (
+                board_name: "GA401",
+                layout_name: "",
+                basic_modes: [Static, Static],
+                basic_zones: [None],
+                advanced_type: Zoned([]),
+                power_zones: [],
+                zone_remap: {Key1: Key2, Key3: Key2},
+            ),
+            (
+                board_name: "GA401",
+                layout_name: "ga401",
+                basic_modes: [Static],
+                basic_zones: [],
+                advanced_type: None,
+                power_zones: [Keyboard],
+            ),
+        ])"#;
+        let (data, issues) = LedSupportFile::validate(text).unwrap();
+        assert_eq!(data.get().len(), 2);
+        assert_eq!(
+            issues,
+            vec![
+                "GA401: layout_name is empty",
+                "GA401: basic_modes has Static more than once",
+                "GA401: basic_zones should not contain None",
+                "GA401: power_zones is empty",
+                "GA401: zone_remap maps more than one zone to Key2",
+                "GA401: advanced_type is Zoned with no LED codes",
+                "GA401: board_name is duplicated",
+            ]
+        );
+    }
+
+    #[test]
+    fn merge_overrides() {
+        let support = LedSupportFile(vec![LaptopLedData {
+            board_name: "GA402".to_owned(),
+            layout_name: "ga401q".to_owned(),
+            basic_modes: vec![AuraModeNum::Static],
+            basic_zones: vec![],
+            advanced_type: AdvancedAuraType::None,
+            power_zones: vec![PowerZones::Keyboard],
+            power_zone_descriptions: BTreeMap::new(),
+            zone_remap: BTreeMap::from([(AuraZone::Key1, AuraZone::Key2)]),
+            power_calibration: None,
+            colour_correction: None,
+        }]);
+        let text = r#"
+            board_name = "GA402"
+            layout_name = "ga402x"
+            basic_modes = ["Static", "Breathe"]
+            advanced_type = "PerKey"
+            power_zones = ["Lightbar"]
+
+            [zone_remap]
+            Key1 = "Key4"
+            Key4 = "Key1"
+
+            [power_calibration.zone_mw]
+            Lightbar = 250
+
+            [colour_correction]
+            gamma = [1.0, 1.0, 1.2]
+        "#;
+        let over: LedSupportOverride = toml::from_str(text).unwrap();
+        let other = LedSupportOverride {
+            board_name: "G513".to_owned(),
+            basic_zones: vec![AuraZone::Key1],
+            ..Default::default()
+        };
+
+        let data = LaptopLedData::merged("GA402XV", Some(support.clone()), &[over, other.clone()])
+            .unwrap();
+        assert_eq!(data.board_name, "GA402");
+        assert_eq!(data.layout_name, "ga402x");
+        assert_eq!(
+            data.basic_modes,
+            vec![AuraModeNum::Static, AuraModeNum::Breathe]
+        );
+        assert!(data.basic_zones.is_empty());
+        assert_eq!(data.advanced_type, AdvancedAuraType::PerKey);
+        assert_eq!(
+            data.power_zones,
+            vec![PowerZones::Keyboard, PowerZones::Lightbar]
+        );
+        assert_eq!(data.hardware_zone(AuraZone::Key1), AuraZone::Key4);
+        assert_eq!(data.hardware_zone(AuraZone::Key4), AuraZone::Key1);
+        assert_eq!(data.hardware_zone(AuraZone::Key2), AuraZone::Key2);
+        let calibration = data.power_calibration.as_ref().unwrap();
+        assert_eq!(calibration.full_mw(PowerZones::Lightbar), 250);
+        assert_eq!(calibration.full_mw(PowerZones::Keyboard), 1200);
+        let correction = data.colour_correction.unwrap();
+        assert_eq!(correction.gamma, [1.0, 1.0, 1.2]);
+        assert_eq!(correction.matrix, ColourCorrection::default().matrix);
+
+        // Boards not in the support data can be added
+        let data = LaptopLedData::merged("G513QY", Some(support), &[other]).unwrap();
+        assert_eq!(data.board_name, "G513");
+        assert_eq!(data.basic_zones, vec![AuraZone::Key1]);
+        assert!(LaptopLedData::merged("X13", None, &[]).is_none());
+    }
+
     #[test]
     fn check_data_file_parse() {
         let mut data = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
@@ -366,7 +366,7 @@ impl From<AuraEffect> for AuraModeNum {
     derive(Type, Value, OwnedValue),
     zvariant(signature = "u")
 )]
-#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
 pub enum AuraZone {
     /// Used if keyboard has no zones, or if setting all
     #[default]
@@ -387,6 +387,22 @@ pub enum AuraZone {
     BarRight = 7,
 }
 
+impl AuraZone {
+    /// The name `from_str` takes for the zone
+    pub const fn name(&self) -> &'static str {
+        match self {
+            AuraZone::None => "none",
+            AuraZone::Key1 => "one",
+            AuraZone::Key2 => "two",
+            AuraZone::Key3 => "three",
+            AuraZone::Key4 => "four",
+            AuraZone::Logo => "logo",
+            AuraZone::BarLeft => "lightbar-left",
+            AuraZone::BarRight => "lightbar-right",
+        }
+    }
+}
+
 impl FromStr for AuraZone {
     type Err = Error;
 
@@ -434,7 +450,7 @@ impl From<AuraZone> for i32 {
 /// ```
 #[typeshare]
 #[cfg_attr(feature = "dbus", derive(Type, Value, OwnedValue))]
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct AuraEffect {
     /// The effect type
     pub mode: AuraModeNum,
@@ -0,0 +1,196 @@
+//! Correction of the colours written to the LEDs. The LEDs of each model have
+//! their own colour response, on some pure white looks blue. Each channel is
+//! raised to its gamma, then the matrix scales and mixes the channels. The
+//! diagonal of the matrix sets the white point: white is written as
+//! `(m[0][0], m[1][1], m[2][2])` of full.
+//!
+//! The support data may have a correction for the model, which the config of
+//! the device replaces if it has one.
+
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{AuraEffect, Colour};
+
+/// The report type of per-key and zoned packets
+const PER_KEY_TYPE: u8 = 0xbc;
+/// Where the RGB values of the keys start in a per-key packet
+const PER_KEY_COLOUR_START: usize = 9;
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ColourCorrection {
+    /// Of red, green and blue. 1.0 leaves the channel as is, higher darkens
+    /// the mid tones.
+    pub gamma: [f32; 3],
+    /// Each row makes one output channel from the gamma corrected red, green
+    /// and blue
+    pub matrix: [[f32; 3]; 3],
+}
+
+impl Default for ColourCorrection {
+    fn default() -> Self {
+        Self {
+            gamma: [1.0; 3],
+            matrix: [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+        }
+    }
+}
+
+impl ColourCorrection {
+    /// Scale each channel, with no gamma or mixing. `white` is what pure white
+    /// is written as.
+    pub fn white_point(white: Colour) -> Self {
+        let mut correction = Self::default();
+        for (i, c) in [white.r, white.g, white.b].into_iter().enumerate() {
+            correction.matrix[i][i] = c as f32 / 255.0;
+        }
+        correction
+    }
+
+    pub fn is_identity(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// A description of the first problem found, if any
+    pub fn check(&self) -> Option<String> {
+        if self.gamma.iter().any(|g| !g.is_finite() || *g <= 0.0) {
+            return Some("gamma must be above 0".to_owned());
+        }
+        if self.matrix.iter().flatten().any(|m| !m.is_finite()) {
+            return Some("matrix must only have finite values".to_owned());
+        }
+        None
+    }
+
+    pub fn apply(&self, colour: Colour) -> Colour {
+        let rgb = [colour.r, colour.g, colour.b];
+        let linear: [f32; 3] = std::array::from_fn(|i| (rgb[i] as f32 / 255.0).powf(self.gamma[i]));
+        let channel = |row: [f32; 3]| {
+            let v: f32 = row.iter().zip(&linear).map(|(m, c)| m * c).sum();
+            (v.clamp(0.0, 1.0) * 255.0).round() as u8
+        };
+        Colour {
+            r: channel(self.matrix[0]),
+            g: channel(self.matrix[1]),
+            b: channel(self.matrix[2]),
+        }
+    }
+
+    /// Correct both colours of `effect`
+    pub fn apply_effect(&self, effect: &AuraEffect) -> AuraEffect {
+        AuraEffect {
+            colour1: self.apply(effect.colour1),
+            colour2: self.apply(effect.colour2),
+            ..effect.clone()
+        }
+    }
+
+    /// Correct the key colours of per-key and zoned packets. Other packets,
+    /// such as raw factory modes, are left alone.
+    pub fn apply_packets<P: AsMut<[u8]>>(&self, packets: &mut [P]) {
+        for packet in packets
+            .iter_mut()
+            .map(|p| p.as_mut())
+            .filter(|p| p.len() > PER_KEY_COLOUR_START && p[1] == PER_KEY_TYPE)
+        {
+            for rgb in packet[PER_KEY_COLOUR_START..].chunks_exact_mut(3) {
+                let c = self.apply(Colour {
+                    r: rgb[0],
+                    g: rgb[1],
+                    b: rgb[2],
+                });
+                rgb.copy_from_slice(&[c.r, c.g, c.b]);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ColourCorrection;
+    use crate::keyboard::{LedCode, LedUsbPackets, UsbPackets};
+    use crate::Colour;
+
+    const WHITE: Colour = Colour {
+        r: 255,
+        g: 255,
+        b: 255,
+    };
+
+    #[test]
+    fn identity_leaves_colours() {
+        let correction = ColourCorrection::default();
+        assert!(correction.is_identity());
+        let c = Colour {
+            r: 12,
+            g: 200,
+            b: 77,
+        };
+        assert_eq!(correction.apply(c), c);
+    }
+
+    #[test]
+    fn white_point_and_gamma() {
+        let correction = ColourCorrection::white_point(Colour {
+            r: 255,
+            g: 230,
+            b: 180,
+        });
+        assert_eq!(
+            correction.apply(WHITE),
+            Colour {
+                r: 255,
+                g: 230,
+                b: 180
+            }
+        );
+
+        let correction = ColourCorrection {
+            gamma: [2.0, 1.0, 1.0],
+            ..Default::default()
+        };
+        let c = correction.apply(Colour {
+            r: 128,
+            g: 128,
+            b: 0,
+        });
+        assert_eq!(
+            c,
+            Colour {
+                r: 64,
+                g: 128,
+                b: 0
+            }
+        );
+        // Full and off are not moved by gamma
+        assert_eq!(correction.apply(WHITE), WHITE);
+
+        assert!(ColourCorrection {
+            gamma: [0.0, 1.0, 1.0],
+            ..Default::default()
+        }
+        .check()
+        .is_some());
+    }
+
+    #[test]
+    fn per_key_colours_corrected() {
+        let mut keys = LedUsbPackets::new_per_key();
+        keys.set(LedCode::Esc, 255, 255, 255);
+        let mut packets: UsbPackets = keys.into();
+        let header = packets[1][..9].to_vec();
+        let correction = ColourCorrection::white_point(Colour {
+            r: 255,
+            g: 0,
+            b: 100,
+        });
+        correction.apply_packets(&mut packets);
+        assert_eq!(packets[1][..9], header);
+        assert_eq!(packets[1][24..27], [255, 0, 100]);
+
+        // Builtin mode packets are not touched
+        let mut builtin = vec![vec![0x5d, 0xb3, 0, 0, 0, 0, 0, 0, 0, 255, 255, 255]];
+        correction.apply_packets(&mut builtin);
+        assert_eq!(builtin[0][9..], [255, 255, 255]);
+    }
+}
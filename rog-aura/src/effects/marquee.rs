@@ -0,0 +1,116 @@
+use serde::{Deserialize, Serialize};
+
+use super::{Effect, EffectState};
+use crate::keyboard::{KeyLayout, LedCode};
+use crate::{effect_state_impl, Colour, Direction, Speed};
+
+const OFF: Colour = Colour { r: 0, g: 0, b: 0 };
+
+/// A colour sequence marching along a row of LEDs, theater-chase style. Each
+/// LED in the row is its own `Marquee` and knows its place in the row, so
+/// build the whole row with `Marquee::row`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Marquee {
+    led: LedCode,
+    /// Place of the LED in the row, counted from the left or top
+    position: usize,
+    /// The colours lit, in order along the row
+    palette: Vec<Colour>,
+    /// `Right` and `Down` march away from position 0, `Left` and `Up` toward
+    direction: Direction,
+    /// Unlit LEDs between each lit one
+    gap: u8,
+    speed: Speed,
+    #[serde(skip)]
+    frame: u32,
+    #[serde(skip)]
+    colour: Colour,
+}
+
+impl Marquee {
+    pub fn new(
+        address: LedCode,
+        position: usize,
+        palette: Vec<Colour>,
+        direction: Direction,
+        gap: u8,
+        speed: Speed,
+    ) -> Self {
+        let colour = colour_at(&palette, gap, direction, position, 0);
+        Self {
+            led: address,
+            position,
+            palette,
+            direction,
+            gap,
+            speed,
+            frame: 0,
+            colour,
+        }
+    }
+
+    /// One effect per LED of `leds`, which are taken in order as the row
+    pub fn row(
+        leds: &[LedCode],
+        palette: &[Colour],
+        direction: Direction,
+        gap: u8,
+        speed: Speed,
+    ) -> Vec<Effect> {
+        leds.iter()
+            .enumerate()
+            .map(|(position, led)| {
+                Effect::Marquee(Self::new(
+                    *led,
+                    position,
+                    palette.to_vec(),
+                    direction,
+                    gap,
+                    speed,
+                ))
+            })
+            .collect()
+    }
+}
+
+/// The colour at `position` once the sequence has moved `step` LEDs
+fn colour_at(
+    palette: &[Colour],
+    gap: u8,
+    direction: Direction,
+    position: usize,
+    step: u32,
+) -> Colour {
+    let span = gap as usize + 1;
+    let period = palette.len() * span;
+    if period == 0 {
+        return OFF;
+    }
+    let shift = step as usize % period;
+    let index = match direction {
+        Direction::Right | Direction::Down => (position + period - shift) % period,
+        Direction::Left | Direction::Up => (position + shift) % period,
+    };
+    if index % span == 0 {
+        palette[index / span]
+    } else {
+        OFF
+    }
+}
+
+impl EffectState for Marquee {
+    effect_state_impl!();
+
+    fn next_colour_state(&mut self, _layout: &KeyLayout) {
+        self.frame = self.frame.wrapping_add(1);
+        // Moves one LED every 8, 4 or 2 frames
+        let frames_per_step = 8 >> u8::from(self.speed);
+        self.colour = colour_at(
+            &self.palette,
+            self.gap,
+            self.direction,
+            self.position,
+            self.frame / frames_per_step,
+        );
+    }
+}
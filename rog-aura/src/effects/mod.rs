@@ -12,6 +12,12 @@ pub use breathe::*;
 mod static_;
 pub use static_::*;
 
+mod marquee;
+pub use marquee::*;
+
+mod typing_trail;
+pub use typing_trail::*;
+
 use crate::keyboard::{KeyLayout, LedCode, LedUsbPackets, UsbPackets};
 use crate::Colour;
 
@@ -100,25 +106,42 @@ impl AdvancedEffects {
         None
     }
 
+    /// Each effect, to step them one at a time rather than with `next_state`
+    pub fn effects_mut(&mut self) -> impl Iterator<Item = &mut Effect> {
+        self.effects.iter_mut()
+    }
+
     pub fn next_state(&mut self, layout: &KeyLayout) {
         for effect in &mut self.effects {
             effect.next_state(layout);
         }
     }
 
-    pub fn create_packets(&self) -> UsbPackets {
-        let mut usb_packets = if self.zoned {
+    /// Empty packets for the kind of keyboard of the effects, to be kept and
+    /// filled by `write_packets` for each frame
+    pub fn new_packets(&self) -> LedUsbPackets {
+        if self.zoned {
             // TODO: figure out if that single byte difference for multizone actually
             // matters
             LedUsbPackets::new_zoned(true)
         } else {
             LedUsbPackets::new_per_key()
-        };
+        }
+    }
 
+    /// Write the colours of the current state in to `packets`. Keys without
+    /// an effect are set off.
+    pub fn write_packets(&self, packets: &mut LedUsbPackets) {
+        packets.clear();
         for effect in &self.effects {
             let c = effect.colour();
-            usb_packets.set(effect.led(), c.r, c.g, c.b);
+            packets.set(effect.led(), c.r, c.g, c.b);
         }
+    }
+
+    pub fn create_packets(&self) -> UsbPackets {
+        let mut usb_packets = self.new_packets();
+        self.write_packets(&mut usb_packets);
         usb_packets.into()
     }
 }
@@ -187,6 +210,7 @@ pub enum Effect {
     Breathe(Breathe),
     DoomFlicker(DoomFlicker),
     DoomLightFlash(DoomLightFlash),
+    Marquee(Marquee),
 }
 
 impl Default for Effect {
@@ -195,13 +219,13 @@ impl Default for Effect {
     }
 }
 
-effect_impl!(Static, Breathe, DoomFlicker, DoomLightFlash);
+effect_impl!(Static, Breathe, DoomFlicker, DoomLightFlash, Marquee);
 
 #[cfg(test)]
 mod tests {
-    use crate::effects::{AdvancedEffects, Breathe, DoomFlicker, Effect, Static};
+    use crate::effects::{AdvancedEffects, Breathe, DoomFlicker, Effect, Marquee, Static};
     use crate::keyboard::{KeyLayout, LedCode};
-    use crate::{Colour, Speed};
+    use crate::{Colour, Direction, Speed};
 
     #[test]
     fn single_key_next_state_then_create() {
@@ -304,4 +328,43 @@ mod tests {
         assert_eq!(packets[5][34], 87);
         assert_eq!(packets[5][35], 40);
     }
+
+    #[test]
+    fn marquee_marches() {
+        let layout = KeyLayout::default_layout();
+        let red = Colour { r: 255, g: 0, b: 0 };
+        let blue = Colour { r: 0, g: 0, b: 255 };
+        let off = Colour::default();
+        let leds = [
+            LedCode::ZonedKbLeft,
+            LedCode::ZonedKbLeftMid,
+            LedCode::ZonedKbRightMid,
+            LedCode::ZonedKbRight,
+        ];
+        let mut row = Marquee::row(&leds, &[red, blue], Direction::Right, 1, Speed::High);
+        let colours = |row: &[Effect]| row.iter().map(|e| e.colour()).collect::<Vec<_>>();
+        assert_eq!(colours(&row), vec![red, off, blue, off]);
+
+        // High speed moves every second frame
+        for effect in &mut row {
+            effect.next_state(&layout);
+        }
+        assert_eq!(colours(&row), vec![red, off, blue, off]);
+        for effect in &mut row {
+            effect.next_state(&layout);
+        }
+        assert_eq!(colours(&row), vec![off, red, off, blue]);
+
+        let row = Marquee::row(&leds, &[red, blue], Direction::Left, 0, Speed::High);
+        let mut seq = AdvancedEffects::new(true);
+        for effect in row {
+            seq.push(effect);
+        }
+        seq.next_state(&layout);
+        seq.next_state(&layout);
+        assert_eq!(
+            seq.effects.iter().map(|e| e.colour()).collect::<Vec<_>>(),
+            vec![blue, red, blue, red]
+        );
+    }
 }
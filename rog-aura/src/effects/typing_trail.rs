@@ -0,0 +1,317 @@
+//! Keys that hold a colour after being pressed, fading out through a gradient
+//! so that what was typed leaves a trail. The key presses are read by asusd
+//! and the frames sent over a frame stream.
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use typeshare::typeshare;
+#[cfg(feature = "dbus")]
+use zbus::zvariant::{OwnedValue, Type, Value};
+
+use crate::keyboard::LedCode;
+use crate::Colour;
+
+const OFF: Colour = Colour { r: 0, g: 0, b: 0 };
+
+/// How keys fade after being pressed
+#[typeshare]
+#[cfg_attr(feature = "dbus", derive(Type, Value, OwnedValue))]
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct TrailConfig {
+    /// The colours a key fades through, from the one shown as it is pressed
+    pub gradient: Vec<Colour>,
+    /// How long a key takes to fade through the gradient, after which it is
+    /// off
+    pub decay_ms: u32,
+}
+
+impl Default for TrailConfig {
+    fn default() -> Self {
+        Self {
+            gradient: vec![
+                Colour {
+                    r: 255,
+                    g: 255,
+                    b: 255,
+                },
+                Colour {
+                    r: 0,
+                    g: 160,
+                    b: 255,
+                },
+                Colour {
+                    r: 80,
+                    g: 0,
+                    b: 255,
+                },
+                OFF,
+            ],
+            decay_ms: 800,
+        }
+    }
+}
+
+impl TrailConfig {
+    /// Check there is a gradient to fade through and time to do it in
+    pub fn validate(&self) -> Result<(), String> {
+        if self.gradient.is_empty() {
+            return Err("The gradient needs at least one colour".to_owned());
+        }
+        if self.decay_ms == 0 {
+            return Err("The decay time must be more than 0ms".to_owned());
+        }
+        Ok(())
+    }
+
+    /// The colour `age` after a press, `None` once the key has faded out
+    pub fn colour_at(&self, age: Duration) -> Option<Colour> {
+        let decay = Duration::from_millis(self.decay_ms as u64);
+        if age >= decay {
+            return None;
+        }
+        let (first, rest) = self.gradient.split_first()?;
+        if rest.is_empty() {
+            return Some(*first);
+        }
+        let pos = age.as_secs_f32() / decay.as_secs_f32() * rest.len() as f32;
+        // Rounding can put an age just short of the decay on the last colour
+        let i = (pos as usize).min(rest.len() - 1);
+        let frac = pos - i as f32;
+        let (a, b) = (self.gradient[i], self.gradient[i + 1]);
+        let lerp = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * frac).round() as u8;
+        Some(Colour {
+            r: lerp(a.r, b.r),
+            g: lerp(a.g, b.g),
+            b: lerp(a.b, b.b),
+        })
+    }
+}
+
+/// Recently pressed keys hold a colour that fades through the gradient of a
+/// `TrailConfig`. Unlike the builtin ripple only the pressed key lights.
+#[derive(Debug, Clone, Default)]
+pub struct TypingTrail {
+    config: TrailConfig,
+    /// The lit keys, with the time since each was pressed
+    keys: Vec<(LedCode, Duration)>,
+}
+
+impl TypingTrail {
+    pub fn new(config: TrailConfig) -> Self {
+        Self {
+            config,
+            keys: Vec::new(),
+        }
+    }
+
+    /// Fade the lit keys through `config` from now on
+    pub fn set_config(&mut self, config: TrailConfig) {
+        self.config = config;
+    }
+
+    /// Light `led`, starting its fade over if it is lit already
+    pub fn press(&mut self, led: LedCode) {
+        match self.keys.iter_mut().find(|(key, _)| *key == led) {
+            Some((_, age)) => *age = Duration::ZERO,
+            None => self.keys.push((led, Duration::ZERO)),
+        }
+    }
+
+    /// The colour of each lit key, which are then aged by `elapsed`. A key
+    /// that faded out is given as off once, then forgotten.
+    pub fn frame(&mut self, elapsed: Duration) -> Vec<(LedCode, Colour)> {
+        let mut colours = Vec::with_capacity(self.keys.len());
+        self.keys.retain_mut(|(led, age)| {
+            let colour = self.config.colour_at(*age);
+            *age += elapsed;
+            colours.push((*led, colour.unwrap_or(OFF)));
+            colour.is_some()
+        });
+        colours
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.keys.is_empty()
+    }
+}
+
+/// The LED under the key with the Linux input event code `code`, for the
+/// keys that are on every per-key layout
+pub fn led_for_key(code: u16) -> Option<LedCode> {
+    Some(match code {
+        1 => LedCode::Esc,
+        2 => LedCode::N1,
+        3 => LedCode::N2,
+        4 => LedCode::N3,
+        5 => LedCode::N4,
+        6 => LedCode::N5,
+        7 => LedCode::N6,
+        8 => LedCode::N7,
+        9 => LedCode::N8,
+        10 => LedCode::N9,
+        11 => LedCode::N0,
+        12 => LedCode::Hyphen,
+        13 => LedCode::Equals,
+        14 => LedCode::Backspace,
+        15 => LedCode::Tab,
+        16 => LedCode::Q,
+        17 => LedCode::W,
+        18 => LedCode::E,
+        19 => LedCode::R,
+        20 => LedCode::T,
+        21 => LedCode::Y,
+        22 => LedCode::U,
+        23 => LedCode::I,
+        24 => LedCode::O,
+        25 => LedCode::P,
+        26 => LedCode::LBracket,
+        27 => LedCode::RBracket,
+        28 => LedCode::Return,
+        29 => LedCode::LCtrl,
+        30 => LedCode::A,
+        31 => LedCode::S,
+        32 => LedCode::D,
+        33 => LedCode::F,
+        34 => LedCode::G,
+        35 => LedCode::H,
+        36 => LedCode::J,
+        37 => LedCode::K,
+        38 => LedCode::L,
+        39 => LedCode::SemiColon,
+        40 => LedCode::Quote,
+        41 => LedCode::Tilde,
+        42 => LedCode::LShift,
+        43 => LedCode::BackSlash,
+        44 => LedCode::Z,
+        45 => LedCode::X,
+        46 => LedCode::C,
+        47 => LedCode::V,
+        48 => LedCode::B,
+        49 => LedCode::N,
+        50 => LedCode::M,
+        51 => LedCode::Comma,
+        52 => LedCode::Period,
+        53 => LedCode::FwdSlash,
+        54 => LedCode::Rshift,
+        56 => LedCode::LAlt,
+        57 => LedCode::Spacebar,
+        58 => LedCode::Caps,
+        59 => LedCode::F1,
+        60 => LedCode::F2,
+        61 => LedCode::F3,
+        62 => LedCode::F4,
+        63 => LedCode::F5,
+        64 => LedCode::F6,
+        65 => LedCode::F7,
+        66 => LedCode::F8,
+        67 => LedCode::F9,
+        68 => LedCode::F10,
+        87 => LedCode::F11,
+        88 => LedCode::F12,
+        97 => LedCode::RCtrl,
+        99 => LedCode::PrtSc,
+        100 => LedCode::RAlt,
+        102 => LedCode::Home,
+        103 => LedCode::Up,
+        104 => LedCode::PgUp,
+        105 => LedCode::Left,
+        106 => LedCode::Right,
+        107 => LedCode::End,
+        108 => LedCode::Down,
+        109 => LedCode::PgDn,
+        111 => LedCode::Del,
+        119 => LedCode::Pause,
+        125 => LedCode::Meta,
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{led_for_key, TrailConfig, TypingTrail};
+    use crate::keyboard::LedCode;
+    use crate::Colour;
+
+    const WHITE: Colour = Colour {
+        r: 255,
+        g: 255,
+        b: 255,
+    };
+    const BLACK: Colour = Colour { r: 0, g: 0, b: 0 };
+
+    fn config() -> TrailConfig {
+        TrailConfig {
+            gradient: vec![WHITE, Colour { r: 255, g: 0, b: 0 }, BLACK],
+            decay_ms: 1000,
+        }
+    }
+
+    #[test]
+    fn gradient_lerp() {
+        let config = config();
+        assert_eq!(config.colour_at(Duration::ZERO), Some(WHITE));
+        assert_eq!(
+            config.colour_at(Duration::from_millis(250)),
+            Some(Colour {
+                r: 255,
+                g: 128,
+                b: 128
+            })
+        );
+        assert_eq!(
+            config.colour_at(Duration::from_millis(500)),
+            Some(Colour { r: 255, g: 0, b: 0 })
+        );
+        assert_eq!(config.colour_at(Duration::from_millis(1000)), None);
+
+        let single = TrailConfig {
+            gradient: vec![WHITE],
+            decay_ms: 100,
+        };
+        assert_eq!(single.colour_at(Duration::from_millis(99)), Some(WHITE));
+        assert!(TrailConfig::default().validate().is_ok());
+        assert!(TrailConfig {
+            decay_ms: 0,
+            ..config.clone()
+        }
+        .validate()
+        .is_err());
+    }
+
+    #[test]
+    fn keys_fade_then_clear() {
+        let mut trail = TypingTrail::new(config());
+        trail.press(LedCode::A);
+        assert_eq!(
+            trail.frame(Duration::from_millis(600)),
+            [(LedCode::A, WHITE)]
+        );
+        trail.press(LedCode::B);
+        let frame = trail.frame(Duration::from_millis(600));
+        assert_eq!(frame.len(), 2);
+        assert_eq!(frame[1], (LedCode::B, WHITE));
+        // A is past the decay time, so is turned off once and forgotten
+        assert_eq!(trail.frame(Duration::ZERO)[0], (LedCode::A, BLACK));
+        assert_eq!(trail.frame(Duration::from_millis(600)).len(), 1);
+        // Pressing again starts the fade over
+        trail.press(LedCode::B);
+        assert_eq!(
+            trail.frame(Duration::from_millis(600)),
+            [(LedCode::B, WHITE)]
+        );
+        trail.frame(Duration::from_millis(600));
+        trail.frame(Duration::ZERO);
+        assert!(trail.is_idle());
+    }
+
+    #[test]
+    fn keycodes() {
+        assert_eq!(led_for_key(30), Some(LedCode::A));
+        assert_eq!(led_for_key(57), Some(LedCode::Spacebar));
+        assert_eq!(led_for_key(0), None);
+    }
+}
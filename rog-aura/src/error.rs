@@ -6,6 +6,8 @@ pub enum Error {
     ParseSpeed,
     ParseDirection,
     ParseBrightness,
+    ParseLedCode,
+    ParsePackets,
     IoPath(String, std::io::Error),
     Ron(ron::Error),
     RonParse(ron::error::SpannedError),
@@ -19,6 +21,8 @@ impl fmt::Display for Error {
             Error::ParseSpeed => write!(f, "Could not parse speed"),
             Error::ParseDirection => write!(f, "Could not parse direction"),
             Error::ParseBrightness => write!(f, "Could not parse brightness"),
+            Error::ParseLedCode => write!(f, "Could not parse LED code"),
+            Error::ParsePackets => write!(f, "Could not parse the USB packets"),
             Error::IoPath(path, io) => write!(f, "IO Error: {path}, {io}"),
             Error::Ron(e) => write!(f, "RON Parse Error: {e}"),
             Error::RonParse(e) => write!(f, "RON Parse Error: {e}"),
@@ -2,12 +2,19 @@ use log::warn;
 use serde::{Deserialize, Serialize};
 use typeshare::typeshare;
 #[cfg(feature = "dbus")]
-use zbus::zvariant::Type;
+use zbus::zvariant::{Signature, Type};
+
+use crate::error::Error;
 
 /// The `LedCode` used in setting up keyboard layouts is important because it
 /// determines the idexing for an RGB value in the final USB packets (for
 /// per-key addressable keyboards).
+///
+/// The discriminant is stable and is used as the key index in raw frame
+/// streams, so new variants must only ever be added before `Spacing`, and in
+/// the same place in `LED_CODES`.
 #[derive(Debug, Default, PartialEq, Eq, Copy, Clone, Serialize, Deserialize)]
+#[repr(u8)]
 pub enum LedCode {
     VolUp,
     VolDown,
@@ -190,21 +197,201 @@ impl LedCode {
     }
 }
 
+/// Every `LedCode` in the order of its discriminant
+const LED_CODES: [LedCode; 135] = [
+    LedCode::VolUp,
+    LedCode::VolDown,
+    LedCode::MicMute,
+    LedCode::RogApp,
+    LedCode::RogFan,
+    LedCode::Esc,
+    LedCode::F1,
+    LedCode::F2,
+    LedCode::F3,
+    LedCode::F4,
+    LedCode::F5,
+    LedCode::F6,
+    LedCode::F7,
+    LedCode::F8,
+    LedCode::F9,
+    LedCode::F10,
+    LedCode::F11,
+    LedCode::F12,
+    LedCode::Del,
+    LedCode::Tilde,
+    LedCode::N1,
+    LedCode::N2,
+    LedCode::N3,
+    LedCode::N4,
+    LedCode::N5,
+    LedCode::N6,
+    LedCode::N7,
+    LedCode::N8,
+    LedCode::N9,
+    LedCode::N0,
+    LedCode::Hyphen,
+    LedCode::Equals,
+    LedCode::Backspace,
+    LedCode::Backspace3_1,
+    LedCode::Backspace3_2,
+    LedCode::Backspace3_3,
+    LedCode::Home,
+    LedCode::Tab,
+    LedCode::Q,
+    LedCode::W,
+    LedCode::E,
+    LedCode::R,
+    LedCode::T,
+    LedCode::Y,
+    LedCode::U,
+    LedCode::I,
+    LedCode::O,
+    LedCode::P,
+    LedCode::LBracket,
+    LedCode::RBracket,
+    LedCode::BackSlash,
+    LedCode::PgUp,
+    LedCode::Caps,
+    LedCode::A,
+    LedCode::S,
+    LedCode::D,
+    LedCode::F,
+    LedCode::G,
+    LedCode::H,
+    LedCode::J,
+    LedCode::K,
+    LedCode::L,
+    LedCode::SemiColon,
+    LedCode::Quote,
+    LedCode::Return,
+    LedCode::Return3_1,
+    LedCode::Return3_2,
+    LedCode::Return3_3,
+    LedCode::PgDn,
+    LedCode::LShift,
+    LedCode::LShift3_1,
+    LedCode::LShift3_2,
+    LedCode::LShift3_3,
+    LedCode::Z,
+    LedCode::X,
+    LedCode::C,
+    LedCode::V,
+    LedCode::B,
+    LedCode::N,
+    LedCode::M,
+    LedCode::Comma,
+    LedCode::Period,
+    LedCode::FwdSlash,
+    LedCode::Star,
+    LedCode::NumPadDel,
+    LedCode::NumPadPlus,
+    LedCode::NumPadEnter,
+    LedCode::NumPadPause,
+    LedCode::NumPadPrtSc,
+    LedCode::NumPadHome,
+    LedCode::NumLock,
+    LedCode::Rshift,
+    LedCode::Rshift3_1,
+    LedCode::Rshift3_2,
+    LedCode::Rshift3_3,
+    LedCode::End,
+    LedCode::LCtrl,
+    LedCode::LFn,
+    LedCode::Meta,
+    LedCode::LAlt,
+    LedCode::Spacebar,
+    LedCode::Spacebar5_1,
+    LedCode::Spacebar5_2,
+    LedCode::Spacebar5_3,
+    LedCode::Spacebar5_4,
+    LedCode::Spacebar5_5,
+    LedCode::Pause,
+    LedCode::RAlt,
+    LedCode::PrtSc,
+    LedCode::RCtrl,
+    LedCode::Up,
+    LedCode::Down,
+    LedCode::Left,
+    LedCode::Right,
+    LedCode::RFn,
+    LedCode::MediaPlay,
+    LedCode::MediaStop,
+    LedCode::MediaNext,
+    LedCode::MediaPrev,
+    LedCode::LidLogo,
+    LedCode::LidLeft,
+    LedCode::LidRight,
+    LedCode::LightbarRight,
+    LedCode::LightbarRightCorner,
+    LedCode::LightbarRightBottom,
+    LedCode::LightbarLeftBottom,
+    LedCode::LightbarLeftCorner,
+    LedCode::LightbarLeft,
+    LedCode::SingleZone,
+    LedCode::ZonedKbLeft,
+    LedCode::ZonedKbLeftMid,
+    LedCode::ZonedKbRightMid,
+    LedCode::ZonedKbRight,
+    LedCode::Spacing,
+    LedCode::Blocking,
+];
+
+impl TryFrom<u8> for LedCode {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        LED_CODES
+            .get(value as usize)
+            .copied()
+            .ok_or(Error::ParseLedCode)
+    }
+}
+
 /// Represents the per-key raw USB packets
 #[typeshare]
 pub type UsbPackets = Vec<Vec<u8>>;
 
-/// A `UsbPackets` contains all data to change the full set of keyboard
+/// The length of one per-key or zoned HID packet
+pub const USB_PACKET_LEN: usize = 64;
+/// The number of packets to set all keys of a per-key keyboard
+pub const PER_KEY_ROWS: usize = 11;
+/// Where the RGB values of the keys start in a per-key or zoned packet
+const COLOUR_START: usize = 9;
+
+/// One per-key or zoned HID packet
+pub type UsbPacket = [u8; USB_PACKET_LEN];
+
+/// A `LedUsbPackets` contains all data to change the full set of keyboard
 /// key colours individually.
 ///
 /// Each row of the internal array is a full HID packet that can be sent
 /// to the keyboard EC. One row controls one group of keys, these keys are not
 /// necessarily all on the same row of the keyboard, with some splitting between
 /// two rows.
+///
+/// The rows are fixed size so that one set can be kept and written to for
+/// each frame of an effect without allocating. `changed_rows` gives the rows
+/// which differ from the frame before, for writing only those. It is sent and
+/// stored as `LedUsbPacketsData`, with only the rows used.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(into = "LedUsbPacketsData", try_from = "LedUsbPacketsData")]
+pub struct LedUsbPackets {
+    /// The packet data used to send data to the USB keyboard
+    usb_packets: [UsbPacket; PER_KEY_ROWS],
+    /// How many of `usb_packets` are used
+    rows: usize,
+    /// Wether or not this packet collection is zoned. The determines which
+    /// starting bytes are used and what the indexing is for lightbar RGB
+    /// colours
+    zoned: bool,
+}
+
+/// The form of `LedUsbPackets` for D-Bus, files and the bindings
 #[typeshare]
 #[cfg_attr(feature = "dbus", derive(Type))]
-#[derive(Debug, Clone, Deserialize, Serialize)]
-pub struct LedUsbPackets {
+#[derive(Deserialize, Serialize)]
+#[serde(rename = "LedUsbPackets")]
+struct LedUsbPacketsData {
     /// The packet data used to send data to the USB keyboard
     usb_packets: UsbPackets,
     /// Wether or not this packet collection is zoned. The determines which
@@ -213,6 +400,44 @@ pub struct LedUsbPackets {
     zoned: bool,
 }
 
+impl From<LedUsbPackets> for LedUsbPacketsData {
+    fn from(packets: LedUsbPackets) -> Self {
+        Self {
+            usb_packets: packets.get(),
+            zoned: packets.zoned,
+        }
+    }
+}
+
+impl TryFrom<LedUsbPacketsData> for LedUsbPackets {
+    type Error = Error;
+
+    fn try_from(data: LedUsbPacketsData) -> Result<Self, Self::Error> {
+        if data.usb_packets.is_empty() || data.usb_packets.len() > PER_KEY_ROWS {
+            return Err(Error::ParsePackets);
+        }
+        let mut usb_packets = [[0u8; USB_PACKET_LEN]; PER_KEY_ROWS];
+        for (row, packet) in usb_packets.iter_mut().zip(&data.usb_packets) {
+            if packet.len() != USB_PACKET_LEN {
+                return Err(Error::ParsePackets);
+            }
+            row.copy_from_slice(packet);
+        }
+        Ok(Self {
+            usb_packets,
+            rows: data.usb_packets.len(),
+            zoned: data.zoned,
+        })
+    }
+}
+
+#[cfg(feature = "dbus")]
+impl Type for LedUsbPackets {
+    fn signature() -> Signature<'static> {
+        LedUsbPacketsData::signature()
+    }
+}
+
 impl Default for LedUsbPackets {
     fn default() -> Self {
         Self::new_per_key()
@@ -225,7 +450,7 @@ impl LedUsbPackets {
     ///
     /// These packets will not work with per-zone keyboards
     pub fn new_per_key() -> Self {
-        let mut set = vec![vec![0u8; 64]; 11];
+        let mut set = [[0u8; USB_PACKET_LEN]; PER_KEY_ROWS];
         // set[0].copy_from_slice(&KeyColourArray::get_init_msg());
         for (count, row) in set.iter_mut().enumerate() {
             row[0] = 0x5d; // Report ID
@@ -244,13 +469,13 @@ impl LedUsbPackets {
         }
         Self {
             usb_packets: set,
+            rows: PER_KEY_ROWS,
             zoned: false,
         }
     }
 
-    /// Create new zoned packets. Although the result is a nested `Vec` only the
-    /// first vector is available. The final packet is slightly different
-    /// for single-zoned compared to multizoned.
+    /// Create new zoned packets. Only the first row is used. The final packet
+    /// is slightly different for single-zoned compared to multizoned.
     ///
     /// This packet will not work with per-key keyboards
     ///
@@ -260,7 +485,8 @@ impl LedUsbPackets {
     /// 5d,bc,01,01,04,00,00,00,00,ff,00,00, RED, multizone
     /// ```
     pub fn new_zoned(multizoned: bool) -> Self {
-        let mut pkt = vec![0u8; 64];
+        let mut set = [[0u8; USB_PACKET_LEN]; PER_KEY_ROWS];
+        let pkt = &mut set[0];
         pkt[0] = 0x5d; // Report ID
         pkt[1] = 0xbc; // Mode = custom??, 0xb3 is builtin
         pkt[2] = 0x01;
@@ -273,7 +499,8 @@ impl LedUsbPackets {
                            // colours
         }
         Self {
-            usb_packets: vec![pkt],
+            usb_packets: set,
+            rows: 1,
             zoned: true,
         }
     }
@@ -298,7 +525,7 @@ impl LedUsbPackets {
         }
     }
 
-    /// Indexes in to `UsbPackets` at the correct row and column
+    /// Indexes in to the packets at the correct row and column
     /// to set a series of three bytes to the chosen R,G,B values
     ///
     /// Indexing is different for `zoned` and assumes that only one packet is
@@ -465,28 +692,57 @@ impl LedUsbPackets {
             return None;
         }
 
-        Some(&mut self.usb_packets[row][col..=col + 2])
+        // Rows past the packets, such as the lid LEDs, are not known yet
+        self.usb_packets[..self.rows]
+            .get_mut(row)
+            .map(|packet| &mut packet[col..=col + 2])
+    }
+
+    /// Set every colour to off, leaving the starting bytes, to reuse the
+    /// packets for a new frame
+    pub fn clear(&mut self) {
+        for row in self.usb_packets[..self.rows].iter_mut() {
+            row[COLOUR_START..].fill(0);
+        }
     }
 
     #[inline]
-    pub fn get(&self) -> UsbPackets {
-        self.usb_packets.clone()
+    pub fn is_zoned(&self) -> bool {
+        self.zoned
     }
 
+    /// The packets to write, one row each
     #[inline]
-    pub fn get_ref(&self) -> &UsbPackets {
-        &self.usb_packets
+    pub fn rows(&self) -> &[UsbPacket] {
+        &self.usb_packets[..self.rows]
     }
 
     #[inline]
-    pub fn get_mut(&mut self) -> &mut UsbPackets {
-        &mut self.usb_packets
+    pub fn rows_mut(&mut self) -> &mut [UsbPacket] {
+        &mut self.usb_packets[..self.rows]
+    }
+
+    /// The rows which differ from those of `last`, the frame written before.
+    /// All rows if `last` is of another kind of keyboard.
+    pub fn changed_rows<'a>(&'a self, last: &'a Self) -> impl Iterator<Item = &'a UsbPacket> {
+        let all = last.zoned != self.zoned || last.rows != self.rows;
+        self.rows()
+            .iter()
+            .zip(last.usb_packets.iter())
+            .filter(move |(row, last)| all || row != last)
+            .map(|(row, _)| row)
+    }
+
+    /// Copy the packets out to send over D-Bus
+    #[inline]
+    pub fn get(&self) -> UsbPackets {
+        self.rows().iter().map(|row| row.to_vec()).collect()
     }
 }
 
 impl From<LedUsbPackets> for UsbPackets {
     fn from(k: LedUsbPackets) -> Self {
-        k.usb_packets
+        k.get()
     }
 }
 
@@ -640,6 +896,7 @@ impl From<&LedCode> for &str {
 
 #[cfg(test)]
 mod tests {
+    use super::{LedUsbPacketsData, LED_CODES};
     use crate::keyboard::{LedCode, LedUsbPackets, UsbPackets};
 
     macro_rules! colour_check_zoned {
@@ -657,6 +914,20 @@ mod tests {
         };
     }
 
+    #[test]
+    fn led_code_from_u8() {
+        assert_eq!(LedCode::try_from(0).unwrap(), LedCode::VolUp);
+        assert_eq!(LedCode::try_from(LedCode::Esc as u8).unwrap(), LedCode::Esc);
+        assert_eq!(
+            LedCode::try_from(LedCode::Blocking as u8).unwrap(),
+            LedCode::Blocking
+        );
+        assert!(LedCode::try_from(LedCode::Blocking as u8 + 1).is_err());
+        for (i, code) in LED_CODES.iter().enumerate() {
+            assert_eq!(*code as usize, i);
+        }
+    }
+
     #[test]
     fn zone_to_packet_check() {
         let zone = LedUsbPackets::new_zoned(true);
@@ -680,6 +951,46 @@ mod tests {
         colour_check_zoned!(LedCode::LightbarLeft, 42);
     }
 
+    #[test]
+    fn changed_rows_only() {
+        let mut last = LedUsbPackets::new_per_key();
+        last.set(LedCode::Esc, 255, 0, 0);
+        let mut next = last.clone();
+        assert_eq!(next.changed_rows(&last).count(), 0);
+
+        next.set(LedCode::D, 0, 255, 0);
+        next.set(LedCode::F, 0, 255, 0);
+        let changed: Vec<_> = next.changed_rows(&last).collect();
+        assert_eq!(changed, vec![&next.rows()[5]]);
+
+        // A new frame clears the colours but not the starting bytes
+        next.clear();
+        assert_eq!(next, LedUsbPackets::new_per_key());
+        let zoned = LedUsbPackets::new_zoned(true);
+        assert_eq!(zoned.changed_rows(&last).count(), 1);
+    }
+
+    #[test]
+    fn packets_serialized_as_rows_used() {
+        let mut zoned = LedUsbPackets::new_zoned(true);
+        zoned.set(LedCode::LightbarLeft, 255, 0, 0);
+        let text = ron::to_string(&zoned).unwrap();
+        let data: LedUsbPacketsData = ron::from_str(&text).unwrap();
+        assert_eq!(data.usb_packets.len(), 1);
+        assert!(data.zoned);
+        assert_eq!(ron::from_str::<LedUsbPackets>(&text).unwrap(), zoned);
+
+        let short = "(usb_packets:[[93,188]],zoned:true)";
+        assert!(ron::from_str::<LedUsbPackets>(short).is_err());
+    }
+
+    #[test]
+    fn lid_out_of_range_ignored() {
+        let mut per_key = LedUsbPackets::new_per_key();
+        per_key.set(LedCode::LidLogo, 255, 255, 255);
+        assert_eq!(per_key, LedUsbPackets::new_per_key());
+    }
+
     #[test]
     fn perkey_to_packet_check() {
         let per_key = LedUsbPackets::new_per_key();
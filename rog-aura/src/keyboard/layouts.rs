@@ -7,6 +7,9 @@ use std::slice::Iter;
 
 use log::warn;
 use serde::{Deserialize, Serialize};
+use typeshare::typeshare;
+#[cfg(feature = "dbus")]
+use zbus::zvariant::Type;
 
 use crate::aura_detection::LaptopLedData;
 use crate::error::Error;
@@ -83,6 +86,30 @@ impl KeyShape {
     }
 }
 
+/// Where one LED of a layout sits, for drawing per-key editors. Sizes and
+/// positions are in units of a typical key like 'A', with the origin at the
+/// top left of the keyboard.
+#[typeshare]
+#[cfg_attr(feature = "dbus", derive(Type))]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct KeyGeometry {
+    /// The name of the `LedCode`, such as `Esc`
+    pub key: String,
+    /// What is printed on the key, such as `Escape`
+    pub label: String,
+    /// The `LedCode` as a `u8`, which is how frame streams address it
+    pub led: u8,
+    /// Row of the layout, counting from 0 at the top
+    pub row: u32,
+    /// Position among the LEDs of the row, blank spacing is not counted
+    pub column: u32,
+    /// Top left corner of the key itself, inside its padding
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
 /// The first `Key` will determine the row height.
 ///
 /// Every row is considered to start a x=0, with the first row being y=0,
@@ -277,6 +304,55 @@ impl KeyLayout {
         width
     }
 
+    /// The position and size of every LED, row by row. Keys whose shape is
+    /// missing are skipped, as when loading the layout.
+    pub fn geometry(&self) -> Vec<KeyGeometry> {
+        let mut keys = Vec::new();
+        let mut y = 0.0;
+        for (row_index, row) in self.key_rows.iter().enumerate() {
+            y += row.pad_top;
+            let mut x = row.pad_left;
+            let mut row_height: f32 = 0.0;
+            let mut column = 0;
+            for (led, shape) in &row.row {
+                let Some(shape) = self.key_shapes.get(shape) else {
+                    continue;
+                };
+                match shape {
+                    KeyShape::Led {
+                        width,
+                        height,
+                        pad_left,
+                        pad_right,
+                        pad_top,
+                        pad_bottom,
+                    } => {
+                        keys.push(KeyGeometry {
+                            key: format!("{led:?}"),
+                            label: <&str>::from(led).to_owned(),
+                            led: *led as u8,
+                            row: row_index as u32,
+                            column,
+                            x: (x + pad_left) as f64,
+                            y: (y + pad_top) as f64,
+                            width: *width as f64,
+                            height: *height as f64,
+                        });
+                        column += 1;
+                        x += pad_left + width + pad_right;
+                        row_height = row_height.max(pad_top + height + pad_bottom);
+                    }
+                    KeyShape::Blank { width, height } => {
+                        x += width;
+                        row_height = row_height.max(*height);
+                    }
+                }
+            }
+            y += row_height;
+        }
+        keys
+    }
+
     /// Find a layout matching the name in `LaptopLedData` in the provided dir
     pub fn find_layout(led_data: LaptopLedData, mut data_path: PathBuf) -> Result<Self, Error> {
         // TODO: locales
@@ -459,7 +535,7 @@ mod tests {
     use std::path::PathBuf;
 
     use crate::aura_detection::LedSupportFile;
-    use crate::keyboard::KeyLayout;
+    use crate::keyboard::{KeyLayout, LedCode};
 
     #[test]
     fn check_parse_all() {
@@ -516,6 +592,20 @@ mod tests {
         // data).unwrap(); file.write_all(json.as_bytes()).unwrap();
     }
 
+    #[test]
+    fn key_geometry() {
+        let keys = KeyLayout::default_layout().geometry();
+        let close = |a: f64, b: f64| (a - b).abs() < 1e-5;
+        let esc = &keys[0];
+        assert_eq!((esc.key.as_str(), esc.row, esc.column), ("Esc", 0, 0));
+        assert!(close(esc.x, 0.2) && close(esc.y, 0.2));
+        assert!(close(keys[1].x, 1.4));
+        // The second row starts below the padded height of the first
+        let tilde = keys.iter().find(|k| k.led == LedCode::Tilde as u8).unwrap();
+        assert_eq!((tilde.row, tilde.column), (1, 0));
+        assert!(close(tilde.y, 1.5) && close(tilde.width, 1.0));
+    }
+
     #[test]
     fn check_layout_file_links() {
         const DATA_DIR: &str = env!("CARGO_MANIFEST_DIR");
@@ -41,7 +41,41 @@ impl Default for AuraPowerState {
     }
 }
 
+/// The phases a power zone can be lit in, one column of `LaptopAuraPower`
+#[typeshare]
+#[cfg_attr(
+    feature = "dbus",
+    derive(Type, Value, OwnedValue),
+    zvariant(signature = "u")
+)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PowerPhase {
+    /// The boot animation
+    Boot = 0,
+    Awake = 1,
+    Sleep = 2,
+    Shutdown = 3,
+}
+
 impl AuraPowerState {
+    pub fn get(&self, phase: PowerPhase) -> bool {
+        match phase {
+            PowerPhase::Boot => self.boot,
+            PowerPhase::Awake => self.awake,
+            PowerPhase::Sleep => self.sleep,
+            PowerPhase::Shutdown => self.shutdown,
+        }
+    }
+
+    pub fn set(&mut self, phase: PowerPhase, on: bool) {
+        match phase {
+            PowerPhase::Boot => self.boot = on,
+            PowerPhase::Awake => self.awake = on,
+            PowerPhase::Sleep => self.sleep = on,
+            PowerPhase::Shutdown => self.shutdown = on,
+        }
+    }
+
     fn default_for(zone: PowerZones) -> Self {
         Self {
             zone,
@@ -52,8 +86,9 @@ impl AuraPowerState {
         }
     }
 
+    /// The boot, awake and sleep states as 0 or 1 each
     fn tuf_to_bytes(&self) -> Vec<u8> {
-        todo!("0s and 1s for bool array")
+        vec![self.boot as u8, self.awake as u8, self.sleep as u8]
     }
 
     /// # Bits for older 0x1866 keyboard model
@@ -183,6 +218,18 @@ impl LaptopAuraPower {
         ]
     }
 
+    /// Set one cell of the zone by phase matrix. Returns false if there are no
+    /// states for `zone`.
+    pub fn set_state(&mut self, zone: PowerZones, phase: PowerPhase, on: bool) -> bool {
+        match self.states.iter_mut().find(|s| s.zone == zone) {
+            Some(state) => {
+                state.set(phase, on);
+                true
+            }
+            None => false,
+        }
+    }
+
     // TODO: use support data to setup correct zones
     pub fn new(aura_type: AuraDeviceType, support_data: &LaptopLedData) -> Self {
         match aura_type {
@@ -204,7 +251,7 @@ impl LaptopAuraPower {
                     }
                 }
             }
-            AuraDeviceType::LaptopTuf => Self {
+            AuraDeviceType::LaptopTuf | AuraDeviceType::LaptopMulticolor => Self {
                 states: vec![AuraPowerState::default_for(PowerZones::Keyboard)],
             },
             AuraDeviceType::ScsiExtDisk => todo!(),
@@ -226,6 +273,8 @@ impl LaptopAuraPower {
                 .cloned()
                 .unwrap_or_default()
                 .tuf_to_bytes(),
+            // Only the brightness, which is set on its own
+            AuraDeviceType::LaptopMulticolor => Vec::new(),
             AuraDeviceType::Unknown => {
                 warn!("Trying to create bytes for an unknown device");
                 self.new_to_bytes()
@@ -235,6 +284,145 @@ impl LaptopAuraPower {
     }
 }
 
+/// The flat list of enabled states that configs stored before the per zone
+/// states, one list per generation of keyboard. Only kept to read old configs.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuraPowerDev {
+    pub tuf: Vec<AuraDevTuf>,
+    pub x1866: Vec<AuraDevRog1>,
+    pub x19b6: Vec<AuraDevRog2>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AuraDevTuf {
+    Boot,
+    Awake,
+    Sleep,
+    Keyboard,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AuraDevRog1 {
+    Awake,
+    Keyboard,
+    Lightbar,
+    Boot,
+    Sleep,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AuraDevRog2 {
+    BootLogo,
+    BootKeyb,
+    AwakeLogo,
+    AwakeKeyb,
+    SleepLogo,
+    SleepKeyb,
+    ShutdownLogo,
+    ShutdownKeyb,
+    BootBar,
+    AwakeBar,
+    SleepBar,
+    ShutdownBar,
+    BootLid,
+    AwakeLid,
+    SleepLid,
+    ShutdownLid,
+    BootRearGlow,
+    AwakeRearGlow,
+    SleepRearGlow,
+    ShutdownRearGlow,
+}
+
+impl AuraDevRog2 {
+    fn cell(self) -> (PowerZones, PowerPhase) {
+        use PowerPhase::*;
+        use PowerZones::*;
+        match self {
+            Self::BootLogo => (Logo, Boot),
+            Self::BootKeyb => (Keyboard, Boot),
+            Self::AwakeLogo => (Logo, Awake),
+            Self::AwakeKeyb => (Keyboard, Awake),
+            Self::SleepLogo => (Logo, Sleep),
+            Self::SleepKeyb => (Keyboard, Sleep),
+            Self::ShutdownLogo => (Logo, Shutdown),
+            Self::ShutdownKeyb => (Keyboard, Shutdown),
+            Self::BootBar => (Lightbar, Boot),
+            Self::AwakeBar => (Lightbar, Awake),
+            Self::SleepBar => (Lightbar, Sleep),
+            Self::ShutdownBar => (Lightbar, Shutdown),
+            Self::BootLid => (Lid, Boot),
+            Self::AwakeLid => (Lid, Awake),
+            Self::SleepLid => (Lid, Sleep),
+            Self::ShutdownLid => (Lid, Shutdown),
+            Self::BootRearGlow => (RearGlow, Boot),
+            Self::AwakeRearGlow => (RearGlow, Awake),
+            Self::SleepRearGlow => (RearGlow, Sleep),
+            Self::ShutdownRearGlow => (RearGlow, Shutdown),
+        }
+    }
+}
+
+impl From<AuraPowerDev> for LaptopAuraPower {
+    /// Whichever list is in use gives the states, anything not in it is off.
+    /// Zones the laptop doesn't have are dropped when the config is checked
+    /// against the support data.
+    fn from(old: AuraPowerDev) -> Self {
+        let off = |zone| AuraPowerState {
+            zone,
+            boot: false,
+            awake: false,
+            sleep: false,
+            shutdown: false,
+        };
+        if !old.x1866.is_empty() {
+            let zone = match (
+                old.x1866.contains(&AuraDevRog1::Keyboard),
+                old.x1866.contains(&AuraDevRog1::Lightbar),
+            ) {
+                (true, true) => PowerZones::KeyboardAndLightbar,
+                (false, true) => PowerZones::Lightbar,
+                _ => PowerZones::Keyboard,
+            };
+            return Self {
+                states: vec![AuraPowerState {
+                    boot: old.x1866.contains(&AuraDevRog1::Boot),
+                    awake: old.x1866.contains(&AuraDevRog1::Awake),
+                    sleep: old.x1866.contains(&AuraDevRog1::Sleep),
+                    ..off(zone)
+                }],
+            };
+        }
+        if !old.tuf.is_empty() {
+            return Self {
+                states: vec![AuraPowerState {
+                    boot: old.tuf.contains(&AuraDevTuf::Boot),
+                    awake: old.tuf.contains(&AuraDevTuf::Awake),
+                    sleep: old.tuf.contains(&AuraDevTuf::Sleep),
+                    ..off(PowerZones::Keyboard)
+                }],
+            };
+        }
+        let mut power = Self {
+            states: [
+                PowerZones::Logo,
+                PowerZones::Keyboard,
+                PowerZones::Lightbar,
+                PowerZones::Lid,
+                PowerZones::RearGlow,
+            ]
+            .into_iter()
+            .map(off)
+            .collect(),
+        };
+        for flag in old.x19b6 {
+            let (zone, phase) = flag.cell();
+            power.set_state(zone, phase, true);
+        }
+        power
+    }
+}
+
 /// |   Byte 1   |   Byte 2   |   Byte 3   | function |   hex    |
 /// |------------|------------|------------|----------|----------|
 /// | 0000, 0000 | 0000, 0000 | 0000, 0010 | Awake    | 00,00,02 |
@@ -277,9 +465,63 @@ impl From<OldAuraPower> for u32 {
 #[cfg(test)]
 mod test {
     use crate::aura_detection::{LaptopLedData, PowerZones};
-    use crate::keyboard::{AuraPowerState, LaptopAuraPower};
+    use crate::keyboard::{
+        AuraDevRog1, AuraDevRog2, AuraPowerDev, AuraPowerState, LaptopAuraPower, PowerPhase,
+    };
     use crate::AuraDeviceType;
 
+    #[test]
+    fn set_one_cell() {
+        let mut power = LaptopAuraPower {
+            states: vec![
+                AuraPowerState::default_for(PowerZones::Keyboard),
+                AuraPowerState::default_for(PowerZones::Lightbar),
+            ],
+        };
+        assert!(power.set_state(PowerZones::Lightbar, PowerPhase::Sleep, false));
+        assert!(!power.states[1].get(PowerPhase::Sleep));
+        assert!(power.states[1].get(PowerPhase::Boot));
+        assert!(power.states[0].get(PowerPhase::Sleep));
+        assert!(!power.set_state(PowerZones::Lid, PowerPhase::Sleep, false));
+        assert_eq!(
+            power.to_bytes(AuraDeviceType::LaptopPost2021),
+            [0b10101010, 0b00010110, 0, 0]
+        );
+    }
+
+    #[test]
+    fn flat_list_migration() {
+        let power = LaptopAuraPower::from(AuraPowerDev {
+            x19b6: vec![
+                AuraDevRog2::BootKeyb,
+                AuraDevRog2::AwakeKeyb,
+                AuraDevRog2::AwakeBar,
+            ],
+            ..Default::default()
+        });
+        assert_eq!(power.states.len(), 5);
+        let keyboard = power.states[1];
+        assert_eq!(keyboard.zone, PowerZones::Keyboard);
+        assert!(keyboard.boot && keyboard.awake && !keyboard.sleep && !keyboard.shutdown);
+        assert!(power.states[2].awake && !power.states[2].boot);
+        assert_eq!(
+            power.to_bytes(AuraDeviceType::LaptopPost2021),
+            [0b00001010, 0b00000100, 0, 0]
+        );
+
+        let power = LaptopAuraPower::from(AuraPowerDev {
+            x1866: vec![
+                AuraDevRog1::Keyboard,
+                AuraDevRog1::Lightbar,
+                AuraDevRog1::Awake,
+            ],
+            ..Default::default()
+        });
+        assert_eq!(power.states.len(), 1);
+        assert_eq!(power.states[0].zone, PowerZones::KeyboardAndLightbar);
+        assert!(power.states[0].awake && !power.states[0].sleep);
+    }
+
     #[test]
     fn check_0x1866_control_bytes() {
         let state = AuraPowerState {
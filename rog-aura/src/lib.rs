@@ -17,10 +17,18 @@ pub mod effects;
 mod builtin_modes;
 pub use builtin_modes::*;
 
+/// A zone showing the active audio output
+pub mod audio_indicator;
 /// Helper for detecting what is available
 pub mod aura_detection;
+/// Per-model correction of the LED colours
+pub mod colour_correction;
 pub mod error;
+/// Estimates of the LED power draw
+pub mod power_estimate;
 pub mod usb;
+/// Lighting of a user's session, shown over the stored lighting
+pub mod user_override;
 
 pub mod keyboard;
 
@@ -74,6 +82,8 @@ pub enum AuraDeviceType {
     LaptopPre2021 = 1,
     LaptopTuf = 2,
     ScsiExtDisk = 3,
+    /// Only a keyboard backlight in the multicolor LED class of sysfs
+    LaptopMulticolor = 4,
     Unknown = 255,
 }
 
@@ -90,6 +100,10 @@ impl AuraDeviceType {
         *self == Self::LaptopPost2021
     }
 
+    pub fn is_multicolor_laptop(&self) -> bool {
+        *self == Self::LaptopMulticolor
+    }
+
     pub fn is_scsi(&self) -> bool {
         *self == Self::ScsiExtDisk
     }
@@ -99,6 +113,7 @@ impl From<&str> for AuraDeviceType {
     fn from(s: &str) -> Self {
         match s.to_lowercase().trim_start_matches("0x") {
             "tuf" => AuraDeviceType::LaptopTuf,
+            "multicolor" => AuraDeviceType::LaptopMulticolor,
             "1932" => AuraDeviceType::ScsiExtDisk,
             "1866" | "18c6" | "1869" | "1854" => Self::LaptopPre2021,
             _ => Self::LaptopPost2021,
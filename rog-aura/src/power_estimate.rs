@@ -0,0 +1,212 @@
+//! A rough estimate of how much power the LEDs draw, for battery panels and
+//! for suggesting where power can be saved. Each power zone has a draw at
+//! full brightness with every LED white, which is scaled by the brightness
+//! and by how lit the current effect keeps the LEDs on average.
+//!
+//! The figures are typical of recent models unless the support data has
+//! calibration for the laptop, so treat them as a guide only.
+
+use std::collections::BTreeMap;
+
+use serde_derive::{Deserialize, Serialize};
+
+use crate::aura_detection::PowerZones;
+use crate::{AuraEffect, AuraModeNum, Colour, LedBrightness};
+
+/// Suggestions are only made for zones drawing at least this much
+const SUGGEST_MIN_MW: u32 = 50;
+
+/// Measured draw of each zone at full brightness, all white, in milliwatts.
+/// Zones not listed use the typical figure.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct PowerCalibration {
+    pub zone_mw: BTreeMap<PowerZones, u32>,
+}
+
+impl PowerCalibration {
+    pub fn full_mw(&self, zone: PowerZones) -> u32 {
+        self.zone_mw
+            .get(&zone)
+            .copied()
+            .unwrap_or_else(|| typical_mw(zone))
+    }
+}
+
+/// Typical draw of a zone at full brightness, all white
+pub const fn typical_mw(zone: PowerZones) -> u32 {
+    match zone {
+        PowerZones::Logo => 100,
+        PowerZones::Keyboard => 1200,
+        PowerZones::Lightbar => 300,
+        PowerZones::Lid => 400,
+        PowerZones::RearGlow => 500,
+        PowerZones::KeyboardAndLightbar => 1500,
+    }
+}
+
+const fn zone_name(zone: PowerZones) -> &'static str {
+    match zone {
+        PowerZones::Logo => "logo",
+        PowerZones::Keyboard => "keyboard backlight",
+        PowerZones::Lightbar => "lightbar",
+        PowerZones::Lid => "lid LEDs",
+        PowerZones::RearGlow => "rear glow",
+        PowerZones::KeyboardAndLightbar => "keyboard and lightbar",
+    }
+}
+
+fn colour_level(c: &Colour) -> f32 {
+    (c.r as f32 + c.g as f32 + c.b as f32) / (3.0 * 255.0)
+}
+
+/// How lit `effect` keeps the LEDs on average, from 0 for always off to 1
+/// for always full white. Colour cycling modes average out at half, and the
+/// reactive modes are mostly dark between key presses.
+pub fn effect_load(effect: &AuraEffect) -> f32 {
+    let c1 = colour_level(&effect.colour1);
+    let both = (c1 + colour_level(&effect.colour2)) / 2.0;
+    match effect.mode {
+        AuraModeNum::Static => c1,
+        AuraModeNum::Breathe => both * 0.5,
+        AuraModeNum::Strobe | AuraModeNum::Rainbow => 0.5,
+        AuraModeNum::Star => both * 0.3,
+        AuraModeNum::Rain => 0.2,
+        AuraModeNum::Pulse | AuraModeNum::Flash => c1 * 0.5,
+        AuraModeNum::Comet => c1 * 0.2,
+        AuraModeNum::Highlight | AuraModeNum::Laser | AuraModeNum::Ripple => c1 * 0.1,
+    }
+}
+
+fn brightness_scale(brightness: LedBrightness) -> f32 {
+    brightness as u8 as f32 / LedBrightness::High as u8 as f32
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PowerEstimate {
+    /// Estimated draw of each lit zone in milliwatts
+    pub zones: BTreeMap<PowerZones, u32>,
+}
+
+impl PowerEstimate {
+    /// Estimate the draw of `lit` zones showing an effect with `load` (from
+    /// `effect_load`) at `brightness`
+    pub fn new(
+        calibration: &PowerCalibration,
+        lit: &[PowerZones],
+        brightness: LedBrightness,
+        load: f32,
+    ) -> Self {
+        let scale = brightness_scale(brightness) * load.clamp(0.0, 1.0);
+        let zones = lit
+            .iter()
+            .map(|zone| {
+                let mw = (calibration.full_mw(*zone) as f32 * scale).round() as u32;
+                (*zone, mw)
+            })
+            .collect();
+        Self { zones }
+    }
+
+    pub fn total_mw(&self) -> u32 {
+        self.zones.values().sum()
+    }
+
+    /// Ways to save power, largest saving first
+    pub fn suggestions(&self, brightness: LedBrightness) -> Vec<String> {
+        let mut savings: Vec<(u32, String)> = self
+            .zones
+            .iter()
+            .filter(|(zone, mw)| {
+                !matches!(zone, PowerZones::Keyboard | PowerZones::KeyboardAndLightbar)
+                    && **mw >= SUGGEST_MIN_MW
+            })
+            .map(|(zone, mw)| {
+                (
+                    *mw,
+                    format!("Disable the {} to save ~{mw} mW", zone_name(*zone)),
+                )
+            })
+            .collect();
+
+        if brightness as u8 > LedBrightness::Low as u8 {
+            let low = brightness_scale(LedBrightness::Low) / brightness_scale(brightness);
+            let saving = (self.total_mw() as f32 * (1.0 - low)).round() as u32;
+            if saving >= SUGGEST_MIN_MW {
+                savings.push((
+                    saving,
+                    format!("Lower the brightness to low to save ~{saving} mW"),
+                ));
+            }
+        }
+        savings.sort_by(|a, b| b.0.cmp(&a.0));
+        savings.into_iter().map(|(_, s)| s).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{effect_load, PowerCalibration, PowerEstimate};
+    use crate::aura_detection::PowerZones;
+    use crate::{AuraEffect, AuraModeNum, Colour, LedBrightness};
+
+    #[test]
+    fn scales_with_brightness_and_colour() {
+        let mut effect = AuraEffect {
+            mode: AuraModeNum::Static,
+            colour1: Colour {
+                r: 255,
+                g: 255,
+                b: 255,
+            },
+            ..Default::default()
+        };
+        assert_eq!(effect_load(&effect), 1.0);
+        effect.colour1 = Colour { r: 255, g: 0, b: 0 };
+        let load = effect_load(&effect);
+
+        let mut calibration = PowerCalibration::default();
+        calibration.zone_mw.insert(PowerZones::Keyboard, 900);
+        let zones = [PowerZones::Keyboard, PowerZones::Lightbar];
+        let full = PowerEstimate::new(&calibration, &zones, LedBrightness::High, 1.0);
+        assert_eq!(full.zones[&PowerZones::Keyboard], 900);
+        assert_eq!(full.zones[&PowerZones::Lightbar], 300);
+        assert_eq!(full.total_mw(), 1200);
+
+        let red = PowerEstimate::new(&calibration, &zones, LedBrightness::High, load);
+        assert_eq!(red.total_mw(), 400);
+        let off = PowerEstimate::new(&calibration, &zones, LedBrightness::Off, 1.0);
+        assert_eq!(off.total_mw(), 0);
+    }
+
+    #[test]
+    fn suggestions_largest_first() {
+        let zones = [PowerZones::Keyboard, PowerZones::Lightbar, PowerZones::Logo];
+        let estimate = PowerEstimate::new(
+            &PowerCalibration::default(),
+            &zones,
+            LedBrightness::High,
+            1.0,
+        );
+        assert_eq!(
+            estimate.suggestions(LedBrightness::High),
+            vec![
+                "Lower the brightness to low to save ~1067 mW".to_owned(),
+                "Disable the lightbar to save ~300 mW".to_owned(),
+                "Disable the logo to save ~100 mW".to_owned(),
+            ]
+        );
+
+        // The logo is under the threshold at low brightness
+        let low = PowerEstimate::new(
+            &PowerCalibration::default(),
+            &zones,
+            LedBrightness::Low,
+            1.0,
+        );
+        assert_eq!(
+            low.suggestions(LedBrightness::Low),
+            vec!["Disable the lightbar to save ~100 mW".to_owned()]
+        );
+    }
+}
@@ -0,0 +1,18 @@
+use serde::{Deserialize, Serialize};
+use typeshare::typeshare;
+#[cfg(feature = "dbus")]
+use zbus::zvariant::{OwnedValue, Type, Value};
+
+use crate::{AuraEffect, LedBrightness};
+
+/// Lighting set by the session daemon of a user, shown in place of the
+/// stored mode and brightness while that user's session is the active one.
+/// The stored lighting is left as it is, and comes back at the greeter or
+/// for another user.
+#[typeshare]
+#[cfg_attr(feature = "dbus", derive(Type, Value, OwnedValue))]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct AuraOverride {
+    pub effect: AuraEffect,
+    pub brightness: LedBrightness,
+}
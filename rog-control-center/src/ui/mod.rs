@@ -8,6 +8,7 @@ use std::sync::{Arc, Mutex};
 use config_traits::StdConfig;
 use rog_dbus::zbus_platform::PlatformProxyBlocking;
 use slint::{ComponentHandle, PhysicalSize, SharedString, Weak};
+use zbus::DBusError;
 
 use crate::config::Config;
 use crate::ui::setup_anime::setup_anime_page;
@@ -78,6 +79,15 @@ macro_rules! set_ui_callbacks {
     };
 }
 
+/// The reason asusd gave for a failed call, such as an unsupported mode
+fn error_reason(e: &zbus::Error) -> Option<String> {
+    match e {
+        zbus::Error::MethodError(_, Some(detail), _) => Some(detail.clone()),
+        zbus::Error::FDO(e) => e.description().map(str::to_owned),
+        _ => None,
+    }
+}
+
 pub fn show_toast(
     success: SharedString,
     fail: SharedString,
@@ -90,7 +100,11 @@ pub fn show_toast(
         }
         Err(e) => slint::invoke_from_event_loop(move || {
             log::warn!("{fail}: {e}");
-            handle.unwrap().invoke_show_toast(fail)
+            let shown = match error_reason(&e) {
+                Some(reason) if !reason.is_empty() => format!("{fail}: {reason}").into(),
+                _ => fail,
+            };
+            handle.unwrap().invoke_show_toast(shown)
         })
         .ok(),
     };
@@ -1,7 +1,8 @@
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, OnceLock};
 
 use rog_aura::keyboard::LaptopAuraPower;
 use rog_dbus::zbus_aura::AuraProxy;
+use rog_dbus::{RogDbusClient, RogDbusClientBlocking};
 use slint::{ComponentHandle, Model, RgbaColor, SharedString};
 
 use crate::config::Config;
@@ -24,49 +25,35 @@ fn decode_hex(s: &str) -> RgbaColor<u8> {
     }
 }
 
-pub fn has_aura_iface_blocking() -> Result<bool, Box<dyn std::error::Error>> {
-    let conn = zbus::blocking::Connection::system()?;
-    let f = zbus::blocking::fdo::ObjectManagerProxy::new(&conn, "org.asuslinux.Daemon", "/org")?;
-    let interfaces = f.get_managed_objects()?;
-    let mut aura_paths = Vec::new();
-    for v in interfaces.iter() {
-        for k in v.1.keys() {
-            if k.as_str() == "org.asuslinux.Aura" {
-                aura_paths.push(v.0.clone());
-            }
-        }
+/// The one connection to asusd, made on first use
+fn client() -> zbus::Result<&'static RogDbusClientBlocking> {
+    static CLIENT: OnceLock<RogDbusClientBlocking> = OnceLock::new();
+    if let Some(client) = CLIENT.get() {
+        return Ok(client);
     }
-    Ok(!aura_paths.is_empty())
+    let client = RogDbusClientBlocking::new("rog-control-center")?;
+    Ok(CLIENT.get_or_init(|| client))
+}
+
+pub fn has_aura_iface_blocking() -> Result<bool, Box<dyn std::error::Error>> {
+    Ok(!client()?.aura_devices()?.is_empty())
 }
 
 /// Returns the first available Aura interface
 // TODO: return all
 async fn find_aura_iface() -> Result<AuraProxy<'static>, Box<dyn std::error::Error>> {
-    let conn = zbus::Connection::system().await?;
-    let f = zbus::fdo::ObjectManagerProxy::new(&conn, "org.asuslinux.Daemon", "/org").await?;
-    let interfaces = f.get_managed_objects().await?;
-    let mut aura_paths = Vec::new();
-    for v in interfaces.iter() {
-        for k in v.1.keys() {
-            if k.as_str() == "org.asuslinux.Aura" {
-                println!("Found aura device at {}, {}", v.0, k);
-                aura_paths.push(v.0.clone());
-            }
-        }
-    }
-    if aura_paths.len() > 1 {
-        println!("Multiple aura devices found: {aura_paths:?}");
+    let client = tokio::task::spawn_blocking(|| client().cloned()).await??;
+    let mut devices = RogDbusClient::from(client).aura_devices().await?;
+    if devices.len() > 1 {
+        println!("Multiple aura devices found");
         println!("TODO: enable selection");
     }
-    if let Some(path) = aura_paths.first() {
-        return Ok(AuraProxy::builder(&conn)
-            .path(path.clone())?
-            .destination("org.asuslinux.Daemon")?
-            .build()
-            .await?);
+    if devices.is_empty() {
+        return Err("No Aura interface".into());
     }
-
-    Err("No Aura interface".into())
+    let aura = devices.remove(0);
+    println!("Found aura device at {}", aura.inner().path());
+    Ok(aura)
 }
 
 pub fn setup_aura_page(ui: &MainWindow, _states: Arc<Mutex<Config>>) {
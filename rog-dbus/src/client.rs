@@ -0,0 +1,326 @@
+//! A connection to asusd with a proxy for each of its interfaces, so that a
+//! client needs neither the object paths nor its own proxies. The proxies are
+//! built on one shared connection and give the typed values of the daemon.
+//! Each signal is a stream of its typed arguments from a method of the
+//! client, such as `hotkeys`, and each property change is a stream from the
+//! `receive_*_changed` method of the proxy:
+//!
+//! ```no_run
+//! # async fn run() -> zbus::Result<()> {
+//! use zbus::export::futures_util::StreamExt;
+//!
+//! let client = rog_dbus::RogDbusClient::new("my-applet").await?;
+//! for aura in client.aura_devices().await? {
+//!     let mut modes = aura.receive_led_mode_data_changed().await;
+//!     while let Some(change) = modes.next().await {
+//!         println!("{:?}", change.get().await?);
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use asusd::ctrl_clients::{Change, Conflict};
+use asusd::ctrl_events::HotkeyEvent;
+use rog_aura::AuraEffect;
+use zbus::export::futures_util::future::ready;
+use zbus::export::futures_util::stream::{self, Stream, StreamExt};
+use zbus::fdo::ManagedObjects;
+use zbus::zvariant::OwnedObjectPath;
+
+use crate::zbus_anime::{AnimeProxy, AnimeProxyBlocking};
+use crate::zbus_aura::{AuraDevicesProxy, AuraDevicesProxyBlocking, AuraProxy, AuraProxyBlocking};
+use crate::zbus_clients::{ClientsProxy, ClientsProxyBlocking};
+use crate::zbus_events::{EventsProxy, EventsProxyBlocking};
+use crate::zbus_fan_curves::{FanCurvesProxy, FanCurvesProxyBlocking};
+use crate::zbus_gpu::{GpuProxy, GpuProxyBlocking};
+use crate::zbus_numpad::{NumpadProxy, NumpadProxyBlocking};
+use crate::zbus_platform::{PlatformProxy, PlatformProxyBlocking};
+use crate::zbus_slash::{SlashProxy, SlashProxyBlocking};
+use crate::DBUS_NAME;
+
+const AURA_IFACE: &str = "org.asuslinux.Aura";
+/// Where asusd serves the object manager listing its objects
+const OBJECT_MANAGER_PATH: &str = "/org";
+
+/// The paths of every object with the Aura interface, in order
+fn aura_paths(objects: &ManagedObjects) -> Vec<OwnedObjectPath> {
+    let mut paths: Vec<OwnedObjectPath> = objects
+        .iter()
+        .filter(|(_, interfaces)| interfaces.keys().any(|i| i.as_str() == AURA_IFACE))
+        .map(|(path, _)| path.clone())
+        .collect();
+    paths.sort();
+    paths
+}
+
+/// A proxy getter for each interface at its default path
+macro_rules! proxies {
+    (blocking $($(#[$attr:meta])* $name:ident: $proxy:ident,)*) => {
+        $(
+            $(#[$attr])*
+            pub fn $name(&self) -> zbus::Result<$proxy<'static>> {
+                $proxy::new(&self.connection)
+            }
+        )*
+    };
+    ($($(#[$attr:meta])* $name:ident: $proxy:ident,)*) => {
+        $(
+            $(#[$attr])*
+            pub async fn $name(&self) -> zbus::Result<$proxy<'static>> {
+                $proxy::new(&self.connection).await
+            }
+        )*
+    };
+}
+
+/// asusd on the system bus, for async clients
+#[derive(Debug, Clone)]
+pub struct RogDbusClient {
+    connection: zbus::Connection,
+}
+
+impl RogDbusClient {
+    /// Connect to the system bus, registering as `name` so that asusd can say
+    /// which client changed a setting. An older asusd without client
+    /// registration is still connected to.
+    pub async fn new(name: &str) -> zbus::Result<Self> {
+        let connection = zbus::Connection::system().await?;
+        let client = Self { connection };
+        if let Ok(clients) = client.clients().await {
+            clients.register_client(name).await.ok();
+        }
+        Ok(client)
+    }
+
+    pub fn connection(&self) -> &zbus::Connection {
+        &self.connection
+    }
+
+    proxies! {
+        platform: PlatformProxy,
+        fan_curves: FanCurvesProxy,
+        anime: AnimeProxy,
+        slash: SlashProxy,
+        gpu: GpuProxy,
+        numpad: NumpadProxy,
+        events: EventsProxy,
+        clients: ClientsProxy,
+        /// The Aura devices seen before, including those unplugged now
+        known_aura_devices: AuraDevicesProxy,
+    }
+
+    /// A proxy for each Aura device asusd has, such as the laptop keyboard
+    /// and any external keyboards or lightbars
+    pub async fn aura_devices(&self) -> zbus::Result<Vec<AuraProxy<'static>>> {
+        let manager =
+            zbus::fdo::ObjectManagerProxy::new(&self.connection, DBUS_NAME, OBJECT_MANAGER_PATH)
+                .await?;
+        let mut devices = Vec::new();
+        for path in aura_paths(&manager.get_managed_objects().await?) {
+            devices.push(
+                AuraProxy::builder(&self.connection)
+                    .path(path)?
+                    .destination(DBUS_NAME)?
+                    .build()
+                    .await?,
+            );
+        }
+        Ok(devices)
+    }
+
+    /// Each hotkey press, repeat and release, from `NotifyHotkey`
+    pub async fn hotkeys(&self) -> zbus::Result<impl Stream<Item = HotkeyEvent>> {
+        let signals = self.events().await?.receive_notify_hotkey().await?;
+        Ok(signals.filter_map(|signal| {
+            ready(signal.args().ok().map(|args| HotkeyEvent {
+                key: *args.key(),
+                action: *args.action(),
+                handled: *args.handled(),
+            }))
+        }))
+    }
+
+    /// Each change request made by any client, from `ChangedBy`
+    pub async fn changes(&self) -> zbus::Result<impl Stream<Item = Change>> {
+        let signals = self.clients().await?.receive_changed_by().await?;
+        Ok(signals.filter_map(|signal| {
+            ready(signal.args().ok().map(|args| Change {
+                interface: args.interface().to_string(),
+                property: args.property().to_string(),
+                sender: args.sender().to_string(),
+                client: args.client().to_string(),
+            }))
+        }))
+    }
+
+    /// Each change made over another client's recent change, from
+    /// `WriteConflict`
+    pub async fn conflicts(&self) -> zbus::Result<impl Stream<Item = Conflict>> {
+        let signals = self.clients().await?.receive_write_conflict().await?;
+        Ok(signals.filter_map(|signal| {
+            ready(signal.args().ok().map(|args| Conflict {
+                change: Change {
+                    interface: args.interface().to_string(),
+                    property: args.property().to_string(),
+                    sender: args.sender().to_string(),
+                    client: args.client().to_string(),
+                },
+                overwritten_sender: args.overwritten_sender().to_string(),
+                overwritten_client: args.overwritten_client().to_string(),
+            }))
+        }))
+    }
+
+    /// The reason for each change that applies only after a reboot, from
+    /// `RebootRequired`
+    pub async fn reboots_required(&self) -> zbus::Result<impl Stream<Item = String>> {
+        let signals = self.gpu().await?.receive_reboot_required().await?;
+        Ok(signals.filter_map(|signal| ready(signal.args().ok().map(|a| a.reason().to_string()))))
+    }
+
+    /// The name of the interface, such as `Platform`, each time asusd reloads
+    /// its config, from the `NotifyConfigReloaded` of every interface. Aura
+    /// devices added later are not included.
+    pub async fn config_reloads(&self) -> zbus::Result<impl Stream<Item = &'static str>> {
+        let mut streams = vec![
+            self.platform()
+                .await?
+                .receive_notify_config_reloaded()
+                .await?
+                .map(|_| "Platform")
+                .boxed(),
+            self.fan_curves()
+                .await?
+                .receive_notify_config_reloaded()
+                .await?
+                .map(|_| "FanCurves")
+                .boxed(),
+            self.anime()
+                .await?
+                .receive_notify_config_reloaded()
+                .await?
+                .map(|_| "Anime")
+                .boxed(),
+            self.slash()
+                .await?
+                .receive_notify_config_reloaded()
+                .await?
+                .map(|_| "Slash")
+                .boxed(),
+        ];
+        for aura in self.aura_devices().await? {
+            streams.push(
+                aura.receive_notify_config_reloaded()
+                    .await?
+                    .map(|_| "Aura")
+                    .boxed(),
+            );
+        }
+        Ok(stream::select_all(streams))
+    }
+}
+
+/// Each effect set on a zone of `aura`, from `NotifyZone`
+pub async fn zone_effects(
+    aura: &AuraProxy<'static>,
+) -> zbus::Result<impl Stream<Item = AuraEffect>> {
+    let signals = aura.receive_notify_zone().await?;
+    Ok(signals.filter_map(|signal| ready(signal.args().ok().map(|args| args.effect().clone()))))
+}
+
+/// Shares the connection, and the client name registered on it
+impl From<RogDbusClientBlocking> for RogDbusClient {
+    fn from(client: RogDbusClientBlocking) -> Self {
+        Self {
+            connection: client.connection.into_inner(),
+        }
+    }
+}
+
+/// asusd on the system bus, for clients without an async runtime
+#[derive(Debug, Clone)]
+pub struct RogDbusClientBlocking {
+    connection: zbus::blocking::Connection,
+}
+
+impl RogDbusClientBlocking {
+    /// See `RogDbusClient::new`
+    pub fn new(name: &str) -> zbus::Result<Self> {
+        let connection = zbus::blocking::Connection::system()?;
+        let client = Self { connection };
+        if let Ok(clients) = client.clients() {
+            clients.register_client(name).ok();
+        }
+        Ok(client)
+    }
+
+    pub fn connection(&self) -> &zbus::blocking::Connection {
+        &self.connection
+    }
+
+    proxies! {
+        blocking
+        platform: PlatformProxyBlocking,
+        fan_curves: FanCurvesProxyBlocking,
+        anime: AnimeProxyBlocking,
+        slash: SlashProxyBlocking,
+        gpu: GpuProxyBlocking,
+        numpad: NumpadProxyBlocking,
+        events: EventsProxyBlocking,
+        clients: ClientsProxyBlocking,
+        /// The Aura devices seen before, including those unplugged now
+        known_aura_devices: AuraDevicesProxyBlocking,
+    }
+
+    /// See `RogDbusClient::aura_devices`
+    pub fn aura_devices(&self) -> zbus::Result<Vec<AuraProxyBlocking<'static>>> {
+        let manager = zbus::blocking::fdo::ObjectManagerProxy::new(
+            &self.connection,
+            DBUS_NAME,
+            OBJECT_MANAGER_PATH,
+        )?;
+        aura_paths(&manager.get_managed_objects()?)
+            .into_iter()
+            .map(|path| {
+                AuraProxyBlocking::builder(&self.connection)
+                    .path(path)?
+                    .destination(DBUS_NAME)?
+                    .build()
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use zbus::fdo::ManagedObjects;
+    use zbus::names::OwnedInterfaceName;
+    use zbus::zvariant::OwnedObjectPath;
+
+    use super::{aura_paths, AURA_IFACE};
+
+    #[test]
+    fn aura_paths_sorted() {
+        let mut objects = ManagedObjects::new();
+        for (path, iface) in [
+            ("/org/asuslinux/19b6", AURA_IFACE),
+            ("/org/asuslinux", "org.asuslinux.Platform"),
+            ("/org/asuslinux/1866", AURA_IFACE),
+            ("/org/asuslinux/Anime", "org.asuslinux.Anime"),
+        ] {
+            objects.insert(
+                OwnedObjectPath::try_from(path).unwrap(),
+                [(
+                    OwnedInterfaceName::try_from(iface).unwrap(),
+                    Default::default(),
+                )]
+                .into_iter()
+                .collect(),
+            );
+        }
+        let paths: Vec<String> = aura_paths(&objects).iter().map(|p| p.to_string()).collect();
+        assert_eq!(paths, ["/org/asuslinux/1866", "/org/asuslinux/19b6"]);
+        assert!(aura_paths(&ManagedObjects::new()).is_empty());
+    }
+}
@@ -1,8 +1,15 @@
 pub use asusd::{DBUS_IFACE, DBUS_NAME, DBUS_PATH};
+pub use client::{RogDbusClient, RogDbusClientBlocking};
 
+/// One connection with a proxy for each interface
+pub mod client;
 pub mod zbus_anime;
 pub mod zbus_aura;
+pub mod zbus_clients;
+pub mod zbus_events;
 pub mod zbus_fan_curves;
+pub mod zbus_gpu;
+pub mod zbus_numpad;
 pub mod zbus_platform;
 pub mod zbus_slash;
 
@@ -1,6 +1,7 @@
 use rog_anime::usb::Brightness;
-use rog_anime::{Animations, AnimeDataBuffer, DeviceState as AnimeDeviceState};
+use rog_anime::{Animations, AnimeDataBuffer, AnimeWidget, DeviceState as AnimeDeviceState};
 use zbus::proxy;
+use zbus::zvariant::OwnedFd;
 
 #[proxy(
     interface = "org.asuslinux.Anime",
@@ -8,12 +9,47 @@ use zbus::proxy;
     default_path = "/org/asuslinux"
 )]
 trait Anime {
+    /// DrawClear method
+    fn draw_clear(&self, brightness: u8) -> zbus::Result<()>;
+
+    /// DrawDither method
+    fn draw_dither(&self, levels: u8) -> zbus::Result<()>;
+
+    /// DrawLine method
+    fn draw_line(&self, x0: i32, y0: i32, x1: i32, y1: i32, brightness: u8) -> zbus::Result<()>;
+
+    /// DrawPixel method
+    fn draw_pixel(&self, x: i32, y: i32, brightness: u8) -> zbus::Result<()>;
+
+    /// DrawPresent method
+    fn draw_present(&self) -> zbus::Result<()>;
+
+    /// DrawRect method
+    fn draw_rect(
+        &self,
+        x: i32,
+        y: i32,
+        w: i32,
+        h: i32,
+        brightness: u8,
+        fill: bool,
+    ) -> zbus::Result<()>;
+
+    /// DrawSprite method
+    fn draw_sprite(&self, x: i32, y: i32, width: u32, sprite: Vec<u8>) -> zbus::Result<()>;
+
     /// DeviceState method
     fn device_state(&self) -> zbus::Result<AnimeDeviceState>;
 
     /// RunMainLoop method
     fn run_main_loop(&self, start: bool) -> zbus::Result<()>;
 
+    /// SetMediaTitle method
+    fn set_media_title(&self, title: &str) -> zbus::Result<()>;
+
+    /// StreamFrames method
+    fn stream_frames(&self, fps: u8) -> zbus::Result<OwnedFd>;
+
     /// Write method
     fn write(&self, input: AnimeDataBuffer) -> zbus::Result<()>;
 
@@ -62,4 +98,20 @@ trait Anime {
     fn off_when_unplugged(&self) -> zbus::Result<bool>;
     #[zbus(property)]
     fn set_off_when_unplugged(&self, value: bool) -> zbus::Result<()>;
+
+    /// Widgets property
+    #[zbus(property)]
+    fn widgets(&self) -> zbus::Result<Vec<AnimeWidget>>;
+    #[zbus(property)]
+    fn set_widgets(&self, value: Vec<AnimeWidget>) -> zbus::Result<()>;
+
+    /// WidgetsEnabled property
+    #[zbus(property)]
+    fn widgets_enabled(&self) -> zbus::Result<bool>;
+    #[zbus(property)]
+    fn set_widgets_enabled(&self, value: bool) -> zbus::Result<()>;
+
+    /// NotifyConfigReloaded signal
+    #[zbus(signal)]
+    fn notify_config_reloaded(&self) -> zbus::Result<()>;
 }
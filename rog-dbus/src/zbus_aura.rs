@@ -22,8 +22,13 @@
 
 use std::collections::BTreeMap;
 
+use asusd::ctrl_aura::known_devices::KnownAuraDevice;
+use asusd::led_automation::LedRule;
+use rog_aura::audio_indicator::{AudioIndicator, AudioOutput};
 use rog_aura::aura_detection::PowerZones;
-use rog_aura::keyboard::{LaptopAuraPower, UsbPackets};
+use rog_aura::effects::TrailConfig;
+use rog_aura::keyboard::{KeyGeometry, LaptopAuraPower, LedUsbPackets, PowerPhase, UsbPackets};
+use rog_aura::user_override::AuraOverride;
 use rog_aura::{AuraDeviceType, AuraEffect, AuraModeNum, AuraZone, LedBrightness};
 use zbus::blocking::Connection;
 use zbus::{proxy, Result};
@@ -39,9 +44,94 @@ trait Aura {
     /// AllModeData method
     fn all_mode_data(&self) -> zbus::Result<BTreeMap<AuraModeNum, AuraEffect>>;
 
+    /// ApplyPreset method
+    fn apply_preset(&self, name: &str) -> zbus::Result<()>;
+
+    /// ApplyRawMode method
+    fn apply_raw_mode(&self, name: &str) -> zbus::Result<()>;
+
+    /// ClearUserOverride method
+    fn clear_user_override(&self) -> zbus::Result<()>;
+
+    /// DirectAddressingFrame method
+    fn direct_addressing_frame(&self, frame: &LedUsbPackets) -> zbus::Result<()>;
+
     /// DirectAddressingRaw method
     fn direct_addressing_raw(&self, data: UsbPackets) -> zbus::Result<()>;
 
+    /// ReloadSupportData method
+    fn reload_support_data(&self) -> zbus::Result<()>;
+
+    /// RemovePreset method
+    fn remove_preset(&self, name: &str) -> zbus::Result<()>;
+
+    /// RemoveRawMode method
+    fn remove_raw_mode(&self, name: &str) -> zbus::Result<()>;
+
+    /// SaveCurrentAsPreset method
+    fn save_current_as_preset(&self, name: &str) -> zbus::Result<()>;
+
+    /// SaveRawMode method
+    fn save_raw_mode(&self, name: &str, packets: UsbPackets) -> zbus::Result<()>;
+
+    /// SetAudioOutput method
+    fn set_audio_output(&self, output: AudioOutput) -> zbus::Result<()>;
+
+    /// RunEffectPlugin method
+    fn run_effect_plugin(&self, name: &str) -> zbus::Result<()>;
+
+    /// RunTypingTrail method
+    fn run_typing_trail(&self) -> zbus::Result<()>;
+
+    /// SetLedPowerState method
+    fn set_led_power_state(
+        &self,
+        zone: PowerZones,
+        phase: PowerPhase,
+        enabled: bool,
+    ) -> zbus::Result<()>;
+
+    /// SetUserOverride method
+    fn set_user_override(&self, lighting: AuraOverride) -> zbus::Result<()>;
+
+    /// SetTemporaryEffect method
+    fn set_temporary_effect(&self, effect: AuraEffect, ttl_secs: u32) -> zbus::Result<()>;
+
+    /// PreviewEffect method
+    fn preview_effect(&self, effect: AuraEffect, timeout_ms: u32) -> zbus::Result<()>;
+
+    /// CancelPreview method
+    fn cancel_preview(&self) -> zbus::Result<()>;
+
+    /// GetKeyboardLayout method
+    fn get_keyboard_layout(&self) -> zbus::Result<Vec<KeyGeometry>>;
+
+    /// GetZones method
+    fn get_zones(&self) -> zbus::Result<Vec<AuraEffect>>;
+
+    /// SetZone method
+    fn set_zone(&self, zone: AuraZone, effect: AuraEffect) -> zbus::Result<()>;
+
+    /// SetZoneBrightness method
+    fn set_zone_brightness(&self, zone: AuraZone, level: LedBrightness) -> zbus::Result<()>;
+
+    /// StreamFrames method
+    fn stream_frames(&self) -> zbus::Result<zbus::zvariant::OwnedFd>;
+
+    /// ActiveLedRule property
+    #[zbus(property)]
+    fn active_led_rule(&self) -> zbus::Result<u32>;
+
+    /// AudioIndicator property
+    #[zbus(property)]
+    fn audio_indicator(&self) -> zbus::Result<AudioIndicator>;
+    #[zbus(property)]
+    fn set_audio_indicator(&self, value: AudioIndicator) -> zbus::Result<()>;
+
+    /// AudioOutput property
+    #[zbus(property)]
+    fn audio_output(&self) -> zbus::Result<AudioOutput>;
+
     /// Brightness property
     #[zbus(property)]
     fn brightness(&self) -> zbus::Result<LedBrightness>;
@@ -52,6 +142,10 @@ trait Aura {
     #[zbus(property)]
     fn device_type(&self) -> zbus::Result<AuraDeviceType>;
 
+    /// EffectPlugins property
+    #[zbus(property)]
+    fn effect_plugins(&self) -> zbus::Result<Vec<String>>;
+
     /// LedMode property
     #[zbus(property)]
     fn led_mode(&self) -> zbus::Result<AuraModeNum>;
@@ -70,6 +164,20 @@ trait Aura {
     #[zbus(property)]
     fn set_led_power(&self, value: LaptopAuraPower) -> zbus::Result<()>;
 
+    /// LedRules property
+    #[zbus(property)]
+    fn led_rules(&self) -> zbus::Result<Vec<LedRule>>;
+    #[zbus(property)]
+    fn set_led_rules(&self, value: &[LedRule]) -> zbus::Result<()>;
+
+    /// Presets property
+    #[zbus(property)]
+    fn presets(&self) -> zbus::Result<Vec<String>>;
+
+    /// RawModes property
+    #[zbus(property)]
+    fn raw_modes(&self) -> zbus::Result<Vec<String>>;
+
     /// SupportedBrightness property
     #[zbus(property)]
     fn supported_brightness(&self) -> zbus::Result<Vec<LedBrightness>>;
@@ -82,9 +190,75 @@ trait Aura {
     #[zbus(property)]
     fn supported_basic_zones(&self) -> zbus::Result<Vec<AuraZone>>;
 
+    /// PowerZoneDescriptions property
+    #[zbus(property)]
+    fn power_zone_descriptions(&self) -> zbus::Result<BTreeMap<PowerZones, String>>;
+
+    /// EstimatedPowerMw property
+    #[zbus(property(emits_changed_signal = "false"))]
+    fn estimated_power_mw(&self) -> zbus::Result<u32>;
+
+    /// EstimatedZonePowerMw property
+    #[zbus(property(emits_changed_signal = "false"))]
+    fn estimated_zone_power_mw(&self) -> zbus::Result<BTreeMap<PowerZones, u32>>;
+
+    /// PowerSavingSuggestions property
+    #[zbus(property(emits_changed_signal = "false"))]
+    fn power_saving_suggestions(&self) -> zbus::Result<Vec<String>>;
+
+    /// ZoneBrightness property
+    #[zbus(property)]
+    fn zone_brightness(&self) -> zbus::Result<BTreeMap<AuraZone, LedBrightness>>;
+
     /// SupportedPowerZones property
     #[zbus(property)]
     fn supported_power_zones(&self) -> zbus::Result<Vec<PowerZones>>;
+
+    /// TypingTrail property
+    #[zbus(property)]
+    fn typing_trail(&self) -> zbus::Result<TrailConfig>;
+    #[zbus(property)]
+    fn set_typing_trail(&self, value: TrailConfig) -> zbus::Result<()>;
+
+    /// IdleTimeout property
+    #[zbus(property)]
+    fn idle_timeout(&self) -> zbus::Result<u32>;
+    #[zbus(property)]
+    fn set_idle_timeout(&self, value: u32) -> zbus::Result<()>;
+
+    /// IdleFadeMs property
+    #[zbus(property)]
+    fn idle_fade_ms(&self) -> zbus::Result<u32>;
+    #[zbus(property)]
+    fn set_idle_fade_ms(&self, value: u32) -> zbus::Result<()>;
+
+    /// NotifyConfigReloaded signal
+    #[zbus(signal)]
+    fn notify_config_reloaded(&self) -> zbus::Result<()>;
+
+    /// NotifyZone signal
+    #[zbus(signal)]
+    fn notify_zone(&self, effect: AuraEffect) -> zbus::Result<()>;
+}
+
+#[proxy(
+    interface = "org.asuslinux.AuraDevices",
+    default_service = "org.asuslinux.Daemon",
+    default_path = "/org/asuslinux/AuraDevices"
+)]
+trait AuraDevices {
+    /// RemoveDevice method
+    fn remove_device(&self, prod_id: &str) -> zbus::Result<()>;
+
+    /// SetOfflineBrightness method
+    fn set_offline_brightness(&self, prod_id: &str, brightness: LedBrightness) -> zbus::Result<()>;
+
+    /// SetOfflineLedModeData method
+    fn set_offline_led_mode_data(&self, prod_id: &str, effect: AuraEffect) -> zbus::Result<()>;
+
+    /// KnownDevices property
+    #[zbus(property)]
+    fn known_devices(&self) -> zbus::Result<Vec<KnownAuraDevice>>;
 }
 
 pub struct AuraProxyPerkey<'a>(AuraProxyBlocking<'a>);
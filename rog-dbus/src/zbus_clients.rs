@@ -0,0 +1,50 @@
+//! # `DBus` interface proxy for: `org.asuslinux.Clients`
+//!
+//! Written by hand to match `asusd::ctrl_clients`.
+
+use std::collections::BTreeMap;
+
+use asusd::ctrl_clients::ChangeRecord;
+use zbus::proxy;
+
+#[proxy(
+    interface = "org.asuslinux.Clients",
+    default_service = "org.asuslinux.Daemon",
+    default_path = "/org/asuslinux"
+)]
+trait Clients {
+    /// RegisterClient method
+    fn register_client(&self, name: &str) -> zbus::Result<()>;
+
+    /// UnregisterClient method
+    fn unregister_client(&self) -> zbus::Result<()>;
+
+    /// LastChanges method
+    fn last_changes(&self) -> zbus::Result<Vec<ChangeRecord>>;
+
+    /// ChangedBy signal
+    #[zbus(signal)]
+    fn changed_by(
+        &self,
+        interface: &str,
+        property: &str,
+        sender: &str,
+        client: &str,
+    ) -> zbus::Result<()>;
+
+    /// WriteConflict signal
+    #[zbus(signal)]
+    fn write_conflict(
+        &self,
+        interface: &str,
+        property: &str,
+        overwritten_sender: &str,
+        overwritten_client: &str,
+        sender: &str,
+        client: &str,
+    ) -> zbus::Result<()>;
+
+    /// Clients property
+    #[zbus(property)]
+    fn clients(&self) -> zbus::Result<BTreeMap<String, String>>;
+}
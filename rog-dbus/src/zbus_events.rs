@@ -0,0 +1,30 @@
+//! # `DBus` interface proxy for: `org.asuslinux.Events`
+//!
+//! Written by hand to match `asusd::ctrl_events`.
+
+use std::collections::BTreeMap;
+
+use asusd::ctrl_events::{Hotkey, HotkeyHandling, KeyAction};
+use zbus::proxy;
+
+#[proxy(
+    interface = "org.asuslinux.Events",
+    default_service = "org.asuslinux.Daemon",
+    default_path = "/org/asuslinux/Events"
+)]
+trait Events {
+    /// SetHotkeyHandling method
+    fn set_hotkey_handling(&self, key: Hotkey, handling: HotkeyHandling) -> zbus::Result<()>;
+
+    /// NotifyHotkey signal
+    #[zbus(signal)]
+    fn notify_hotkey(&self, key: Hotkey, action: KeyAction, handled: bool) -> zbus::Result<()>;
+
+    /// HotkeyHandling property
+    #[zbus(property)]
+    fn hotkey_handling(&self) -> zbus::Result<BTreeMap<Hotkey, HotkeyHandling>>;
+
+    /// SupportedHotkeys property
+    #[zbus(property)]
+    fn supported_hotkeys(&self) -> zbus::Result<Vec<Hotkey>>;
+}
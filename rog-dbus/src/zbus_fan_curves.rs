@@ -59,4 +59,8 @@ trait FanCurves {
         fan: FanCurvePU,
         enabled: bool,
     ) -> zbus::Result<()>;
+
+    /// NotifyConfigReloaded signal
+    #[zbus(signal)]
+    fn notify_config_reloaded(&self) -> zbus::Result<()>;
 }
@@ -0,0 +1,31 @@
+//! # `DBus` interface proxy for: `org.asuslinux.Gpu`
+//!
+//! Written by hand to match `asusd::ctrl_gpu`.
+
+use rog_platform::platform::GpuMuxMode;
+use zbus::proxy;
+
+#[proxy(
+    interface = "org.asuslinux.Gpu",
+    default_service = "org.asuslinux.Daemon",
+    default_path = "/org/asuslinux"
+)]
+trait Gpu {
+    /// SetMuxMode method
+    fn set_mux_mode(&self, mode: GpuMuxMode) -> zbus::Result<()>;
+
+    /// SetDgpuDisable method
+    fn set_dgpu_disable(&self, disable: bool) -> zbus::Result<()>;
+
+    /// RebootRequired signal
+    #[zbus(signal)]
+    fn reboot_required(&self, reason: &str) -> zbus::Result<()>;
+
+    /// MuxMode property
+    #[zbus(property)]
+    fn mux_mode(&self) -> zbus::Result<GpuMuxMode>;
+
+    /// DgpuDisable property
+    #[zbus(property)]
+    fn dgpu_disable(&self) -> zbus::Result<bool>;
+}
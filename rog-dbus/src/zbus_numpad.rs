@@ -0,0 +1,25 @@
+//! # `DBus` interface proxy for: `org.asuslinux.Numpad`
+//!
+//! Written by hand to match `asusd::ctrl_numpad`.
+
+use rog_aura::LedBrightness;
+use zbus::proxy;
+
+#[proxy(
+    interface = "org.asuslinux.Numpad",
+    default_service = "org.asuslinux.Daemon",
+    default_path = "/org/asuslinux"
+)]
+trait Numpad {
+    /// Enabled property
+    #[zbus(property)]
+    fn enabled(&self) -> zbus::Result<bool>;
+    #[zbus(property)]
+    fn set_enabled(&self, value: bool) -> zbus::Result<()>;
+
+    /// Brightness property
+    #[zbus(property)]
+    fn brightness(&self) -> zbus::Result<LedBrightness>;
+    #[zbus(property)]
+    fn set_brightness(&self, value: LedBrightness) -> zbus::Result<()>;
+}
@@ -20,6 +20,9 @@
 //!
 //! …consequently `zbus-xmlgen` did not generate code for the above interfaces.
 
+use asusd::charge_schedule::ChargeRule;
+use asusd::first_run::FirstRunState;
+use asusd::settings_schema::SettingSchema;
 use rog_platform::cpu::CPUEPP;
 use rog_platform::platform::{GpuMode, Properties, ThrottlePolicy};
 use zbus::proxy;
@@ -33,21 +36,49 @@ trait Platform {
     #[zbus(property)]
     fn version(&self) -> zbus::Result<String>;
 
+    /// ApplySuggestedDefaults method
+    fn apply_suggested_defaults(&self) -> zbus::Result<()>;
+
+    /// CompleteFirstRun method
+    fn complete_first_run(&self) -> zbus::Result<()>;
+
+    /// EffectMetrics method
+    fn effect_metrics(&self) -> zbus::Result<String>;
+
     /// NextThrottleThermalPolicy method
     fn next_throttle_thermal_policy(&self) -> zbus::Result<()>;
 
+    /// SettingsSchema method
+    fn settings_schema(&self) -> zbus::Result<Vec<SettingSchema>>;
+
     /// SupportedInterfaces method
     fn supported_interfaces(&self) -> zbus::Result<Vec<String>>;
 
     /// SupportedProperties method
     fn supported_properties(&self) -> zbus::Result<Vec<Properties>>;
 
+    /// FirstRunState property
+    #[zbus(property)]
+    fn first_run_state(&self) -> zbus::Result<FirstRunState>;
+
     /// ChargeControlEndThreshold property
     #[zbus(property)]
     fn charge_control_end_threshold(&self) -> zbus::Result<u8>;
     #[zbus(property)]
     fn set_charge_control_end_threshold(&self, value: u8) -> zbus::Result<()>;
 
+    /// ChargeSchedule method
+    fn charge_schedule(&self) -> zbus::Result<Vec<ChargeRule>>;
+
+    /// SetChargeSchedule method
+    fn set_charge_schedule(&self, rules: &[ChargeRule]) -> zbus::Result<()>;
+
+    /// ChargeFullOnce property
+    #[zbus(property)]
+    fn charge_full_once(&self) -> zbus::Result<bool>;
+    #[zbus(property)]
+    fn set_charge_full_once(&self, value: bool) -> zbus::Result<()>;
+
     /// DgpuDisable property
     #[zbus(property)]
     fn dgpu_disable(&self) -> zbus::Result<bool>;
@@ -80,6 +111,10 @@ trait Platform {
     #[zbus(property)]
     fn set_nv_temp_target(&self, value: u8) -> zbus::Result<()>;
 
+    /// TabletMode property
+    #[zbus(property)]
+    fn tablet_mode(&self) -> zbus::Result<bool>;
+
     /// PanelOd property
     #[zbus(property)]
     fn panel_od(&self) -> zbus::Result<bool>;
@@ -163,4 +198,8 @@ trait Platform {
     fn throttle_thermal_policy(&self) -> zbus::Result<ThrottlePolicy>;
     #[zbus(property)]
     fn set_throttle_thermal_policy(&self, throttle_policy: ThrottlePolicy) -> zbus::Result<()>;
+
+    /// NotifyConfigReloaded signal
+    #[zbus(signal)]
+    fn notify_config_reloaded(&self) -> zbus::Result<()>;
 }
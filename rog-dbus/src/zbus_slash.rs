@@ -30,4 +30,20 @@ trait Slash {
     fn slash_mode(&self) -> zbus::Result<SlashMode>;
     #[zbus(property)]
     fn set_slash_mode(&self, value: SlashMode) -> zbus::Result<()>;
+
+    /// OffWhenSuspended property
+    #[zbus(property)]
+    fn off_when_suspended(&self) -> zbus::Result<bool>;
+    #[zbus(property)]
+    fn set_off_when_suspended(&self, value: bool) -> zbus::Result<()>;
+
+    /// OffAtBoot property
+    #[zbus(property)]
+    fn off_at_boot(&self) -> zbus::Result<bool>;
+    #[zbus(property)]
+    fn set_off_at_boot(&self, value: bool) -> zbus::Result<()>;
+
+    /// NotifyConfigReloaded signal
+    #[zbus(signal)]
+    fn notify_config_reloaded(&self) -> zbus::Result<()>;
 }
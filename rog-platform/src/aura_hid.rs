@@ -0,0 +1,140 @@
+//! The writes asusd makes to an Aura device. `MockHid` records them instead,
+//! so that tests and `asusd --aura-dry-run` can check the exact packets without the
+//! hardware.
+
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::{Arc, Mutex};
+
+use log::info;
+
+use crate::error::{PlatformError, Result};
+use crate::hid_raw::HidRaw;
+use crate::keyboard_led::KeyboardLed;
+
+pub trait AuraHidWrite: std::fmt::Debug + Send {
+    /// Write one packet. This is a HID report on USB keyboards, and the values
+    /// for `kbd_rgb_mode` on TUF keyboards.
+    fn write_bytes(&self, message: &[u8]) -> Result<()>;
+
+    /// Read the feature report with the ID in `report[0]` in to `report`.
+    /// `NotSupported` for devices that can't be read.
+    fn read_feature(&self, _report: &mut [u8]) -> Result<()> {
+        Err(PlatformError::NotSupported)
+    }
+}
+
+impl AuraHidWrite for HidRaw {
+    fn write_bytes(&self, message: &[u8]) -> Result<()> {
+        HidRaw::write_bytes(self, message)
+    }
+
+    fn read_feature(&self, report: &mut [u8]) -> Result<()> {
+        self.get_feature_report(report).map(|_| ())
+    }
+}
+
+impl AuraHidWrite for KeyboardLed {
+    fn write_bytes(&self, message: &[u8]) -> Result<()> {
+        self.set_kbd_rgb_mode(message)
+    }
+}
+
+/// Stands in for any Aura device. Clones share the record, so a test can keep
+/// one while the controller owns another.
+#[derive(Debug, Default, Clone)]
+pub struct MockHid {
+    prod_id: String,
+    writes: Arc<Mutex<Vec<Vec<u8>>>>,
+    brightness: Arc<AtomicU8>,
+    feature: Arc<Mutex<Vec<u8>>>,
+}
+
+impl MockHid {
+    /// A device with the product ID `prod_id`, such as `19b6`
+    pub fn new(prod_id: &str) -> Self {
+        Self {
+            prod_id: prod_id.to_owned(),
+            ..Default::default()
+        }
+    }
+
+    pub fn prod_id(&self) -> &str {
+        &self.prod_id
+    }
+
+    pub fn get_brightness(&self) -> Result<u8> {
+        Ok(self.brightness.load(Ordering::Relaxed))
+    }
+
+    pub fn set_brightness(&self, value: u8) -> Result<()> {
+        info!("MockHid {}: brightness {value}", self.prod_id);
+        self.brightness.store(value, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Give `report` when its feature report is read, in place of
+    /// `NotSupported`
+    pub fn set_feature_report(&self, report: &[u8]) {
+        if let Ok(mut feature) = self.feature.lock() {
+            *feature = report.to_vec();
+        }
+    }
+
+    /// Every packet written so far, oldest first
+    pub fn writes(&self) -> Vec<Vec<u8>> {
+        self.writes.lock().map(|w| w.clone()).unwrap_or_default()
+    }
+
+    /// Return the packets written so far and clear the record
+    pub fn take_writes(&self) -> Vec<Vec<u8>> {
+        self.writes
+            .lock()
+            .map(|mut w| std::mem::take(&mut *w))
+            .unwrap_or_default()
+    }
+}
+
+impl AuraHidWrite for MockHid {
+    fn write_bytes(&self, message: &[u8]) -> Result<()> {
+        info!("MockHid {}: {message:02x?}", self.prod_id);
+        if let Ok(mut writes) = self.writes.lock() {
+            writes.push(message.to_vec());
+        }
+        Ok(())
+    }
+
+    fn read_feature(&self, report: &mut [u8]) -> Result<()> {
+        let feature = self.feature.lock().map(|f| f.clone()).unwrap_or_default();
+        if feature.is_empty() || feature[0] != report[0] {
+            return Err(PlatformError::NotSupported);
+        }
+        let len = feature.len().min(report.len());
+        report[..len].copy_from_slice(&feature[..len]);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AuraHidWrite, MockHid};
+
+    #[test]
+    fn clones_share_the_record() {
+        let mock = MockHid::new("19b6");
+        let writer = mock.clone();
+        writer.write_bytes(&[0x5d, 0xb5]).unwrap();
+        writer.write_bytes(&[0x5d, 0xb4]).unwrap();
+        assert_eq!(mock.writes(), vec![vec![0x5d, 0xb5], vec![0x5d, 0xb4]]);
+        assert_eq!(mock.take_writes().len(), 2);
+        assert!(mock.writes().is_empty());
+
+        writer.set_brightness(2).unwrap();
+        assert_eq!(mock.get_brightness().unwrap(), 2);
+
+        let mut report = [0x5d, 0, 0];
+        assert!(writer.read_feature(&mut report).is_err());
+        mock.set_feature_report(&[0x5d, 0xbd]);
+        writer.read_feature(&mut report).unwrap();
+        assert_eq!(report, [0x5d, 0xbd, 0]);
+    }
+}
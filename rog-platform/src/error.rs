@@ -70,9 +70,22 @@ impl From<std::io::Error> for PlatformError {
 impl From<PlatformError> for FdoErr {
     fn from(error: PlatformError) -> Self {
         log::error!("PlatformError: got: {error}");
+        let msg = format!("Failed with {error}");
         match error {
             PlatformError::NotSupported => FdoErr::NotSupported("".to_owned()),
-            _ => FdoErr::Failed(format!("Failed with {error}")),
+            PlatformError::AttrNotFound(_) | PlatformError::MissingFunction(_) => {
+                FdoErr::NotSupported(msg)
+            }
+            PlatformError::NoAuraKeyboard
+            | PlatformError::NoAuraNode
+            | PlatformError::MissingLedBrightNode(..) => FdoErr::FileNotFound(msg),
+            PlatformError::Path(..)
+            | PlatformError::Read(..)
+            | PlatformError::Write(..)
+            | PlatformError::IoPath(..)
+            | PlatformError::Io(_)
+            | PlatformError::USB(_) => FdoErr::IOError(msg),
+            _ => FdoErr::Failed(msg),
         }
     }
 }
@@ -1,6 +1,7 @@
 use std::cell::RefCell;
 use std::fs::{File, OpenOptions};
 use std::io::Write;
+use std::os::fd::AsRawFd;
 use std::path::PathBuf;
 
 use log::{info, warn};
@@ -8,6 +9,12 @@ use udev::Device;
 
 use crate::error::{PlatformError, Result};
 
+/// `HIDIOCGFEATURE(len)` from `linux/hidraw.h`
+const fn hidiocgfeature(len: usize) -> u64 {
+    const IOC_READ_WRITE: u64 = 3;
+    (IOC_READ_WRITE << 30) | ((len as u64) << 16) | ((b'H' as u64) << 8) | 0x07
+}
+
 /// A USB device that utilizes hidraw for I/O
 #[derive(Debug)]
 pub struct HidRaw {
@@ -119,6 +126,28 @@ impl HidRaw {
         Ok(())
     }
 
+    /// Read the feature report with the ID in `report[0]` in to `report`,
+    /// returning the length the device gave
+    pub fn get_feature_report(&self, report: &mut [u8]) -> Result<usize> {
+        let file = self.file.borrow();
+        // SAFETY: the length in the request is that of `report`, which lives
+        // past the call
+        let res = unsafe {
+            libc::ioctl(
+                file.as_raw_fd(),
+                hidiocgfeature(report.len()) as _,
+                report.as_mut_ptr(),
+            )
+        };
+        if res < 0 {
+            return Err(PlatformError::IoPath(
+                self.devfs_path.to_string_lossy().to_string(),
+                std::io::Error::last_os_error(),
+            ));
+        }
+        Ok(res as usize)
+    }
+
     /// This method was added for certain devices like AniMe to prevent them
     /// waking the laptop
     pub fn set_wakeup_disabled(&self) -> Result<()> {
@@ -126,3 +155,14 @@ impl HidRaw {
         Ok(dev.set_attribute_value("power/wakeup", "disabled")?)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::hidiocgfeature;
+
+    #[test]
+    fn feature_ioctl_matches_kernel_header() {
+        // HIDIOCGFEATURE(17) as computed by the C macros
+        assert_eq!(hidiocgfeature(17), 0xc011_4807);
+    }
+}
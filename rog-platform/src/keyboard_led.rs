@@ -3,7 +3,87 @@ use std::path::PathBuf;
 use log::{info, warn};
 
 use crate::error::{PlatformError, Result};
-use crate::{attr_u8, has_attr, set_attr_u8_array, to_device};
+use crate::{attr_u8, has_attr, set_attr_u8_array, to_device, watch_attr};
+
+/// The first value of `kbd_rgb_mode` and `kbd_rgb_state`
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub enum KbdRgbCmd {
+    /// Shown until the next write or reboot
+    #[default]
+    Show = 0,
+    /// Kept by the firmware over a reboot
+    Save = 1,
+}
+
+/// The firmware modes of `kbd_rgb_mode`. The driver shows 9 and anything from
+/// 12 as `Pulse`.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub enum KbdRgbModeNum {
+    #[default]
+    Static = 0,
+    Breathe = 1,
+    Strobe = 2,
+    Rainbow = 3,
+    Star = 4,
+    Rain = 5,
+    Highlight = 6,
+    Laser = 7,
+    Ripple = 8,
+    Pulse = 10,
+    Comet = 11,
+}
+
+/// The values of `kbd_rgb_mode`, written in the order of
+/// `kbd_rgb_mode_index`: `cmd mode red green blue speed`
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct KbdRgbMode {
+    pub cmd: KbdRgbCmd,
+    pub mode: KbdRgbModeNum,
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+    /// 0 to 2 from slow to fast, the driver takes any other as 1. Rainbow and
+    /// the other animated modes run at this speed.
+    pub speed: u8,
+}
+
+impl KbdRgbMode {
+    pub fn to_values(&self) -> [u8; 6] {
+        [
+            self.cmd as u8,
+            self.mode as u8,
+            self.red,
+            self.green,
+            self.blue,
+            self.speed,
+        ]
+    }
+}
+
+/// The values of `kbd_rgb_state`, written in the order of
+/// `kbd_rgb_state_index`: `cmd boot awake sleep keyboard`
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct KbdRgbState {
+    pub cmd: KbdRgbCmd,
+    /// The animation shown while booting
+    pub boot: bool,
+    pub awake: bool,
+    pub sleep: bool,
+    /// The keyboard lighting as a whole
+    pub keyboard: bool,
+}
+
+impl KbdRgbState {
+    pub fn to_values(&self) -> [u8; 5] {
+        [
+            self.cmd as u8,
+            self.boot as u8,
+            self.awake as u8,
+            self.sleep as u8,
+            self.keyboard as u8,
+        ]
+    }
+}
 
 #[derive(Debug, Default, PartialEq, Eq, PartialOrd, Clone)]
 pub struct KeyboardLed {
@@ -21,6 +101,12 @@ impl KeyboardLed {
         path
     );
 
+    watch_attr!(
+        /// Notifies on any write to kbd_rgb_mode, including our own
+        "kbd_rgb_mode"
+        path
+    );
+
     has_attr!("kbd_rgb_state" path);
 
     set_attr_u8_array!(
@@ -29,6 +115,14 @@ impl KeyboardLed {
         path
     );
 
+    pub fn write_kbd_rgb_mode(&self, mode: &KbdRgbMode) -> Result<()> {
+        self.set_kbd_rgb_mode(&mode.to_values())
+    }
+
+    pub fn write_kbd_rgb_state(&self, state: &KbdRgbState) -> Result<()> {
+        self.set_kbd_rgb_state(&state.to_values())
+    }
+
     pub fn new() -> Result<Self> {
         let mut enumerator = udev::Enumerator::new().map_err(|err| {
             warn!("{}", err);
@@ -63,3 +157,27 @@ impl KeyboardLed {
         ))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{KbdRgbCmd, KbdRgbMode, KbdRgbModeNum, KbdRgbState};
+
+    #[test]
+    fn value_order() {
+        let mode = KbdRgbMode {
+            cmd: KbdRgbCmd::Save,
+            mode: KbdRgbModeNum::Rainbow,
+            red: 10,
+            green: 20,
+            blue: 30,
+            speed: 2,
+        };
+        assert_eq!(mode.to_values(), [1, 3, 10, 20, 30, 2]);
+        let state = KbdRgbState {
+            boot: true,
+            sleep: true,
+            ..Default::default()
+        };
+        assert_eq!(state.to_values(), [0, 1, 0, 1, 0]);
+    }
+}
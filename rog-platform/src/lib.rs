@@ -1,13 +1,16 @@
 //! This crate functions as a wrapper of all the relevant ASUS functionality
 //! on ROG, Strix, and TUF laptops.
 
+pub mod aura_hid;
 pub mod cpu;
 pub mod error;
 pub mod hid_raw;
 pub mod keyboard_led;
 pub(crate) mod macros;
+pub mod multicolor_led;
 pub mod platform;
 pub mod power;
+pub mod touchpad_numpad;
 pub mod usb_raw;
 
 use std::path::Path;
@@ -0,0 +1,219 @@
+//! Keyboards whose RGB is only given through the multicolor class of
+//! `/sys/class/leds`, as some models and newer kernels do in place of the
+//! ROG HID path or `kbd_rgb_mode`. The colour is `multi_intensity`, in the
+//! channel order of `multi_index`, and the level is `brightness` up to
+//! `max_brightness`. Breathing and flashing are left to the kernel `pattern`
+//! and `timer` triggers where it has them.
+
+use std::path::PathBuf;
+
+use log::{info, warn};
+
+use crate::error::{PlatformError, Result};
+use crate::{has_attr, to_device, watch_attr};
+
+/// The brightness levels asusd steps through, as `LedBrightness`
+const LEVELS: u32 = 3;
+
+/// What the kernel drives the LED with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LedTrigger {
+    /// Held at the brightness set
+    None,
+    /// Ramps from off up to the brightness and back over `period_ms`, with
+    /// the `pattern` trigger
+    Breathe { period_ms: u32 },
+    /// On and off, with the `timer` trigger
+    Blink { on_ms: u32, off_ms: u32 },
+}
+
+impl LedTrigger {
+    /// The name written to `trigger`
+    pub const fn name(&self) -> &'static str {
+        match self {
+            LedTrigger::None => "none",
+            LedTrigger::Breathe { .. } => "pattern",
+            LedTrigger::Blink { .. } => "timer",
+        }
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Eq, PartialOrd, Clone)]
+pub struct MulticolorLed {
+    path: PathBuf,
+}
+
+impl MulticolorLed {
+    has_attr!("multi_intensity" path);
+
+    watch_attr!(
+        /// Notifies on any write to brightness, including our own
+        "brightness"
+        path
+    );
+
+    /// The first keyboard backlight in the `leds` class with a
+    /// `multi_intensity`
+    pub fn new() -> Result<Self> {
+        let mut enumerator = udev::Enumerator::new().map_err(|err| {
+            warn!("{}", err);
+            PlatformError::Udev("enumerator failed".into(), err)
+        })?;
+
+        enumerator.match_subsystem("leds").map_err(|err| {
+            warn!("{}", err);
+            PlatformError::Udev("match_subsystem failed".into(), err)
+        })?;
+
+        enumerator.match_sysname("*kbd_backlight*").map_err(|err| {
+            warn!("{}", err);
+            PlatformError::Udev("match_sysname failed".into(), err)
+        })?;
+
+        for device in enumerator.scan_devices().map_err(|err| {
+            warn!("{}", err);
+            PlatformError::Udev("scan_devices failed".into(), err)
+        })? {
+            if crate::has_attr(&device, "multi_intensity") {
+                info!("Found multicolor keyboard LED at {:?}", device.sysname());
+                return Ok(Self {
+                    path: device.syspath().to_owned(),
+                });
+            }
+        }
+        Err(PlatformError::MissingFunction(
+            "No multicolor kbd_backlight found".into(),
+        ))
+    }
+
+    fn read_u32(&self, attr: &str) -> Result<u32> {
+        crate::read_attr_string(&to_device(&self.path)?, attr)?
+            .trim()
+            .parse()
+            .map_err(|_| PlatformError::ParseNum)
+    }
+
+    pub fn get_max_brightness(&self) -> Result<u32> {
+        self.read_u32("max_brightness")
+    }
+
+    pub fn get_raw_brightness(&self) -> Result<u32> {
+        self.read_u32("brightness")
+    }
+
+    pub fn set_raw_brightness(&self, value: u32) -> Result<()> {
+        crate::write_attr_string(
+            &mut to_device(&self.path)?,
+            "brightness",
+            &value.to_string(),
+        )
+    }
+
+    /// The brightness as one of the levels asusd uses, 0 to 3
+    pub fn get_brightness(&self) -> Result<u8> {
+        let max = self.get_max_brightness()?.max(1);
+        let raw = self.get_raw_brightness()?;
+        Ok(((raw * LEVELS + max / 2) / max) as u8)
+    }
+
+    /// Set the brightness from one of the levels asusd uses, 0 to 3
+    pub fn set_brightness(&self, level: u8) -> Result<()> {
+        let max = self.get_max_brightness()?;
+        self.set_raw_brightness(level.min(LEVELS as u8) as u32 * max / LEVELS)
+    }
+
+    /// The triggers the kernel has for the LED
+    pub fn get_triggers(&self) -> Result<Vec<String>> {
+        let triggers = crate::read_attr_string(&to_device(&self.path)?, "trigger")?;
+        Ok(parse_triggers(&triggers))
+    }
+
+    pub fn has_trigger(&self, name: &str) -> bool {
+        self.get_triggers()
+            .is_ok_and(|t| t.iter().any(|trigger| trigger == name))
+    }
+
+    /// Set the colour, scaled to `max_brightness` in the channel order of
+    /// `multi_index`
+    pub fn set_colour(&self, red: u8, green: u8, blue: u8) -> Result<()> {
+        let mut device = to_device(&self.path)?;
+        let index = crate::read_attr_string(&device, "multi_index")?;
+        let max = self.get_max_brightness()?;
+        let values = intensities(&index, [red, green, blue], max);
+        crate::write_attr_string(&mut device, "multi_intensity", &values)
+    }
+
+    /// Hand the LED to `trigger`. Setting a trigger, or taking one away,
+    /// turns the LED off so the brightness is kept and written again. The
+    /// peak of a breathe is the brightness at the time.
+    pub fn set_trigger(&self, trigger: LedTrigger) -> Result<()> {
+        let brightness = self.get_raw_brightness()?;
+        let mut device = to_device(&self.path)?;
+        crate::write_attr_string(&mut device, "trigger", trigger.name())?;
+        match trigger {
+            LedTrigger::None => self.set_raw_brightness(brightness),
+            LedTrigger::Breathe { period_ms } => {
+                let half = period_ms / 2;
+                let pattern = format!("0 {half} {brightness} {half}");
+                // The attributes of a trigger only appear once it is set
+                let mut device = to_device(&self.path)?;
+                crate::write_attr_string(&mut device, "pattern", &pattern)
+            }
+            LedTrigger::Blink { on_ms, off_ms } => {
+                let mut device = to_device(&self.path)?;
+                crate::write_attr_string(&mut device, "delay_on", &on_ms.to_string())?;
+                crate::write_attr_string(&mut device, "delay_off", &off_ms.to_string())?;
+                self.set_raw_brightness(brightness)
+            }
+        }
+    }
+}
+
+/// The names in a `trigger` attribute, where the one set is in brackets
+fn parse_triggers(triggers: &str) -> Vec<String> {
+    triggers
+        .split_whitespace()
+        .map(|t| t.trim_start_matches('[').trim_end_matches(']').to_owned())
+        .collect()
+}
+
+/// The values of `multi_intensity` for the colour. Channels other than red,
+/// green and blue are left off.
+fn intensities(index: &str, rgb: [u8; 3], max: u32) -> String {
+    index
+        .split_whitespace()
+        .map(|channel| {
+            let value = match channel {
+                "red" => rgb[0],
+                "green" => rgb[1],
+                "blue" => rgb[2],
+                _ => 0,
+            };
+            (value as u32 * max / 255).to_string()
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{intensities, parse_triggers};
+
+    #[test]
+    fn triggers_listed() {
+        assert_eq!(
+            parse_triggers("none [timer] pattern kbd-capslock\n"),
+            vec!["none", "timer", "pattern", "kbd-capslock"]
+        );
+    }
+
+    #[test]
+    fn colour_in_index_order() {
+        assert_eq!(
+            intensities("red green blue", [255, 128, 0], 255),
+            "255 128 0"
+        );
+        assert_eq!(intensities("blue green red", [255, 0, 51], 100), "20 0 100");
+        assert_eq!(intensities("red white", [255, 255, 255], 3), "3 0");
+    }
+}
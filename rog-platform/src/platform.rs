@@ -245,6 +245,54 @@ impl Display for GpuMode {
     }
 }
 
+#[typeshare]
+#[repr(u32)]
+#[derive(
+    Deserialize, Serialize, Default, Type, Value, OwnedValue, Debug, PartialEq, Eq, Clone, Copy,
+)]
+#[zvariant(signature = "u")]
+/// `gpu_mux_mode` in asus_wmi. The values are those the attribute takes.
+pub enum GpuMuxMode {
+    /// The dGPU drives the internal panel directly
+    Dedicated = 0,
+    /// The iGPU drives the internal panel, the dGPU is used for offload
+    #[default]
+    Integrated = 1,
+}
+
+impl From<u8> for GpuMuxMode {
+    fn from(num: u8) -> Self {
+        if num == 0 {
+            return Self::Dedicated;
+        }
+        Self::Integrated
+    }
+}
+
+impl From<GpuMuxMode> for u8 {
+    fn from(mode: GpuMuxMode) -> Self {
+        mode as u8
+    }
+}
+
+impl std::str::FromStr for GpuMuxMode {
+    type Err = PlatformError;
+
+    fn from_str(mode: &str) -> Result<Self> {
+        match mode.to_ascii_lowercase().trim() {
+            "dedicated" => Ok(GpuMuxMode::Dedicated),
+            "integrated" | "hybrid" => Ok(GpuMuxMode::Integrated),
+            _ => Err(PlatformError::NotSupported),
+        }
+    }
+}
+
+impl Display for GpuMuxMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
 #[typeshare]
 #[repr(u32)]
 #[derive(
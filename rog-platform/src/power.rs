@@ -3,7 +3,7 @@ use std::path::PathBuf;
 use log::{info, warn};
 
 use crate::error::{PlatformError, Result};
-use crate::{attr_u8, to_device};
+use crate::{attr_string, attr_u8, to_device};
 
 /// The "platform" device provides access to things like:
 /// - `dgpu_disable`
@@ -22,6 +22,8 @@ pub struct AsusPower {
 impl AsusPower {
     attr_u8!("charge_control_end_threshold", battery);
 
+    attr_string!("status", battery);
+
     attr_u8!("online", mains);
 
     /// When checking for battery this will look in order:
@@ -0,0 +1,106 @@
+//! The numpad printed on the touchpad of many Vivobook and Zenbook models,
+//! which lights up while it is in use. The touchpad is an I2C HID device and
+//! the light is set with a vendor feature report on its hidraw node. Turning
+//! touches in to key presses is left to the desktop or a userspace driver.
+
+use std::fs::{File, OpenOptions};
+use std::os::fd::AsRawFd;
+use std::path::PathBuf;
+
+use log::info;
+
+use crate::error::{PlatformError, Result};
+
+/// The ACPI vendor id of ASUS's own touchpads, which are the ones with a numpad
+const NUMPAD_TOUCHPAD_PREFIX: &str = "ASUE";
+/// The bus type of I2C devices as it starts `HID_ID`
+const BUS_I2C: &str = "0018:";
+const NUMPAD_REPORT_ID: u8 = 0x0d;
+/// Report values for off, then the levels from dim to bright
+const LEVELS: [u8; 4] = [0x00, 0x1f, 0x18, 0x01];
+
+/// `HIDIOCSFEATURE(len)` from `linux/hidraw.h`
+const fn hidiocsfeature(len: usize) -> u64 {
+    const IOC_READ_WRITE: u64 = 3;
+    (IOC_READ_WRITE << 30) | ((len as u64) << 16) | ((b'H' as u64) << 8) | 0x06
+}
+
+#[derive(Debug)]
+pub struct TouchpadNumpad {
+    /// The path to the `/dev/<name>` of the touchpad
+    devfs_path: PathBuf,
+    file: File,
+}
+
+impl TouchpadNumpad {
+    /// Find the hidraw node of an ASUS touchpad with a numpad
+    pub fn new() -> Result<Self> {
+        let mut enumerator = udev::Enumerator::new()
+            .map_err(|e| PlatformError::Udev("enumerator failed".into(), e))?;
+        enumerator
+            .match_subsystem("hidraw")
+            .map_err(|e| PlatformError::Udev("match_subsystem failed".into(), e))?;
+
+        for endpoint in enumerator
+            .scan_devices()
+            .map_err(|e| PlatformError::IoPath("enumerator".to_owned(), e))?
+        {
+            let Ok(Some(hid)) = endpoint.parent_with_subsystem("hid") else {
+                continue;
+            };
+            let property = |name: &str| {
+                hid.property_value(name)
+                    .map(|v| v.to_string_lossy().into_owned())
+                    .unwrap_or_default()
+            };
+            let name = property("HID_NAME");
+            if !property("HID_ID").starts_with(BUS_I2C) || !name.starts_with(NUMPAD_TOUCHPAD_PREFIX)
+            {
+                continue;
+            }
+            if let Some(dev_node) = endpoint.devnode() {
+                info!("Using touchpad {name} at {dev_node:?} for the numpad light");
+                return Ok(Self {
+                    file: OpenOptions::new().read(true).write(true).open(dev_node)?,
+                    devfs_path: dev_node.to_owned(),
+                });
+            }
+        }
+        Err(PlatformError::MissingFunction(
+            "No ASUS touchpad with a numpad".to_owned(),
+        ))
+    }
+
+    /// Light the numpad at `level`, 0 is off and 1 to 3 go from dim to bright
+    pub fn set_level(&self, level: u8) -> Result<()> {
+        let value = LEVELS[usize::from(level.min(3))];
+        let mut report = [NUMPAD_REPORT_ID, 0x14, 0x03, value, 0xad];
+        // SAFETY: the length in the request is that of `report`, which lives
+        // past the call
+        let res = unsafe {
+            libc::ioctl(
+                self.file.as_raw_fd(),
+                hidiocsfeature(report.len()) as _,
+                report.as_mut_ptr(),
+            )
+        };
+        if res < 0 {
+            return Err(PlatformError::IoPath(
+                self.devfs_path.to_string_lossy().to_string(),
+                std::io::Error::last_os_error(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::hidiocsfeature;
+
+    #[test]
+    fn feature_ioctl_matches_kernel_header() {
+        // HIDIOCSFEATURE(5) as computed by the C macros
+        assert_eq!(hidiocsfeature(5), 0xc005_4806);
+    }
+}
@@ -16,6 +16,8 @@ pub enum ProfileError {
     /// (pwm/temp, prev, next)
     ParseFanCurvePrevHigher(&'static str, u8, u8),
     ParseFanCurvePercentOver100(u8),
+    /// A temperature point above `fan_curve_set::CURVE_TEMP_MAX`
+    FanCurveTempOverMax(u8),
     NotEnoughPoints,
     // Zbus(zbus::Error),
 }
@@ -42,7 +44,13 @@ impl fmt::Display for ProfileError {
             ),
             ProfileError::ParseFanCurvePercentOver100(value) => {
                 write!(f, "Invalid percentage, {} is higher than 100", value)
-            } // Error::Zbus(detail) => write!(f, "Zbus error: {}", detail),
+            }
+            ProfileError::FanCurveTempOverMax(value) => write!(
+                f,
+                "Invalid temperature, {}c is higher than {}c",
+                value,
+                crate::fan_curve_set::CURVE_TEMP_MAX
+            ), // Error::Zbus(detail) => write!(f, "Zbus error: {}", detail),
         }
     }
 }
@@ -8,6 +8,10 @@ use zbus::zvariant::Type;
 use crate::error::ProfileError;
 use crate::FanCurvePU;
 
+/// Highest temperature point accepted for a curve. The EC will take larger
+/// values but a point this high will never be reached on a working machine.
+pub const CURVE_TEMP_MAX: u8 = 120;
+
 fn set_sysfs_name(string: &mut [u8], fan: char, index: usize) {
     string[3] = fan as u8;
     string[15] = char::from_digit(index as u32 + 1, 10).unwrap() as u8;
@@ -33,7 +37,7 @@ pub(crate) fn temp_str(fan: char, index: usize) -> String {
 
 #[typeshare]
 #[cfg_attr(feature = "dbus", derive(Type))]
-#[derive(Deserialize, Serialize, Default, Debug, Clone)]
+#[derive(Deserialize, Serialize, Default, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub struct CurveData {
     pub fan: FanCurvePU,
     pub pwm: [u8; 8],
@@ -142,6 +146,55 @@ impl CurveData {
         self.fan = fan;
     }
 
+    /// Check that both temperature and fan power only ever increase from one
+    /// point to the next, and that no temperature is above `CURVE_TEMP_MAX`.
+    /// The kernel rejects a non-monotonic curve, so this lets a client catch
+    /// it before anything is sent.
+    pub fn validate(&self) -> Result<(), ProfileError> {
+        for pair in self.temp.windows(2) {
+            if pair[0] > pair[1] {
+                return Err(ProfileError::ParseFanCurvePrevHigher(
+                    "temperature",
+                    pair[0],
+                    pair[1],
+                ));
+            }
+        }
+        for pair in self.pwm.windows(2) {
+            if pair[0] > pair[1] {
+                return Err(ProfileError::ParseFanCurvePrevHigher(
+                    "fan power",
+                    pair[0],
+                    pair[1],
+                ));
+            }
+        }
+        if let Some(temp) = self.temp.iter().find(|t| **t > CURVE_TEMP_MAX) {
+            return Err(ProfileError::FanCurveTempOverMax(*temp));
+        }
+        Ok(())
+    }
+
+    /// Interpolated fan power at `temp`, following the curve linearly between
+    /// points and holding the end values outside of them
+    pub fn pwm_at(&self, temp: u8) -> u8 {
+        if temp <= self.temp[0] {
+            return self.pwm[0];
+        }
+        for i in 1..self.temp.len() {
+            if temp <= self.temp[i] {
+                let (t0, t1) = (self.temp[i - 1] as u32, self.temp[i] as u32);
+                let (p0, p1) = (self.pwm[i - 1] as u32, self.pwm[i] as u32);
+                if t1 == t0 {
+                    return p1 as u8;
+                }
+                let p = p0 + (p1.saturating_sub(p0)) * (temp as u32 - t0) / (t1 - t0);
+                return p as u8;
+            }
+        }
+        self.pwm[7]
+    }
+
     fn set_val_from_attr(tmp: &str, device: &Device, buf: &mut [u8; 8]) {
         if let Some(n) = tmp.chars().nth(15) {
             let i = n.to_digit(10).unwrap() as usize;
@@ -237,6 +290,29 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn curve_data_validate() {
+        let mut curve =
+            CurveData::from_str("30:1,49:2,59:3,69:4,79:31,89:49,99:56,109:58").unwrap();
+        assert!(curve.validate().is_ok());
+        assert_eq!(curve.pwm_at(0), 1);
+        assert_eq!(curve.pwm_at(84), 40);
+        assert_eq!(curve.pwm_at(255), 58);
+
+        curve.pwm[3] = 50;
+        assert!(matches!(
+            curve.validate(),
+            Err(ProfileError::ParseFanCurvePrevHigher("fan power", 50, 31))
+        ));
+
+        curve.pwm[3] = 4;
+        curve.temp[7] = CURVE_TEMP_MAX + 1;
+        assert!(matches!(
+            curve.validate(),
+            Err(ProfileError::FanCurveTempOverMax(_))
+        ));
+    }
+
     #[test]
     fn check_pwm_str() {
         assert_eq!(pwm_str('1', 0), "pwm1_auto_point1_pwm");
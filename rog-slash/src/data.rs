@@ -12,15 +12,29 @@ use crate::error::SlashError;
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize, Serialize)]
 pub enum SlashType {
     GA403,
+    GA605,
+    GU605,
     Unknown,
 }
 
+impl SlashType {
+    /// The Slash fitted to the board `board_name`, such as `GA403UV`
+    pub fn from_board_name(board_name: &str) -> Self {
+        [Self::GA403, Self::GA605, Self::GU605]
+            .into_iter()
+            .find(|slash| board_name.contains(&format!("{slash:?}")))
+            .unwrap_or(Self::Unknown)
+    }
+}
+
 impl FromStr for SlashType {
     type Err = SlashError;
 
     fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
         Ok(match s {
             "ga403" | "GA403" => Self::GA403,
+            "ga605" | "GA605" => Self::GA605,
+            "gu605" | "GU605" => Self::GU605,
             _ => Self::Unknown,
         })
     }
@@ -133,3 +147,15 @@ pub struct DeviceState {
     pub slash_interval: u8,
     pub slash_mode: SlashMode,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::SlashType;
+
+    #[test]
+    fn slash_from_board_name() {
+        assert_eq!(SlashType::from_board_name("GA403UV"), SlashType::GA403);
+        assert_eq!(SlashType::from_board_name("GU605MI"), SlashType::GU605);
+        assert_eq!(SlashType::from_board_name("GA402XV"), SlashType::Unknown);
+    }
+}
@@ -22,21 +22,23 @@ pub const PROD_ID_STR: &str = "193B";
 
 pub type SlashUsbPacket = [u8; PACKET_SIZE];
 
-/// `get_anime_type` is very broad, matching on part of the laptop board name
+/// `get_slash_type` is very broad, matching on part of the laptop board name
 /// only. For this reason `find_node()` must be used also to verify if the USB
 /// device is available.
 ///
-/// The currently known USB device is `193B`.
+/// The currently known USB device is `193B`, which is used with the same
+/// packets on each of the known boards.
 #[inline]
 pub fn get_slash_type() -> Result<SlashType, SlashError> {
     let dmi = DMIID::new().map_err(|_| SlashError::NoDevice)?; // TODO: better error
-    let board_name = dmi.board_name;
-
-    if board_name.contains("GA403") {
-        return Ok(SlashType::GA403);
+    let slash_type = SlashType::from_board_name(&dmi.board_name);
+    if slash_type == SlashType::Unknown {
+        log::warn!(
+            "Slash device found on {} but not yet supported, will default to a GA403 layout",
+            dmi.board_name
+        );
     }
-    log::warn!("AniMe Slash device found but not yet supported, will default to a GA403 layout");
-    Ok(SlashType::Unknown)
+    Ok(slash_type)
 }
 
 /// Get the two device initialization packets. These are required for device